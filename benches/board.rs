@@ -0,0 +1,162 @@
+//! Benchmarks for the board and queue code every tick runs through.
+//!
+//! Everything here is plain functions over [`Board`], [`BitBoard`],
+//! [`Tetromino`] and [`PieceQueue`], and the AI's search over them, with no
+//! Bevy app involved. Run with `cargo bench`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use rustblocks::ai::{evaluate, Planner, Weights};
+use rustblocks::components::bitboard::BitBoard;
+use rustblocks::components::board::Board;
+use rustblocks::components::tetromino::{
+    PieceKind, PieceQueue, QueueRules, RotationSystem, Tetromino,
+};
+
+/// A mid-game stack: ragged, eight rows high, with a few covered holes and
+/// a well down the right.
+const MID_GAME: [&str; 8] = [
+    "..........",
+    "J.........",
+    "JJ..T.....",
+    "LJ.TTT..O.",
+    "LLL.ZZ.OO.",
+    "IIIIZZSS..",
+    "TTT.SSLLL.",
+    "OOTJJJ.LO.",
+];
+
+/// A board from `rows`, written top down with `.` for an empty cell and a
+/// piece letter for a block, resting on the floor.
+fn board_from_rows(rows: &[&str]) -> Board {
+    let mut board = Board::default();
+    for (depth, row) in rows.iter().enumerate() {
+        let y = (rows.len() - 1 - depth) as i32;
+        for (x, letter) in row.chars().enumerate() {
+            let kind = PieceKind::ALL
+                .into_iter()
+                .find(|kind| kind.letter() == letter);
+            board.set(x as i32, y, kind.map(PieceKind::index));
+        }
+    }
+    board
+}
+
+/// Every visible row complete, the most [`Board::clear_lines`] can face.
+fn full_board() -> Board {
+    let mut board = Board::default();
+    for y in 0..board.visible_height() as i32 {
+        for x in 0..board.width() as i32 {
+            board.set(x, y, Some(PieceKind::ALL[(x + y) as usize % 7].index()));
+        }
+    }
+    board
+}
+
+/// Every kind in every rotation at every column, just above the stack.
+fn placements(board: &Board) -> Vec<Tetromino> {
+    let mut pieces = Vec::new();
+    for kind in PieceKind::ALL {
+        let spawned = Tetromino::spawn(kind, board);
+        let mut piece = spawned;
+        for _ in 0..4 {
+            for x in -2..board.width() as i32 + 2 {
+                for y in 0..10 {
+                    pieces.push(Tetromino { x, y, ..piece });
+                }
+            }
+            piece = piece.rotated(true);
+        }
+    }
+    pieces
+}
+
+fn collides(c: &mut Criterion) {
+    for (name, board) in [
+        ("empty", Board::default()),
+        ("mid_game", board_from_rows(&MID_GAME)),
+    ] {
+        let pieces = placements(&board);
+        c.bench_function(&format!("collides/{name}"), |b| {
+            b.iter(|| {
+                pieces
+                    .iter()
+                    .filter(|piece| black_box(&board).collides(piece))
+                    .count()
+            })
+        });
+        let bits = BitBoard::from(&board);
+        c.bench_function(&format!("collides/bitboard_{name}"), |b| {
+            b.iter(|| {
+                pieces
+                    .iter()
+                    .filter(|piece| black_box(&bits).collides(piece))
+                    .count()
+            })
+        });
+    }
+}
+
+fn clear_lines(c: &mut Criterion) {
+    let full = full_board();
+    c.bench_function("clear_lines/full_board", |b| {
+        b.iter_batched_ref(
+            || full.clone(),
+            |board| board.clear_lines(),
+            BatchSize::SmallInput,
+        )
+    });
+    let mid_game = board_from_rows(&MID_GAME);
+    c.bench_function("clear_lines/mid_game", |b| {
+        b.iter_batched_ref(
+            || mid_game.clone(),
+            |board| board.clear_lines(),
+            BatchSize::SmallInput,
+        )
+    });
+    let bits = BitBoard::from(&full);
+    c.bench_function("clear_lines/bitboard_full_board", |b| {
+        b.iter_batched_ref(
+            || bits.clone(),
+            |board| board.clear_lines(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn seven_bag(c: &mut Criterion) {
+    c.bench_function("seven_bag/deal_70", |b| {
+        b.iter_batched_ref(
+            || PieceQueue::with_seed(42, QueueRules::default()),
+            |queue| {
+                for _ in 0..70 {
+                    black_box(queue.next_piece());
+                }
+            },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn ai(c: &mut Criterion) {
+    let bits = BitBoard::from(&board_from_rows(&MID_GAME));
+    let weights = Weights::default();
+    c.bench_function("ai/evaluate_mid_game", |b| {
+        b.iter(|| evaluate(black_box(&bits), 0, &weights))
+    });
+    let next = [PieceKind::T, PieceKind::I, PieceKind::Z];
+    c.bench_function("ai/plan_mid_game", |b| {
+        b.iter(|| {
+            Planner::default().plan(
+                black_box(&bits),
+                PieceKind::L,
+                Some(PieceKind::O),
+                true,
+                &next,
+                RotationSystem::Srs,
+            )
+        })
+    });
+}
+
+criterion_group!(benches, collides, clear_lines, seven_bag, ai);
+criterion_main!(benches);
@@ -0,0 +1,529 @@
+//! The computer player behind the attract-mode demo.
+//!
+//! For each new piece the [`Planner`] tries every rotation and column for
+//! the piece in play, and for the held or next piece if holding is
+//! allowed, then does the same for the piece after on each resulting
+//! board. The placement leading to the best-scoring board is the plan, and
+//! [`AiPlayer`] presses the actions that carry it out with the same
+//! [`ActionEvent`]s the keyboard sends.
+//!
+//! The search runs on [`BitBoard`]s and looks at most
+//! [`Planner::depth`] pieces ahead, which keeps each plan to a few
+//! thousand placements and well inside a frame.
+
+use std::collections::{HashSet, VecDeque};
+
+use bevy::prelude::*;
+
+use crate::components::bitboard::BitBoard;
+use crate::components::board::Board;
+use crate::components::tetromino::{PieceKind, PieceQueue, RotationSystem, Tetromino};
+use crate::game::{ActivePiece, Hold, Modifiers, TickSet};
+use crate::input::keyboard::{
+    apply_action_events, read_keyboard, send_held, Action, ActionEvent, ActionState,
+};
+
+/// How much each feature of a board counts towards its score. Negative
+/// weights are penalties.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weights {
+    /// Per row of the column heights summed.
+    pub height: f32,
+    /// Per empty cell with a block somewhere above it.
+    pub holes: f32,
+    /// Per row of difference between neighbouring columns.
+    pub bumpiness: f32,
+    /// Per row cleared on the way to the board.
+    pub lines: f32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            height: -0.51,
+            holes: -0.36,
+            bumpiness: -0.18,
+            lines: 0.76,
+        }
+    }
+}
+
+/// How high each column of `board` is stacked, left to right: one more
+/// than the row of its topmost block, or 0 for an empty column.
+pub fn column_heights(board: &Board) -> Vec<u32> {
+    BitBoard::from(board).column_heights()
+}
+
+/// How many empty cells of `board` have a block somewhere above them in
+/// their column, overhangs included.
+pub fn count_holes(board: &Board) -> u32 {
+    BitBoard::from(board).holes()
+}
+
+/// What each feature of a board adds to its [`evaluate`] score, weighted.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Breakdown {
+    pub height: f32,
+    pub holes: f32,
+    pub bumpiness: f32,
+    pub lines: f32,
+}
+
+impl Breakdown {
+    /// The features of `board`, reached by clearing `lines` rows, under
+    /// `weights`.
+    pub fn of(board: &BitBoard, lines: usize, weights: &Weights) -> Self {
+        let heights = board.column_heights();
+        let height: u32 = heights.iter().sum();
+        let bumpiness: u32 = heights
+            .windows(2)
+            .map(|pair| pair[0].abs_diff(pair[1]))
+            .sum();
+        Self {
+            height: weights.height * height as f32,
+            holes: weights.holes * board.holes() as f32,
+            bumpiness: weights.bumpiness * bumpiness as f32,
+            lines: weights.lines * lines as f32,
+        }
+    }
+
+    pub fn total(&self) -> f32 {
+        self.height + self.holes + self.bumpiness + self.lines
+    }
+}
+
+/// Scores `board`, reached by clearing `lines` rows, under `weights`.
+/// Higher is better.
+pub fn evaluate(board: &BitBoard, lines: usize, weights: &Weights) -> f32 {
+    Breakdown::of(board, lines, weights).total()
+}
+
+/// Whether locking `piece` would leave a block above the visible field,
+/// which the search treats as topping out whatever the rules.
+pub fn tops_out(board: &BitBoard, piece: &Tetromino) -> bool {
+    piece
+        .cells()
+        .iter()
+        .any(|&(_, y)| y >= board.visible_height() as i32)
+}
+
+/// Every resting place `kind` can reach from its spawn by turning, with
+/// kicks, and sliding before dropping straight down, or none if it can't
+/// spawn.
+pub fn placements(board: &BitBoard, kind: PieceKind, system: RotationSystem) -> Vec<Tetromino> {
+    let spawned = Tetromino::spawn_sized(kind, system, board.width(), board.visible_height());
+    let mut landings = Vec::new();
+    if board.collides(&spawned) {
+        return landings;
+    }
+    let key = |piece: &Tetromino| (piece.rotation, piece.x, piece.y);
+    let mut seen = HashSet::from([key(&spawned)]);
+    let mut landed = HashSet::new();
+    let mut pending = VecDeque::from([spawned]);
+    while let Some(piece) = pending.pop_front() {
+        let landing = board.landing_position(&piece);
+        if landed.insert(key(&landing)) {
+            landings.push(landing);
+        }
+        let slides = [piece.moved(-1, 0), piece.moved(1, 0)]
+            .into_iter()
+            .filter(|slid| !board.collides(slid));
+        let turns = [true, false]
+            .into_iter()
+            .filter_map(|clockwise| piece.rotate_with_kicks(board, clockwise))
+            .map(|(turned, _)| turned);
+        for next in slides.chain(turns) {
+            if seen.insert(key(&next)) {
+                pending.push_back(next);
+            }
+        }
+    }
+    landings
+}
+
+/// Where to put the piece in play.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plan {
+    /// The piece at rest where it should lock.
+    pub target: Tetromino,
+    /// Hold first and place the piece that comes out instead.
+    pub hold: bool,
+    /// The score of the best board the plan leads to.
+    pub score: f32,
+}
+
+/// The placement search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Planner {
+    /// Pieces placed in each line of play searched, the one in play
+    /// included.
+    pub depth: usize,
+    /// Consider holding.
+    pub use_hold: bool,
+    pub weights: Weights,
+}
+
+impl Default for Planner {
+    fn default() -> Self {
+        Self {
+            depth: 2,
+            use_hold: true,
+            weights: Weights::default(),
+        }
+    }
+}
+
+impl Planner {
+    /// Places the piece in play only, greedily and without holding.
+    pub fn shallow() -> Self {
+        Self {
+            depth: 1,
+            use_hold: false,
+            ..default()
+        }
+    }
+
+    /// The best plan for `current` on `board` with `hold` held and `next`
+    /// coming, or `None` if no piece can be placed at all.
+    pub fn plan(
+        &self,
+        board: &BitBoard,
+        current: PieceKind,
+        hold: Option<PieceKind>,
+        can_hold: bool,
+        next: &[PieceKind],
+        system: RotationSystem,
+    ) -> Option<Plan> {
+        self.search(board, current, hold, can_hold, next, system, self.depth, 0)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn search(
+        &self,
+        board: &BitBoard,
+        current: PieceKind,
+        hold: Option<PieceKind>,
+        can_hold: bool,
+        next: &[PieceKind],
+        system: RotationSystem,
+        depth: usize,
+        lines: usize,
+    ) -> Option<Plan> {
+        // (held, piece placed, hold afterwards, pieces still to come)
+        let mut options = vec![(false, current, hold, next)];
+        if self.use_hold && can_hold {
+            match (hold, next.split_first()) {
+                (Some(held), _) if held != current => {
+                    options.push((true, held, Some(current), next));
+                }
+                (None, Some((&first, rest))) if first != current => {
+                    options.push((true, first, Some(current), rest));
+                }
+                _ => {}
+            }
+        }
+
+        let mut best: Option<Plan> = None;
+        for (held, kind, hold_after, upcoming) in options {
+            for target in placements(board, kind, system) {
+                let score = if tops_out(board, &target) {
+                    f32::NEG_INFINITY
+                } else {
+                    let mut after = board.clone();
+                    after.lock_piece(&target);
+                    let lines = lines + after.clear_lines();
+                    match upcoming.split_first() {
+                        Some((&following, rest)) if depth > 1 => self
+                            .search(
+                                &after,
+                                following,
+                                hold_after,
+                                true,
+                                rest,
+                                system,
+                                depth - 1,
+                                lines,
+                            )
+                            .map_or(f32::NEG_INFINITY, |plan| plan.score),
+                        _ => evaluate(&after, lines, &self.weights),
+                    }
+                };
+                if best.is_none_or(|best| score > best.score) {
+                    best = Some(Plan {
+                        target,
+                        hold: held,
+                        score,
+                    });
+                }
+            }
+        }
+        best
+    }
+}
+
+/// The [`AiPlayer`]'s latest plan, kept for the debug overlay to show.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct AiDecision {
+    pub plan: Option<Plan>,
+    /// How the board scores once the planned piece locks, before the
+    /// lookahead the plan's own score includes.
+    pub breakdown: Breakdown,
+    /// Plans made so far.
+    pub count: u32,
+}
+
+impl AiDecision {
+    fn record(&mut self, board: &BitBoard, plan: Plan, weights: &Weights) {
+        let mut after = board.clone();
+        after.lock_piece(&plan.target);
+        let lines = after.clear_lines();
+        self.plan = Some(plan);
+        self.breakdown = Breakdown::of(&after, lines, weights);
+        self.count += 1;
+    }
+}
+
+/// Plays the game in place of the player while present.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct AiPlayer {
+    pub planner: Planner,
+    /// The plan for the piece in play, once made.
+    pub plan: Option<Plan>,
+    /// An action was pressed last tick, so this one releases it to let the
+    /// next press register.
+    pressed: bool,
+    /// The piece's kind, rotation and column when the last action was
+    /// pressed, to notice when it had no effect.
+    last: Option<(PieceKind, u8, i32)>,
+}
+
+impl AiPlayer {
+    /// The action to hold this tick towards the plan, making the plan
+    /// first, and noting it in `decision`, if the piece in play doesn't
+    /// have one yet.
+    pub fn next_action(
+        &mut self,
+        board: &Board,
+        active: Option<Tetromino>,
+        hold: &Hold,
+        can_hold: bool,
+        queue: &PieceQueue,
+        decision: &mut AiDecision,
+    ) -> Option<Action> {
+        if std::mem::take(&mut self.pressed) {
+            return None;
+        }
+        let Some(piece) = active else {
+            self.plan = None;
+            return None;
+        };
+        if self.plan.is_none() {
+            let next: Vec<_> = queue.peek(PieceQueue::PREVIEW_LEN).collect();
+            let bits = BitBoard::from(board);
+            self.plan = self.planner.plan(
+                &bits,
+                piece.kind,
+                hold.piece,
+                can_hold && hold.can_hold,
+                &next,
+                piece.system,
+            );
+            if let Some(plan) = self.plan {
+                decision.record(&bits, plan, &self.planner.weights);
+            }
+        }
+        let target = self.plan?.target;
+        let now = (piece.kind, piece.rotation, piece.x);
+        let action = if piece.kind != target.kind {
+            Action::Hold
+        } else if self.last == Some(now) {
+            // Blocked on the way; settle for wherever the piece is.
+            Action::HardDrop
+        } else if piece.rotation != target.rotation {
+            if (target.rotation + 1) % 4 == piece.rotation {
+                Action::RotateCcw
+            } else {
+                Action::RotateCw
+            }
+        } else if piece.x < target.x {
+            Action::MoveRight
+        } else if piece.x > target.x {
+            Action::MoveLeft
+        } else {
+            Action::HardDrop
+        };
+        if action == Action::HardDrop {
+            self.plan = None;
+            self.last = None;
+        } else {
+            self.last = Some(now);
+        }
+        self.pressed = true;
+        Some(action)
+    }
+}
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AiDecision>().add_systems(
+            FixedUpdate,
+            play_ai
+                .in_set(TickSet::Input)
+                .after(read_keyboard)
+                .before(apply_action_events)
+                .run_if(resource_exists::<AiPlayer>()),
+        );
+    }
+}
+
+/// Stands in for [`read_keyboard`] while the [`AiPlayer`] plays.
+#[allow(clippy::too_many_arguments)]
+fn play_ai(
+    mut ai: ResMut<AiPlayer>,
+    mut decision: ResMut<AiDecision>,
+    board: Res<Board>,
+    active: Res<ActivePiece>,
+    hold: Res<Hold>,
+    queue: Res<PieceQueue>,
+    modifiers: Res<Modifiers>,
+    actions: Res<ActionState>,
+    mut events: EventWriter<ActionEvent>,
+) {
+    let can_hold = !modifiers.no_hold;
+    let action = ai.next_action(&board, active.0, &hold, can_hold, &queue, &mut decision);
+    send_held(&actions, |held| Some(held) == action, &mut events);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::components::scoring::Score;
+    use crate::game::{AppState, GamePlugin, Phase};
+
+    /// Rows 0 to 18 filled but for the last column, leaving no room for
+    /// anything but an I down the well.
+    fn tall_well() -> BitBoard {
+        let mut board = Board::default();
+        for y in 0..19 {
+            for x in 0..board.width() as i32 - 1 {
+                board.set(x, y, Some(0));
+            }
+        }
+        BitBoard::from(&board)
+    }
+
+    #[test]
+    fn test_an_empty_board_has_no_height_or_holes() {
+        let board = Board::default();
+        assert_eq!(column_heights(&board), vec![0; board.width()]);
+        assert_eq!(count_holes(&board), 0);
+    }
+
+    #[test]
+    fn test_a_flat_stack_has_even_heights_and_no_holes() {
+        let mut board = Board::default();
+        for y in 0..3 {
+            for x in 0..board.width() as i32 - 1 {
+                board.set(x, y, Some(0));
+            }
+        }
+        let mut heights = vec![3; board.width()];
+        heights[board.width() - 1] = 0;
+        assert_eq!(column_heights(&board), heights);
+        assert_eq!(count_holes(&board), 0);
+    }
+
+    #[test]
+    fn test_cells_under_an_overhang_count_as_holes() {
+        let mut board = Board::default();
+        // A ledge over columns 1 to 3 on row 3, with column 1 resting on a
+        // block at row 1 and the rest open to the floor beneath.
+        for x in 1..4 {
+            board.set(x, 3, Some(0));
+        }
+        board.set(1, 1, Some(0));
+        board.set(5, 0, Some(0));
+        assert_eq!(column_heights(&board)[..6], [0, 4, 4, 4, 0, 1]);
+        assert_eq!(count_holes(&board), 2 + 3 + 3);
+    }
+
+    #[test]
+    fn test_lookahead_holds_to_survive_where_the_shallow_search_tops_out() {
+        let board = tall_well();
+        let next = [PieceKind::I, PieceKind::O, PieceKind::T];
+        let srs = RotationSystem::Srs;
+
+        let shallow = Planner::shallow()
+            .plan(&board, PieceKind::S, None, true, &next, srs)
+            .unwrap();
+        assert!(tops_out(&board, &shallow.target));
+
+        let plan = Planner::default()
+            .plan(&board, PieceKind::S, None, true, &next, srs)
+            .unwrap();
+        assert!(plan.hold);
+        assert_eq!(plan.target.kind, PieceKind::I);
+        assert!(!tops_out(&board, &plan.target));
+        let mut after = board.clone();
+        after.lock_piece(&plan.target);
+        assert_eq!(after.clear_lines(), 4);
+    }
+
+    #[test]
+    fn test_ai_player_clears_lines_without_topping_out() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin, AiPlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+            .insert_resource(PieceQueue::with_seed(5, default()))
+            .init_resource::<AiPlayer>();
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+        for _ in 0..6000 {
+            app.world.run_schedule(FixedUpdate);
+            assert_ne!(*app.world.resource::<Phase>(), Phase::ToppedOut);
+        }
+        assert!(app.world.resource::<Score>().lines >= 20);
+    }
+
+    #[test]
+    fn test_every_plan_the_ai_makes_is_recorded_for_the_overlay() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin, AiPlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+            .insert_resource(PieceQueue::with_seed(9, default()))
+            .init_resource::<AiPlayer>();
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+        let mut pieces = 0;
+        for _ in 0..600 {
+            if app.world.resource::<ActivePiece>().0.is_some()
+                && app.world.resource::<AiPlayer>().plan.is_none()
+            {
+                pieces += 1;
+            }
+            app.world.run_schedule(FixedUpdate);
+            let decision = app.world.resource::<AiDecision>();
+            assert_eq!(decision.count, pieces);
+            if let (Some(plan), Some(planned)) =
+                (app.world.resource::<AiPlayer>().plan, decision.plan)
+            {
+                assert_eq!(plan, planned);
+            }
+        }
+        assert!(pieces > 10);
+        let decision = app.world.resource::<AiDecision>();
+        assert!(decision.breakdown.height < 0.0);
+    }
+}
@@ -0,0 +1,229 @@
+//! Sound effect management.
+//!
+//! Effects are preloaded at startup and triggered from [`GameplayEvent`]s,
+//! each on its own kira channel so volume can be set per category. With
+//! [`Settings::audio_cues`] on, spawns and hold coming back also tick.
+
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::*;
+
+use crate::audio::device::AudioDevice;
+use crate::game::GameplayEvent;
+use crate::utils::config::Settings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SoundEffect {
+    Move,
+    Rotate,
+    Hold,
+    HoldRejected,
+    Lock,
+    LineClear,
+    Tetris,
+    LevelUp,
+    GameOver,
+    SpawnCue,
+    HoldCue,
+}
+
+impl SoundEffect {
+    pub const ALL: [SoundEffect; 11] = [
+        SoundEffect::Move,
+        SoundEffect::Rotate,
+        SoundEffect::Hold,
+        SoundEffect::HoldRejected,
+        SoundEffect::Lock,
+        SoundEffect::LineClear,
+        SoundEffect::Tetris,
+        SoundEffect::LevelUp,
+        SoundEffect::GameOver,
+        SoundEffect::SpawnCue,
+        SoundEffect::HoldCue,
+    ];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            SoundEffect::Move => "sfx.move",
+            SoundEffect::Rotate => "sfx.rotate",
+            SoundEffect::Hold => "sfx.hold",
+            SoundEffect::HoldRejected => "sfx.hold_rejected",
+            SoundEffect::Lock => "sfx.lock",
+            SoundEffect::LineClear => "sfx.line_clear",
+            SoundEffect::Tetris => "sfx.tetris",
+            SoundEffect::LevelUp => "sfx.level_up",
+            SoundEffect::GameOver => "sfx.game_over",
+            SoundEffect::SpawnCue => "sfx.spawn_cue",
+            SoundEffect::HoldCue => "sfx.hold_cue",
+        }
+    }
+
+    fn path(self) -> &'static str {
+        match self {
+            SoundEffect::Move => "audio/move.wav",
+            SoundEffect::Rotate => "audio/rotate.wav",
+            SoundEffect::Hold => "audio/hold.wav",
+            SoundEffect::HoldRejected => "audio/buzz.wav",
+            SoundEffect::Lock => "audio/lock.wav",
+            SoundEffect::LineClear => "audio/line_clear.wav",
+            SoundEffect::Tetris => "audio/tetris.wav",
+            SoundEffect::LevelUp => "audio/level_up.wav",
+            SoundEffect::GameOver => "audio/game_over.wav",
+            SoundEffect::SpawnCue => "audio/spawn_cue.wav",
+            SoundEffect::HoldCue => "audio/hold_cue.wav",
+        }
+    }
+
+    /// The effect that accompanies a gameplay event, if any.
+    pub fn for_event(event: &GameplayEvent) -> Option<Self> {
+        match event {
+            GameplayEvent::Spawned(_)
+            | GameplayEvent::Kicked(_)
+            | GameplayEvent::GarbageRose(_)
+            | GameplayEvent::HoldReady
+            | GameplayEvent::Reset => None,
+            GameplayEvent::Moved => Some(SoundEffect::Move),
+            GameplayEvent::Rotated => Some(SoundEffect::Rotate),
+            GameplayEvent::Held => Some(SoundEffect::Hold),
+            GameplayEvent::HoldRejected => Some(SoundEffect::HoldRejected),
+            GameplayEvent::Locked(_) => Some(SoundEffect::Lock),
+            GameplayEvent::LinesCleared { count: 4.., .. } => Some(SoundEffect::Tetris),
+            GameplayEvent::LinesCleared { .. } => Some(SoundEffect::LineClear),
+            GameplayEvent::LevelUp(_) | GameplayEvent::Finished => Some(SoundEffect::LevelUp),
+            GameplayEvent::GameOver => Some(SoundEffect::GameOver),
+        }
+    }
+
+    /// The [`Settings::audio_cues`] tick for a gameplay event, if any.
+    pub fn cue_for_event(event: &GameplayEvent) -> Option<Self> {
+        match event {
+            GameplayEvent::Spawned(_) => Some(SoundEffect::SpawnCue),
+            GameplayEvent::HoldReady => Some(SoundEffect::HoldCue),
+            _ => None,
+        }
+    }
+
+    /// Everything to play for a gameplay event under `settings`.
+    pub fn for_event_with(
+        event: &GameplayEvent,
+        settings: &Settings,
+    ) -> impl Iterator<Item = Self> {
+        let cue = Self::cue_for_event(event).filter(|_| settings.audio_cues);
+        Self::for_event(event).into_iter().chain(cue)
+    }
+}
+
+/// Preloaded handles for every [`SoundEffect`], indexed by discriminant.
+#[derive(Resource, Debug, Clone)]
+pub struct SoundEffects {
+    handles: Vec<Handle<AudioSource>>,
+}
+
+impl SoundEffects {
+    pub fn get(&self, effect: SoundEffect) -> Handle<AudioSource> {
+        self.handles[effect as usize].clone()
+    }
+}
+
+/// Channel for in-game sound effects.
+#[derive(Resource)]
+pub struct SfxChannel;
+
+/// Channel for sounds previewed from the settings menu, kept apart so
+/// stopping previews never cuts in-game audio.
+#[derive(Resource)]
+pub struct PreviewChannel;
+
+pub struct SfxPlugin;
+
+impl Plugin for SfxPlugin {
+    fn build(&self, app: &mut App) {
+        if AudioDevice::is_missing(app) {
+            return;
+        }
+        app.add_audio_channel::<SfxChannel>()
+            .add_audio_channel::<PreviewChannel>()
+            .add_systems(Startup, load_sound_effects)
+            .add_systems(Update, (apply_sfx_volume, play_gameplay_sounds));
+    }
+}
+
+fn load_sound_effects(mut commands: Commands, assets: Res<AssetServer>) {
+    let handles = SoundEffect::ALL
+        .iter()
+        .map(|effect| assets.load(effect.path()))
+        .collect();
+    commands.insert_resource(SoundEffects { handles });
+}
+
+fn apply_sfx_volume(settings: Res<Settings>, channel: Res<AudioChannel<SfxChannel>>) {
+    if settings.is_changed() {
+        channel.set_volume(f64::from(settings.sfx_volume));
+    }
+}
+
+fn play_gameplay_sounds(
+    mut events: EventReader<GameplayEvent>,
+    settings: Res<Settings>,
+    effects: Res<SoundEffects>,
+    channel: Res<AudioChannel<SfxChannel>>,
+) {
+    let sounds = events
+        .read()
+        .flat_map(|event| SoundEffect::for_event_with(event, &settings));
+    for effect in sounds {
+        channel.play(effects.get(effect));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::event::ManualEventReader;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::game::{AppState, GamePlugin};
+
+    /// The sounds the first tick of a new game asks for with the cues on
+    /// or off.
+    fn first_tick_sounds(audio_cues: bool) -> Vec<SoundEffect> {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+        app.world.resource_mut::<Settings>().audio_cues = audio_cues;
+        let mut reader = ManualEventReader::<GameplayEvent>::default();
+        reader.read(app.world.resource::<Events<GameplayEvent>>());
+        app.world.run_schedule(FixedUpdate);
+
+        let settings = app.world.resource::<Settings>();
+        reader
+            .read(app.world.resource::<Events<GameplayEvent>>())
+            .flat_map(|event| SoundEffect::for_event_with(event, settings))
+            .collect()
+    }
+
+    #[test]
+    fn test_spawning_ticks_only_with_audio_cues_on() {
+        assert_eq!(first_tick_sounds(true), [SoundEffect::SpawnCue]);
+        assert_eq!(first_tick_sounds(false), []);
+    }
+
+    #[test]
+    fn test_hold_ready_has_its_own_cue() {
+        let settings = Settings {
+            audio_cues: true,
+            ..Settings::default()
+        };
+        let sounds: Vec<_> =
+            SoundEffect::for_event_with(&GameplayEvent::HoldReady, &settings).collect();
+        assert_eq!(sounds, [SoundEffect::HoldCue]);
+        assert_ne!(SoundEffect::HoldCue.path(), SoundEffect::SpawnCue.path());
+    }
+}
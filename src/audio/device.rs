@@ -0,0 +1,99 @@
+//! Running without sound when there is nothing to play it on.
+//!
+//! Headless machines, CI and some Linux setups have no output device. Kira
+//! then starts without one but keeps every command sent to it, so rather
+//! than feed it, [`AudioDevicePlugin`] checks for a device first and leaves
+//! the audio plugins out when there is none, reporting a
+//! [`GameError::Audio`]. The game logs one warning and runs silently, with
+//! the sound settings greyed out.
+
+use bevy::prelude::*;
+use bevy_kira_audio::AudioPlugin;
+use cpal::traits::{DeviceTrait, HostTrait};
+
+use crate::error::{report, GameError};
+
+/// Whether sound can be played on this machine, decided once at startup.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioDevice {
+    Available,
+    Missing,
+}
+
+impl AudioDevice {
+    /// Looks for a default output device that can report a stream format.
+    pub fn detect() -> Self {
+        let usable = cpal::default_host()
+            .default_output_device()
+            .is_some_and(|device| device.default_output_config().is_ok());
+        if usable {
+            AudioDevice::Available
+        } else {
+            AudioDevice::Missing
+        }
+    }
+
+    /// Whether `app` runs without sound. Apps that never added
+    /// [`AudioDevicePlugin`] are taken to have sound.
+    pub fn is_missing(app: &App) -> bool {
+        app.world.get_resource::<AudioDevice>() == Some(&AudioDevice::Missing)
+    }
+}
+
+/// Adds kira's [`AudioPlugin`] when there is a device to play on. Must come
+/// before the plugins that play sound; an [`AudioDevice`] inserted earlier
+/// is kept rather than detected.
+pub struct AudioDevicePlugin;
+
+impl Plugin for AudioDevicePlugin {
+    fn build(&self, app: &mut App) {
+        let device = *app.world.get_resource_or_insert_with(AudioDevice::detect);
+        match device {
+            AudioDevice::Available => {
+                app.add_plugins(AudioPlugin);
+            }
+            AudioDevice::Missing => report(app, GameError::Audio("no output device found".into())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+    use bevy_kira_audio::AudioChannel;
+
+    use super::*;
+    use crate::audio::aeffects::{SfxChannel, SfxPlugin};
+    use crate::audio::music::{MusicChannel, MusicPlugin};
+    use crate::game::{AppState, GamePlugin};
+
+    #[test]
+    fn test_a_missing_device_leaves_the_game_running_silently() {
+        let mut app = App::new();
+        app.insert_resource(AudioDevice::Missing)
+            .add_plugins((MinimalPlugins, AudioDevicePlugin, GamePlugin))
+            .add_plugins((SfxPlugin, MusicPlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        for _ in 0..3 {
+            app.update();
+            app.world.run_schedule(FixedUpdate);
+        }
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Paused);
+        app.update();
+
+        assert_eq!(
+            *app.world.resource::<State<AppState>>().get(),
+            AppState::Paused
+        );
+        assert!(!app.world.contains_resource::<AudioChannel<SfxChannel>>());
+        assert!(!app.world.contains_resource::<AudioChannel<MusicChannel>>());
+    }
+}
@@ -0,0 +1,162 @@
+//! Background music management.
+
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::*;
+
+use crate::audio::device::AudioDevice;
+use crate::components::scoring::Score;
+use crate::game::AppState;
+use crate::utils::config::Settings;
+
+/// How much faster the game music plays per level above the first.
+const RATE_PER_LEVEL: f64 = 0.02;
+
+/// Fastest the game music plays, whatever the level.
+pub const MAX_MUSIC_RATE: f64 = 1.25;
+
+/// Playback rate of the game music at `level`.
+pub fn music_rate(level: u32) -> f64 {
+    (1.0 + RATE_PER_LEVEL * f64::from(level.saturating_sub(1))).min(MAX_MUSIC_RATE)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MusicTrack {
+    Menu,
+    Theme,
+}
+
+impl MusicTrack {
+    pub const ALL: [MusicTrack; 2] = [MusicTrack::Menu, MusicTrack::Theme];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            MusicTrack::Menu => "music.menu",
+            MusicTrack::Theme => "music.theme",
+        }
+    }
+
+    fn path(self) -> &'static str {
+        match self {
+            MusicTrack::Menu => "audio/menu.wav",
+            MusicTrack::Theme => "audio/theme.wav",
+        }
+    }
+}
+
+/// Preloaded handles for every [`MusicTrack`], indexed by discriminant.
+#[derive(Resource, Debug, Clone)]
+pub struct MusicTracks {
+    handles: Vec<Handle<AudioSource>>,
+}
+
+impl MusicTracks {
+    pub fn get(&self, track: MusicTrack) -> Handle<AudioSource> {
+        self.handles[track as usize].clone()
+    }
+}
+
+#[derive(Resource)]
+pub struct MusicChannel;
+
+/// The playback rate last set on the music channel. Every reason to speed
+/// the music up folds into the one rate [`apply_music_tempo`] sets, so
+/// none of them stacks on another.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+struct MusicTempo(f64);
+
+impl Default for MusicTempo {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        if AudioDevice::is_missing(app) {
+            return;
+        }
+        app.add_audio_channel::<MusicChannel>()
+            .init_resource::<MusicTempo>()
+            .add_systems(PreStartup, load_music)
+            .add_systems(OnEnter(AppState::MainMenu), play_track(MusicTrack::Menu))
+            .add_systems(OnExit(AppState::MainMenu), play_track(MusicTrack::Theme))
+            .add_systems(OnEnter(AppState::Paused), pause_music)
+            .add_systems(OnExit(AppState::Paused), resume_music)
+            .add_systems(OnEnter(AppState::GameOver), stop_music)
+            .add_systems(Update, (apply_music_volume, apply_music_tempo));
+    }
+}
+
+fn load_music(mut commands: Commands, assets: Res<AssetServer>) {
+    let handles = MusicTrack::ALL
+        .iter()
+        .map(|track| assets.load(track.path()))
+        .collect();
+    commands.insert_resource(MusicTracks { handles });
+}
+
+/// A system that replaces whatever is playing with `track`, looped, at
+/// its own speed.
+fn play_track(
+    track: MusicTrack,
+) -> impl Fn(Res<MusicTracks>, Res<AudioChannel<MusicChannel>>, ResMut<MusicTempo>) {
+    move |tracks, channel, mut tempo| {
+        channel.stop();
+        channel.play(tracks.get(track)).looped();
+        *tempo = MusicTempo::default();
+    }
+}
+
+fn pause_music(channel: Res<AudioChannel<MusicChannel>>) {
+    channel.pause();
+}
+
+fn resume_music(channel: Res<AudioChannel<MusicChannel>>) {
+    channel.resume();
+}
+
+fn stop_music(channel: Res<AudioChannel<MusicChannel>>) {
+    channel.stop();
+}
+
+fn apply_music_volume(settings: Res<Settings>, channel: Res<AudioChannel<MusicChannel>>) {
+    if settings.is_changed() {
+        channel.set_volume(f64::from(settings.music_volume));
+    }
+}
+
+/// Speeds the game music up with the level. The menu music keeps its own
+/// pace.
+fn apply_music_tempo(
+    state: Res<State<AppState>>,
+    score: Res<Score>,
+    channel: Res<AudioChannel<MusicChannel>>,
+    mut tempo: ResMut<MusicTempo>,
+) {
+    let rate = match state.get() {
+        AppState::MainMenu => 1.0,
+        _ => music_rate(score.level),
+    };
+    if tempo.0 != rate {
+        channel.set_playback_rate(rate);
+        tempo.0 = rate;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_music_speeds_up_with_the_level_up_to_the_cap() {
+        assert_eq!(music_rate(0), 1.0);
+        assert_eq!(music_rate(1), 1.0);
+        assert!((music_rate(6) - 1.1).abs() < 1e-9);
+        assert!(music_rate(12) < MAX_MUSIC_RATE);
+        assert_eq!(music_rate(14), MAX_MUSIC_RATE);
+        assert_eq!(music_rate(u32::MAX), MAX_MUSIC_RATE);
+    }
+}
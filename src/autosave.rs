@@ -0,0 +1,318 @@
+//! Saving the game in progress so it can be continued after a crash or a
+//! quit.
+//!
+//! The simulation is deterministic, so the recording of the game so far is
+//! a complete save: continuing plays it back to its last tick, which puts
+//! the board, score, queue position, hold, mode and timers back exactly,
+//! then carries on recording from there. The recording is written every
+//! [`AUTOSAVE_SECS`] of play and whenever the game pauses, and removed once
+//! the game ends. A picture of the board is saved beside it, so the menu
+//! can show which game Continue resumes without playing it back first.
+//!
+//! A corrupt or hand-edited save can still play back to a board no real
+//! game leaves, so the board is [validated](validate_board) once it is
+//! back and [compacted](Board::compact) if it fails, rather than carrying
+//! on from a state the rules never expect.
+
+use bevy::ecs::system::SystemState;
+use bevy::prelude::*;
+
+use crate::components::board::{validate_board, Board};
+use crate::game::{reset_game, AppState, GameplayEvent};
+use crate::replay::{Replay, ReplayPlayback, ReplayRecorder, ReplayRules};
+use crate::utils::storage;
+
+/// Seconds of play between autosaves.
+pub const AUTOSAVE_SECS: f32 = 5.0;
+
+const FOLDER: &str = "saves";
+const FILE: &str = "autosave.ron";
+const PREVIEW_FILE: &str = "preview.ron";
+
+/// The saved game, if there is one to continue.
+#[derive(Resource, Debug, Default)]
+pub struct Autosave(pub Option<Replay>);
+
+impl Autosave {
+    pub fn load(app: &mut App) -> Self {
+        Self(storage::read_ron(app, FOLDER, FILE))
+    }
+
+    /// The board to show on the Continue option, or `None` when there is no
+    /// game to continue and the option is hidden. A save without a
+    /// [`AutosavePreview`] shows no board.
+    pub fn continue_preview<'a>(&self, preview: &'a AutosavePreview) -> Option<&'a str> {
+        self.0
+            .as_ref()
+            .map(|_| preview.0.as_deref().unwrap_or_default())
+    }
+}
+
+/// The visible board of the [`Autosave`]d game as it was saved, in the
+/// layout of [`Board::to_ascii`].
+#[derive(Resource, Debug, Default)]
+pub struct AutosavePreview(pub Option<String>);
+
+impl AutosavePreview {
+    pub fn load(app: &mut App) -> Self {
+        Self(storage::read_ron(app, FOLDER, PREVIEW_FILE))
+    }
+}
+
+/// Continues the [`Autosave`]d game.
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct ContinueGame;
+
+#[derive(Resource, Debug)]
+struct AutosaveTimer(Timer);
+
+impl Default for AutosaveTimer {
+    fn default() -> Self {
+        Self(Timer::from_seconds(AUTOSAVE_SECS, TimerMode::Repeating))
+    }
+}
+
+pub struct AutosavePlugin;
+
+impl Plugin for AutosavePlugin {
+    fn build(&self, app: &mut App) {
+        let (save, preview) = (Autosave::load(app), AutosavePreview::load(app));
+        app.add_event::<ContinueGame>()
+            .insert_resource(save)
+            .insert_resource(preview)
+            .init_resource::<AutosaveTimer>()
+            .add_systems(OnEnter(AppState::Paused), save_game)
+            .add_systems(OnEnter(AppState::GameOver), delete_save)
+            .add_systems(
+                Update,
+                (
+                    save_periodically.run_if(in_state(AppState::Playing)),
+                    continue_game.run_if(on_event::<ContinueGame>()),
+                ),
+            );
+    }
+}
+
+fn save_periodically(
+    time: Res<Time>,
+    mut timer: ResMut<AutosaveTimer>,
+    recorder: Option<Res<ReplayRecorder>>,
+    board: Res<Board>,
+    autosave: ResMut<Autosave>,
+    preview: ResMut<AutosavePreview>,
+) {
+    if timer.0.tick(time.delta()).just_finished() {
+        save_game(recorder, board, autosave, preview);
+    }
+}
+
+fn save_game(
+    recorder: Option<Res<ReplayRecorder>>,
+    board: Res<Board>,
+    mut autosave: ResMut<Autosave>,
+    mut preview: ResMut<AutosavePreview>,
+) {
+    let Some(recorder) = recorder else {
+        return;
+    };
+    if let Err(error) = storage::write_ron(FOLDER, FILE, &recorder.0) {
+        warn!("couldn't autosave the game: {error}");
+    }
+    autosave.0 = Some(recorder.0.clone());
+    let rows = board.to_ascii();
+    if let Err(error) = storage::write_ron(FOLDER, PREVIEW_FILE, &rows) {
+        warn!("couldn't save the board preview: {error}");
+    }
+    preview.0 = Some(rows);
+}
+
+fn delete_save(mut autosave: ResMut<Autosave>, mut preview: ResMut<AutosavePreview>) {
+    if autosave.0.take().is_none() {
+        return;
+    }
+    preview.0 = None;
+    for file in [FILE, PREVIEW_FILE] {
+        if let Ok(path) = storage::file_path(FOLDER, file) {
+            if let Err(error) = std::fs::remove_file(path) {
+                if error.kind() != std::io::ErrorKind::NotFound {
+                    warn!("couldn't remove the autosave: {error}");
+                }
+            }
+        }
+    }
+}
+
+fn continue_game(world: &mut World) {
+    world.resource_mut::<Events<ContinueGame>>().clear();
+    if let Some(replay) = world.resource::<Autosave>().0.clone() {
+        resume(world, replay);
+    }
+}
+
+/// Puts the game recorded in `replay` back as it was on its last tick,
+/// paused and recording again.
+pub fn resume(world: &mut World, replay: Replay) {
+    let mut state: SystemState<(Commands, ReplayRules)> = SystemState::new(world);
+    let (mut commands, mut rules) = state.get_mut(world);
+    rules.apply(&replay);
+    reset_game(&mut commands, replay.piece_queue());
+    state.apply(world);
+
+    world.remove_resource::<ReplayRecorder>();
+    world.insert_resource(ReplayPlayback::new(replay.clone()));
+    world.insert_resource(NextState(Some(AppState::Playing)));
+    world.run_schedule(StateTransition);
+    if replay.length > 0 {
+        while !world.resource::<ReplayPlayback>().paused {
+            world.run_schedule(FixedUpdate);
+        }
+    }
+    world.remove_resource::<ReplayPlayback>();
+    repair(&mut world.resource_mut::<Board>());
+    world.insert_resource(ReplayRecorder(replay));
+    world.insert_resource(NextState(Some(AppState::Paused)));
+    world.run_schedule(StateTransition);
+
+    // Nothing from the replayed ticks should sound or flash again.
+    let mut events = world.resource_mut::<Events<GameplayEvent>>();
+    events.clear();
+    events.send(GameplayEvent::Reset);
+}
+
+/// Compacts `board` if it fails [`validate_board`], warning why.
+fn repair(board: &mut Board) {
+    if let Err(error) = validate_board(board) {
+        warn!("the continued game's board is inconsistent ({error}), compacting it");
+        board.compact();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::components::board::BoardError;
+    use crate::components::garbage::GARBAGE_CELL;
+    use crate::components::scoring::Score;
+    use crate::components::tetromino::PieceQueue;
+    use crate::game::{ActivePiece, GamePlugin, Hold};
+    use crate::replay::tests::playback_app;
+    use crate::replay::ReplayPlugin;
+    use crate::ui::attract::demo_replay;
+
+    /// The demo game cut off part way through, as an autosave taken then
+    /// would hold it.
+    fn mid_game() -> Replay {
+        let mut replay = demo_replay();
+        replay.length = replay.length.min(900);
+        replay.inputs.retain(|&(tick, _)| tick < replay.length);
+        replay
+    }
+
+    #[test]
+    fn test_continuing_an_autosave_restores_the_game() {
+        let saved = mid_game();
+        let mut played = playback_app(saved.clone(), AppState::Playing);
+        while !played.world.resource::<ReplayPlayback>().paused {
+            played.world.run_schedule(FixedUpdate);
+        }
+        assert!(!played.world.resource::<Board>().is_empty());
+        assert_eq!(validate_board(played.world.resource::<Board>()), Ok(()));
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin, ReplayPlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        app.update();
+        let loaded = Replay::from_ron(&saved.to_ron()).unwrap();
+        resume(&mut app.world, loaded);
+
+        assert_eq!(
+            *app.world.resource::<State<AppState>>().get(),
+            AppState::Paused
+        );
+        assert_eq!(
+            app.world.resource::<Board>(),
+            played.world.resource::<Board>()
+        );
+        assert_eq!(
+            app.world.resource::<Score>(),
+            played.world.resource::<Score>()
+        );
+        assert_eq!(
+            app.world.resource::<Hold>(),
+            played.world.resource::<Hold>()
+        );
+        assert_eq!(
+            app.world.resource::<ActivePiece>(),
+            played.world.resource::<ActivePiece>()
+        );
+        let upcoming = |app: &App| -> Vec<_> {
+            app.world
+                .resource::<PieceQueue>()
+                .peek(PieceQueue::PREVIEW_LEN)
+                .collect()
+        };
+        assert_eq!(upcoming(&app), upcoming(&played));
+        assert!(!app.world.contains_resource::<ReplayPlayback>());
+        assert_eq!(app.world.resource::<ReplayRecorder>().0, saved);
+    }
+
+    #[test]
+    fn test_a_malformed_board_is_caught_and_compacted() {
+        let mut board = Board::default();
+        board.set(0, 0, Some(GARBAGE_CELL));
+        board.set(1, 0, Some(GARBAGE_CELL + 5));
+        board.set(3, 2, Some(0));
+        board.set(4, 4, Some(1));
+        assert_eq!(
+            validate_board(&board),
+            Err(BoardError::UnknownCell {
+                x: 1,
+                y: 0,
+                cell: GARBAGE_CELL + 5
+            })
+        );
+        board.set(1, 0, Some(2));
+        assert_eq!(
+            validate_board(&board),
+            Err(BoardError::FloatingRow { y: 1 })
+        );
+
+        board.set(1, 0, Some(GARBAGE_CELL + 5));
+        repair(&mut board);
+        assert_eq!(validate_board(&board), Ok(()));
+        assert_eq!(board.get(1, 0), Some(GARBAGE_CELL));
+        assert_eq!(board.get(3, 1), Some(0));
+        assert_eq!(board.get(4, 2), Some(1));
+        assert_eq!(board.get(3, 2), None);
+        assert_eq!(board.get(4, 4), None);
+
+        let mut valid = board.clone();
+        repair(&mut valid);
+        assert_eq!(valid, board);
+    }
+
+    #[test]
+    fn test_continue_is_offered_only_with_an_autosave() {
+        let mut board = Board::default();
+        board.set(0, 0, Some(GARBAGE_CELL));
+        let preview = AutosavePreview(Some(board.to_ascii()));
+        assert_eq!(Autosave::default().continue_preview(&preview), None);
+        assert_eq!(
+            Autosave::default().continue_preview(&AutosavePreview::default()),
+            None
+        );
+
+        let autosave = Autosave(Some(mid_game()));
+        let shown = autosave.continue_preview(&preview).unwrap();
+        assert!(shown.lines().last().unwrap().starts_with('#'));
+        assert_eq!(
+            autosave.continue_preview(&AutosavePreview::default()),
+            Some("")
+        );
+    }
+}
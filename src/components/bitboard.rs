@@ -0,0 +1,245 @@
+//! A compact, colourless copy of the board for searching placements.
+//!
+//! Each row is a bitmask with bit `x` set when column `x` is filled, so
+//! collision tests and full-row checks are a few bit operations and a copy
+//! of the whole board is a few hundred bytes. The AI searches on these and
+//! leaves the colour-aware [`Board`] to the game and the renderer.
+
+use super::board::{Board, Playfield};
+use super::garbage::GARBAGE_CELL;
+use super::tetromino::Tetromino;
+
+/// Which cells of a [`Board`] are filled, one bitmask per row.
+///
+/// Row 0 is the bottom, as on the [`Board`] it was taken from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitBoard {
+    width: usize,
+    visible_height: usize,
+    rows: Vec<u32>,
+}
+
+impl BitBoard {
+    /// Widest board a row mask can hold.
+    pub const MAX_WIDTH: usize = u32::BITS as usize;
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Total rows including the hidden buffer.
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn visible_height(&self) -> usize {
+        self.visible_height
+    }
+
+    /// The mask of a completely filled row.
+    pub fn full_row(&self) -> u32 {
+        u32::MAX >> (Self::MAX_WIDTH - self.width)
+    }
+
+    /// The filled columns of row `y`, or none off the board.
+    pub fn row(&self, y: usize) -> u32 {
+        self.rows.get(y).copied().unwrap_or(0)
+    }
+
+    pub fn is_filled(&self, x: i32, y: i32) -> bool {
+        self.is_in_bounds(x, y) && self.rows[y as usize] & 1 << x != 0
+    }
+
+    pub fn is_in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|&row| row == 0)
+    }
+
+    /// One more than the row of each column's topmost block, left to
+    /// right, or 0 for an empty column.
+    pub fn column_heights(&self) -> Vec<u32> {
+        (0..self.width)
+            .map(|x| {
+                self.rows
+                    .iter()
+                    .rposition(|&row| row & 1 << x != 0)
+                    .map_or(0, |y| y as u32 + 1)
+            })
+            .collect()
+    }
+
+    /// Empty cells with a block anywhere above them in the same column.
+    pub fn holes(&self) -> u32 {
+        self.column_heights()
+            .into_iter()
+            .enumerate()
+            .map(|(x, top)| {
+                self.rows[..top as usize]
+                    .iter()
+                    .filter(|&&row| row & 1 << x == 0)
+                    .count() as u32
+            })
+            .sum()
+    }
+
+    /// Whether any cell of `piece` is off the board or already filled.
+    pub fn collides(&self, piece: &Tetromino) -> bool {
+        piece
+            .cells()
+            .into_iter()
+            .any(|(x, y)| !self.is_in_bounds(x, y) || self.rows[y as usize] & 1 << x != 0)
+    }
+
+    /// How many rows `piece` can fall before it lands.
+    pub fn drop_distance(&self, piece: &Tetromino) -> i32 {
+        let mut distance = 0;
+        while !self.collides(&piece.moved(0, -(distance + 1))) {
+            distance += 1;
+        }
+        distance
+    }
+
+    /// Where `piece` would come to rest if dropped straight down.
+    pub fn landing_position(&self, piece: &Tetromino) -> Tetromino {
+        piece.moved(0, -self.drop_distance(piece))
+    }
+
+    /// Fills the cells of `piece` that are on the board.
+    pub fn lock_piece(&mut self, piece: &Tetromino) {
+        for (x, y) in piece.cells() {
+            if self.is_in_bounds(x, y) {
+                self.rows[y as usize] |= 1 << x;
+            }
+        }
+    }
+
+    /// Bitmask of the completed rows, bit `y` set when row `y` is full.
+    pub fn full_rows(&self) -> u64 {
+        let full = self.full_row();
+        self.rows
+            .iter()
+            .enumerate()
+            .filter(|&(_, &row)| row == full)
+            .fold(0, |mask, (y, _)| mask | 1 << y)
+    }
+
+    /// Removes every completed row, shifting the rows above down, and
+    /// returns how many rows were cleared.
+    pub fn clear_lines(&mut self) -> usize {
+        let full = self.full_row();
+        let height = self.rows.len();
+        self.rows.retain(|&row| row != full);
+        let cleared = height - self.rows.len();
+        self.rows.resize(height, 0);
+        cleared
+    }
+
+    /// A [`Board`] with the same cells filled. Colours aren't kept, so every
+    /// block comes back as garbage.
+    pub fn to_board(&self) -> Board {
+        let mut board = Board::new(self.width, self.visible_height);
+        for (y, &row) in self.rows.iter().enumerate() {
+            for x in 0..self.width {
+                if row & 1 << x != 0 {
+                    board.set(x as i32, y as i32, Some(GARBAGE_CELL));
+                }
+            }
+        }
+        board
+    }
+}
+
+impl Playfield for BitBoard {
+    fn is_filled(&self, x: i32, y: i32) -> bool {
+        BitBoard::is_filled(self, x, y)
+    }
+
+    fn collides(&self, piece: &Tetromino) -> bool {
+        BitBoard::collides(self, piece)
+    }
+}
+
+impl From<&Board> for BitBoard {
+    /// # Panics
+    ///
+    /// If `board` is wider than [`BitBoard::MAX_WIDTH`].
+    fn from(board: &Board) -> Self {
+        assert!(
+            board.width() <= Self::MAX_WIDTH,
+            "a {}-column board doesn't fit a bitboard row",
+            board.width()
+        );
+        let rows = (0..board.height() as i32)
+            .map(|y| {
+                (0..board.width() as i32)
+                    .filter(|&x| board.get(x, y).is_some())
+                    .fold(0, |row, x| row | 1 << x)
+            })
+            .collect();
+        Self {
+            width: board.width(),
+            visible_height: board.visible_height(),
+            rows,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::components::tetromino::PieceKind;
+
+    /// A board whose bottom rows are filled at random, some of them all the
+    /// way across.
+    fn random_board(rng: &mut ChaCha8Rng) -> Board {
+        let mut board = Board::new(rng.gen_range(4..=12), 20);
+        let stack = rng.gen_range(0..16);
+        for y in 0..stack {
+            let complete = rng.gen_bool(0.3);
+            for x in 0..board.width() as i32 {
+                if complete || rng.gen_bool(0.6) {
+                    board.set(x, y, Some(rng.gen_range(0..=GARBAGE_CELL)));
+                }
+            }
+        }
+        board
+    }
+
+    #[test]
+    fn test_matches_the_board_on_random_fixtures() {
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        for _ in 0..200 {
+            let mut board = random_board(&mut rng);
+            let mut bits = BitBoard::from(&board);
+            assert_eq!(BitBoard::from(&bits.to_board()), bits);
+            assert_eq!(bits.is_empty(), board.is_empty());
+
+            for _ in 0..50 {
+                let kind = PieceKind::ALL[rng.gen_range(0..PieceKind::ALL.len())];
+                let mut piece = Tetromino::spawn(kind, &board);
+                piece.rotation = rng.gen_range(0..4);
+                piece.x = rng.gen_range(-3..board.width() as i32 + 1);
+                piece.y = rng.gen_range(-1..board.height() as i32 + 2);
+                assert_eq!(bits.collides(&piece), board.collides(&piece), "{piece:?}");
+                if !board.collides(&piece) {
+                    assert_eq!(bits.drop_distance(&piece), board.drop_distance(&piece));
+                }
+            }
+
+            let piece = board.landing_position(&Tetromino::spawn(PieceKind::I, &board));
+            if !board.collides(&piece) {
+                board.lock_piece(&piece);
+                bits.lock_piece(&piece);
+            }
+            assert_eq!(bits.full_rows(), board.full_rows());
+            assert_eq!(bits.clear_lines(), board.clear_lines());
+            assert_eq!(bits, BitBoard::from(&board));
+        }
+    }
+}
@@ -0,0 +1,444 @@
+//! The playfield grid, collision detection and line clearing.
+
+use std::fmt;
+
+use bevy::prelude::*;
+
+use super::garbage::GARBAGE_CELL;
+use super::tetromino::{PieceKind, Tetromino};
+
+/// Columns on a standard board.
+pub const BOARD_WIDTH: usize = 10;
+/// Rows shown to the player.
+pub const VISIBLE_HEIGHT: usize = 20;
+/// Narrowest board a piece fits across, the length of an I.
+pub const MIN_BOARD_WIDTH: usize = 4;
+/// Hidden rows above the visible field that pieces spawn into and can be
+/// rotated through.
+pub const BUFFER_ROWS: usize = 20;
+
+/// What moving and turning a piece needs to know about the cells it moves
+/// through, so the rules work the same on a [`Board`] and on the colourless
+/// copies the AI searches.
+pub trait Playfield {
+    /// Whether the cell is in bounds and filled.
+    fn is_filled(&self, x: i32, y: i32) -> bool;
+
+    /// Whether any cell of `piece` is off the field or already filled.
+    fn collides(&self, piece: &Tetromino) -> bool;
+}
+
+/// A playfield with a second piece in play on it, as two players' pieces
+/// share one board in co-op. The other piece's cells count as filled, so
+/// neither piece lands through the other; with no other piece it is the
+/// field alone.
+#[derive(Debug, Clone, Copy)]
+pub struct SharedField<'a, P> {
+    field: &'a P,
+    other: Option<&'a Tetromino>,
+}
+
+impl<'a, P: Playfield> SharedField<'a, P> {
+    pub fn new(field: &'a P, other: Option<&'a Tetromino>) -> Self {
+        Self { field, other }
+    }
+
+    /// How many rows `piece` can fall before it lands on the field or the
+    /// other piece.
+    pub fn drop_distance(&self, piece: &Tetromino) -> i32 {
+        let mut distance = 0;
+        while !self.collides(&piece.moved(0, -(distance + 1))) {
+            distance += 1;
+        }
+        distance
+    }
+
+    /// Where `piece` would come to rest if dropped straight down.
+    pub fn landing_position(&self, piece: &Tetromino) -> Tetromino {
+        piece.moved(0, -self.drop_distance(piece))
+    }
+}
+
+impl<P: Playfield> Playfield for SharedField<'_, P> {
+    fn is_filled(&self, x: i32, y: i32) -> bool {
+        self.field.is_filled(x, y)
+            || self
+                .other
+                .is_some_and(|other| other.cells().contains(&(x, y)))
+    }
+
+    fn collides(&self, piece: &Tetromino) -> bool {
+        self.field.collides(piece)
+            || self.other.is_some_and(|other| {
+                let cells = other.cells();
+                piece.cells().iter().any(|cell| cells.contains(cell))
+            })
+    }
+}
+
+/// The locked cells of the playfield.
+///
+/// Each cell holds the [`PieceKind`](super::tetromino::PieceKind) index of
+/// the piece that filled it. Row 0 is the bottom of the board and rows grow
+/// upwards, with [`BUFFER_ROWS`] hidden rows above the visible field.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct Board {
+    width: usize,
+    visible_height: usize,
+    cells: Vec<Option<usize>>,
+}
+
+/// Why a board couldn't be made: it is narrower than [`MIN_BOARD_WIDTH`],
+/// so an I piece couldn't spawn flat on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardTooNarrow {
+    pub width: usize,
+}
+
+impl fmt::Display for BoardTooNarrow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a {}-column board is narrower than the {MIN_BOARD_WIDTH} columns a piece needs",
+            self.width
+        )
+    }
+}
+
+impl std::error::Error for BoardTooNarrow {}
+
+/// Why a board, read back from a file, couldn't have come out of play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardError {
+    /// A cell holds neither a piece kind nor garbage.
+    UnknownCell { x: usize, y: usize, cell: usize },
+    /// Row `y` is empty under blocks. Every piece rests on the floor or on
+    /// a block and clears only take whole rows out, so the filled rows of
+    /// a played board always run unbroken from the bottom.
+    FloatingRow { y: usize },
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownCell { x, y, cell } => {
+                write!(f, "cell ({x}, {y}) holds unknown block {cell}")
+            }
+            Self::FloatingRow { y } => write!(f, "row {y} is empty under blocks"),
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+/// Checks `board` for cells and gaps play can't leave behind, or
+/// [`Board::compact`] repairs.
+pub fn validate_board(board: &Board) -> Result<(), BoardError> {
+    let mut empty_below = None;
+    for y in 0..board.height() {
+        let row = board.row(y);
+        if let Some(x) = row
+            .iter()
+            .position(|cell| cell.is_some_and(|cell| cell > GARBAGE_CELL))
+        {
+            let cell = row[x].unwrap_or_default();
+            return Err(BoardError::UnknownCell { x, y, cell });
+        }
+        if row.iter().all(Option::is_none) {
+            empty_below.get_or_insert(y);
+        } else if let Some(y) = empty_below {
+            return Err(BoardError::FloatingRow { y });
+        }
+    }
+    Ok(())
+}
+
+impl Board {
+    /// # Panics
+    ///
+    /// If `width` is below [`MIN_BOARD_WIDTH`]; see [`Self::try_new`].
+    pub fn new(width: usize, visible_height: usize) -> Self {
+        Self::try_new(width, visible_height).unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// An empty board, or an error if it is too narrow to spawn pieces on.
+    pub fn try_new(width: usize, visible_height: usize) -> Result<Self, BoardTooNarrow> {
+        if width < MIN_BOARD_WIDTH {
+            return Err(BoardTooNarrow { width });
+        }
+        Ok(Self {
+            width,
+            visible_height,
+            cells: vec![None; width * (visible_height + BUFFER_ROWS)],
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Total rows including the hidden buffer.
+    pub fn height(&self) -> usize {
+        self.cells.len() / self.width
+    }
+
+    pub fn visible_height(&self) -> usize {
+        self.visible_height
+    }
+
+    pub fn is_in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height()
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> Option<usize> {
+        if self.is_in_bounds(x, y) {
+            self.cells[self.index(x, y)]
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, cell: Option<usize>) {
+        if self.is_in_bounds(x, y) {
+            let index = self.index(x, y);
+            self.cells[index] = cell;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.iter().all(Option::is_none)
+    }
+
+    /// Whether any cell of `piece` is outside the board or overlaps a
+    /// locked cell.
+    pub fn collides(&self, piece: &Tetromino) -> bool {
+        piece
+            .cells()
+            .iter()
+            .any(|&(x, y)| !self.is_in_bounds(x, y) || self.cells[self.index(x, y)].is_some())
+    }
+
+    /// How many rows `piece` can fall before it lands.
+    pub fn drop_distance(&self, piece: &Tetromino) -> i32 {
+        let mut distance = 0;
+        while !self.collides(&piece.moved(0, -(distance + 1))) {
+            distance += 1;
+        }
+        distance
+    }
+
+    /// Where `piece` would come to rest if dropped straight down.
+    pub fn landing_position(&self, piece: &Tetromino) -> Tetromino {
+        piece.moved(0, -self.drop_distance(piece))
+    }
+
+    /// Solidifies `piece` into the board.
+    pub fn lock_piece(&mut self, piece: &Tetromino) {
+        for (x, y) in piece.cells() {
+            self.set(x, y, Some(piece.kind.index()));
+        }
+    }
+
+    pub fn is_row_full(&self, y: usize) -> bool {
+        self.row(y).iter().all(Option::is_some)
+    }
+
+    /// Bitmask of the completed rows, bit `y` set when row `y` is full.
+    pub fn full_rows(&self) -> u64 {
+        (0..self.height())
+            .filter(|&y| self.is_row_full(y))
+            .fold(0, |mask, y| mask | 1 << y)
+    }
+
+    /// Removes every completed row, shifting the rows above down, and
+    /// returns how many rows were cleared.
+    pub fn clear_lines(&mut self) -> usize {
+        let mut cleared = 0;
+        for y in 0..self.height() {
+            if self.is_row_full(y) {
+                cleared += 1;
+            } else if cleared > 0 {
+                let from = y * self.width;
+                self.cells
+                    .copy_within(from..from + self.width, from - cleared * self.width);
+            }
+        }
+        let len = self.cells.len();
+        self.cells[len - cleared * self.width..].fill(None);
+        cleared
+    }
+
+    /// Pushes every row up one and fills the bottom row with garbage, all
+    /// but column `gap`. Returns `false`, leaving the board as it was, if a
+    /// block on the top row would be pushed off.
+    pub fn raise(&mut self, gap: usize) -> bool {
+        if self.row(self.height() - 1).iter().any(Option::is_some) {
+            return false;
+        }
+        let len = self.cells.len();
+        self.cells.copy_within(0..len - self.width, self.width);
+        for x in 0..self.width {
+            self.cells[x] = (x != gap).then_some(GARBAGE_CELL);
+        }
+        true
+    }
+
+    /// Makes the board one [`validate_board`] accepts: unknown cells become
+    /// garbage and empty rows under blocks are taken out, the rows above
+    /// falling to fill them. Returns how many rows fell.
+    pub fn compact(&mut self) -> usize {
+        for cell in self.cells.iter_mut().flatten() {
+            if *cell > GARBAGE_CELL {
+                *cell = GARBAGE_CELL;
+            }
+        }
+        let top = (0..self.height())
+            .rev()
+            .find(|&y| self.row(y).iter().any(Option::is_some))
+            .map_or(0, |y| y + 1);
+        let mut removed = 0;
+        for y in 0..top {
+            if self.row(y).iter().all(Option::is_none) {
+                removed += 1;
+            } else if removed > 0 {
+                let from = y * self.width;
+                self.cells
+                    .copy_within(from..from + self.width, from - removed * self.width);
+            }
+        }
+        self.cells[(top - removed) * self.width..top * self.width].fill(None);
+        removed
+    }
+
+    /// Rows holding any garbage.
+    pub fn garbage_rows(&self) -> usize {
+        (0..self.height())
+            .filter(|&y| self.row(y).contains(&Some(GARBAGE_CELL)))
+            .count()
+    }
+
+    /// The visible rows from the top down, one line each: `.` for an empty
+    /// cell, the piece letter for a locked block and `#` for garbage.
+    pub fn to_ascii(&self) -> String {
+        let mut ascii = String::with_capacity((self.width + 1) * self.visible_height);
+        for y in (0..self.visible_height).rev() {
+            for cell in self.row(y) {
+                ascii.push(match cell {
+                    None => '.',
+                    Some(index) => PieceKind::from_index(*index).map_or('#', PieceKind::letter),
+                });
+            }
+            ascii.push('\n');
+        }
+        ascii
+    }
+
+    fn row(&self, y: usize) -> &[Option<usize>] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        y as usize * self.width + x as usize
+    }
+}
+
+impl Playfield for Board {
+    fn is_filled(&self, x: i32, y: i32) -> bool {
+        self.get(x, y).is_some()
+    }
+
+    fn collides(&self, piece: &Tetromino) -> bool {
+        Board::collides(self, piece)
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new(BOARD_WIDTH, VISIBLE_HEIGHT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::tetromino::RotationSystem;
+
+    #[test]
+    fn test_boards_too_narrow_for_an_i_piece_are_refused() {
+        assert_eq!(Board::try_new(3, 20), Err(BoardTooNarrow { width: 3 }));
+        let board = Board::try_new(MIN_BOARD_WIDTH, 20).unwrap();
+        for system in RotationSystem::ALL {
+            let piece = Tetromino::spawn_in(PieceKind::I, system, &board);
+            assert!(piece.cells().iter().all(|&(x, y)| board.is_in_bounds(x, y)));
+            assert!(!board.collides(&piece));
+        }
+    }
+
+    #[test]
+    fn test_line_clearing() {
+        let mut board = Board::default();
+        for x in 0..10 {
+            board.set(x, 0, Some(0));
+        }
+        board.set(3, 1, Some(2));
+
+        assert_eq!(board.clear_lines(), 1);
+        assert_eq!(board.get(3, 0), Some(2));
+        assert_eq!(board.get(0, 0), None);
+        assert_eq!(board.get(3, 1), None);
+    }
+
+    #[test]
+    fn test_garbage_rises_within_the_board() {
+        let mut board = Board::default();
+        board.set(2, 0, Some(3));
+        assert!(board.raise(4));
+        assert_eq!(board.height(), VISIBLE_HEIGHT + BUFFER_ROWS);
+        assert_eq!(board.get(2, 1), Some(3));
+        assert_eq!(board.get(4, 0), None);
+        assert!((0..10)
+            .filter(|&x| x != 4)
+            .all(|x| board.get(x, 0) == Some(GARBAGE_CELL)));
+
+        let top = board.height() as i32 - 1;
+        board.set(0, top, Some(1));
+        let full = board.clone();
+        assert!(!board.raise(0));
+        assert_eq!(board, full);
+    }
+
+    #[test]
+    fn test_collision_with_walls_and_stack() {
+        let mut board = Board::default();
+        let piece = Tetromino::spawn(PieceKind::T, &board);
+        assert!(!board.collides(&piece));
+        assert!(board.collides(&piece.moved(-4, 0)));
+
+        let landed = board.landing_position(&piece);
+        assert_eq!(board.drop_distance(&piece), piece.y - landed.y);
+        board.lock_piece(&landed);
+        assert!(board.collides(&landed));
+        assert!(board.collides(&landed.moved(0, 1)));
+    }
+
+    #[test]
+    fn test_a_piece_lands_on_the_other_piece_in_play() {
+        let board = Board::default();
+        let piece = Tetromino::spawn(PieceKind::O, &board);
+        let alone = SharedField::new(&board, None);
+        assert_eq!(alone.drop_distance(&piece), board.drop_distance(&piece));
+
+        // The other player's O resting on the floor right under this one.
+        let other = board.landing_position(&piece);
+        let shared = SharedField::new(&board, Some(&other));
+        let landed = shared.landing_position(&piece);
+        assert_eq!(landed.y, other.y + 2);
+        assert!(!shared.collides(&landed));
+        assert!(shared.collides(&landed.moved(0, -1)));
+        assert!(other.cells().iter().all(|&(x, y)| shared.is_filled(x, y)));
+
+        // Beside it, the other piece is no obstacle.
+        let beside = piece.moved(3, 0);
+        assert_eq!(shared.drop_distance(&beside), board.drop_distance(&beside));
+    }
+}
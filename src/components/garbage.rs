@@ -0,0 +1,67 @@
+//! Garbage rows: part-filled rows that rise into the board from below.
+
+use bevy::prelude::*;
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use super::board::Board;
+use super::tetromino::PieceKind;
+
+/// The [`Board`] cell value of a garbage block, just past the piece kinds.
+pub const GARBAGE_CELL: usize = PieceKind::ALL.len();
+
+/// Why garbage could not rise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GarbageOverflow;
+
+/// Garbage rows waiting to rise into the board, each with a single gap in
+/// a column drawn from the game's seed.
+#[derive(Resource, Debug, Clone)]
+pub struct GarbageQueue {
+    pending: u32,
+    rng: ChaCha8Rng,
+}
+
+impl GarbageQueue {
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            pending: 0,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Rows waiting to rise.
+    pub fn pending(&self) -> u32 {
+        self.pending
+    }
+
+    pub fn push(&mut self, rows: u32) {
+        self.pending += rows;
+    }
+
+    /// Takes back up to `rows` pending rows, as clearing lines does.
+    pub fn cancel(&mut self, rows: u32) {
+        self.pending = self.pending.saturating_sub(rows);
+    }
+
+    /// Raises every pending row into `board`, returning how many rose. Fails
+    /// once the stack would be pushed off the top of the board, leaving the
+    /// rows that fit in place.
+    pub fn insert(&mut self, board: &mut Board) -> Result<u32, GarbageOverflow> {
+        let rows = std::mem::take(&mut self.pending);
+        for _ in 0..rows {
+            let gap = self.rng.gen_range(0..board.width());
+            if !board.raise(gap) {
+                return Err(GarbageOverflow);
+            }
+        }
+        Ok(rows)
+    }
+}
+
+impl Default for GarbageQueue {
+    fn default() -> Self {
+        Self::with_seed(rand::random())
+    }
+}
@@ -0,0 +1,463 @@
+//! Score tracking, level progression and the saved high scores.
+
+use std::cmp::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::tetromino::RandomizerKind;
+use crate::game::{CheeseRows, GameMode, Modifiers, SprintGoal};
+use crate::utils::config::TICKS_PER_SECOND;
+use crate::utils::storage;
+
+/// Lines needed to advance one level.
+pub const LINES_PER_LEVEL: u32 = 10;
+
+/// Most soft-drop points one piece earns under the Guideline, a full
+/// well's fall.
+pub const SOFT_DROP_CAP: u32 = 20;
+
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct Score {
+    pub points: u32,
+    pub lines: u32,
+    pub level: u32,
+    /// Pieces locked so far.
+    pub pieces: u32,
+}
+
+/// Which table points are scored from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ScoringRules {
+    /// 100, 300, 500 and 800 points a level for one to four lines, one a
+    /// row soft-dropped and two a row hard-dropped.
+    #[default]
+    Guideline,
+    /// The NES table of 40, 100, 300 and 1200 points a level, and one a row
+    /// for any drop.
+    Classic,
+}
+
+impl ScoringRules {
+    pub const ALL: [ScoringRules; 2] = [ScoringRules::Guideline, ScoringRules::Classic];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            ScoringRules::Guideline => "scoring.guideline",
+            ScoringRules::Classic => "scoring.classic",
+        }
+    }
+
+    /// Points for clearing `lines` rows at once on `level`.
+    pub fn line_clear_points(self, lines: u32, level: u32) -> u32 {
+        let table = match self {
+            ScoringRules::Guideline => [0, 100, 300, 500, 800],
+            ScoringRules::Classic => [0, 40, 100, 300, 1200],
+        };
+        table[lines.min(4) as usize] * level
+    }
+
+    /// Points for each row hard-dropped.
+    fn hard_drop_row(self) -> u32 {
+        match self {
+            ScoringRules::Guideline => 2,
+            ScoringRules::Classic => 1,
+        }
+    }
+}
+
+impl Score {
+    /// Records `cleared` lines, returning `true` when this levels up.
+    pub fn add_lines(&mut self, cleared: u32, rules: ScoringRules) -> bool {
+        self.points += rules.line_clear_points(cleared, self.level);
+        self.lines += cleared;
+        let level = self.lines / LINES_PER_LEVEL + 1;
+        let levelled_up = level > self.level;
+        self.level = self.level.max(level);
+        levelled_up
+    }
+
+    /// One point per row the player soft-drops.
+    pub fn add_soft_drop(&mut self, rows: u32) {
+        self.points += rows;
+    }
+
+    /// Points for the rows the player hard-drops, two each under
+    /// [`ScoringRules::Guideline`].
+    pub fn add_hard_drop(&mut self, rows: u32, rules: ScoringRules) {
+        self.points += rows * rules.hard_drop_row();
+    }
+}
+
+impl Default for Score {
+    fn default() -> Self {
+        Self {
+            points: 0,
+            lines: 0,
+            level: 1,
+            pieces: 0,
+        }
+    }
+}
+
+/// Entries kept in [`HighScores`].
+pub const HIGH_SCORE_LEN: usize = 10;
+
+/// A finished game on the high-score table, with the rules it was played
+/// under so leaderboards can tell them apart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighScoreEntry {
+    pub points: u32,
+    pub lines: u32,
+    pub level: u32,
+    pub seed: u64,
+    pub mode: GameMode,
+    #[serde(default)]
+    pub modifiers: Modifiers,
+    #[serde(default)]
+    pub randomizer: RandomizerKind,
+    #[serde(default)]
+    pub scoring: ScoringRules,
+    /// Seconds since the Unix epoch when the game ended, 0 for entries
+    /// saved before the time was kept.
+    #[serde(default)]
+    pub recorded_at: u64,
+    /// Pieces locked over the game.
+    #[serde(default)]
+    pub pieces: u32,
+}
+
+impl HighScoreEntry {
+    /// The order entries rank in, best first: more points, then the
+    /// earlier of two equal scores, then the one that took fewer pieces.
+    pub fn rank(&self, other: &Self) -> Ordering {
+        other
+            .points
+            .cmp(&self.points)
+            .then(self.recorded_at.cmp(&other.recorded_at))
+            .then(self.pieces.cmp(&other.pieces))
+    }
+}
+
+/// Seconds since the Unix epoch, for [`HighScoreEntry::recorded_at`].
+pub fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// The fastest finish of a sprint goal under a set of modifiers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SprintRecord {
+    pub goal: SprintGoal,
+    pub modifiers: Modifiers,
+    pub ticks: u32,
+    pub seed: u64,
+}
+
+/// The fastest dig through a cheese race's rows under a set of modifiers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheeseRecord {
+    pub rows: CheeseRows,
+    pub modifiers: Modifiers,
+    pub ticks: u32,
+    pub seed: u64,
+}
+
+/// The best finished games, highest first, and the best sprint and cheese
+/// race times. Saved between runs.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighScores {
+    entries: Vec<HighScoreEntry>,
+    #[serde(default)]
+    sprints: Vec<SprintRecord>,
+    #[serde(default)]
+    cheese: Vec<CheeseRecord>,
+}
+
+impl HighScores {
+    const FOLDER: &'static str = "scores";
+    const FILE: &'static str = "high_scores.ron";
+
+    /// The saved table, or an empty one if none has been saved or it can't
+    /// be read, reporting to `app` a table that doesn't parse.
+    pub fn load(app: &mut App) -> Self {
+        let mut scores: Self = storage::read_ron(app, Self::FOLDER, Self::FILE).unwrap_or_default();
+        scores.entries.sort_by(HighScoreEntry::rank);
+        scores
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        storage::write_ron(Self::FOLDER, Self::FILE, self)
+    }
+
+    pub fn entries(&self) -> &[HighScoreEntry] {
+        &self.entries
+    }
+
+    /// Adds `entry` in [`HighScoreEntry::rank`] order, dropping the lowest
+    /// past [`HIGH_SCORE_LEN`]. Returns whether it made the table.
+    pub fn insert(&mut self, entry: HighScoreEntry) -> bool {
+        let rank = self
+            .entries
+            .partition_point(|other| other.rank(&entry) != Ordering::Greater);
+        if rank >= HIGH_SCORE_LEN {
+            return false;
+        }
+        self.entries.insert(rank, entry);
+        self.entries.truncate(HIGH_SCORE_LEN);
+        true
+    }
+
+    /// The best entry played under `mode`, `modifiers` and `scoring`.
+    pub fn best(
+        &self,
+        mode: GameMode,
+        modifiers: Modifiers,
+        scoring: ScoringRules,
+    ) -> Option<&HighScoreEntry> {
+        self.entries.iter().find(|entry| {
+            entry.mode == mode && entry.modifiers == modifiers && entry.scoring == scoring
+        })
+    }
+
+    /// Keeps `record` if it is the fastest yet for its goal and modifiers.
+    /// Returns whether it was.
+    pub fn record_sprint(&mut self, record: SprintRecord) -> bool {
+        keep_fastest(
+            &mut self.sprints,
+            record,
+            |best, record| best.goal == record.goal && best.modifiers == record.modifiers,
+            |record| record.ticks,
+        )
+    }
+
+    /// The fastest finish of `goal` under `modifiers`.
+    pub fn best_sprint(&self, goal: SprintGoal, modifiers: Modifiers) -> Option<&SprintRecord> {
+        self.sprints
+            .iter()
+            .find(|best| best.goal == goal && best.modifiers == modifiers)
+    }
+
+    /// Keeps `record` if it is the fastest yet for its rows and modifiers.
+    /// Returns whether it was.
+    pub fn record_cheese(&mut self, record: CheeseRecord) -> bool {
+        keep_fastest(
+            &mut self.cheese,
+            record,
+            |best, record| best.rows == record.rows && best.modifiers == record.modifiers,
+            |record| record.ticks,
+        )
+    }
+
+    /// The fastest dig through `rows` under `modifiers`.
+    pub fn best_cheese(&self, rows: CheeseRows, modifiers: Modifiers) -> Option<&CheeseRecord> {
+        self.cheese
+            .iter()
+            .find(|best| best.rows == rows && best.modifiers == modifiers)
+    }
+}
+
+/// Puts `record` in place of the record in `records` that `same` pairs it
+/// with if it took fewer `ticks`, or adds it if there is none. Returns
+/// whether it was kept.
+fn keep_fastest<T>(
+    records: &mut Vec<T>,
+    record: T,
+    same: impl Fn(&T, &T) -> bool,
+    ticks: impl Fn(&T) -> u32,
+) -> bool {
+    match records.iter_mut().find(|best| same(best, &record)) {
+        Some(best) if ticks(best) <= ticks(&record) => false,
+        Some(best) => {
+            *best = record;
+            true
+        }
+        None => {
+            records.push(record);
+            true
+        }
+    }
+}
+
+/// Ticks between gravity drops on `level`, following the Guideline curve
+/// `(0.8 - (level - 1) * 0.007) ^ (level - 1)` seconds per row.
+pub fn gravity_interval(level: u32) -> u32 {
+    let level = level.clamp(1, 20) as f64;
+    let seconds = (0.8 - (level - 1.0) * 0.007).powf(level - 1.0);
+    ((seconds * TICKS_PER_SECOND).round() as u32).max(1)
+}
+
+/// Ticks between garbage rows in survival once `elapsed` ticks have
+/// passed: eight seconds at first, a tenth shorter every 30 seconds, down
+/// to one second.
+pub fn survival_interval(elapsed: u32) -> u32 {
+    let steps = elapsed / (30 * TICKS_PER_SECOND as u32);
+    let seconds = (8.0 * 0.9_f64.powi(steps.min(64) as i32)).max(1.0);
+    (seconds * TICKS_PER_SECOND).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(points: u32, modifiers: Modifiers) -> HighScoreEntry {
+        HighScoreEntry {
+            points,
+            lines: 0,
+            level: 1,
+            seed: 0,
+            mode: GameMode::Marathon,
+            modifiers,
+            randomizer: RandomizerKind::default(),
+            scoring: ScoringRules::default(),
+            recorded_at: 0,
+            pieces: 0,
+        }
+    }
+
+    #[test]
+    fn test_tied_scores_rank_earlier_then_fewer_pieces_first() {
+        let tied = |recorded_at, pieces| HighScoreEntry {
+            recorded_at,
+            pieces,
+            ..entry(1000, default())
+        };
+        let mut scores = HighScores::default();
+        for entry in [
+            tied(300, 50),
+            tied(100, 80),
+            entry(2000, default()),
+            tied(100, 60),
+            tied(200, 40),
+        ] {
+            scores.insert(entry);
+        }
+        let order: Vec<_> = scores
+            .entries()
+            .iter()
+            .map(|entry| (entry.points, entry.recorded_at, entry.pieces))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (2000, 0, 0),
+                (1000, 100, 60),
+                (1000, 100, 80),
+                (1000, 200, 40),
+                (1000, 300, 50),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_high_scores_rank_and_keep_modifiers_apart() {
+        let no_hold = Modifiers {
+            no_hold: true,
+            ..default()
+        };
+        let mut scores = HighScores::default();
+        assert!(scores.insert(entry(500, default())));
+        assert!(scores.insert(entry(900, no_hold)));
+        assert!(scores.insert(entry(700, default())));
+        let points: Vec<_> = scores.entries().iter().map(|entry| entry.points).collect();
+        assert_eq!(points, [900, 700, 500]);
+        assert_eq!(
+            scores
+                .best(GameMode::Marathon, default(), default())
+                .unwrap()
+                .points,
+            700
+        );
+        assert_eq!(
+            scores
+                .best(GameMode::Marathon, no_hold, default())
+                .unwrap()
+                .points,
+            900
+        );
+        assert!(scores.best(GameMode::Zen, default(), default()).is_none());
+
+        for points in 1000..1000 + HIGH_SCORE_LEN as u32 {
+            scores.insert(entry(points, default()));
+        }
+        assert_eq!(scores.entries().len(), HIGH_SCORE_LEN);
+        assert!(!scores.insert(entry(1, default())));
+        assert!(scores
+            .best(GameMode::Marathon, default(), ScoringRules::Classic)
+            .is_none());
+    }
+
+    #[test]
+    fn test_sprint_times_are_kept_per_goal() {
+        let record = |goal, ticks| SprintRecord {
+            goal,
+            modifiers: default(),
+            ticks,
+            seed: 0,
+        };
+        let mut scores = HighScores::default();
+        assert!(scores.record_sprint(record(SprintGoal::Lines20, 3000)));
+        assert!(scores.record_sprint(record(SprintGoal::Lines40, 9000)));
+        assert!(!scores.record_sprint(record(SprintGoal::Lines20, 3600)));
+        assert!(scores.record_sprint(record(SprintGoal::Lines20, 2400)));
+
+        let best = |goal| scores.best_sprint(goal, default()).map(|best| best.ticks);
+        assert_eq!(best(SprintGoal::Lines20), Some(2400));
+        assert_eq!(best(SprintGoal::Lines40), Some(9000));
+        assert_eq!(best(SprintGoal::Lines100), None);
+        let no_hold = Modifiers {
+            no_hold: true,
+            ..default()
+        };
+        assert!(scores.best_sprint(SprintGoal::Lines20, no_hold).is_none());
+    }
+
+    #[test]
+    fn test_cheese_times_are_kept_per_row_count() {
+        let record = |rows, ticks| CheeseRecord {
+            rows,
+            modifiers: default(),
+            ticks,
+            seed: 0,
+        };
+        let mut scores = HighScores::default();
+        assert!(scores.record_cheese(record(CheeseRows::Rows10, 4000)));
+        assert!(!scores.record_cheese(record(CheeseRows::Rows10, 4200)));
+        assert!(scores.record_cheese(record(CheeseRows::Rows10, 3000)));
+        assert!(scores.record_cheese(record(CheeseRows::Rows5, 2000)));
+
+        let best = |rows| scores.best_cheese(rows, default()).map(|best| best.ticks);
+        assert_eq!(best(CheeseRows::Rows10), Some(3000));
+        assert_eq!(best(CheeseRows::Rows5), Some(2000));
+        assert_eq!(best(CheeseRows::Rows18), None);
+        assert!(scores.best_sprint(SprintGoal::Lines40, default()).is_none());
+    }
+
+    #[test]
+    fn test_guideline_and_classic_tables() {
+        let guideline = ScoringRules::Guideline;
+        assert_eq!(guideline.line_clear_points(1, 1), 100);
+        assert_eq!(guideline.line_clear_points(4, 1), 800);
+        assert_eq!(guideline.line_clear_points(2, 5), 1500);
+        assert_eq!(guideline.line_clear_points(3, 10), 5000);
+
+        let classic = ScoringRules::Classic;
+        assert_eq!(classic.line_clear_points(1, 1), 40);
+        assert_eq!(classic.line_clear_points(4, 1), 1200);
+        assert_eq!(classic.line_clear_points(2, 5), 500);
+        assert_eq!(classic.line_clear_points(3, 10), 3000);
+        assert_eq!(classic.line_clear_points(0, 10), 0);
+
+        let mut modern = Score::default();
+        let mut nes = Score::default();
+        for (score, rules) in [(&mut modern, guideline), (&mut nes, classic)] {
+            score.add_soft_drop(5);
+            score.add_hard_drop(10, rules);
+        }
+        assert_eq!(modern.points, 25);
+        assert_eq!(nes.points, 15);
+    }
+}
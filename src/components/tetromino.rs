@@ -0,0 +1,853 @@
+//! Tetromino shapes, the Super and Arika Rotation Systems and the piece
+//! queue with its randomizers.
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use super::board::{Board, Playfield};
+
+/// The seven standard Tetris pieces.
+///
+/// The discriminant doubles as the colour index stored in [`Board`] cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceKind {
+    I,
+    O,
+    T,
+    S,
+    Z,
+    J,
+    L,
+}
+
+impl PieceKind {
+    pub const ALL: [PieceKind; 7] = [
+        PieceKind::I,
+        PieceKind::O,
+        PieceKind::T,
+        PieceKind::S,
+        PieceKind::Z,
+        PieceKind::J,
+        PieceKind::L,
+    ];
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    pub fn from_index(index: usize) -> Option<Self> {
+        Self::ALL.get(index).copied()
+    }
+
+    pub fn letter(self) -> char {
+        match self {
+            PieceKind::I => 'I',
+            PieceKind::O => 'O',
+            PieceKind::T => 'T',
+            PieceKind::S => 'S',
+            PieceKind::Z => 'Z',
+            PieceKind::J => 'J',
+            PieceKind::L => 'L',
+        }
+    }
+
+    /// Side length of the square box the piece rotates inside.
+    pub fn box_size(self) -> i32 {
+        match self {
+            PieceKind::I => 4,
+            PieceKind::O => 2,
+            _ => 3,
+        }
+    }
+
+    /// Cells of the spawn orientation, as `(column, row)` with rows counted
+    /// downwards from the top of the rotation box.
+    fn spawn_cells(self) -> [(i32, i32); 4] {
+        match self {
+            PieceKind::I => [(0, 1), (1, 1), (2, 1), (3, 1)],
+            PieceKind::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+            PieceKind::T => [(1, 0), (0, 1), (1, 1), (2, 1)],
+            PieceKind::S => [(1, 0), (2, 0), (0, 1), (1, 1)],
+            PieceKind::Z => [(0, 0), (1, 0), (1, 1), (2, 1)],
+            PieceKind::J => [(0, 0), (0, 1), (1, 1), (2, 1)],
+            PieceKind::L => [(2, 0), (0, 1), (1, 1), (2, 1)],
+        }
+    }
+
+    /// Cells of the piece in the given rotation state (0 = spawn, 1 = R,
+    /// 2 = 180°, 3 = L), in rotation-box coordinates with rows counted down.
+    pub fn shape(self, rotation: u8) -> [(i32, i32); 4] {
+        let size = self.box_size();
+        let mut cells = self.spawn_cells();
+        for _ in 0..rotation % 4 {
+            for cell in &mut cells {
+                *cell = (size - 1 - cell.1, cell.0);
+            }
+        }
+        cells
+    }
+
+    /// Cells of the piece in the given rotation state under `system`, as
+    /// [`Self::shape`] gives them for SRS.
+    pub fn shape_in(self, system: RotationSystem, rotation: u8) -> [(i32, i32); 4] {
+        match system {
+            RotationSystem::Srs => self.shape(rotation),
+            RotationSystem::Ars => {
+                let (state, drop) = self.ars_state(rotation);
+                self.shape(state).map(|(column, row)| (column, row + drop))
+            }
+        }
+    }
+
+    /// The SRS state an ARS rotation state is drawn from, and how many rows
+    /// it sits lower in the box.
+    fn ars_state(self, rotation: u8) -> (u8, i32) {
+        let rotation = rotation % 4;
+        match self {
+            PieceKind::O => (0, 0),
+            // Two states: flat on the second row, or standing in the
+            // column right of centre.
+            PieceKind::I => (rotation % 2, 0),
+            // Two states: flat on the bottom rows, or standing on the left
+            // (S) or right (Z).
+            PieceKind::S => ([2, 3][rotation as usize % 2], 0),
+            PieceKind::Z => ([2, 1][rotation as usize % 2], 0),
+            // Four states starting flat side up, all resting on the bottom
+            // row of the box.
+            PieceKind::T | PieceKind::J | PieceKind::L => match rotation {
+                0 => (2, 0),
+                1 => (3, 0),
+                2 => (0, 1),
+                _ => (1, 0),
+            },
+        }
+    }
+}
+
+/// How pieces spawn, turn and kick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RotationSystem {
+    /// The Super Rotation System of the guideline games.
+    #[default]
+    Srs,
+    /// The Arika Rotation System of the Grand Master series: pieces rest on
+    /// the bottom of their box, T, J and L spawn flat side up, and the only
+    /// kicks are a single column either way, so nothing kicks off the
+    /// floor.
+    Ars,
+}
+
+impl RotationSystem {
+    pub const ALL: [RotationSystem; 2] = [RotationSystem::Srs, RotationSystem::Ars];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            RotationSystem::Srs => "rotation.srs",
+            RotationSystem::Ars => "rotation.ars",
+        }
+    }
+}
+
+/// SRS wall kicks for J, L, S, T and Z, indexed by [`transition_index`].
+/// Offsets are `(dx, dy)` with positive `dy` pointing up the board.
+const JLSTZ_KICKS: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+];
+
+/// SRS wall kicks for the I piece, indexed by [`transition_index`].
+const I_KICKS: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+];
+
+const NO_KICKS: [(i32, i32); 1] = [(0, 0)];
+
+/// ARS kicks for every piece but I and O: in place, then one column right,
+/// then one left.
+const ARS_KICKS: [(i32, i32); 3] = [(0, 0), (1, 0), (-1, 0)];
+
+/// Maps a rotation `from -> to` onto a row of the kick tables, in the
+/// order 0→R, R→0, R→2, 2→R, 2→L, L→2, L→0, 0→L.
+fn transition_index(from: u8, to: u8) -> usize {
+    match (from % 4, to % 4) {
+        (0, 1) => 0,
+        (1, 0) => 1,
+        (1, 2) => 2,
+        (2, 1) => 3,
+        (2, 3) => 4,
+        (3, 2) => 5,
+        (3, 0) => 6,
+        (0, 3) => 7,
+        (from, to) => unreachable!("no quarter-turn from {from} to {to}"),
+    }
+}
+
+/// The kick offsets SRS tries, in order, when rotating `kind` from one
+/// rotation state to an adjacent one.
+pub fn kick_offsets(kind: PieceKind, from: u8, to: u8) -> &'static [(i32, i32)] {
+    match kind {
+        PieceKind::O => &NO_KICKS,
+        PieceKind::I => &I_KICKS[transition_index(from, to)],
+        _ => &JLSTZ_KICKS[transition_index(from, to)],
+    }
+}
+
+/// The kick offsets ARS tries, in order, when rotating `kind`.
+pub fn ars_kick_offsets(kind: PieceKind) -> &'static [(i32, i32)] {
+    match kind {
+        PieceKind::I | PieceKind::O => &NO_KICKS,
+        _ => &ARS_KICKS,
+    }
+}
+
+/// A piece on the board: its kind, rotation, the system it turns under and
+/// the board position of the top-left cell of its rotation box.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tetromino {
+    pub kind: PieceKind,
+    pub rotation: u8,
+    pub system: RotationSystem,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Tetromino {
+    /// Creates an SRS piece centred at the top of `board`, with the bottom
+    /// row of its spawn orientation on the highest visible row.
+    pub fn spawn(kind: PieceKind, board: &Board) -> Self {
+        Self::spawn_in(kind, RotationSystem::Srs, board)
+    }
+
+    /// Creates a piece turning under `system`, placed as [`Self::spawn`]
+    /// places it.
+    pub fn spawn_in(kind: PieceKind, system: RotationSystem, board: &Board) -> Self {
+        Self::spawn_sized(kind, system, board.width(), board.visible_height())
+    }
+
+    /// Places a piece as [`Self::spawn_in`] does on a board `width` columns
+    /// wide with `visible_height` rows shown.
+    pub fn spawn_sized(
+        kind: PieceKind,
+        system: RotationSystem,
+        width: usize,
+        visible_height: usize,
+    ) -> Self {
+        let size = kind.box_size();
+        let bottom = kind
+            .shape_in(system, 0)
+            .iter()
+            .map(|&(_, row)| row)
+            .max()
+            .unwrap_or(0);
+        Self {
+            kind,
+            rotation: 0,
+            system,
+            // Centred, or flush left on a board too narrow to centre on,
+            // which Board refuses to make.
+            x: ((width as i32 - size) / 2).max(0),
+            y: visible_height as i32 - 1 + bottom,
+        }
+    }
+
+    /// Absolute board coordinates of the four cells, with `y` growing upwards.
+    pub fn cells(&self) -> [(i32, i32); 4] {
+        self.kind
+            .shape_in(self.system, self.rotation)
+            .map(|(column, row)| (self.x + column, self.y - row))
+    }
+
+    pub fn moved(&self, dx: i32, dy: i32) -> Self {
+        Self {
+            x: self.x + dx,
+            y: self.y + dy,
+            ..*self
+        }
+    }
+
+    /// The piece rotated a quarter turn in place, without any kicks.
+    pub fn rotated(&self, clockwise: bool) -> Self {
+        let turn = if clockwise { 1 } else { 3 };
+        Self {
+            rotation: (self.rotation + turn) % 4,
+            ..*self
+        }
+    }
+
+    /// Rotates using the kicks of the piece's rotation system, returning the
+    /// first position that fits on `board` together with the kick offset
+    /// that was applied.
+    pub fn rotate_with_kicks(
+        &self,
+        board: &impl Playfield,
+        clockwise: bool,
+    ) -> Option<(Self, (i32, i32))> {
+        let rotated = self.rotated(clockwise);
+        let kicks = match self.system {
+            RotationSystem::Srs => kick_offsets(self.kind, self.rotation, rotated.rotation),
+            RotationSystem::Ars if rotated.blocked_in_centre_column(board) => &NO_KICKS,
+            RotationSystem::Ars => ars_kick_offsets(self.kind),
+        };
+        kicks
+            .iter()
+            .map(|&(dx, dy)| (rotated.moved(dx, dy), (dx, dy)))
+            .find(|(candidate, _)| !board.collides(candidate))
+    }
+
+    /// The ARS centre column rule: a J, L or T whose first blocked cell,
+    /// reading its box from the top left, is in the middle column doesn't
+    /// kick.
+    fn blocked_in_centre_column(&self, board: &impl Playfield) -> bool {
+        if !matches!(self.kind, PieceKind::J | PieceKind::L | PieceKind::T) {
+            return false;
+        }
+        let mut cells = self.kind.shape_in(self.system, self.rotation);
+        cells.sort_by_key(|&(column, row)| (row, column));
+        cells
+            .into_iter()
+            .find(|&(column, row)| board.is_filled(self.x + column, self.y - row))
+            .is_some_and(|(column, _)| column == 1)
+    }
+}
+
+/// Pieces that can't lie flat on an empty floor without an overhang.
+pub const OVERHANG_KINDS: [PieceKind; 3] = [PieceKind::S, PieceKind::Z, PieceKind::O];
+
+/// Deals pieces one at a time, drawing whatever randomness it needs from
+/// the queue's RNG.
+pub trait Randomizer {
+    fn next(&mut self, rng: &mut ChaCha8Rng) -> PieceKind;
+
+    /// The first piece of the game when it mustn't be one of the
+    /// [`OVERHANG_KINDS`]. Redraws until it isn't, by default.
+    fn safe_first(&mut self, rng: &mut ChaCha8Rng) -> PieceKind {
+        loop {
+            let kind = self.next(rng);
+            if !OVERHANG_KINDS.contains(&kind) {
+                return kind;
+            }
+        }
+    }
+}
+
+/// Every piece drawn independently, so droughts and floods are possible.
+#[derive(Debug, Clone, Default)]
+pub struct PureRandom;
+
+impl Randomizer for PureRandom {
+    fn next(&mut self, rng: &mut ChaCha8Rng) -> PieceKind {
+        PieceKind::ALL[rng.gen_range(0..PieceKind::ALL.len())]
+    }
+}
+
+/// Shuffled bags holding each of the seven kinds once, or `copies` times
+/// each for the smoother 14-piece and larger bags some rulesets deal from.
+#[derive(Debug, Clone)]
+pub struct SevenBag {
+    /// What is left of the current bag, dealt from the front.
+    bag: VecDeque<PieceKind>,
+    copies: usize,
+}
+
+impl SevenBag {
+    pub fn with_copies(copies: u8) -> Self {
+        let copies = copies.max(1) as usize;
+        Self {
+            bag: VecDeque::with_capacity(PieceKind::ALL.len() * copies),
+            copies,
+        }
+    }
+
+    /// Refills the bag and shuffles it in place, so a refill doesn't
+    /// allocate.
+    fn refill(&mut self, rng: &mut ChaCha8Rng) {
+        self.bag.clear();
+        for _ in 0..self.copies {
+            self.bag.extend(PieceKind::ALL);
+        }
+        self.bag.make_contiguous().shuffle(rng);
+    }
+}
+
+impl Default for SevenBag {
+    fn default() -> Self {
+        Self::with_copies(1)
+    }
+}
+
+impl Randomizer for SevenBag {
+    fn next(&mut self, rng: &mut ChaCha8Rng) -> PieceKind {
+        if self.bag.is_empty() {
+            self.refill(rng);
+        }
+        self.bag.pop_front().expect("bags are never empty")
+    }
+
+    /// Reshuffles the whole first bag rather than redrawing, so it still
+    /// holds every kind as often as the rest.
+    fn safe_first(&mut self, rng: &mut ChaCha8Rng) -> PieceKind {
+        self.refill(rng);
+        while OVERHANG_KINDS.contains(&self.bag[0]) {
+            self.refill(rng);
+        }
+        self.next(rng)
+    }
+}
+
+/// The Grand Master randomizer: each piece is rerolled up to
+/// [`TgmHistory::ROLLS`] times while it matches one of the last four dealt,
+/// which makes repeats rare without forbidding them.
+#[derive(Debug, Clone)]
+pub struct TgmHistory {
+    /// The last four pieces dealt, most recent last.
+    history: VecDeque<PieceKind>,
+}
+
+impl TgmHistory {
+    pub const ROLLS: usize = 4;
+    /// Pieces the first deal is drawn from, as in the arcade game.
+    const FIRST: [PieceKind; 4] = [PieceKind::I, PieceKind::J, PieceKind::L, PieceKind::T];
+
+    fn deal(&mut self, kind: PieceKind) -> PieceKind {
+        self.history.pop_front();
+        self.history.push_back(kind);
+        kind
+    }
+}
+
+impl Default for TgmHistory {
+    /// A history of Zs, as the arcade game starts with.
+    fn default() -> Self {
+        Self {
+            history: [PieceKind::Z; 4].into(),
+        }
+    }
+}
+
+impl Randomizer for TgmHistory {
+    fn next(&mut self, rng: &mut ChaCha8Rng) -> PieceKind {
+        let mut kind = PureRandom.next(rng);
+        for _ in 1..Self::ROLLS {
+            if !self.history.contains(&kind) {
+                break;
+            }
+            kind = PureRandom.next(rng);
+        }
+        self.deal(kind)
+    }
+
+    fn safe_first(&mut self, rng: &mut ChaCha8Rng) -> PieceKind {
+        let kind = Self::FIRST[rng.gen_range(0..Self::FIRST.len())];
+        self.deal(kind)
+    }
+}
+
+/// Which [`Randomizer`] a [`PieceQueue`] deals with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RandomizerKind {
+    PureRandom,
+    #[default]
+    SevenBag,
+    TgmHistory,
+}
+
+impl RandomizerKind {
+    pub const ALL: [RandomizerKind; 3] = [
+        RandomizerKind::PureRandom,
+        RandomizerKind::SevenBag,
+        RandomizerKind::TgmHistory,
+    ];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            RandomizerKind::PureRandom => "randomizer.pure_random",
+            RandomizerKind::SevenBag => "randomizer.seven_bag",
+            RandomizerKind::TgmHistory => "randomizer.tgm_history",
+        }
+    }
+
+    fn build(self, bag_multiplier: u8) -> AnyRandomizer {
+        match self {
+            RandomizerKind::PureRandom => AnyRandomizer::PureRandom(PureRandom),
+            RandomizerKind::SevenBag => {
+                AnyRandomizer::SevenBag(SevenBag::with_copies(bag_multiplier))
+            }
+            RandomizerKind::TgmHistory => AnyRandomizer::TgmHistory(TgmHistory::default()),
+        }
+    }
+}
+
+/// The randomizer a [`PieceQueue`] holds, kept as an enum so the queue
+/// stays a plain cloneable resource.
+#[derive(Debug, Clone)]
+enum AnyRandomizer {
+    PureRandom(PureRandom),
+    SevenBag(SevenBag),
+    TgmHistory(TgmHistory),
+}
+
+impl AnyRandomizer {
+    fn get(&mut self) -> &mut dyn Randomizer {
+        match self {
+            AnyRandomizer::PureRandom(randomizer) => randomizer,
+            AnyRandomizer::SevenBag(randomizer) => randomizer,
+            AnyRandomizer::TgmHistory(randomizer) => randomizer,
+        }
+    }
+}
+
+/// Options for how [`PieceQueue`] deals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct QueueRules {
+    /// Keep the first piece from being one of the [`OVERHANG_KINDS`].
+    pub safe_first_piece: bool,
+    #[serde(default)]
+    pub randomizer: RandomizerKind,
+    /// Copies of each kind in a [`SevenBag`].
+    #[serde(default = "one_copy")]
+    pub bag_multiplier: u8,
+}
+
+impl QueueRules {
+    /// The largest [`Self::bag_multiplier`] the settings offer.
+    pub const MAX_BAG_MULTIPLIER: u8 = 4;
+}
+
+impl Default for QueueRules {
+    fn default() -> Self {
+        Self {
+            safe_first_piece: false,
+            randomizer: RandomizerKind::default(),
+            bag_multiplier: one_copy(),
+        }
+    }
+}
+
+fn one_copy() -> u8 {
+    1
+}
+
+/// The upcoming pieces, dealt by the [`Randomizer`] the rules choose.
+///
+/// The queue owns its own seeded RNG so a seed fully determines the
+/// sequence of pieces.
+#[derive(Resource, Debug, Clone)]
+pub struct PieceQueue {
+    seed: u64,
+    rng: ChaCha8Rng,
+    randomizer: AnyRandomizer,
+    upcoming: VecDeque<PieceKind>,
+    dealt: u64,
+}
+
+impl PieceQueue {
+    /// Number of upcoming pieces shown in the next preview.
+    pub const PREVIEW_LEN: usize = 5;
+
+    pub fn new() -> Self {
+        Self::with_seed(rand::random(), QueueRules::default())
+    }
+
+    /// A queue whose pieces are fully determined by `seed` and `rules`.
+    pub fn with_seed(seed: u64, rules: QueueRules) -> Self {
+        let mut queue = Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            randomizer: rules.randomizer.build(rules.bag_multiplier),
+            upcoming: VecDeque::with_capacity(Self::PREVIEW_LEN + 1),
+            dealt: 0,
+        };
+        if rules.safe_first_piece {
+            // Redrawing uses the same RNG, so the seed still decides the
+            // whole sequence.
+            let first = queue.randomizer.get().safe_first(&mut queue.rng);
+            queue.upcoming.push_back(first);
+        }
+        queue.refill();
+        queue
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Pieces dealt so far, less any put back.
+    pub fn dealt(&self) -> u64 {
+        self.dealt
+    }
+
+    /// Removes and returns the next piece, topping up the queue so the
+    /// preview never runs dry.
+    pub fn next_piece(&mut self) -> PieceKind {
+        let kind = self
+            .upcoming
+            .pop_front()
+            .expect("piece queue is refilled before it empties");
+        self.dealt += 1;
+        self.refill();
+        kind
+    }
+
+    /// Puts `kind` back at the front of the queue, to be dealt next.
+    pub fn push_front(&mut self, kind: PieceKind) {
+        self.upcoming.push_front(kind);
+        self.dealt = self.dealt.saturating_sub(1);
+    }
+
+    /// The next `count` pieces without removing them.
+    pub fn peek(&self, count: usize) -> impl Iterator<Item = PieceKind> + '_ {
+        self.upcoming.iter().copied().take(count)
+    }
+
+    fn refill(&mut self) {
+        while self.upcoming.len() <= Self::PREVIEW_LEN {
+            let kind = self.randomizer.get().next(&mut self.rng);
+            self.upcoming.push_back(kind);
+        }
+    }
+}
+
+impl Default for PieceQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tetromino_rotation() {
+        for kind in PieceKind::ALL {
+            let piece = Tetromino::spawn(kind, &Board::default());
+            let mut rotated = piece;
+            for _ in 0..4 {
+                rotated = rotated.rotated(true);
+            }
+            assert_eq!(rotated.cells(), piece.cells());
+            assert_eq!(piece.rotated(true).rotated(false), piece);
+        }
+    }
+
+    #[test]
+    fn test_wall_kick_off_left_wall() {
+        let board = Board::default();
+        let mut piece = Tetromino::spawn(PieceKind::I, &board).rotated(false);
+        while !board.collides(&piece.moved(-1, 0)) {
+            piece = piece.moved(-1, 0);
+        }
+        let (kicked, kick) = piece.rotate_with_kicks(&board, true).unwrap();
+        assert_ne!(kick, (0, 0));
+        assert!(kicked.cells().iter().all(|&(x, _)| x >= 0));
+    }
+
+    /// A T-spin triple slot standing in column 4 with its nub in column 3,
+    /// under a roof at (4, 4), and a T pointing down just above it.
+    fn t_spin_triple_setup(system: RotationSystem) -> (Board, Tetromino, [(i32, i32); 4]) {
+        let mut board = Board::default();
+        let slot = [(3, 1), (4, 0), (4, 1), (4, 2)];
+        for y in 0..3 {
+            for x in 0..10 {
+                if !slot.contains(&(x, y)) {
+                    board.set(x, y, Some(0));
+                }
+            }
+        }
+        board.set(4, 4, Some(0));
+        // ARS's spawn state is SRS's 180° state, so both systems hold the
+        // T in the same cells.
+        let rotation = match system {
+            RotationSystem::Srs => 2,
+            RotationSystem::Ars => 0,
+        };
+        let piece = Tetromino {
+            rotation,
+            x: 3,
+            y: 4,
+            ..Tetromino::spawn_in(PieceKind::T, system, &board)
+        };
+        (board, piece, slot)
+    }
+
+    #[test]
+    fn test_srs_kicks_into_a_t_spin_triple_that_ars_refuses() {
+        let (board, srs, slot) = t_spin_triple_setup(RotationSystem::Srs);
+        let (_, ars, _) = t_spin_triple_setup(RotationSystem::Ars);
+        assert!(!board.collides(&srs));
+        assert_eq!(srs.cells(), ars.cells());
+
+        let (spun, kick) = srs.rotate_with_kicks(&board, true).unwrap();
+        assert_eq!(kick, (0, -2));
+        let mut cells = spun.cells();
+        cells.sort_unstable();
+        assert_eq!(cells, slot);
+
+        // The roof blocks the centre column, so ARS doesn't even try its
+        // sideways kicks, and it has no downward ones to try.
+        assert_eq!(ars.rotate_with_kicks(&board, true), None);
+    }
+
+    #[test]
+    fn test_ars_spawns_flat_side_up_resting_on_the_top_row() {
+        let board = Board::default();
+        let top = board.visible_height() as i32 - 1;
+        let t = Tetromino::spawn_in(PieceKind::T, RotationSystem::Ars, &board);
+        let mut cells = t.cells();
+        cells.sort_unstable();
+        assert_eq!(cells, [(3, top + 1), (4, top), (4, top + 1), (5, top + 1)]);
+        for kind in PieceKind::ALL {
+            let piece = Tetromino::spawn_in(kind, RotationSystem::Ars, &board);
+            let lowest = piece.cells().iter().map(|&(_, y)| y).min();
+            assert_eq!(lowest, Some(top), "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn test_queue_deals_whole_bags() {
+        let mut queue = PieceQueue::with_seed(7, default());
+        for _ in 0..3 {
+            let mut bag: Vec<usize> = (0..7).map(|_| queue.next_piece().index()).collect();
+            bag.sort_unstable();
+            assert_eq!(bag, (0..7).collect::<Vec<_>>());
+        }
+        let replay = PieceQueue::with_seed(7, default());
+        let mut fresh = PieceQueue::with_seed(7, default());
+        assert!(replay.peek(5).eq((0..5).map(|_| fresh.next_piece())));
+    }
+
+    #[test]
+    fn test_safe_first_piece_never_opens_with_an_overhang() {
+        let rules = QueueRules {
+            safe_first_piece: true,
+            ..default()
+        };
+        for seed in 0..500 {
+            let mut queue = PieceQueue::with_seed(seed, rules);
+            let first = queue.next_piece();
+            assert!(
+                !OVERHANG_KINDS.contains(&first),
+                "seed {seed} opens with {first:?}"
+            );
+            let mut bag: Vec<usize> = std::iter::once(first)
+                .chain((1..7).map(|_| queue.next_piece()))
+                .map(PieceKind::index)
+                .collect();
+            bag.sort_unstable();
+            assert_eq!(bag, (0..7).collect::<Vec<_>>());
+            let again = PieceQueue::with_seed(seed, rules);
+            assert_eq!(again.peek(1).next(), Some(first));
+        }
+        let opens_with_overhang = (0..100).any(|seed| {
+            let mut queue = PieceQueue::with_seed(seed, default());
+            OVERHANG_KINDS.contains(&queue.next_piece())
+        });
+        assert!(opens_with_overhang);
+    }
+
+    fn deal(randomizer: RandomizerKind, count: usize) -> Vec<PieceKind> {
+        let rules = QueueRules {
+            randomizer,
+            ..default()
+        };
+        let mut queue = PieceQueue::with_seed(3, rules);
+        (0..count).map(|_| queue.next_piece()).collect()
+    }
+
+    fn repeats(pieces: &[PieceKind]) -> usize {
+        pieces.windows(2).filter(|pair| pair[0] == pair[1]).count()
+    }
+
+    #[test]
+    fn test_pure_random_can_repeat_and_flood() {
+        let pieces = deal(RandomizerKind::PureRandom, 700);
+        assert!(repeats(&pieces) > 50);
+        // Some stretch of seven is missing a kind, which no bag allows.
+        assert!(pieces
+            .chunks(7)
+            .any(|chunk| { PieceKind::ALL.iter().any(|kind| !chunk.contains(kind)) }));
+    }
+
+    #[test]
+    fn test_seven_bag_keeps_every_kind_within_bag_bounds() {
+        let pieces = deal(RandomizerKind::SevenBag, 700);
+        for chunk in pieces.chunks(7) {
+            assert!(PieceKind::ALL.iter().all(|kind| chunk.contains(kind)));
+        }
+        // The longest a kind can wait is from the start of one bag to the
+        // end of the next.
+        for kind in PieceKind::ALL {
+            let seen: Vec<usize> = (0..pieces.len()).filter(|&i| pieces[i] == kind).collect();
+            assert!(seen.windows(2).all(|pair| pair[1] - pair[0] <= 13));
+        }
+        assert_eq!(
+            deal(RandomizerKind::SevenBag, 20),
+            deal(RandomizerKind::default(), 20)
+        );
+    }
+
+    #[test]
+    fn test_a_double_bag_deals_each_kind_twice_a_bag() {
+        let rules = QueueRules {
+            bag_multiplier: 2,
+            ..default()
+        };
+        let mut queue = PieceQueue::with_seed(3, rules);
+        let pieces: Vec<_> = (0..1400).map(|_| queue.next_piece()).collect();
+        for kind in PieceKind::ALL {
+            assert_eq!(pieces.iter().filter(|&&dealt| dealt == kind).count(), 200);
+        }
+        for chunk in pieces.chunks(14) {
+            for kind in PieceKind::ALL {
+                assert_eq!(chunk.iter().filter(|&&dealt| dealt == kind).count(), 2);
+            }
+        }
+        // A run of one kind can only outlast a bag by spilling into the
+        // next, so it is never longer than both bags' copies together.
+        let longest = pieces
+            .chunk_by(|a, b| a == b)
+            .map(<[PieceKind]>::len)
+            .max()
+            .unwrap();
+        assert!(longest <= 4);
+        assert!(pieces
+            .chunks(14)
+            .all(|bag| bag.chunk_by(|a, b| a == b).all(|run| run.len() <= 2)));
+    }
+
+    #[test]
+    fn test_tgm_history_mostly_avoids_recent_pieces() {
+        let pieces = deal(RandomizerKind::TgmHistory, 700);
+        let pure = deal(RandomizerKind::PureRandom, 700);
+        assert!(repeats(&pieces) * 4 < repeats(&pure));
+        let rules = QueueRules {
+            safe_first_piece: true,
+            randomizer: RandomizerKind::TgmHistory,
+            ..default()
+        };
+        for seed in 0..100 {
+            let first = PieceQueue::with_seed(seed, rules).next_piece();
+            assert!(TgmHistory::FIRST.contains(&first));
+        }
+    }
+}
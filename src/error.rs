@@ -0,0 +1,194 @@
+//! What can go wrong as the game starts, and the screen that says so.
+//!
+//! Start-up checks [`report`] a [`GameError`] instead of panicking or
+//! carrying on quietly. Most are logged and played through: the game runs
+//! silently without sound, and a saved setting that doesn't parse is left
+//! at its default. A fatal one, translations that don't load or a data
+//! directory that can't be saved to, opens the error screen in place of
+//! the main menu.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::game::AppState;
+use crate::ui::menus::menu_window;
+use crate::utils::i18n::{Language, Locale};
+use crate::utils::storage;
+
+/// Something the game couldn't set up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameError {
+    /// A bundled asset doesn't load.
+    Asset { name: &'static str, reason: String },
+    /// Files can't be read from or written to `path`.
+    Storage { path: PathBuf, reason: String },
+    /// Sound couldn't be started.
+    Audio(String),
+    /// A saved file at `path` doesn't hold valid settings.
+    Config { path: PathBuf, reason: String },
+}
+
+impl GameError {
+    /// Whether the player has to hear about it before playing: the game
+    /// can't show its text without its translations, and can't keep scores
+    /// or settings without somewhere to save them.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, GameError::Asset { .. } | GameError::Storage { .. })
+    }
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::Asset { name, reason } => write!(f, "couldn't load the {name}: {reason}"),
+            GameError::Storage { path, reason } => {
+                write!(f, "couldn't use {}: {reason}", path.display())
+            }
+            GameError::Audio(reason) => write!(f, "playing without sound: {reason}"),
+            GameError::Config { path, reason } => {
+                write!(f, "ignored {}, which isn't valid: {reason}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+/// Every error reported while the game started, in order.
+#[derive(Resource, Debug, Default)]
+pub struct StartupErrors(pub Vec<GameError>);
+
+impl StartupErrors {
+    pub fn fatal(&self) -> impl Iterator<Item = &GameError> {
+        self.0.iter().filter(|error| error.is_fatal())
+    }
+
+    /// Whether the game can be played past the errors, if without saving.
+    pub fn can_continue(&self) -> bool {
+        !self
+            .0
+            .iter()
+            .any(|error| matches!(error, GameError::Asset { .. }))
+    }
+}
+
+/// Logs `error` and records it among the [`StartupErrors`] of the app
+/// being built.
+pub fn report(app: &mut App, error: GameError) {
+    if error.is_fatal() {
+        error!("{error}");
+    } else {
+        warn!("{error}");
+    }
+    app.world
+        .get_resource_or_insert_with(StartupErrors::default)
+        .0
+        .push(error);
+}
+
+/// Loads the translations unless the app already has them, [`report`]ing
+/// why if they don't load and carrying on untranslated.
+pub fn init_locale(app: &mut App) {
+    if app.world.contains_resource::<Locale>() {
+        return;
+    }
+    let locale = Locale::load(Language::default()).unwrap_or_else(|error| {
+        report(
+            app,
+            GameError::Asset {
+                name: "translations",
+                reason: error.to_string(),
+            },
+        );
+        Locale::untranslated()
+    });
+    app.insert_resource(locale);
+}
+
+/// Checks the data directory and loads the translations. Must come before
+/// the plugins that use either.
+pub struct ErrorPlugin;
+
+impl Plugin for ErrorPlugin {
+    fn build(&self, app: &mut App) {
+        if let Err(error) = storage::check_writable(&storage::data_dir()) {
+            report(app, error);
+        }
+        init_locale(app);
+        app.init_resource::<StartupErrors>()
+            .add_systems(Startup, open_error_screen)
+            .add_systems(Update, error_screen.run_if(in_state(AppState::Error)));
+    }
+}
+
+/// Opens the error screen if a fatal error was reported.
+fn open_error_screen(errors: Res<StartupErrors>, mut next_state: ResMut<NextState<AppState>>) {
+    if errors.fatal().next().is_some() {
+        next_state.set(AppState::Error);
+    }
+}
+
+fn error_screen(
+    mut contexts: EguiContexts,
+    locale: Res<Locale>,
+    errors: Res<StartupErrors>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    menu_window(locale.t("error.title")).show(contexts.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            for error in errors.fatal() {
+                ui.colored_label(egui::Color32::LIGHT_RED, error.to_string());
+            }
+            if errors.can_continue() && ui.button(locale.t("error.continue")).clicked() {
+                next_state.set(AppState::MainMenu);
+            }
+            if ui.button(locale.t("menu.quit")).clicked() {
+                exit.send(AppExit);
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::game::GamePlugin;
+
+    #[test]
+    fn test_an_unwritable_data_directory_opens_the_error_screen() {
+        // A directory can't be made under a plain file.
+        let file =
+            std::env::temp_dir().join(format!("rustblocks-{}-not-a-dir", std::process::id()));
+        std::fs::write(&file, "").unwrap();
+        let error = storage::check_writable(&file.join("saves")).unwrap_err();
+        std::fs::remove_file(&file).unwrap();
+        assert!(matches!(&error, GameError::Storage { path, .. } if path == &file.join("saves")));
+        assert!(error.is_fatal());
+
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+            .add_systems(Startup, open_error_screen);
+        report(&mut app, error.clone());
+        report(&mut app, GameError::Audio("no output device".into()));
+        app.update();
+        app.update();
+        assert_eq!(
+            *app.world.resource::<State<AppState>>().get(),
+            AppState::Error
+        );
+        let errors = app.world.resource::<StartupErrors>();
+        assert_eq!(errors.fatal().collect::<Vec<_>>(), [&error]);
+        assert!(errors.can_continue());
+    }
+}
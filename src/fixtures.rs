@@ -0,0 +1,174 @@
+//! Deterministic fixtures shared by the tests: a headless game on a fixed
+//! seed driven by a scripted input sequence, and golden snapshots of the
+//! boards those scripts leave behind.
+//!
+//! A change to gravity, rotation or line clearing that moves where a
+//! script's pieces end up shows up as a mismatch against its golden board.
+
+use bevy::prelude::*;
+
+use crate::components::board::Board;
+use crate::components::tetromino::{PieceKind, Tetromino};
+use crate::game::{ActivePiece, AppState, GameMode, Phase};
+use crate::input::keyboard::Action::{self, *};
+use crate::input::keyboard::ActionState;
+use crate::replay::tests::playback_app;
+use crate::replay::{Replay, ReplayPlayback};
+use crate::utils::config::LINE_CLEAR_DELAY;
+
+/// The seed every scripted game is dealt from, opening with
+/// I, S, J, L, T, Z, O.
+pub(crate) const FIXTURE_SEED: u64 = 42;
+
+/// One step of a script: the actions pressed for a single tick, and the
+/// ticks to wait with nothing held afterwards.
+pub(crate) type Step = (&'static [Action], u32);
+
+/// A tap of `actions` followed by a tick with nothing held, enough for the
+/// next step to register as a fresh press.
+pub(crate) const fn tap(actions: &'static [Action]) -> Step {
+    (actions, 1)
+}
+
+/// A recorded Marathon game on [`FIXTURE_SEED`], under the default rules,
+/// in which the player plays `script`.
+pub(crate) fn scripted_replay(script: &[Step]) -> Replay {
+    let mut replay = Replay::new(
+        FIXTURE_SEED,
+        GameMode::Marathon,
+        default(),
+        &default(),
+        &default(),
+    );
+    for &(actions, wait) in script {
+        let mut held = ActionState::default();
+        held.update(|action| actions.contains(&action));
+        replay.record(held.held_bits());
+        for _ in 0..wait {
+            replay.record(0);
+        }
+    }
+    replay
+}
+
+/// A headless game that has played `script` to the end.
+pub(crate) fn play_script(script: &[Step]) -> App {
+    let mut app = playback_app(scripted_replay(script), AppState::Playing);
+    while !app.world.resource::<ReplayPlayback>().paused {
+        app.world.run_schedule(FixedUpdate);
+    }
+    app
+}
+
+/// Leaves `world`'s board two rows full but for where an O dropped from
+/// spawn lands, with that O falling, so a hard drop clears a double.
+pub(crate) fn set_up_a_double(world: &mut World) {
+    let mut board = Board::default();
+    let piece = Tetromino::spawn(PieceKind::O, &board);
+    let gaps = board.landing_position(&piece).cells();
+    for y in 0..2 {
+        for x in 0..board.width() as i32 {
+            if !gaps.contains(&(x, y)) {
+                board.set(x, y, Some(0));
+            }
+        }
+    }
+    world.insert_resource(board);
+    world.insert_resource(ActivePiece(Some(piece)));
+    world.insert_resource(Phase::Falling);
+}
+
+/// The I slid to the left wall, the S held, then the J and L dropped
+/// beside it to clear the bottom row before a rotated T lands on the
+/// leftovers.
+pub(crate) const LINE_CLEAR_SCRIPT: &[Step] = &[
+    tap(&[MoveLeft]),
+    tap(&[MoveLeft]),
+    tap(&[MoveLeft]),
+    tap(&[HardDrop]),
+    tap(&[Hold]),
+    tap(&[MoveRight]),
+    tap(&[HardDrop]),
+    tap(&[MoveRight]),
+    tap(&[MoveRight]),
+    tap(&[MoveRight]),
+    tap(&[MoveRight]),
+    (&[HardDrop], LINE_CLEAR_DELAY + 1),
+    tap(&[RotateCw]),
+    tap(&[HardDrop]),
+];
+
+pub(crate) const LINE_CLEAR_GOLDEN: &str = "\
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+....T.....
+....TT....
+....T.....
+....J....L
+";
+
+/// The I stood up and moved right, then left to gravity and lock delay
+/// along with the S after it.
+pub(crate) const GRAVITY_SCRIPT: &[Step] = &[
+    tap(&[RotateCw]),
+    tap(&[MoveRight]),
+    tap(&[MoveRight]),
+    (&[MoveRight], 2400),
+];
+
+pub(crate) const GRAVITY_GOLDEN: &str = "\
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+..........
+........I.
+........I.
+....SS..I.
+...SS...I.
+";
+
+mod tests {
+    use super::*;
+
+    fn assert_golden(script: &[Step], golden: &str) {
+        let app = play_script(script);
+        let board = app.world.resource::<Board>().to_ascii();
+        assert_eq!(board, golden, "\n{board}");
+    }
+
+    #[test]
+    fn test_line_clear_matches_its_golden_board() {
+        assert_golden(LINE_CLEAR_SCRIPT, LINE_CLEAR_GOLDEN);
+    }
+
+    #[test]
+    fn test_gravity_matches_its_golden_board() {
+        assert_golden(GRAVITY_SCRIPT, GRAVITY_GOLDEN);
+    }
+}
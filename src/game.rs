@@ -0,0 +1,2720 @@
+//! Game loop and state management.
+//!
+//! Gameplay advances on Bevy's `FixedUpdate` schedule at
+//! [`TICKS_PER_SECOND`], one system per step of the piece life cycle,
+//! chained so every tick resolves input, movement, gravity, locking and
+//! clearing in a fixed order.
+
+use std::collections::VecDeque;
+
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::input::touch::Touches;
+use bevy::prelude::*;
+use bevy::winit::{UpdateMode, WinitSettings};
+use serde::{Deserialize, Serialize};
+
+use crate::components::board::{Board, Playfield, SharedField, BOARD_WIDTH, VISIBLE_HEIGHT};
+use crate::components::garbage::GarbageQueue;
+use crate::components::scoring::{gravity_interval, survival_interval, Score};
+use crate::components::tetromino::{PieceKind, PieceQueue, Tetromino};
+use crate::input::keyboard::{
+    apply_action_events, read_keyboard, Action, ActionEvent, ActionState, KeyBindings,
+};
+use crate::replay::{Replay, ReplayPlayback, ReplayRecorder};
+use crate::utils::checksum::{hash_state, StateHash};
+use crate::utils::config::{
+    InputConfig, LockReset, OpposingDirections, Settings, BIG_BLOCK_SCALE, DISSOLVE_SECS,
+    HARD_DROP_GRACE, LINE_CLEAR_DELAY, LOCK_DELAY, MAX_LOCK_RESETS, TICKS_PER_SECOND,
+    UNDO_HISTORY_LEN, ZEN_LOCK_DELAY,
+};
+use crate::utils::debug::{audit_tick_order, self_check, GAMEPLAY_TARGET};
+
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AppState {
+    #[default]
+    MainMenu,
+    Playing,
+    Paused,
+    /// The board dissolves after a top-out; input is ignored until the
+    /// game-over screen appears.
+    Dissolving,
+    GameOver,
+    /// A recorded game plays back in attract mode.
+    Demo,
+    /// A finished game plays back under the replay controls.
+    Review,
+    /// Something the game needs failed to start, and the error screen says
+    /// what.
+    Error,
+}
+
+/// The two halves of a gameplay tick.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TickSet {
+    /// Turns the keyboard, a replay or the AI into [`ActionEvent`]s and
+    /// folds them into [`ActionState`].
+    Input,
+    /// Advances the piece life cycle from [`ActionState`], in the
+    /// [`TickStage`]s.
+    Simulate,
+}
+
+/// The stages of [`TickSet::Simulate`], run in the order of
+/// [`TickStage::ORDER`].
+///
+/// A tick is input, then these stages, each testing for collisions as it
+/// moves the piece; the board is drawn afterwards in `Update`, which Bevy
+/// runs once the frame's ticks are done. [`audit_tick_order`] checks debug
+/// builds still wire the schedule this way.
+///
+/// [`audit_tick_order`]: crate::utils::debug::audit_tick_order
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TickStage {
+    /// Undo, rising garbage, spawning and holding put a piece in play.
+    Spawn,
+    /// The player's moves, rotations and drops.
+    Move,
+    /// Gravity pulls the piece down until it lands.
+    Fall,
+    /// A landed piece's lock delay runs out and it joins the stack.
+    Lock,
+    /// Completed rows are cleared and scored.
+    Clear,
+    /// Mode clocks count the tick and check their goals.
+    Clock,
+}
+
+impl TickStage {
+    pub const ORDER: [TickStage; 6] = [
+        TickStage::Spawn,
+        TickStage::Move,
+        TickStage::Fall,
+        TickStage::Lock,
+        TickStage::Clear,
+        TickStage::Clock,
+    ];
+}
+
+/// The rule set for the current game, chosen from the main menu.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Endless play with gravity rising every [`LINES_PER_LEVEL`] lines.
+    ///
+    /// [`LINES_PER_LEVEL`]: crate::components::scoring::LINES_PER_LEVEL
+    #[default]
+    Marathon,
+    /// Unhurried practice at level 1 gravity, with undo.
+    Zen,
+    /// Garbage rises from below ever faster; the score is the seconds
+    /// survived.
+    Survival,
+    /// A race to clear the goal's lines at level 1 gravity, timed.
+    Sprint(SprintGoal),
+    /// A race to dig out rows of garbage laid at the start, timed.
+    Cheese(CheeseRows),
+    /// Two players on one keyboard, each on their own board and dealt the
+    /// same pieces, sending each other garbage until one tops out. See
+    /// [`crate::versus`].
+    Versus,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 6] = [
+        GameMode::Marathon,
+        GameMode::Zen,
+        GameMode::Survival,
+        GameMode::Sprint(SprintGoal::Lines40),
+        GameMode::Cheese(CheeseRows::Rows10),
+        GameMode::Versus,
+    ];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            GameMode::Marathon => "mode.marathon",
+            GameMode::Zen => "mode.zen",
+            GameMode::Survival => "mode.survival",
+            GameMode::Sprint(_) => "mode.sprint",
+            GameMode::Cheese(_) => "mode.cheese",
+            GameMode::Versus => "mode.versus",
+        }
+    }
+
+    /// The level whose gravity applies when the score is at `level`.
+    pub fn gravity_level(self, level: u32) -> u32 {
+        match self {
+            GameMode::Marathon | GameMode::Survival | GameMode::Versus => level,
+            GameMode::Zen | GameMode::Sprint(_) | GameMode::Cheese(_) => 1,
+        }
+    }
+
+    /// Ticks a grounded piece waits before it locks: longer in Zen, where
+    /// there's no hurry.
+    pub fn lock_delay(self) -> u32 {
+        match self {
+            GameMode::Zen => ZEN_LOCK_DELAY,
+            GameMode::Marathon
+            | GameMode::Survival
+            | GameMode::Sprint(_)
+            | GameMode::Cheese(_)
+            | GameMode::Versus => LOCK_DELAY,
+        }
+    }
+
+    /// Whether locked pieces can be taken back with [`Action::Undo`], and
+    /// the game kept and gone back to with a [`Checkpoint`].
+    pub fn allows_undo(self) -> bool {
+        self == GameMode::Zen
+    }
+
+    /// Whether [`Settings::freeplay`] applies, leaving pieces to lock only
+    /// when dropped.
+    pub fn allows_freeplay(self) -> bool {
+        self == GameMode::Zen
+    }
+}
+
+/// How many lines a [`GameMode::Sprint`] asks for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SprintGoal {
+    Lines20,
+    #[default]
+    Lines40,
+    Lines100,
+    Lines1000,
+}
+
+impl SprintGoal {
+    pub const ALL: [SprintGoal; 4] = [
+        SprintGoal::Lines20,
+        SprintGoal::Lines40,
+        SprintGoal::Lines100,
+        SprintGoal::Lines1000,
+    ];
+
+    pub fn lines(self) -> u32 {
+        match self {
+            SprintGoal::Lines20 => 20,
+            SprintGoal::Lines40 => 40,
+            SprintGoal::Lines100 => 100,
+            SprintGoal::Lines1000 => 1000,
+        }
+    }
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            SprintGoal::Lines20 => "sprint.20",
+            SprintGoal::Lines40 => "sprint.40",
+            SprintGoal::Lines100 => "sprint.100",
+            SprintGoal::Lines1000 => "sprint.1000",
+        }
+    }
+}
+
+/// How many garbage rows a [`GameMode::Cheese`] race lays down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CheeseRows {
+    Rows5,
+    #[default]
+    Rows10,
+    Rows18,
+}
+
+impl CheeseRows {
+    pub const ALL: [CheeseRows; 3] = [CheeseRows::Rows5, CheeseRows::Rows10, CheeseRows::Rows18];
+
+    /// Rows left clear above the cheese.
+    const HEADROOM: usize = 2;
+
+    pub fn rows(self) -> u32 {
+        match self {
+            CheeseRows::Rows5 => 5,
+            CheeseRows::Rows10 => 10,
+            CheeseRows::Rows18 => 18,
+        }
+    }
+
+    /// The rows laid on `board`, fewer when it's too short to leave
+    /// [`Self::HEADROOM`] above them.
+    pub fn rows_on(self, board: &Board) -> u32 {
+        let room = board.visible_height().saturating_sub(Self::HEADROOM);
+        self.rows().min(room as u32)
+    }
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            CheeseRows::Rows5 => "cheese.5",
+            CheeseRows::Rows10 => "cheese.10",
+            CheeseRows::Rows18 => "cheese.18",
+        }
+    }
+}
+
+/// Self-imposed challenges chosen alongside the [`GameMode`].
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Modifiers {
+    /// The hold key does nothing.
+    pub no_hold: bool,
+    /// The hard drop key does nothing.
+    pub no_hard_drop: bool,
+    /// Locked blocks stop being drawn shortly after they land.
+    pub invisible: bool,
+    /// Every block covers [`BIG_BLOCK_SCALE`] cells each way, on a board
+    /// that many times narrower and shorter.
+    #[serde(default)]
+    pub big: bool,
+}
+
+impl Modifiers {
+    /// Translation keys of the active modifiers' names.
+    pub fn label_keys(self) -> impl Iterator<Item = &'static str> {
+        [
+            (self.no_hold, "modifier.no_hold"),
+            (self.no_hard_drop, "modifier.no_hard_drop"),
+            (self.invisible, "modifier.invisible"),
+            (self.big, "modifier.big"),
+        ]
+        .into_iter()
+        .filter_map(|(active, key)| active.then_some(key))
+    }
+
+    /// Screen cells a board cell covers along each side.
+    pub fn block_scale(self) -> usize {
+        if self.big {
+            BIG_BLOCK_SCALE
+        } else {
+            1
+        }
+    }
+
+    /// An empty board for a game played under these modifiers.
+    pub fn board(self) -> Board {
+        let scale = self.block_scale();
+        Board::new(BOARD_WIDTH / scale, VISIBLE_HEIGHT / scale)
+    }
+}
+
+/// Where the current piece is in its life cycle while [`AppState::Playing`].
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Phase {
+    /// The next piece enters on the coming tick.
+    #[default]
+    Spawning,
+    /// The active piece is airborne and subject to gravity.
+    Falling,
+    /// The active piece is resting on the stack, waiting out its lock delay.
+    Locking,
+    /// A piece just locked and completed rows are being removed, animated
+    /// over [`LINE_CLEAR_DELAY`] ticks unless
+    /// [`Settings::instant_line_clear`] is set.
+    Clearing,
+    /// The stack reached the top; the game is over.
+    ToppedOut,
+    /// The race's goal was reached; the game is over.
+    Finished,
+}
+
+/// The piece the player is controlling, if any.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ActivePiece(pub Option<Tetromino>);
+
+/// A second player's piece on the same board, which the [`ActivePiece`]
+/// lands on rather than through, and its ghost with it. Groundwork for
+/// local co-op: nothing puts a piece here yet, and with none every landing
+/// is the board's alone.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PartnerPiece(pub Option<Tetromino>);
+
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct Hold {
+    pub piece: Option<PieceKind>,
+    /// Cleared when a piece is held and restored once the next piece locks.
+    pub can_hold: bool,
+}
+
+impl Default for Hold {
+    fn default() -> Self {
+        Self {
+            piece: None,
+            can_hold: true,
+        }
+    }
+}
+
+/// Progress of the active piece towards its next gravity drop.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct Gravity {
+    /// Ticks since the piece last fell a row under the level curve, with
+    /// each soft-dropped tick counting [`InputConfig::soft_drop_factor`]
+    /// times.
+    pub ticks: u32,
+    /// Fraction of a row accumulated under custom gravity.
+    pub rows: f32,
+    /// Soft-drop points the piece has earned so far, counted against
+    /// [`Settings::soft_drop_cap`].
+    pub soft_dropped: u32,
+}
+
+impl Gravity {
+    /// Counts `rows` soft-dropped against `cap`, returning how many of them
+    /// still score.
+    pub fn soft_drop(&mut self, rows: u32, cap: Option<u32>) -> u32 {
+        let scored = cap.map_or(rows, |cap| rows.min(cap.saturating_sub(self.soft_dropped)));
+        self.soft_dropped += scored;
+        scored
+    }
+}
+
+#[derive(Resource, Debug, Default, Clone)]
+pub struct LockDelay {
+    pub ticks_left: u32,
+    pub resets: u32,
+    /// Lowest row the piece has landed on, from the bottom of the board.
+    pub lowest: Option<i32>,
+    /// Whether the piece is resting out a hard drop's grace, which moves
+    /// and landings restart at [`HARD_DROP_GRACE`] rather than the mode's
+    /// full delay.
+    pub hard_dropped: bool,
+}
+
+impl LockDelay {
+    /// Starts the delay over at `delay` ticks as the piece lands at height
+    /// `y`, if `policy` gives a landing there a fresh delay.
+    fn land(&mut self, y: i32, policy: LockReset, delay: u32) {
+        let fresh = match policy {
+            LockReset::Move => true,
+            LockReset::Step => self.lowest.is_none_or(|lowest| y < lowest),
+            LockReset::Never => self.lowest.is_none(),
+        };
+        if fresh {
+            self.ticks_left = self.restarted(delay);
+        }
+        self.lowest = Some(self.lowest.map_or(y, |lowest| lowest.min(y)));
+    }
+
+    /// What a restart of `delay` comes to, cut short during a hard drop's
+    /// grace.
+    fn restarted(&self, delay: u32) -> u32 {
+        if self.hard_dropped {
+            HARD_DROP_GRACE
+        } else {
+            delay
+        }
+    }
+}
+
+/// Rows being cleared while [`Phase::Clearing`].
+#[derive(Resource, Debug, Default, Clone, PartialEq, Eq)]
+pub struct LineClear {
+    /// Bitmask of the full rows, bit `y` set for row `y`.
+    pub rows: u64,
+    pub ticks_left: u32,
+}
+
+/// The game as it was just before a piece locked.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    board: Board,
+    piece: PieceKind,
+    hold: Hold,
+    queue: PieceQueue,
+    score: Score,
+}
+
+/// Recent [`Snapshot`]s for undo, newest last, kept only in modes that
+/// [allow undo](GameMode::allows_undo).
+#[derive(Resource, Debug, Default, Clone)]
+pub struct UndoHistory {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl UndoHistory {
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == UNDO_HISTORY_LEN {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+}
+
+/// A [`Snapshot`] to drill a setup from, kept by
+/// [`Action::SetCheckpoint`] and gone back to by
+/// [`Action::ResetCheckpoint`] as often as the player likes, in modes that
+/// [allow undo](GameMode::allows_undo).
+#[derive(Resource, Debug, Default, Clone)]
+pub struct Checkpoint(Option<Snapshot>);
+
+impl Checkpoint {
+    pub fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+/// The clock of a [`GameMode::Survival`] game.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct Survival {
+    /// Ticks survived so far.
+    pub ticks: u32,
+    /// Ticks until the next garbage row is queued.
+    until_rise: u32,
+}
+
+/// The clock of a [`GameMode::Sprint`] or [`GameMode::Cheese`] race.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct Sprint {
+    /// Ticks from the first spawn until the goal was reached.
+    pub ticks: u32,
+}
+
+/// Longest wait between frames while the game sits idle-paused.
+const IDLE_FRAME_WAIT: Duration = Duration::from_millis(500);
+
+/// Seconds of play without any input, for
+/// [`Settings::auto_pause`].
+#[derive(Resource, Debug, Default)]
+pub struct IdleTimer {
+    pub seconds: f32,
+    /// The frame rate has been dropped for an idle pause.
+    pub low_power: bool,
+}
+
+/// Time left in [`AppState::Dissolving`].
+#[derive(Resource, Debug, Default)]
+pub struct Dissolve(pub Timer);
+
+/// Whether the quit confirmation is showing over a game in progress.
+#[derive(Resource, Debug, Default)]
+pub struct ExitPrompt {
+    pub open: bool,
+}
+
+/// Quits once the player confirms the [`ExitPrompt`].
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct ConfirmExit;
+
+/// Delayed auto shift state for horizontal movement.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct AutoShift {
+    direction: i32,
+    ticks: u32,
+}
+
+impl AutoShift {
+    /// Advances one tick and returns how many columns to move in
+    /// `self.direction`.
+    fn update(&mut self, actions: &ActionState, config: &InputConfig, max_steps: u32) -> u32 {
+        let left = actions.held(Action::MoveLeft);
+        let right = actions.held(Action::MoveRight);
+        let cancelled = left && right && config.opposing == OpposingDirections::Cancel;
+        // A direction of 0 stands still, restarting the auto shift of
+        // whichever direction moves next.
+        let newly = match (
+            actions.just_pressed(Action::MoveLeft),
+            actions.just_pressed(Action::MoveRight),
+        ) {
+            _ if cancelled => Some(0),
+            (true, true) => Some(0),
+            (true, false) => Some(-1),
+            (false, true) => Some(1),
+            (false, false) => match (self.direction, left, right) {
+                (-1, true, _) | (1, _, true) => None,
+                (0, true, true) => Some(0),
+                (_, true, _) => Some(-1),
+                (_, _, true) => Some(1),
+                _ => Some(0),
+            },
+        };
+        if let Some(direction) = newly {
+            self.direction = direction;
+            self.ticks = 0;
+            return u32::from(direction != 0);
+        }
+
+        self.ticks += 1;
+        if !repeat_due(self.ticks, config.das, config.arr) {
+            0
+        } else if config.arr == 0 {
+            max_steps
+        } else {
+            1
+        }
+    }
+}
+
+/// Whether a key held for `ticks` ticks repeats on this one: first once it
+/// has been held `delay` ticks, and then every `interval` ticks, or every
+/// tick with an `interval` of 0.
+pub fn repeat_due(ticks: u32, delay: u32, interval: u32) -> bool {
+    ticks >= delay && (interval == 0 || (ticks - delay).is_multiple_of(interval))
+}
+
+/// Starts a fresh game from any state.
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct NewGame {
+    /// Seeds the piece queue, or `None` for a random sequence.
+    pub seed: Option<u64>,
+}
+
+impl NewGame {
+    /// Starts over on the seed `queue` was dealt from, for another try at
+    /// the same sequence of pieces.
+    pub fn retry(queue: &PieceQueue) -> Self {
+        Self {
+            seed: Some(queue.seed()),
+        }
+    }
+}
+
+/// Notable moments in play, for audio, visual feedback and logging to react
+/// to.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameplayEvent {
+    Spawned(Tetromino),
+    Moved,
+    Rotated,
+    /// The rotation just made only fitted with the piece shifted by this
+    /// `(dx, dy)` wall kick.
+    Kicked((i32, i32)),
+    Held,
+    /// Hold was pressed while the held piece couldn't be swapped back.
+    HoldRejected,
+    /// A piece locked after a hold, so hold can be used again.
+    HoldReady,
+    Locked(Tetromino),
+    /// Completed rows collapsed; `rows` is their bitmask before removal,
+    /// and `cancelled` the pending garbage rows the clear took back.
+    LinesCleared {
+        count: u32,
+        rows: u64,
+        cancelled: u32,
+    },
+    LevelUp(u32),
+    /// This many garbage rows rose from the bottom, lifting the stack.
+    GarbageRose(u32),
+    GameOver,
+    /// The race's goal was reached.
+    Finished,
+    /// The board was replaced wholesale, by a new game or an undo, so
+    /// anything tracking individual cells should start over.
+    Reset,
+}
+
+/// The [`Board`] changed since the last frame, by a lock, a clear, rising
+/// garbage, an undo or a new board altogether, for whatever draws it to
+/// redraw. Sent at most once a frame by [`announce_board_changes`].
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardChanged;
+
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state::<AppState>()
+            .add_event::<NewGame>()
+            .add_event::<ConfirmExit>()
+            .add_event::<GameplayEvent>()
+            .add_event::<BoardChanged>()
+            .insert_resource(Time::<Fixed>::from_hz(TICKS_PER_SECOND))
+            .add_systems(Startup, self_check)
+            .init_resource::<Board>()
+            .init_resource::<ActivePiece>()
+            .init_resource::<PartnerPiece>()
+            .init_resource::<Hold>()
+            .init_resource::<PieceQueue>()
+            .init_resource::<Score>()
+            .init_resource::<Phase>()
+            .init_resource::<Gravity>()
+            .init_resource::<LockDelay>()
+            .init_resource::<LineClear>()
+            .init_resource::<AutoShift>()
+            .init_resource::<ActionState>()
+            .add_event::<ActionEvent>()
+            .init_resource::<KeyBindings>()
+            .init_resource::<InputConfig>()
+            .init_resource::<Settings>()
+            .init_resource::<Dissolve>()
+            .init_resource::<ExitPrompt>()
+            .init_resource::<GameMode>()
+            .init_resource::<Modifiers>()
+            .init_resource::<UndoHistory>()
+            .init_resource::<Checkpoint>()
+            .init_resource::<GarbageQueue>()
+            .init_resource::<StateHash>()
+            .init_resource::<Survival>()
+            .init_resource::<Sprint>()
+            .init_resource::<IdleTimer>()
+            .add_systems(
+                Update,
+                (
+                    start_new_game,
+                    toggle_pause,
+                    restart_game,
+                    handle_exit,
+                    pause_when_idle,
+                ),
+            )
+            .add_systems(
+                Update,
+                announce_board_changes
+                    .after(start_new_game)
+                    .after(restart_game),
+            )
+            .add_systems(OnExit(AppState::Paused), close_exit_prompt)
+            .add_systems(OnEnter(AppState::Dissolving), start_dissolve)
+            .add_systems(
+                Update,
+                finish_dissolve.run_if(in_state(AppState::Dissolving)),
+            )
+            .add_systems(OnExit(AppState::Dissolving), clear_board)
+            .configure_sets(
+                FixedUpdate,
+                (TickSet::Input, TickSet::Simulate).chain().run_if(
+                    in_state(AppState::Playing)
+                        .or_else(in_state(AppState::Demo))
+                        .or_else(in_state(AppState::Review)),
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    read_keyboard.run_if(not(resource_exists::<ReplayPlayback>())),
+                    apply_action_events.after(read_keyboard),
+                )
+                    .in_set(TickSet::Input),
+            )
+            .configure_sets(
+                FixedUpdate,
+                (
+                    TickStage::Spawn,
+                    TickStage::Move,
+                    TickStage::Fall,
+                    TickStage::Lock,
+                    TickStage::Clear,
+                    TickStage::Clock,
+                )
+                    .chain()
+                    .in_set(TickSet::Simulate),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    (
+                        undo_piece,
+                        checkpoint,
+                        rise_garbage,
+                        spawn_tetromino,
+                        hold_piece,
+                    )
+                        .chain()
+                        .in_set(TickStage::Spawn),
+                    (move_tetromino, rotate_tetromino, hard_drop, firm_drop)
+                        .chain()
+                        .in_set(TickStage::Move),
+                    fall_tetromino.in_set(TickStage::Fall),
+                    lock_tetromino.in_set(TickStage::Lock),
+                    clear_lines.in_set(TickStage::Clear),
+                    (survival_clock, sprint_clock, cheese_clock)
+                        .chain()
+                        .in_set(TickStage::Clock),
+                    hash_state.in_set(TickSet::Simulate).after(TickStage::Clock),
+                ),
+            );
+        if cfg!(debug_assertions) {
+            app.add_systems(Last, audit_tick_order);
+        }
+    }
+}
+
+/// Everything needed to put a new piece into play.
+#[derive(SystemParam)]
+struct PieceSpawner<'w> {
+    board: Res<'w, Board>,
+    active: ResMut<'w, ActivePiece>,
+    phase: ResMut<'w, Phase>,
+    gravity: ResMut<'w, Gravity>,
+    lock: ResMut<'w, LockDelay>,
+    next_state: ResMut<'w, NextState<AppState>>,
+    events: EventWriter<'w, GameplayEvent>,
+    settings: Res<'w, Settings>,
+}
+
+impl PieceSpawner<'_> {
+    /// Spawns `kind` at the top of the board. A spawn that overlaps the
+    /// stack ends the game under block-out, unless
+    /// [`Settings::spawn_shift_up`] is on, and otherwise rises until it
+    /// fits, ending the game only when nowhere up to the top of the buffer
+    /// is free.
+    fn spawn(&mut self, kind: PieceKind) {
+        let spawn = Tetromino::spawn_in(kind, self.settings.rotation_system, &self.board);
+        *self.gravity = Gravity::default();
+        *self.lock = LockDelay::default();
+        let rise = if self.settings.top_out.block_out() && !self.settings.spawn_shift_up {
+            0
+        } else {
+            self.board.height() as i32
+        };
+        let piece = (0..=rise)
+            .map(|dy| spawn.moved(0, dy))
+            .find(|piece| !self.board.collides(piece));
+        self.active.0 = piece;
+        if let Some(piece) = piece {
+            *self.phase = Phase::Falling;
+            self.events.send(GameplayEvent::Spawned(piece));
+        } else {
+            top_out(
+                &mut self.phase,
+                &self.settings,
+                &mut self.next_state,
+                &mut self.events,
+            );
+        }
+    }
+}
+
+/// Everything needed to lock a piece into the board.
+#[derive(SystemParam)]
+struct PieceLocker<'w> {
+    board: ResMut<'w, Board>,
+    phase: ResMut<'w, Phase>,
+    hold: ResMut<'w, Hold>,
+    score: ResMut<'w, Score>,
+    queue: Res<'w, PieceQueue>,
+    mode: Res<'w, GameMode>,
+    history: ResMut<'w, UndoHistory>,
+    settings: Res<'w, Settings>,
+    next_state: ResMut<'w, NextState<AppState>>,
+    events: EventWriter<'w, GameplayEvent>,
+}
+
+impl PieceLocker<'_> {
+    /// Locks `piece` into the board and hands over to line clearing, or
+    /// ends the game if lock-out applies and the piece is wholly above the
+    /// visible field.
+    fn lock(&mut self, piece: &Tetromino) {
+        if self.mode.allows_undo() {
+            self.history.push(Snapshot {
+                board: self.board.clone(),
+                piece: piece.kind,
+                hold: self.hold.clone(),
+                queue: self.queue.clone(),
+                score: self.score.clone(),
+            });
+        }
+        self.board.lock_piece(piece);
+        self.score.pieces += 1;
+        *self.phase = Phase::Clearing;
+        self.events.send(GameplayEvent::Locked(*piece));
+        if !self.hold.can_hold {
+            self.hold.can_hold = true;
+            self.events.send(GameplayEvent::HoldReady);
+        }
+
+        let visible = self.board.visible_height() as i32;
+        if self.settings.top_out.lock_out() && piece.cells().iter().all(|&(_, y)| y >= visible) {
+            top_out(
+                &mut self.phase,
+                &self.settings,
+                &mut self.next_state,
+                &mut self.events,
+            );
+        }
+    }
+}
+
+/// Ends the game, dissolving the board first if the settings ask for it.
+fn top_out(
+    phase: &mut Phase,
+    settings: &Settings,
+    next_state: &mut NextState<AppState>,
+    events: &mut EventWriter<GameplayEvent>,
+) {
+    *phase = Phase::ToppedOut;
+    next_state.set(if settings.effects().dissolve {
+        AppState::Dissolving
+    } else {
+        AppState::GameOver
+    });
+    events.send(GameplayEvent::GameOver);
+}
+
+/// Refreshes the lock delay to `mode`'s after a successful move or
+/// rotation on the ground, up to [`MAX_LOCK_RESETS`] times per piece,
+/// under [`LockReset::Move`].
+fn reset_lock_delay(phase: Phase, policy: LockReset, mode: GameMode, lock: &mut LockDelay) {
+    if phase == Phase::Locking && policy == LockReset::Move && lock.resets < MAX_LOCK_RESETS {
+        lock.ticks_left = lock.restarted(mode.lock_delay());
+        lock.resets += 1;
+    }
+}
+
+fn is_controllable(phase: Phase) -> bool {
+    matches!(phase, Phase::Falling | Phase::Locking)
+}
+
+/// Every resource a gameplay tick reads or writes, captured between ticks
+/// so replays can seek.
+#[derive(Debug, Clone)]
+pub struct TickState {
+    board: Board,
+    active: ActivePiece,
+    hold: Hold,
+    queue: PieceQueue,
+    score: Score,
+    phase: Phase,
+    gravity: Gravity,
+    lock: LockDelay,
+    clear: LineClear,
+    shift: AutoShift,
+    actions: ActionState,
+    garbage: GarbageQueue,
+    survival: Survival,
+    sprint: Sprint,
+    history: UndoHistory,
+    checkpoint: Checkpoint,
+}
+
+impl TickState {
+    pub fn capture(world: &World) -> Self {
+        Self {
+            board: world.resource::<Board>().clone(),
+            active: *world.resource::<ActivePiece>(),
+            hold: world.resource::<Hold>().clone(),
+            queue: world.resource::<PieceQueue>().clone(),
+            score: world.resource::<Score>().clone(),
+            phase: *world.resource::<Phase>(),
+            gravity: world.resource::<Gravity>().clone(),
+            lock: world.resource::<LockDelay>().clone(),
+            clear: world.resource::<LineClear>().clone(),
+            shift: world.resource::<AutoShift>().clone(),
+            actions: world.resource::<ActionState>().clone(),
+            garbage: world.resource::<GarbageQueue>().clone(),
+            survival: world.resource::<Survival>().clone(),
+            sprint: world.resource::<Sprint>().clone(),
+            history: world.resource::<UndoHistory>().clone(),
+            checkpoint: world.resource::<Checkpoint>().clone(),
+        }
+    }
+
+    pub fn restore(self, world: &mut World) {
+        world.insert_resource(self.board);
+        world.insert_resource(self.active);
+        world.insert_resource(self.hold);
+        world.insert_resource(self.queue);
+        world.insert_resource(self.score);
+        world.insert_resource(self.phase);
+        world.insert_resource(self.gravity);
+        world.insert_resource(self.lock);
+        world.insert_resource(self.clear);
+        world.insert_resource(self.shift);
+        world.insert_resource(self.actions);
+        world.insert_resource(self.garbage);
+        world.insert_resource(self.survival);
+        world.insert_resource(self.sprint);
+        world.insert_resource(self.history);
+        world.insert_resource(self.checkpoint);
+    }
+}
+
+/// Replaces every piece of game state with a fresh game dealing from
+/// `queue`, on a board sized for the [`Modifiers`] in force once the
+/// commands apply.
+pub fn reset_game(commands: &mut Commands, queue: PieceQueue) {
+    commands.add(|world: &mut World| {
+        let board = world.resource::<Modifiers>().board();
+        world.insert_resource(board);
+        world.send_event(GameplayEvent::Reset);
+    });
+    commands.insert_resource(ActivePiece::default());
+    commands.insert_resource(Hold::default());
+    commands.insert_resource(GarbageQueue::with_seed(queue.seed()));
+    commands.add(|world: &mut World| {
+        if let Some(&GameMode::Cheese(cheese)) = world.get_resource::<GameMode>() {
+            let rows = cheese.rows_on(world.resource::<Board>());
+            world.resource_mut::<GarbageQueue>().push(rows);
+        }
+    });
+    commands.insert_resource(Survival::default());
+    commands.insert_resource(Sprint::default());
+    commands.insert_resource(queue);
+    commands.insert_resource(Score::default());
+    commands.insert_resource(Phase::default());
+    commands.insert_resource(Gravity::default());
+    commands.insert_resource(LockDelay::default());
+    commands.insert_resource(LineClear::default());
+    commands.insert_resource(UndoHistory::default());
+    commands.insert_resource(Checkpoint::default());
+    commands.insert_resource(AutoShift::default());
+    commands.insert_resource(ActionState::default());
+}
+
+/// Deals the game the last [`NewGame`] asked for, under the rules in
+/// play, and starts recording it.
+pub fn start_new_game(
+    mut commands: Commands,
+    mut events: EventReader<NewGame>,
+    mode: Res<GameMode>,
+    modifiers: Res<Modifiers>,
+    input: Res<InputConfig>,
+    settings: Res<Settings>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+    let seed = event.seed.unwrap_or_else(rand::random);
+    let queue = PieceQueue::with_seed(seed, settings.queue);
+    debug!(
+        target: GAMEPLAY_TARGET,
+        seed = queue.seed(),
+        mode = ?*mode,
+        modifiers = ?*modifiers,
+        "new game"
+    );
+    let replay = Replay::new(queue.seed(), *mode, *modifiers, &input, &settings);
+    reset_game(&mut commands, queue);
+    commands.insert_resource(ReplayRecorder(replay));
+    next_state.set(AppState::Playing);
+}
+
+fn toggle_pause(
+    keys: Res<Input<KeyCode>>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !keys.just_pressed(KeyCode::P) {
+        return;
+    }
+    match state.get() {
+        AppState::Playing => next_state.set(AppState::Paused),
+        AppState::Paused => next_state.set(AppState::Playing),
+        _ => {}
+    }
+}
+
+/// Pauses the game and drops the frame rate once [`Settings::auto_pause`]
+/// seconds pass without input, restoring the frame rate on the next input.
+///
+/// The game stays paused until the player resumes it, and the fixed ticks
+/// that come due while paused are skipped rather than saved up, so
+/// resuming doesn't drop the piece by the time spent away.
+#[allow(clippy::too_many_arguments)]
+fn pause_when_idle(
+    time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Option<Res<Input<MouseButton>>>,
+    touches: Option<Res<Touches>>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut idle: ResMut<IdleTimer>,
+    winit: Option<ResMut<WinitSettings>>,
+) {
+    let active = keys.get_pressed().next().is_some()
+        || mouse.is_some_and(|mouse| mouse.get_pressed().next().is_some())
+        || touches.is_some_and(|touches| touches.iter().next().is_some());
+    let mut low_power = idle.low_power;
+    if active {
+        idle.seconds = 0.0;
+        low_power = false;
+    } else if *state.get() == AppState::Playing {
+        idle.seconds += time.delta_seconds();
+        if settings
+            .auto_pause
+            .is_some_and(|limit| idle.seconds >= limit)
+        {
+            next_state.set(AppState::Paused);
+            low_power = true;
+        }
+    }
+    if low_power != idle.low_power {
+        idle.low_power = low_power;
+        if let Some(mut winit) = winit {
+            *winit = if low_power {
+                WinitSettings {
+                    focused_mode: UpdateMode::ReactiveLowPower {
+                        wait: IDLE_FRAME_WAIT,
+                    },
+                    unfocused_mode: UpdateMode::ReactiveLowPower {
+                        wait: IDLE_FRAME_WAIT,
+                    },
+                    ..WinitSettings::game()
+                }
+            } else {
+                WinitSettings::game()
+            };
+        }
+    }
+}
+
+fn restart_game(
+    keys: Res<Input<KeyCode>>,
+    state: Res<State<AppState>>,
+    mut new_game: EventWriter<NewGame>,
+) {
+    if keys.just_pressed(KeyCode::R)
+        && matches!(state.get(), AppState::Playing | AppState::GameOver)
+    {
+        new_game.send(NewGame::default());
+    }
+}
+
+/// Quits on Escape, first asking for confirmation over a game in progress
+/// unless [`Settings::confirm_exit`] is off. While asking, Enter quits and
+/// Escape goes back to the paused game.
+fn handle_exit(
+    keys: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    state: Res<State<AppState>>,
+    mut prompt: ResMut<ExitPrompt>,
+    mut confirmed: EventReader<ConfirmExit>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if confirmed.read().count() > 0 || (prompt.open && keys.just_pressed(KeyCode::Return)) {
+        exit.send(AppExit);
+        return;
+    }
+    if !keys.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    if prompt.open {
+        prompt.open = false;
+        return;
+    }
+    let in_game = matches!(state.get(), AppState::Playing | AppState::Paused);
+    if in_game && settings.confirm_exit {
+        prompt.open = true;
+        if *state.get() == AppState::Playing {
+            next_state.set(AppState::Paused);
+        }
+    } else {
+        exit.send(AppExit);
+    }
+}
+
+fn close_exit_prompt(mut prompt: ResMut<ExitPrompt>) {
+    prompt.open = false;
+}
+
+fn start_dissolve(mut dissolve: ResMut<Dissolve>) {
+    dissolve.0 = Timer::from_seconds(DISSOLVE_SECS, TimerMode::Once);
+}
+
+fn finish_dissolve(
+    time: Res<Time>,
+    mut dissolve: ResMut<Dissolve>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if dissolve.0.tick(time.delta()).finished() {
+        next_state.set(AppState::GameOver);
+    }
+}
+
+/// Empties the well once its blocks have dissolved away.
+fn clear_board(
+    modifiers: Res<Modifiers>,
+    mut board: ResMut<Board>,
+    mut events: EventWriter<GameplayEvent>,
+) {
+    *board = modifiers.board();
+    events.send(GameplayEvent::Reset);
+}
+
+/// Sends [`BoardChanged`] once for every frame the board was written to,
+/// whichever system wrote it.
+pub fn announce_board_changes(board: Res<Board>, mut changes: EventWriter<BoardChanged>) {
+    if board.is_changed() {
+        changes.send(BoardChanged);
+    }
+}
+
+/// Everything a [`Snapshot`] puts back.
+#[derive(SystemParam)]
+struct Rewind<'w> {
+    board: ResMut<'w, Board>,
+    active: ResMut<'w, ActivePiece>,
+    hold: ResMut<'w, Hold>,
+    queue: ResMut<'w, PieceQueue>,
+    score: ResMut<'w, Score>,
+    phase: ResMut<'w, Phase>,
+    clear: ResMut<'w, LineClear>,
+    events: EventWriter<'w, GameplayEvent>,
+}
+
+impl Rewind<'_> {
+    /// The game as it stands, with `piece` about to spawn.
+    fn snapshot(&self, piece: PieceKind) -> Snapshot {
+        Snapshot {
+            board: self.board.clone(),
+            piece,
+            hold: self.hold.clone(),
+            queue: self.queue.clone(),
+            score: self.score.clone(),
+        }
+    }
+
+    /// Puts the game back as `snapshot` left it, its piece at the front of
+    /// the queue to spawn again this tick.
+    fn restore(&mut self, snapshot: Snapshot) {
+        *self.board = snapshot.board;
+        *self.hold = snapshot.hold;
+        *self.queue = snapshot.queue;
+        self.queue.push_front(snapshot.piece);
+        *self.score = snapshot.score;
+        self.active.0 = None;
+        *self.clear = LineClear::default();
+        *self.phase = Phase::Spawning;
+        self.events.send(GameplayEvent::Reset);
+    }
+}
+
+/// Takes back the last locked piece, returning it to the front of the
+/// queue to spawn again this tick.
+///
+/// Undo is an [`Action`] sampled with the rest of the tick's input, so
+/// replays record it and take it back on the same tick.
+fn undo_piece(actions: Res<ActionState>, mut history: ResMut<UndoHistory>, mut rewind: Rewind) {
+    if !actions.just_pressed(Action::Undo) {
+        return;
+    }
+    if let Some(snapshot) = history.snapshots.pop_back() {
+        rewind.restore(snapshot);
+    }
+}
+
+/// Keeps the game as it stands, the piece in play back at spawn, as the
+/// [`Checkpoint`], or goes back to the one kept. Going back drops the undo
+/// history, which belongs to the attempt given up on.
+///
+/// Like undo, both are [`Action`]s, so replays go back on the same tick.
+fn checkpoint(
+    actions: Res<ActionState>,
+    mode: Res<GameMode>,
+    mut checkpoint: ResMut<Checkpoint>,
+    mut history: ResMut<UndoHistory>,
+    mut rewind: Rewind,
+) {
+    if !mode.allows_undo() {
+        return;
+    }
+    if actions.just_pressed(Action::SetCheckpoint) && is_controllable(*rewind.phase) {
+        if let Some(piece) = rewind.active.0 {
+            checkpoint.0 = Some(rewind.snapshot(piece.kind));
+        }
+    }
+    if actions.just_pressed(Action::ResetCheckpoint) {
+        if let Some(snapshot) = checkpoint.0.clone() {
+            history.snapshots.clear();
+            rewind.restore(snapshot);
+        }
+    }
+}
+
+/// Raises pending garbage before the next piece spawns, ending the game if
+/// it pushes the stack off the top.
+fn rise_garbage(
+    settings: Res<Settings>,
+    mut garbage: ResMut<GarbageQueue>,
+    mut board: ResMut<Board>,
+    mut phase: ResMut<Phase>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut events: EventWriter<GameplayEvent>,
+) {
+    if *phase != Phase::Spawning || garbage.pending() == 0 {
+        return;
+    }
+    match garbage.insert(&mut board) {
+        Ok(rows) => events.send(GameplayEvent::GarbageRose(rows)),
+        Err(_) => top_out(&mut phase, &settings, &mut next_state, &mut events),
+    }
+}
+
+fn spawn_tetromino(mut queue: ResMut<PieceQueue>, mut spawner: PieceSpawner) {
+    if *spawner.phase == Phase::Spawning {
+        spawner.spawn(queue.next_piece());
+    }
+}
+
+fn hold_piece(
+    actions: Res<ActionState>,
+    modifiers: Res<Modifiers>,
+    mut hold: ResMut<Hold>,
+    mut queue: ResMut<PieceQueue>,
+    mut spawner: PieceSpawner,
+) {
+    if modifiers.no_hold || !actions.just_pressed(Action::Hold) || !is_controllable(*spawner.phase)
+    {
+        return;
+    }
+    if !hold.can_hold {
+        spawner.events.send(GameplayEvent::HoldRejected);
+        return;
+    }
+    let Some(piece) = spawner.active.0 else {
+        return;
+    };
+    let kind = hold
+        .piece
+        .replace(piece.kind)
+        .unwrap_or_else(|| queue.next_piece());
+    hold.can_hold = false;
+    spawner.events.send(GameplayEvent::Held);
+    spawner.spawn(kind);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn move_tetromino(
+    actions: Res<ActionState>,
+    config: Res<InputConfig>,
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    board: Res<Board>,
+    phase: Res<Phase>,
+    mut active: ResMut<ActivePiece>,
+    mut shift: ResMut<AutoShift>,
+    mut lock: ResMut<LockDelay>,
+    mut events: EventWriter<GameplayEvent>,
+) {
+    let steps = shift.update(&actions, &config, board.width() as u32);
+    let Some(piece) = active.0.as_mut().filter(|_| is_controllable(*phase)) else {
+        return;
+    };
+    for _ in 0..steps {
+        let moved = piece.moved(shift.direction, 0);
+        if board.collides(&moved) {
+            break;
+        }
+        *piece = moved;
+        reset_lock_delay(*phase, settings.lock_reset, *mode, &mut lock);
+        events.send(GameplayEvent::Moved);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn rotate_tetromino(
+    actions: Res<ActionState>,
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    board: Res<Board>,
+    phase: Res<Phase>,
+    mut active: ResMut<ActivePiece>,
+    mut lock: ResMut<LockDelay>,
+    mut events: EventWriter<GameplayEvent>,
+) {
+    let clockwise = if actions.just_pressed(Action::RotateCw) {
+        true
+    } else if actions.just_pressed(Action::RotateCcw) {
+        false
+    } else {
+        return;
+    };
+    let Some(piece) = active.0.as_mut().filter(|_| is_controllable(*phase)) else {
+        return;
+    };
+    if let Some((rotated, kick)) = piece.rotate_with_kicks(&*board, clockwise) {
+        *piece = rotated;
+        reset_lock_delay(*phase, settings.lock_reset, *mode, &mut lock);
+        events.send(GameplayEvent::Rotated);
+        if kick != (0, 0) {
+            events.send(GameplayEvent::Kicked(kick));
+        }
+    }
+}
+
+/// Drops the piece to where it lands and locks it, or under
+/// [`Settings::hard_drop_grace`] rests it there for [`HARD_DROP_GRACE`]
+/// ticks first unless it was already resting.
+fn hard_drop(
+    actions: Res<ActionState>,
+    modifiers: Res<Modifiers>,
+    partner: Res<PartnerPiece>,
+    mut active: ResMut<ActivePiece>,
+    mut lock: ResMut<LockDelay>,
+    mut locker: PieceLocker,
+) {
+    if modifiers.no_hard_drop
+        || !actions.just_pressed(Action::HardDrop)
+        || !is_controllable(*locker.phase)
+    {
+        return;
+    }
+    let Some(piece) = active.0.take() else {
+        return;
+    };
+    let distance = SharedField::new(&*locker.board, partner.0.as_ref()).drop_distance(&piece);
+    let landed = piece.moved(0, -distance);
+    locker
+        .score
+        .add_hard_drop(distance as u32, locker.settings.scoring);
+    let freeplay = locker.settings.freeplay && locker.mode.allows_freeplay();
+    let grace = locker.settings.hard_drop_grace && !freeplay && distance > 0;
+    if !grace {
+        locker.lock(&landed);
+        return;
+    }
+    active.0 = Some(landed);
+    locker.events.send(GameplayEvent::Moved);
+    *locker.phase = Phase::Locking;
+    lock.ticks_left = HARD_DROP_GRACE;
+    lock.hard_dropped = true;
+    lock.lowest = Some(lock.lowest.map_or(landed.y, |lowest| lowest.min(landed.y)));
+}
+
+/// Drops the piece to where it lands, scoring the rows as soft-dropped,
+/// and lands it there as gravity would, so [`LockReset::Step`] restarts
+/// the delay of a piece that firm drops off a ledge.
+#[allow(clippy::too_many_arguments)]
+fn firm_drop(
+    actions: Res<ActionState>,
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    board: Res<Board>,
+    partner: Res<PartnerPiece>,
+    mut score: ResMut<Score>,
+    mut active: ResMut<ActivePiece>,
+    mut phase: ResMut<Phase>,
+    mut gravity: ResMut<Gravity>,
+    mut lock: ResMut<LockDelay>,
+    mut events: EventWriter<GameplayEvent>,
+) {
+    if !actions.just_pressed(Action::FirmDrop) || !is_controllable(*phase) {
+        return;
+    }
+    let Some(piece) = active.0.as_mut() else {
+        return;
+    };
+    let distance = SharedField::new(&*board, partner.0.as_ref()).drop_distance(piece);
+    if distance > 0 {
+        *piece = piece.moved(0, -distance);
+        score.add_soft_drop(gravity.soft_drop(distance as u32, settings.soft_drop_cap));
+        events.send(GameplayEvent::Moved);
+    }
+    if distance > 0 || *phase == Phase::Falling {
+        *phase = Phase::Locking;
+        lock.land(piece.y, settings.lock_reset, mode.lock_delay());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn fall_tetromino(
+    actions: Res<ActionState>,
+    config: Res<InputConfig>,
+    settings: Res<Settings>,
+    board: Res<Board>,
+    partner: Res<PartnerPiece>,
+    mode: Res<GameMode>,
+    mut score: ResMut<Score>,
+    mut active: ResMut<ActivePiece>,
+    mut phase: ResMut<Phase>,
+    mut gravity: ResMut<Gravity>,
+    mut lock: ResMut<LockDelay>,
+) {
+    let Some(piece) = active.0.as_mut().filter(|_| is_controllable(*phase)) else {
+        return;
+    };
+    let field = SharedField::new(&*board, partner.0.as_ref());
+    if field.collides(&piece.moved(0, -1)) {
+        if *phase == Phase::Falling {
+            *phase = Phase::Locking;
+            lock.land(piece.y, settings.lock_reset, mode.lock_delay());
+        }
+        return;
+    }
+    *phase = Phase::Falling;
+
+    let soft_drop = actions.held(Action::SoftDrop);
+    let factor = if soft_drop {
+        config.soft_drop_factor.max(1)
+    } else {
+        1
+    };
+    let rows = match settings.custom_gravity {
+        _ if soft_drop && config.soft_drop_infinite => i32::MAX,
+        // Custom gravity accumulates fractions of a row, dropping several
+        // rows in one tick above 1G.
+        Some(g) => {
+            gravity.rows += g * factor as f32;
+            let rows = gravity.rows.floor();
+            gravity.rows -= rows;
+            rows as i32
+        }
+        // Counting in whole ticks keeps the curve exact: a row falls for
+        // every `interval` ticks' worth of progress.
+        None => {
+            let interval = gravity_interval(mode.gravity_level(score.level));
+            gravity.ticks += factor;
+            let rows = gravity.ticks / interval;
+            gravity.ticks %= interval;
+            rows as i32
+        }
+    };
+    let rows = rows.min(field.drop_distance(piece));
+    if rows > 0 {
+        *piece = piece.moved(0, -rows);
+        if soft_drop {
+            score.add_soft_drop(gravity.soft_drop(rows as u32, settings.soft_drop_cap));
+        }
+    }
+}
+
+/// Counts down the lock delay of a resting piece and locks it once the
+/// delay runs out, unless freeplay leaves it resting for good.
+fn lock_tetromino(
+    mut active: ResMut<ActivePiece>,
+    mut lock: ResMut<LockDelay>,
+    mut locker: PieceLocker,
+) {
+    let freeplay = locker.settings.freeplay && locker.mode.allows_freeplay();
+    if *locker.phase != Phase::Locking || freeplay {
+        return;
+    }
+    lock.ticks_left = lock.ticks_left.saturating_sub(1);
+    if lock.ticks_left > 0 {
+        return;
+    }
+    if let Some(piece) = active.0.take() {
+        locker.lock(&piece);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn clear_lines(
+    settings: Res<Settings>,
+    mut board: ResMut<Board>,
+    mut phase: ResMut<Phase>,
+    mut clear: ResMut<LineClear>,
+    mut score: ResMut<Score>,
+    mut garbage: ResMut<GarbageQueue>,
+    mut events: EventWriter<GameplayEvent>,
+) {
+    if *phase != Phase::Clearing {
+        return;
+    }
+    if clear.rows == 0 {
+        clear.rows = board.full_rows();
+        if clear.rows != 0 && !settings.instant_line_clear {
+            clear.ticks_left = LINE_CLEAR_DELAY;
+        }
+    }
+    if clear.ticks_left > 0 {
+        clear.ticks_left -= 1;
+        return;
+    }
+    let rows = std::mem::take(&mut clear.rows);
+    let cleared = board.clear_lines() as u32;
+    let pending = garbage.pending();
+    garbage.cancel(cleared);
+    if cleared > 0 {
+        events.send(GameplayEvent::LinesCleared {
+            count: cleared,
+            rows,
+            cancelled: pending - garbage.pending(),
+        });
+        if score.add_lines(cleared, settings.scoring) {
+            events.send(GameplayEvent::LevelUp(score.level));
+        }
+    }
+    *phase = Phase::Spawning;
+}
+
+/// Counts the time survived as the score, queueing garbage at the rate
+/// [`survival_interval`] sets.
+fn survival_clock(
+    mode: Res<GameMode>,
+    phase: Res<Phase>,
+    mut survival: ResMut<Survival>,
+    mut garbage: ResMut<GarbageQueue>,
+    mut score: ResMut<Score>,
+) {
+    if *mode != GameMode::Survival || *phase == Phase::ToppedOut {
+        return;
+    }
+    if survival.ticks == 0 {
+        survival.until_rise = survival_interval(0);
+    }
+    survival.ticks += 1;
+    score.points = survival.ticks / TICKS_PER_SECOND as u32;
+    survival.until_rise -= 1;
+    if survival.until_rise == 0 {
+        garbage.push(1);
+        survival.until_rise = survival_interval(survival.ticks);
+    }
+}
+
+/// Times a sprint, ending it once the goal's lines are cleared.
+fn sprint_clock(
+    mode: Res<GameMode>,
+    score: Res<Score>,
+    mut phase: ResMut<Phase>,
+    mut sprint: ResMut<Sprint>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut events: EventWriter<GameplayEvent>,
+) {
+    let GameMode::Sprint(goal) = *mode else {
+        return;
+    };
+    if matches!(*phase, Phase::ToppedOut | Phase::Finished) {
+        return;
+    }
+    sprint.ticks += 1;
+    if score.lines >= goal.lines() {
+        *phase = Phase::Finished;
+        next_state.set(AppState::GameOver);
+        events.send(GameplayEvent::Finished);
+    }
+}
+
+/// Times a cheese race, ending it once every garbage row is cleared.
+fn cheese_clock(
+    mode: Res<GameMode>,
+    board: Res<Board>,
+    garbage: Res<GarbageQueue>,
+    mut phase: ResMut<Phase>,
+    mut sprint: ResMut<Sprint>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut events: EventWriter<GameplayEvent>,
+) {
+    if !matches!(*mode, GameMode::Cheese(_)) || matches!(*phase, Phase::ToppedOut | Phase::Finished)
+    {
+        return;
+    }
+    sprint.ticks += 1;
+    if garbage.pending() == 0 && board.garbage_rows() == 0 {
+        *phase = Phase::Finished;
+        next_state.set(AppState::GameOver);
+        events.send(GameplayEvent::Finished);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::event::ManualEventReader;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::components::board::BUFFER_ROWS;
+    use crate::components::garbage::GARBAGE_CELL;
+    use crate::utils::config::{TopOutRule, MAX_GRAVITY};
+    use crate::utils::debug::tick_order_errors;
+
+    /// A headless app in [`AppState::Playing`] whose fixed ticks only run
+    /// when stepped by [`tick`].
+    fn game_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+        app
+    }
+
+    fn tick(app: &mut App) {
+        app.world.run_schedule(FixedUpdate);
+    }
+
+    /// Fills the bottom row except where an I piece dropped from spawn
+    /// lands, and puts that piece in play.
+    fn setup_i_piece_clear(app: &mut App) {
+        let mut board = Board::default();
+        let piece = Tetromino::spawn(PieceKind::I, &board);
+        let gaps = board.landing_position(&piece).cells();
+        for x in 0..board.width() as i32 {
+            if !gaps.contains(&(x, 0)) {
+                board.set(x, 0, Some(0));
+            }
+        }
+        board.set(0, 1, Some(1));
+        app.world.insert_resource(board);
+        app.world.insert_resource(ActivePiece(Some(piece)));
+        app.world.insert_resource(Phase::Falling);
+    }
+
+    /// Fills the whole board so the next spawn tops out.
+    fn setup_top_out(app: &mut App) {
+        let mut board = Board::default();
+        for y in 0..board.height() as i32 {
+            for x in 0..board.width() as i32 {
+                board.set(x, y, Some(0));
+            }
+        }
+        app.world.insert_resource(board);
+        app.world.insert_resource(Phase::Spawning);
+    }
+
+    fn state(app: &App) -> AppState {
+        *app.world.resource::<State<AppState>>().get()
+    }
+
+    #[test]
+    fn test_top_out_dissolves_before_game_over() {
+        let mut app = game_app();
+        setup_top_out(&mut app);
+        tick(&mut app);
+        app.update();
+        assert_eq!(state(&app), AppState::Dissolving);
+
+        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::R);
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            250,
+        )));
+        app.update();
+        app.update();
+        assert_eq!(state(&app), AppState::Dissolving);
+
+        app.world.resource_mut::<Input<KeyCode>>().reset(KeyCode::R);
+        for _ in 0..4 {
+            app.update();
+        }
+        assert_eq!(state(&app), AppState::GameOver);
+        assert!(app.world.resource::<Board>().is_empty());
+    }
+
+    #[test]
+    fn test_idling_past_the_limit_pauses_the_game() {
+        let mut app = game_app();
+        app.world.resource_mut::<Settings>().auto_pause = Some(1.0);
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            250,
+        )));
+        // Holding a key counts as input however long it's held.
+        app.world
+            .resource_mut::<Input<KeyCode>>()
+            .press(KeyCode::Left);
+        for _ in 0..8 {
+            app.update();
+        }
+        assert_eq!(state(&app), AppState::Playing);
+
+        app.world
+            .resource_mut::<Input<KeyCode>>()
+            .release(KeyCode::Left);
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(state(&app), AppState::Playing);
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(state(&app), AppState::Paused);
+        assert!(app.world.resource::<IdleTimer>().low_power);
+
+        app.world
+            .resource_mut::<Input<KeyCode>>()
+            .press(KeyCode::Left);
+        app.update();
+        assert!(!app.world.resource::<IdleTimer>().low_power);
+        assert_eq!(state(&app), AppState::Paused);
+    }
+
+    #[test]
+    fn test_top_out_skips_dissolve_when_disabled() {
+        let mut app = game_app();
+        app.world.resource_mut::<Settings>().game_over_dissolve = false;
+        setup_top_out(&mut app);
+        tick(&mut app);
+        app.update();
+        assert_eq!(state(&app), AppState::GameOver);
+    }
+
+    fn press(app: &mut App, keys: &[KeyCode]) {
+        let mut input = app.world.resource_mut::<Input<KeyCode>>();
+        input.reset_all();
+        for &key in keys {
+            input.press(key);
+        }
+    }
+
+    #[test]
+    fn test_undo_restores_board_and_queue() {
+        let mut app = game_app();
+        app.world.insert_resource(GameMode::Zen);
+        tick(&mut app);
+        let piece = app.world.resource::<ActivePiece>().0.unwrap();
+        let board = app.world.resource::<Board>().clone();
+
+        press(&mut app, &[KeyCode::Space]);
+        tick(&mut app);
+        assert_ne!(*app.world.resource::<Board>(), board);
+        assert_eq!(app.world.resource::<UndoHistory>().len(), 1);
+
+        press(&mut app, &[KeyCode::Back]);
+        tick(&mut app);
+        assert_eq!(*app.world.resource::<Board>(), board);
+        assert!(app.world.resource::<UndoHistory>().is_empty());
+        assert_eq!(app.world.resource::<ActivePiece>().0, Some(piece));
+        let actions = app.world.resource::<ActionState>();
+        assert_ne!(actions.held_bits() & 1 << Action::Undo as u16, 0);
+
+        // Still holding the key neither undoes again nor moves the piece.
+        tick(&mut app);
+        assert_eq!(*app.world.resource::<Board>(), board);
+        let active = app.world.resource::<ActivePiece>().0.unwrap();
+        assert_eq!(
+            (active.kind, active.rotation, active.x),
+            (piece.kind, 0, piece.x)
+        );
+    }
+
+    #[test]
+    fn test_resetting_to_a_checkpoint_restores_its_board_and_queue() {
+        let mut app = game_app();
+        app.world.insert_resource(GameMode::Zen);
+        tick(&mut app);
+        tap(&mut app, Action::HardDrop);
+        tick(&mut app);
+        for _ in 0..LINE_CLEAR_DELAY + 2 {
+            tick(&mut app);
+        }
+        let piece = app.world.resource::<ActivePiece>().0.unwrap();
+        let board = app.world.resource::<Board>().clone();
+        let queue = app.world.resource::<PieceQueue>().clone();
+        let score = app.world.resource::<Score>().clone();
+        tap(&mut app, Action::SetCheckpoint);
+        tick(&mut app);
+        assert!(app.world.resource::<Checkpoint>().is_set());
+
+        // Drill the setup twice over, placing pieces each time.
+        for attempt in 0..2 {
+            for _ in 0..3 {
+                tap(&mut app, Action::HardDrop);
+                for _ in 0..LINE_CLEAR_DELAY + 2 {
+                    tick(&mut app);
+                }
+            }
+            assert_ne!(*app.world.resource::<Board>(), board, "attempt {attempt}");
+            tap(&mut app, Action::ResetCheckpoint);
+            tick(&mut app);
+            assert_eq!(*app.world.resource::<Board>(), board);
+            assert_eq!(*app.world.resource::<Score>(), score);
+            assert_eq!(app.world.resource::<ActivePiece>().0, Some(piece));
+            let dealt = app.world.resource::<PieceQueue>();
+            assert_eq!(dealt.dealt(), queue.dealt());
+            assert!(dealt
+                .peek(PieceQueue::PREVIEW_LEN)
+                .eq(queue.peek(PieceQueue::PREVIEW_LEN)));
+            assert!(app.world.resource::<UndoHistory>().is_empty());
+        }
+    }
+
+    /// Presses and releases `action` within the next tick.
+    fn tap(app: &mut App, action: Action) {
+        app.world.send_event(ActionEvent::press(action));
+        app.world.send_event(ActionEvent::release(action));
+        tick(app);
+    }
+
+    #[test]
+    fn test_action_events_alone_place_a_piece() {
+        let mut app = game_app();
+        tick(&mut app);
+        let board = app.world.resource::<Board>().clone();
+        let piece = app.world.resource::<ActivePiece>().0.unwrap();
+        let (turned, _) = piece.moved(-2, 0).rotate_with_kicks(&board, true).unwrap();
+        let landing = board.landing_position(&turned);
+
+        tap(&mut app, Action::MoveLeft);
+        tap(&mut app, Action::MoveLeft);
+        tap(&mut app, Action::RotateCw);
+        let active = app.world.resource::<ActivePiece>().0.unwrap();
+        assert_eq!((active.x, active.rotation), (turned.x, turned.rotation));
+
+        tap(&mut app, Action::HardDrop);
+        tick(&mut app);
+        let board = app.world.resource::<Board>();
+        for (x, y) in landing.cells() {
+            assert_eq!(board.get(x, y), Some(piece.kind.index()));
+        }
+        let next = app.world.resource::<ActivePiece>().0.unwrap();
+        assert_eq!((next.rotation, next.y), (0, piece.y));
+    }
+
+    #[test]
+    fn test_undo_is_unavailable_in_marathon() {
+        let mut app = game_app();
+        tick(&mut app);
+        press(&mut app, &[KeyCode::Space]);
+        tick(&mut app);
+        let board = app.world.resource::<Board>().clone();
+
+        press(&mut app, &[KeyCode::Back]);
+        tick(&mut app);
+        assert!(app.world.resource::<UndoHistory>().is_empty());
+        assert_eq!(*app.world.resource::<Board>(), board);
+    }
+
+    #[test]
+    fn test_survival_garbage_rises_until_it_tops_out() {
+        let mut app = game_app();
+        app.world.insert_resource(GameMode::Survival);
+        app.world.resource_mut::<GarbageQueue>().push(3);
+        tick(&mut app);
+        let board = app.world.resource::<Board>();
+        assert_eq!(board.height(), VISIBLE_HEIGHT + BUFFER_ROWS);
+        for y in 0..3 {
+            let blocks = (0..board.width() as i32)
+                .filter(|&x| board.get(x, y) == Some(GARBAGE_CELL))
+                .count();
+            assert_eq!(blocks, board.width() - 1);
+        }
+        assert!(app.world.resource::<ActivePiece>().0.is_some());
+        assert_eq!(app.world.resource::<Survival>().ticks, 1);
+
+        let mut board = Board::default();
+        board.set(0, board.height() as i32 - 1, Some(0));
+        app.world.insert_resource(board);
+        app.world.insert_resource(Phase::Spawning);
+        app.world.resource_mut::<GarbageQueue>().push(1);
+        tick(&mut app);
+        assert_eq!(*app.world.resource::<Phase>(), Phase::ToppedOut);
+        app.update();
+        assert_eq!(state(&app), AppState::Dissolving);
+    }
+
+    #[test]
+    fn test_cleared_lines_cancel_pending_garbage_before_it_rises() {
+        let mut app = game_app();
+        app.world.resource_mut::<Settings>().instant_line_clear = true;
+        let mut board = Board::default();
+        let piece = Tetromino::spawn(PieceKind::I, &board).rotated(true);
+        let (gap, _) = board.landing_position(&piece).cells()[0];
+        for y in 0..2 {
+            for x in (0..board.width() as i32).filter(|&x| x != gap) {
+                board.set(x, y, Some(0));
+            }
+        }
+        app.world.insert_resource(board);
+        app.world.insert_resource(ActivePiece(Some(piece)));
+        app.world.insert_resource(Phase::Falling);
+        app.world.resource_mut::<GarbageQueue>().push(3);
+
+        press(&mut app, &[KeyCode::Space]);
+        tick(&mut app);
+        assert_eq!(app.world.resource::<Score>().lines, 2);
+        assert_eq!(app.world.resource::<GarbageQueue>().pending(), 1);
+
+        press(&mut app, &[]);
+        tick(&mut app);
+        assert_eq!(app.world.resource::<GarbageQueue>().pending(), 0);
+        let board = app.world.resource::<Board>();
+        let garbage_rows = (0..board.height() as i32)
+            .filter(|&y| (0..board.width() as i32).any(|x| board.get(x, y) == Some(GARBAGE_CELL)))
+            .count();
+        assert_eq!(garbage_rows, 1);
+    }
+
+    #[test]
+    fn test_sprint_ends_on_reaching_its_goal() {
+        let mut app = game_app();
+        app.world
+            .insert_resource(GameMode::Sprint(SprintGoal::Lines20));
+        app.world.resource_mut::<Settings>().instant_line_clear = true;
+        tick(&mut app);
+        for _ in 0..30 {
+            tick(&mut app);
+        }
+        app.world.resource_mut::<Score>().lines = 19;
+        setup_i_piece_clear(&mut app);
+        press(&mut app, &[KeyCode::Space]);
+        tick(&mut app);
+        // Nothing spawns or counts on once the goal is reached.
+        tick(&mut app);
+
+        assert_eq!(app.world.resource::<Score>().lines, 20);
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Finished);
+        assert_eq!(app.world.resource::<ActivePiece>().0, None);
+        // The clock ran from the first tick to the one the goal fell on.
+        assert_eq!(app.world.resource::<Sprint>().ticks, 32);
+        app.update();
+        assert_eq!(state(&app), AppState::GameOver);
+    }
+
+    #[test]
+    fn test_cheese_race_finishes_once_the_garbage_is_dug_out() {
+        let mut app = game_app();
+        app.world
+            .insert_resource(GameMode::Cheese(CheeseRows::Rows5));
+        app.world.send_event(NewGame { seed: Some(11) });
+        app.update();
+        tick(&mut app);
+        let board = app.world.resource::<Board>().clone();
+        assert_eq!(board.garbage_rows(), 5);
+        let gaps: Vec<i32> = (0..5)
+            .map(|y| {
+                let empty: Vec<i32> = (0..board.width() as i32)
+                    .filter(|&x| board.get(x, y).is_none())
+                    .collect();
+                assert_eq!(empty.len(), 1, "row {y} has one gap");
+                empty[0]
+            })
+            .collect();
+        tick(&mut app);
+        assert_eq!(state(&app), AppState::Playing);
+
+        let mut board = app.world.resource_mut::<Board>();
+        for (y, &x) in gaps.iter().enumerate() {
+            board.set(x, y as i32, Some(0));
+        }
+        tap(&mut app, Action::HardDrop);
+        for _ in 0..LINE_CLEAR_DELAY + 1 {
+            tick(&mut app);
+        }
+        app.update();
+        assert_eq!(app.world.resource::<Board>().garbage_rows(), 0);
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Finished);
+        assert_eq!(state(&app), AppState::GameOver);
+        let ticks = app.world.resource::<Sprint>().ticks;
+        assert!(ticks > 2);
+        tick(&mut app);
+        assert_eq!(app.world.resource::<Sprint>().ticks, ticks);
+    }
+
+    #[test]
+    fn test_restart_spawns_a_falling_piece_on_the_first_tick() {
+        let mut app = game_app();
+        app.world
+            .insert_resource(GameMode::Sprint(SprintGoal::Lines40));
+        for _ in 0..90 {
+            tick(&mut app);
+        }
+        press(&mut app, &[KeyCode::Space]);
+        tick(&mut app);
+        assert!(!app.world.resource::<Board>().is_empty());
+
+        press(&mut app, &[KeyCode::R]);
+        app.update();
+        press(&mut app, &[]);
+        assert_eq!(state(&app), AppState::Playing);
+        assert!(app.world.resource::<Board>().is_empty());
+        assert_eq!(app.world.resource::<Sprint>().ticks, 0);
+        tick(&mut app);
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Falling);
+        assert!(app.world.resource::<ActivePiece>().0.is_some());
+        assert_eq!(app.world.resource::<Sprint>().ticks, 1);
+    }
+
+    /// The kinds of the next `count` pieces, hard dropping each in turn.
+    fn drop_pieces(app: &mut App, count: usize) -> Vec<PieceKind> {
+        let mut kinds = Vec::new();
+        while kinds.len() < count {
+            tick(app);
+            if let Some(piece) = app.world.resource::<ActivePiece>().0 {
+                kinds.push(piece.kind);
+                tap(app, Action::HardDrop);
+            }
+        }
+        kinds
+    }
+
+    #[test]
+    fn test_retry_deals_the_previous_sequence_again() {
+        let mut app = game_app();
+        app.world.insert_resource(GameMode::Zen);
+        let first = drop_pieces(&mut app, 8);
+
+        let queue = app.world.resource::<PieceQueue>().clone();
+        app.world.send_event(NewGame::retry(&queue));
+        app.update();
+        assert!(app.world.resource::<Board>().is_empty());
+        assert_eq!(drop_pieces(&mut app, 8), first);
+
+        app.world.send_event(NewGame::default());
+        app.update();
+        assert_ne!(app.world.resource::<PieceQueue>().seed(), queue.seed());
+    }
+
+    #[test]
+    fn test_board_changes_are_announced_once_per_frame_written() {
+        let mut app = game_app();
+        let mut reader = ManualEventReader::<BoardChanged>::default();
+        let mut frame = |app: &mut App| {
+            app.update();
+            reader
+                .read(app.world.resource::<Events<BoardChanged>>())
+                .count()
+        };
+        frame(&mut app);
+        tick(&mut app);
+        tick(&mut app);
+        assert_eq!(frame(&mut app), 0);
+
+        tap(&mut app, Action::HardDrop);
+        assert_eq!(frame(&mut app), 1);
+        assert_eq!(frame(&mut app), 0);
+    }
+
+    #[test]
+    fn test_hard_drop_grace_rests_the_piece_before_locking() {
+        let blocks = |app: &App| {
+            let board = app.world.resource::<Board>().to_ascii();
+            board.chars().filter(char::is_ascii_alphabetic).count()
+        };
+        let mut app = game_app();
+        tick(&mut app);
+        tap(&mut app, Action::HardDrop);
+        assert_eq!(blocks(&app), 4);
+
+        app.world.resource_mut::<Settings>().hard_drop_grace = true;
+        tick(&mut app);
+        let piece = app.world.resource::<ActivePiece>().0.unwrap();
+        let landing = app.world.resource::<Board>().landing_position(&piece);
+        tap(&mut app, Action::HardDrop);
+        assert_eq!(app.world.resource::<ActivePiece>().0, Some(landing));
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Locking);
+        assert_eq!(blocks(&app), 4);
+        for _ in 1..HARD_DROP_GRACE {
+            tick(&mut app);
+        }
+        assert_eq!(blocks(&app), 8);
+
+        // Hard dropping a piece that's already resting locks it at once.
+        tick(&mut app);
+        tap(&mut app, Action::HardDrop);
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Locking);
+        tap(&mut app, Action::HardDrop);
+        assert_eq!(blocks(&app), 12);
+
+        // Sliding along the floor during the grace doesn't buy back the
+        // full delay.
+        tick(&mut app);
+        app.world.insert_resource(Board::default());
+        tap(&mut app, Action::HardDrop);
+        tap(&mut app, Action::MoveLeft);
+        let lock = app.world.resource::<LockDelay>();
+        assert_eq!((lock.resets, lock.ticks_left), (1, HARD_DROP_GRACE - 1));
+        for _ in 1..HARD_DROP_GRACE {
+            tick(&mut app);
+        }
+        assert_eq!(blocks(&app), 4);
+    }
+
+    #[test]
+    fn test_a_hard_drop_lands_on_the_partner_piece() {
+        let mut app = game_app();
+        tick(&mut app);
+        let piece = app.world.resource::<ActivePiece>().0.unwrap();
+        let partner = app.world.resource::<Board>().landing_position(&piece);
+        app.world.insert_resource(PartnerPiece(Some(partner)));
+
+        let landed = SharedField::new(&Board::default(), Some(&partner)).landing_position(&piece);
+        assert!(landed.y > partner.y);
+
+        tap(&mut app, Action::HardDrop);
+        let board = app.world.resource::<Board>();
+        assert!(partner
+            .cells()
+            .iter()
+            .all(|&(x, y)| board.get(x, y).is_none()));
+        assert!(landed
+            .cells()
+            .iter()
+            .all(|&(x, y)| board.get(x, y).is_some()));
+    }
+
+    #[test]
+    fn test_firm_drop_grounds_the_piece_without_locking_it() {
+        let mut app = game_app();
+        tick(&mut app);
+        let piece = app.world.resource::<ActivePiece>().0.unwrap();
+        let landing = app.world.resource::<Board>().landing_position(&piece);
+
+        tap(&mut app, Action::FirmDrop);
+        assert_eq!(app.world.resource::<ActivePiece>().0, Some(landing));
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Locking);
+        assert!(app.world.resource::<Board>().is_empty());
+        // The delay started this tick, and has only just begun counting.
+        assert_eq!(app.world.resource::<LockDelay>().ticks_left, LOCK_DELAY - 1);
+
+        // It still slides along the floor before the delay runs out.
+        tap(&mut app, Action::MoveLeft);
+        let slid = app.world.resource::<ActivePiece>().0.unwrap();
+        assert_eq!((slid.x, slid.y), (landing.x - 1, landing.y));
+        assert!(app.world.resource::<Board>().is_empty());
+    }
+
+    #[test]
+    fn test_firm_dropping_off_a_ledge_lands_the_piece_lower() {
+        let mut app = game_app();
+        app.world.resource_mut::<Settings>().lock_reset = LockReset::Step;
+        tick(&mut app);
+        let piece = app.world.resource::<ActivePiece>().0.unwrap();
+        let landing = app.world.resource::<Board>().landing_position(&piece);
+        // Resting above the floor, as if it had just slid off a ledge.
+        *app.world.resource_mut::<Phase>() = Phase::Locking;
+        *app.world.resource_mut::<LockDelay>() = LockDelay {
+            ticks_left: 2,
+            lowest: Some(piece.y),
+            ..default()
+        };
+
+        tap(&mut app, Action::FirmDrop);
+        assert_eq!(app.world.resource::<ActivePiece>().0, Some(landing));
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Locking);
+        let lock = app.world.resource::<LockDelay>();
+        assert_eq!(lock.lowest, Some(landing.y));
+        assert_eq!(lock.ticks_left, LOCK_DELAY - 1);
+    }
+
+    #[test]
+    fn test_each_mode_grounds_pieces_with_its_own_lock_delay() {
+        let grounded = |mode: GameMode| {
+            let mut app = game_app();
+            app.world.insert_resource(mode);
+            tick(&mut app);
+            tap(&mut app, Action::FirmDrop);
+            app.world.resource::<LockDelay>().ticks_left + 1
+        };
+        assert_eq!(grounded(GameMode::Marathon), LOCK_DELAY);
+        assert_eq!(grounded(GameMode::Zen), ZEN_LOCK_DELAY);
+        assert_eq!(GameMode::Survival.lock_delay(), LOCK_DELAY);
+    }
+
+    #[test]
+    fn test_instant_line_clear_collapses_in_the_same_tick() {
+        let mut app = game_app();
+        app.world.resource_mut::<Settings>().instant_line_clear = true;
+        setup_i_piece_clear(&mut app);
+        app.world
+            .resource_mut::<Input<KeyCode>>()
+            .press(KeyCode::Space);
+
+        tick(&mut app);
+        let board = app.world.resource::<Board>();
+        assert_eq!(board.full_rows(), 0);
+        assert_eq!(board.get(0, 0), Some(1));
+        assert_eq!(board.get(0, 1), None);
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Spawning);
+        assert_eq!(app.world.resource::<Score>().lines, 1);
+    }
+
+    #[test]
+    fn test_animated_line_clear_waits_before_collapsing() {
+        let mut app = game_app();
+        setup_i_piece_clear(&mut app);
+        app.world
+            .resource_mut::<Input<KeyCode>>()
+            .press(KeyCode::Space);
+
+        tick(&mut app);
+        assert_eq!(app.world.resource::<Board>().full_rows(), 1);
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Clearing);
+        for _ in 0..LINE_CLEAR_DELAY {
+            tick(&mut app);
+        }
+        assert_eq!(app.world.resource::<Board>().full_rows(), 0);
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Spawning);
+    }
+
+    /// Spawns a piece under `g` of custom gravity, returning its height.
+    fn spawn_with_gravity(app: &mut App, g: f32) -> i32 {
+        app.world.resource_mut::<Settings>().custom_gravity = Some(g);
+        tick(app);
+        app.world.resource::<ActivePiece>().0.unwrap().y
+    }
+
+    fn piece_y(app: &App) -> i32 {
+        app.world.resource::<ActivePiece>().0.unwrap().y
+    }
+
+    /// Columns the piece moves each tick with `keys` held in turn.
+    fn shifts(app: &mut App, keys: &[&[KeyCode]]) -> Vec<i32> {
+        keys.iter()
+            .map(|&held| {
+                let x = app.world.resource::<ActivePiece>().0.unwrap().x;
+                press(app, held);
+                tick(app);
+                app.world.resource::<ActivePiece>().0.unwrap().x - x
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_tick_stages_resolve_to_their_documented_order() {
+        let mut app = game_app();
+        tick(&mut app);
+        let schedules = app.world.resource::<Schedules>();
+        let schedule = schedules.get(FixedUpdate).unwrap();
+        assert_eq!(tick_order_errors(schedule), Vec::<String>::new());
+
+        let graph = schedule.graph();
+        let position = |set: &dyn SystemSet| {
+            let (node, _, _) = graph
+                .system_sets()
+                .find(|&(_, candidate, _)| candidate == set)
+                .unwrap();
+            graph
+                .dependency()
+                .cached_topsort()
+                .iter()
+                .position(|&sorted| sorted == node)
+                .unwrap()
+        };
+        assert!(position(&TickSet::Input) < position(&TickSet::Simulate));
+        let sorted: Vec<_> = TickStage::ORDER
+            .iter()
+            .map(|stage| position(stage))
+            .collect();
+        assert!(
+            sorted.windows(2).all(|pair| pair[0] < pair[1]),
+            "{sorted:?}"
+        );
+    }
+
+    #[test]
+    fn test_left_and_right_pressed_together_stand_still() {
+        let both: &[KeyCode] = &[KeyCode::Left, KeyCode::Right];
+        for policy in OpposingDirections::ALL {
+            let mut app = game_app();
+            app.world.resource_mut::<InputConfig>().opposing = policy;
+            tick(&mut app);
+            let moved = shifts(&mut app, &[both, both, both, &[KeyCode::Right]]);
+            assert_eq!(moved, vec![0, 0, 0, 1], "{policy:?}");
+        }
+    }
+
+    #[test]
+    fn test_rotations_faster_than_the_cooldown_are_dropped() {
+        let rotations = |cooldown: u32| {
+            let mut app = game_app();
+            app.world.resource_mut::<InputConfig>().cooldowns[Action::RotateCw as usize] = cooldown;
+            tick(&mut app);
+            let t = Tetromino::spawn(PieceKind::T, app.world.resource::<Board>());
+            app.world.insert_resource(ActivePiece(Some(t.moved(0, -4))));
+            let mut rotations = 0;
+            // A turbo button: pressed one tick, released the next.
+            for step in 0..24 {
+                let keys: &[KeyCode] = if step % 2 == 0 { &[KeyCode::Up] } else { &[] };
+                press(&mut app, keys);
+                let before = app.world.resource::<ActivePiece>().0.unwrap().rotation;
+                tick(&mut app);
+                let after = app.world.resource::<ActivePiece>().0.unwrap().rotation;
+                rotations += u32::from(before != after);
+            }
+            rotations
+        };
+        assert_eq!(rotations(0), 12);
+        assert_eq!(rotations(6), 4);
+        assert_eq!(rotations(30), 1);
+    }
+
+    #[test]
+    fn test_opposing_policy_decides_a_press_over_a_held_direction() {
+        let left: &[KeyCode] = &[KeyCode::Left];
+        let both: &[KeyCode] = &[KeyCode::Left, KeyCode::Right];
+        let mut app = game_app();
+        tick(&mut app);
+        let moved = shifts(&mut app, &[left, both, both, left]);
+        assert_eq!(moved, vec![-1, 1, 0, -1]);
+
+        let mut app = game_app();
+        app.world.resource_mut::<InputConfig>().opposing = OpposingDirections::Cancel;
+        tick(&mut app);
+        let moved = shifts(&mut app, &[left, both, both, left]);
+        assert_eq!(moved, vec![-1, 0, 0, -1]);
+    }
+
+    #[test]
+    fn test_half_g_falls_one_row_every_two_ticks() {
+        let mut app = game_app();
+        let spawn_y = spawn_with_gravity(&mut app, 0.5);
+        let fallen: Vec<i32> = (0..4)
+            .map(|_| {
+                tick(&mut app);
+                spawn_y - piece_y(&app)
+            })
+            .collect();
+        assert_eq!(fallen, vec![1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_twenty_g_drops_straight_to_the_floor() {
+        let mut app = game_app();
+        spawn_with_gravity(&mut app, 20.0);
+        let piece = app.world.resource::<ActivePiece>().0.unwrap();
+        let board = app.world.resource::<Board>();
+        assert_eq!(board.drop_distance(&piece), 0);
+
+        tick(&mut app);
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Locking);
+    }
+
+    #[test]
+    fn test_hold_does_nothing_when_disabled() {
+        let mut app = game_app();
+        app.world.resource_mut::<Modifiers>().no_hold = true;
+        tick(&mut app);
+        let piece = app.world.resource::<ActivePiece>().0;
+        let upcoming: Vec<_> = app.world.resource::<PieceQueue>().peek(5).collect();
+
+        press(&mut app, &[KeyCode::C]);
+        tick(&mut app);
+        assert_eq!(*app.world.resource::<Hold>(), Hold::default());
+        let queue = app.world.resource::<PieceQueue>();
+        assert_eq!(queue.peek(5).collect::<Vec<_>>(), upcoming);
+        assert_eq!(
+            app.world
+                .resource::<ActivePiece>()
+                .0
+                .map(|piece| piece.kind),
+            piece.map(|piece| piece.kind)
+        );
+    }
+
+    #[test]
+    fn test_rejected_hold_sends_feedback_and_keeps_the_held_piece() {
+        let mut app = game_app();
+        tick(&mut app);
+        press(&mut app, &[KeyCode::C]);
+        tick(&mut app);
+        press(&mut app, &[]);
+        tick(&mut app);
+        let hold = app.world.resource::<Hold>().clone();
+        assert!(!hold.can_hold);
+        let active = app.world.resource::<ActivePiece>().0;
+        app.world.resource_mut::<Events<GameplayEvent>>().clear();
+
+        press(&mut app, &[KeyCode::C]);
+        tick(&mut app);
+        assert_eq!(*app.world.resource::<Hold>(), hold);
+        let piece = app.world.resource::<ActivePiece>().0.unwrap();
+        assert_eq!(Some(piece.kind), active.map(|active| active.kind));
+        let events = app.world.resource::<Events<GameplayEvent>>();
+        let sent: Vec<_> = events.get_reader().read(events).copied().collect();
+        assert!(sent.contains(&GameplayEvent::HoldRejected));
+        assert!(!sent.contains(&GameplayEvent::Held));
+    }
+
+    /// Rotates `piece` clockwise in an otherwise empty game and returns the
+    /// events the tick sent.
+    fn rotate_events(piece: Tetromino) -> Vec<GameplayEvent> {
+        let mut app = game_app();
+        app.world.insert_resource(ActivePiece(Some(piece)));
+        app.world.insert_resource(Phase::Falling);
+        app.world.resource_mut::<Events<GameplayEvent>>().clear();
+        press(&mut app, &[KeyCode::Up]);
+        tick(&mut app);
+        let events = app.world.resource::<Events<GameplayEvent>>();
+        events.get_reader().read(events).copied().collect()
+    }
+
+    #[test]
+    fn test_only_kicked_rotations_report_their_kick() {
+        let board = Board::default();
+        let centred = rotate_events(Tetromino::spawn(PieceKind::T, &board).moved(0, -5));
+        assert!(centred.contains(&GameplayEvent::Rotated));
+        assert!(!centred
+            .iter()
+            .any(|event| matches!(event, GameplayEvent::Kicked(_))));
+
+        let mut upright = Tetromino::spawn(PieceKind::I, &board)
+            .rotated(false)
+            .moved(0, -5);
+        while !board.collides(&upright.moved(-1, 0)) {
+            upright = upright.moved(-1, 0);
+        }
+        let kicked = rotate_events(upright);
+        assert!(kicked.contains(&GameplayEvent::Rotated));
+        assert!(kicked
+            .iter()
+            .any(|event| matches!(event, GameplayEvent::Kicked((dx, _)) if *dx > 0)));
+    }
+
+    /// Puts an O piece resting on a stack of its columns that reaches the
+    /// top of the visible field, so it locks wholly inside the buffer.
+    fn setup_buffer_lock(app: &mut App) {
+        let mut board = Board::default();
+        let spawn = Tetromino::spawn(PieceKind::O, &board);
+        let visible = board.visible_height() as i32;
+        let bottom = spawn.cells().iter().map(|&(_, y)| y).min().unwrap();
+        let piece = spawn.moved(0, visible - bottom);
+        for (x, _) in piece.cells() {
+            for y in 0..visible {
+                board.set(x, y, Some(0));
+            }
+        }
+        assert_eq!(board.drop_distance(&piece), 0);
+        app.world.insert_resource(board);
+        app.world.insert_resource(ActivePiece(Some(piece)));
+        app.world.insert_resource(Phase::Falling);
+    }
+
+    #[test]
+    fn test_locking_in_the_buffer_tops_out_only_under_lock_out() {
+        for (rule, tops_out) in [
+            (TopOutRule::BlockOut, false),
+            (TopOutRule::LockOut, true),
+            (TopOutRule::Both, true),
+        ] {
+            let mut app = game_app();
+            app.world.resource_mut::<Settings>().top_out = rule;
+            setup_buffer_lock(&mut app);
+            press(&mut app, &[KeyCode::Space]);
+            tick(&mut app);
+            let topped_out = *app.world.resource::<Phase>() == Phase::ToppedOut;
+            assert_eq!(topped_out, tops_out, "{rule:?}");
+        }
+    }
+
+    #[test]
+    fn test_shift_up_spawns_one_more_piece_in_the_buffer_before_topping_out() {
+        for shift_up in [false, true] {
+            let mut app = game_app();
+            app.world.resource_mut::<Settings>().spawn_shift_up = shift_up;
+            tick(&mut app);
+
+            // Every row but the top two of the buffer filled, bar a gap in
+            // the first column, so nothing clears and an O fits only there.
+            let mut board = Board::default();
+            for y in 0..board.height() as i32 - 2 {
+                for x in 1..board.width() as i32 {
+                    board.set(x, y, Some(0));
+                }
+            }
+            let mut queue = app.world.resource::<PieceQueue>().clone();
+            queue.push_front(PieceKind::O);
+            queue.push_front(PieceKind::O);
+            app.world.insert_resource(board);
+            app.world.insert_resource(queue);
+            app.world.insert_resource(ActivePiece(None));
+            app.world.insert_resource(Phase::Spawning);
+            tick(&mut app);
+            let spawned = app.world.resource::<ActivePiece>().0;
+            assert_eq!(spawned.is_some(), shift_up);
+            if !shift_up {
+                assert_eq!(*app.world.resource::<Phase>(), Phase::ToppedOut);
+                continue;
+            }
+            let top = Board::default().height() as i32 - 1;
+            assert!(spawned.unwrap().cells().iter().any(|&(_, y)| y == top));
+
+            press(&mut app, &[KeyCode::Space]);
+            for _ in 0..LINE_CLEAR_DELAY + 2 {
+                tick(&mut app);
+            }
+            assert_eq!(*app.world.resource::<Phase>(), Phase::ToppedOut);
+            assert_eq!(app.world.resource::<Score>().pieces, 1);
+        }
+    }
+
+    /// Puts an O piece on the floor of `board`, or on the ledge `board`
+    /// has, and counts the ticks until it locks while `keys` says what is
+    /// pressed on each tick.
+    fn ticks_to_lock(
+        policy: LockReset,
+        board: Board,
+        keys: impl Fn(u32) -> &'static [KeyCode],
+    ) -> u32 {
+        let mut app = game_app();
+        {
+            let mut settings = app.world.resource_mut::<Settings>();
+            settings.lock_reset = policy;
+            settings.custom_gravity = Some(MAX_GRAVITY);
+        }
+        let piece = Tetromino::spawn(PieceKind::O, &board);
+        let piece = board.landing_position(&piece);
+        app.world.insert_resource(board);
+        app.world.insert_resource(ActivePiece(Some(piece)));
+        app.world.insert_resource(Phase::Falling);
+        for tick_count in 1..200 {
+            press(&mut app, keys(tick_count));
+            tick(&mut app);
+            if app.world.resource::<ActivePiece>().0.is_none() {
+                return tick_count;
+            }
+        }
+        panic!("the piece never locked under {policy:?}");
+    }
+
+    #[test]
+    fn test_freeplay_leaves_a_resting_piece_in_play() {
+        let mut app = game_app();
+        app.world.insert_resource(GameMode::Zen);
+        {
+            let mut settings = app.world.resource_mut::<Settings>();
+            settings.freeplay = true;
+            settings.custom_gravity = Some(MAX_GRAVITY);
+        }
+        tick(&mut app);
+        let piece = app.world.resource::<ActivePiece>().0.unwrap();
+        let landing = app.world.resource::<Board>().landing_position(&piece);
+        for _ in 0..3 * LOCK_DELAY {
+            tick(&mut app);
+        }
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Locking);
+        assert_eq!(app.world.resource::<ActivePiece>().0, Some(landing));
+        assert!(app.world.resource::<Board>().is_empty());
+
+        let blocks = |app: &App| {
+            let board = app.world.resource::<Board>().to_ascii();
+            board.chars().filter(char::is_ascii_alphabetic).count()
+        };
+        tap(&mut app, Action::HardDrop);
+        assert_eq!(blocks(&app), 4);
+
+        // Outside Zen the delay runs out as usual.
+        app.world.insert_resource(GameMode::Marathon);
+        for _ in 0..LOCK_DELAY + 2 {
+            tick(&mut app);
+        }
+        assert_eq!(blocks(&app), 8);
+    }
+
+    #[test]
+    fn test_lock_reset_policies_against_the_floor() {
+        // Shuffling along the floor only buys time under move reset, and
+        // only up to the cap.
+        let shuffle = |tick: u32| -> &'static [KeyCode] {
+            match tick % 4 {
+                1 => &[KeyCode::Left],
+                3 => &[KeyCode::Right],
+                _ => &[],
+            }
+        };
+        let flat = |policy| ticks_to_lock(policy, Board::default(), shuffle);
+        assert_eq!(flat(LockReset::Step), LOCK_DELAY);
+        assert_eq!(flat(LockReset::Never), LOCK_DELAY);
+        let moving = flat(LockReset::Move);
+        assert!(moving > LOCK_DELAY && moving <= 2 * MAX_LOCK_RESETS + LOCK_DELAY);
+
+        // Stepping off a ledge onto the floor restarts the delay under step
+        // reset, and not without it.
+        let mut ledge = Board::default();
+        for x in 0..6 {
+            ledge.set(x, 0, Some(0));
+        }
+        let step_off = |tick: u32| -> &'static [KeyCode] {
+            match tick {
+                20 | 22 => &[KeyCode::Right],
+                _ => &[],
+            }
+        };
+        let never = ticks_to_lock(LockReset::Never, ledge.clone(), step_off);
+        let step = ticks_to_lock(LockReset::Step, ledge.clone(), step_off);
+        let moved = ticks_to_lock(LockReset::Move, ledge, step_off);
+        assert!(never <= LOCK_DELAY + 1, "{never}");
+        assert!(step > 20 + LOCK_DELAY, "{step}");
+        assert!(moved > 20 + LOCK_DELAY, "{moved}");
+    }
+
+    #[test]
+    fn test_soft_drop_factor_multiplies_gravity() {
+        let mut app = game_app();
+        app.world.resource_mut::<InputConfig>().soft_drop_factor = 20;
+        tick(&mut app);
+        let spawn_y = piece_y(&app);
+        press(&mut app, &[KeyCode::Down]);
+        // Level 1 falls a row every 60 ticks, so 20x soft drop every 3.
+        let fallen: Vec<i32> = (0..6)
+            .map(|_| {
+                tick(&mut app);
+                spawn_y - piece_y(&app)
+            })
+            .collect();
+        assert_eq!(fallen, vec![0, 0, 1, 1, 1, 2]);
+        assert_eq!(app.world.resource::<Score>().points, 2);
+    }
+
+    #[test]
+    fn test_infinite_soft_drop_reaches_the_floor_in_one_tick() {
+        let mut app = game_app();
+        app.world.resource_mut::<InputConfig>().soft_drop_infinite = true;
+        tick(&mut app);
+        press(&mut app, &[KeyCode::Down]);
+        tick(&mut app);
+        let piece = app.world.resource::<ActivePiece>().0.unwrap();
+        assert_eq!(app.world.resource::<Board>().drop_distance(&piece), 0);
+        assert_eq!(*app.world.resource::<Phase>(), Phase::Falling);
+        assert!(app.world.resource::<Board>().is_empty());
+    }
+
+    /// How far the freshly spawned piece falls to the floor, and the
+    /// points scored by the time it locks after `keys` are held for a tick.
+    fn drop_points(app: &mut App, keys: &[KeyCode]) -> (u32, u32) {
+        tick(app);
+        let piece = app.world.resource::<ActivePiece>().0.unwrap();
+        let distance = app.world.resource::<Board>().drop_distance(&piece) as u32;
+        press(app, keys);
+        tick(app);
+        press(app, &[]);
+        for _ in 0..=LOCK_DELAY {
+            tick(app);
+        }
+        assert!(!app.world.resource::<Board>().is_empty());
+        (distance, app.world.resource::<Score>().points)
+    }
+
+    #[test]
+    fn test_drops_from_spawn_score_the_rows_travelled() {
+        let mut app = game_app();
+        app.world.resource_mut::<InputConfig>().soft_drop_infinite = true;
+        let (distance, points) = drop_points(&mut app, &[KeyCode::Down]);
+        assert_eq!(points, distance);
+
+        let mut app = game_app();
+        let (distance, points) = drop_points(&mut app, &[KeyCode::Space]);
+        assert_eq!(points, 2 * distance);
+    }
+
+    #[test]
+    fn test_soft_drop_points_stop_at_the_cap() {
+        let mut app = game_app();
+        app.world.resource_mut::<InputConfig>().soft_drop_infinite = true;
+        app.world.resource_mut::<Settings>().soft_drop_cap = Some(5);
+        let (distance, points) = drop_points(&mut app, &[KeyCode::Down]);
+        assert!(distance > 5, "{distance}");
+        assert_eq!(points, 5);
+
+        // Later drops of the same piece share what is left of the cap.
+        let mut gravity = Gravity::default();
+        assert_eq!(gravity.soft_drop(3, Some(5)), 3);
+        assert_eq!(gravity.soft_drop(3, Some(5)), 2);
+        assert_eq!(gravity.soft_drop(3, Some(5)), 0);
+        assert_eq!(gravity.soft_drop(3, None), 3);
+    }
+
+    #[test]
+    fn test_escape_during_play_asks_before_quitting() {
+        let mut app = game_app();
+        let exits = |app: &App| app.world.resource::<Events<AppExit>>().len();
+        tick(&mut app);
+        press(&mut app, &[KeyCode::Escape]);
+        app.update();
+        assert!(app.world.resource::<ExitPrompt>().open);
+        assert_eq!(exits(&app), 0);
+        press(&mut app, &[]);
+        app.update();
+        assert_eq!(state(&app), AppState::Paused);
+        assert_eq!(exits(&app), 0);
+
+        // Escape again backs out, and the game stays paused.
+        press(&mut app, &[KeyCode::Escape]);
+        app.update();
+        assert!(!app.world.resource::<ExitPrompt>().open);
+        assert_eq!(exits(&app), 0);
+
+        press(&mut app, &[KeyCode::Escape]);
+        app.update();
+        assert!(app.world.resource::<ExitPrompt>().open);
+        press(&mut app, &[]);
+        app.world.send_event(ConfirmExit);
+        app.update();
+        assert_eq!(exits(&app), 1);
+    }
+}
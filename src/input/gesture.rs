@@ -0,0 +1,302 @@
+//! Swipes and taps on the playfield, recognised into the same [`Action`]s
+//! the keys and touch buttons produce.
+//!
+//! Dragging sideways moves the piece a column for every
+//! [`GestureConfig::column_distance`] covered, dragging down soft-drops for
+//! as long as the finger stays down, and flicking down hard-drops. A quick
+//! tap rotates clockwise and a two-finger tap holds. Touches that start on
+//! a [`TouchControls`] button are left to the button.
+
+use std::collections::{HashMap, VecDeque};
+
+use bevy::input::touch::{TouchInput, TouchPhase};
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+
+use crate::game::{AppState, TickSet};
+use crate::input::keyboard::{read_keyboard, Action};
+use crate::input::touch::{read_touches, TouchControls};
+use crate::utils::config::GestureConfig;
+
+/// A finger on the playfield.
+#[derive(Debug, Clone, Copy)]
+struct Stroke {
+    start: Vec2,
+    started_at: f32,
+    /// Where the next column's worth of sideways travel is measured from.
+    anchor_x: f32,
+}
+
+/// Turns touch events into actions.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct GestureRecognizer {
+    strokes: HashMap<u64, Stroke>,
+    /// More than one finger has been down since the screen was last clear.
+    multi_touch: bool,
+    /// A finger of the current touch has moved, so it can't end in a tap.
+    any_moved: bool,
+    /// The finger soft-dropping, if one is.
+    soft_drop: Option<u64>,
+    /// Recognised one-off actions not yet played.
+    pending: VecDeque<Action>,
+}
+
+impl GestureRecognizer {
+    /// Takes one touch event, `now` seconds into the game.
+    pub fn feed(&mut self, event: &TouchInput, now: f32, config: &GestureConfig) {
+        match event.phase {
+            TouchPhase::Started => {
+                if !self.strokes.is_empty() {
+                    self.multi_touch = true;
+                }
+                self.strokes.insert(
+                    event.id,
+                    Stroke {
+                        start: event.position,
+                        started_at: now,
+                        anchor_x: event.position.x,
+                    },
+                );
+            }
+            TouchPhase::Moved => self.track(event, config),
+            TouchPhase::Ended => {
+                self.track(event, config);
+                self.finish(event, now, config);
+            }
+            TouchPhase::Canceled => {
+                self.strokes.remove(&event.id);
+                if self.soft_drop == Some(event.id) {
+                    self.soft_drop = None;
+                }
+                if self.strokes.is_empty() {
+                    self.multi_touch = false;
+                    self.any_moved = false;
+                }
+            }
+        }
+    }
+
+    fn track(&mut self, event: &TouchInput, config: &GestureConfig) {
+        let Some(stroke) = self.strokes.get_mut(&event.id) else {
+            return;
+        };
+        let travel = event.position - stroke.start;
+        if travel.length() > config.tap_distance {
+            self.any_moved = true;
+        }
+        if self.multi_touch {
+            return;
+        }
+        let column = config.column_distance.max(1.0);
+        while (event.position.x - stroke.anchor_x).abs() >= column {
+            let sign = (event.position.x - stroke.anchor_x).signum();
+            stroke.anchor_x += sign * column;
+            self.pending.push_back(if sign < 0.0 {
+                Action::MoveLeft
+            } else {
+                Action::MoveRight
+            });
+        }
+        // Screen y grows downwards.
+        if travel.y >= config.swipe_distance && travel.y > travel.x.abs() {
+            self.soft_drop = Some(event.id);
+        }
+    }
+
+    fn finish(&mut self, event: &TouchInput, now: f32, config: &GestureConfig) {
+        let Some(stroke) = self.strokes.remove(&event.id) else {
+            return;
+        };
+        if self.soft_drop == Some(event.id) {
+            self.soft_drop = None;
+        }
+        let travel = event.position - stroke.start;
+        let seconds = (now - stroke.started_at).max(f32::EPSILON);
+        let quick = seconds <= config.tap_secs;
+        if !self.multi_touch
+            && travel.y >= config.swipe_distance
+            && travel.y / seconds >= config.hard_drop_speed
+        {
+            self.pending.push_back(Action::HardDrop);
+        } else if self.strokes.is_empty() && quick && !self.any_moved {
+            self.pending.push_back(if self.multi_touch {
+                Action::Hold
+            } else {
+                Action::RotateCw
+            });
+        }
+        if self.strokes.is_empty() {
+            self.multi_touch = false;
+            self.any_moved = false;
+        }
+    }
+
+    /// Whether a finger is soft-dropping.
+    pub fn soft_dropping(&self) -> bool {
+        self.soft_drop.is_some()
+    }
+
+    /// Removes and returns the oldest recognised one-off action.
+    pub fn next_action(&mut self) -> Option<Action> {
+        self.pending.pop_front()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// The gesture actions held on the current tick.
+///
+/// One-off actions are each pressed for a tick and then released for one,
+/// so repeats of the same action still register as separate presses.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct Gestures {
+    pressed: Option<Action>,
+    soft_drop: bool,
+}
+
+impl Gestures {
+    pub fn held(&self, action: Action) -> bool {
+        self.pressed == Some(action) || (action == Action::SoftDrop && self.soft_drop)
+    }
+}
+
+pub struct GesturePlugin;
+
+impl Plugin for GesturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GestureRecognizer>()
+            .init_resource::<Gestures>()
+            .init_resource::<GestureConfig>()
+            .add_systems(
+                PreUpdate,
+                recognize_gestures.after(InputSystem).after(read_touches),
+            )
+            .add_systems(
+                FixedUpdate,
+                play_gestures
+                    .in_set(TickSet::Input)
+                    .before(read_keyboard)
+                    .run_if(in_state(AppState::Playing)),
+            );
+    }
+}
+
+/// Feeds the frame's touch events to the recognizer while a game is on.
+pub fn recognize_gestures(
+    mut events: EventReader<TouchInput>,
+    time: Res<Time<Real>>,
+    config: Res<GestureConfig>,
+    controls: Option<Res<TouchControls>>,
+    state: Res<State<AppState>>,
+    mut recognizer: ResMut<GestureRecognizer>,
+) {
+    if !config.enabled || *state.get() != AppState::Playing {
+        events.clear();
+        recognizer.clear();
+        return;
+    }
+    let now = time.elapsed_seconds();
+    for event in events.read() {
+        let on_button = controls.as_ref().is_some_and(|controls| {
+            controls.shown
+                && controls
+                    .buttons()
+                    .iter()
+                    .any(|(_, rect)| rect.contains(event.position))
+        });
+        if event.phase == TouchPhase::Started && on_button {
+            continue;
+        }
+        recognizer.feed(event, now, &config);
+    }
+}
+
+/// Presses the next recognised action for this tick.
+fn play_gestures(mut recognizer: ResMut<GestureRecognizer>, mut gestures: ResMut<Gestures>) {
+    gestures.pressed = match gestures.pressed {
+        Some(_) => None,
+        None => recognizer.next_action(),
+    };
+    gestures.soft_drop = recognizer.soft_dropping();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TouchPhase::{Ended, Moved, Started};
+
+    /// Plays `events`, each `(finger, phase, x, y, seconds)`, and returns
+    /// the actions recognised and whether anything soft-dropped.
+    fn recognize(events: &[(u64, TouchPhase, f32, f32, f32)]) -> (Vec<Action>, bool) {
+        let config = GestureConfig::default();
+        let mut recognizer = GestureRecognizer::default();
+        let mut soft_dropped = false;
+        for &(id, phase, x, y, now) in events {
+            let event = TouchInput {
+                phase,
+                position: Vec2::new(x, y),
+                force: None,
+                id,
+            };
+            recognizer.feed(&event, now, &config);
+            soft_dropped |= recognizer.soft_dropping();
+        }
+        let actions = std::iter::from_fn(|| recognizer.next_action()).collect();
+        (actions, soft_dropped)
+    }
+
+    #[test]
+    fn test_swipes_move_and_drop() {
+        let column = GestureConfig::default().column_distance;
+        let (actions, soft) = recognize(&[
+            (0, Started, 300.0, 300.0, 0.0),
+            (0, Moved, 300.0 - column * 1.5, 300.0, 0.1),
+            (0, Moved, 300.0 - column * 3.2, 302.0, 0.2),
+            (0, Ended, 300.0 - column * 3.2, 302.0, 0.3),
+        ]);
+        assert_eq!(actions, [Action::MoveLeft; 3]);
+        assert!(!soft);
+
+        let (actions, soft) = recognize(&[
+            (0, Started, 300.0, 300.0, 0.0),
+            (0, Moved, 302.0, 380.0, 0.4),
+            (0, Moved, 304.0, 460.0, 0.8),
+            (0, Ended, 304.0, 460.0, 1.0),
+        ]);
+        assert!(actions.is_empty());
+        assert!(soft);
+
+        let (actions, _) = recognize(&[
+            (0, Started, 300.0, 300.0, 0.0),
+            (0, Moved, 300.0, 400.0, 0.05),
+            (0, Ended, 300.0, 500.0, 0.1),
+        ]);
+        assert_eq!(actions, [Action::HardDrop]);
+    }
+
+    #[test]
+    fn test_taps_rotate_and_two_finger_taps_hold() {
+        let (actions, _) = recognize(&[
+            (0, Started, 300.0, 300.0, 0.0),
+            (0, Ended, 302.0, 301.0, 0.1),
+        ]);
+        assert_eq!(actions, [Action::RotateCw]);
+
+        let (actions, _) = recognize(&[
+            (0, Started, 300.0, 300.0, 0.0),
+            (1, Started, 400.0, 300.0, 0.02),
+            (0, Ended, 300.0, 300.0, 0.1),
+            (1, Ended, 401.0, 300.0, 0.12),
+        ]);
+        assert_eq!(actions, [Action::Hold]);
+
+        // Resting a finger too long isn't a tap.
+        let (actions, _) = recognize(&[
+            (0, Started, 300.0, 300.0, 0.0),
+            (0, Ended, 300.0, 300.0, 2.0),
+        ]);
+        assert!(actions.is_empty());
+    }
+}
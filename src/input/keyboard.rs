@@ -0,0 +1,411 @@
+//! Keyboard bindings, action events and per-tick action state.
+
+use bevy::prelude::*;
+
+use crate::input::gesture::Gestures;
+use crate::input::touch::TouchControls;
+use crate::utils::config::InputConfig;
+
+/// A logical gameplay input, independent of the key that produced it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateCw,
+    RotateCcw,
+    Hold,
+    /// Takes back the last locked piece, in modes that allow it.
+    Undo,
+    /// Drops the piece to where it lands and leaves it to lock once its
+    /// lock delay runs out, rather than at once as a hard drop does.
+    FirmDrop,
+    /// Keeps the game as it stands to come back to, in modes that allow
+    /// undo.
+    SetCheckpoint,
+    /// Puts the game back as it was when the checkpoint was set.
+    ResetCheckpoint,
+}
+
+impl Action {
+    pub const ALL: [Action; 11] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateCw,
+        Action::RotateCcw,
+        Action::Hold,
+        Action::Undo,
+        Action::FirmDrop,
+        Action::SetCheckpoint,
+        Action::ResetCheckpoint,
+    ];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "action.move_left",
+            Action::MoveRight => "action.move_right",
+            Action::SoftDrop => "action.soft_drop",
+            Action::HardDrop => "action.hard_drop",
+            Action::RotateCw => "action.rotate_cw",
+            Action::RotateCcw => "action.rotate_ccw",
+            Action::Hold => "action.hold",
+            Action::Undo => "action.undo",
+            Action::FirmDrop => "action.firm_drop",
+            Action::SetCheckpoint => "action.set_checkpoint",
+            Action::ResetCheckpoint => "action.reset_checkpoint",
+        }
+    }
+}
+
+/// Why [`KeyBindings::bind`] refused a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindError {
+    /// The key already triggers this other action.
+    Taken(Action),
+}
+
+/// The keys bound to each [`Action`].
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+    keys: [Vec<KeyCode>; Action::ALL.len()],
+}
+
+impl KeyBindings {
+    /// The second player's keys in [versus](crate::game::GameMode::Versus),
+    /// clear of the defaults: J and L to move, K to soft drop, I to hard
+    /// drop, O and U to rotate, N to hold and M to firm drop.
+    pub fn player_two() -> Self {
+        Self {
+            keys: [
+                vec![KeyCode::J],
+                vec![KeyCode::L],
+                vec![KeyCode::K],
+                vec![KeyCode::I],
+                vec![KeyCode::O],
+                vec![KeyCode::U],
+                vec![KeyCode::N],
+                vec![],
+                vec![KeyCode::M],
+                vec![],
+                vec![],
+            ],
+        }
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        &self.keys[action as usize]
+    }
+
+    /// The action `key` triggers, if any.
+    pub fn action_for(&self, key: KeyCode) -> Option<Action> {
+        Action::ALL
+            .into_iter()
+            .find(|&action| self.keys(action).contains(&key))
+    }
+
+    /// Makes `key` the first key of `action`, in place of the one there.
+    /// A key that already triggers another action is refused, leaving the
+    /// bindings as they were.
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> Result<(), BindError> {
+        match self.action_for(key) {
+            Some(other) if other != action => Err(BindError::Taken(other)),
+            _ => {
+                self.set_primary(action, key);
+                Ok(())
+            }
+        }
+    }
+
+    /// Binds `key` to `action` as [`Self::bind`] does, handing the key it
+    /// replaces to whichever action `key` is taken from.
+    pub fn swap(&mut self, action: Action, key: KeyCode) {
+        let Some(other) = self.action_for(key).filter(|&other| other != action) else {
+            self.set_primary(action, key);
+            return;
+        };
+        let replaced = self.keys[action as usize].first().copied();
+        let keys = &mut self.keys[other as usize];
+        match replaced {
+            Some(replaced) if !keys.contains(&replaced) => {
+                for bound in keys.iter_mut().filter(|bound| **bound == key) {
+                    *bound = replaced;
+                }
+            }
+            _ => keys.retain(|&bound| bound != key),
+        }
+        self.set_primary(action, key);
+    }
+
+    fn set_primary(&mut self, action: Action, key: KeyCode) {
+        let keys = &mut self.keys[action as usize];
+        keys.retain(|&bound| bound != key);
+        match keys.first_mut() {
+            Some(primary) => *primary = key,
+            None => keys.push(key),
+        }
+    }
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            keys: [
+                vec![KeyCode::Left],
+                vec![KeyCode::Right],
+                vec![KeyCode::Down],
+                vec![KeyCode::Space],
+                vec![KeyCode::Up, KeyCode::X],
+                vec![KeyCode::Z, KeyCode::ControlLeft],
+                vec![KeyCode::C, KeyCode::ShiftLeft],
+                vec![KeyCode::Back],
+                vec![KeyCode::V],
+                vec![KeyCode::F5],
+                vec![KeyCode::F6],
+            ],
+        }
+    }
+}
+
+/// A logical action starting or stopping being held.
+///
+/// Every source of input, whether the keyboard, touch, a replay or the AI,
+/// reports what it does as these, and [`apply_action_events`] folds each
+/// tick's events into [`ActionState`] for the gameplay systems to read.
+/// Tests can drive a game by sending them directly.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionEvent {
+    pub action: Action,
+    pub pressed: bool,
+}
+
+impl ActionEvent {
+    pub fn press(action: Action) -> Self {
+        Self {
+            action,
+            pressed: true,
+        }
+    }
+
+    pub fn release(action: Action) -> Self {
+        Self {
+            action,
+            pressed: false,
+        }
+    }
+}
+
+/// Which actions are held, and which started being held, as of the current
+/// gameplay tick.
+///
+/// Edges are derived by comparing consecutive ticks rather than from
+/// `Input::just_pressed`, which tracks render frames and would double-fire
+/// or drop presses whenever the fixed tick runs more or less than once per
+/// frame.
+#[derive(Resource, Debug, Default, Clone, PartialEq, Eq)]
+pub struct ActionState {
+    held: [bool; Action::ALL.len()],
+    pressed: [bool; Action::ALL.len()],
+    /// Released in the same tick they were pressed, so still held for that
+    /// tick and let go at the start of the next.
+    releasing: [bool; Action::ALL.len()],
+}
+
+impl ActionState {
+    pub fn held(&self, action: Action) -> bool {
+        self.held[action as usize]
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.pressed[action as usize]
+    }
+
+    /// The held actions as bits, bit `n` set when `Action::ALL[n]` is held.
+    pub fn held_bits(&self) -> u16 {
+        Action::ALL
+            .iter()
+            .filter(|&&action| self.held(action))
+            .fold(0, |bits, &action| bits | 1 << action as u16)
+    }
+
+    /// Updates the state from whether each action is held this tick.
+    pub fn update(&mut self, held: impl Fn(Action) -> bool) {
+        for action in Action::ALL {
+            let now = held(action);
+            self.pressed[action as usize] = now && !self.held[action as usize];
+            self.held[action as usize] = now;
+        }
+    }
+
+    /// Moves on to the next tick, applying its `events` in order.
+    ///
+    /// An action pressed and released within one tick counts as held for
+    /// that tick, so that the press isn't lost and a recording of the held
+    /// actions plays it back the same.
+    pub fn apply(&mut self, events: impl IntoIterator<Item = ActionEvent>) {
+        for (held, releasing) in self.held.iter_mut().zip(&mut self.releasing) {
+            if std::mem::take(releasing) {
+                *held = false;
+            }
+        }
+        let before = self.held;
+        let mut now = before;
+        for ActionEvent { action, pressed } in events {
+            let i = action as usize;
+            if pressed {
+                now[i] = true;
+                self.releasing[i] = false;
+            } else if now[i] && !before[i] {
+                self.releasing[i] = true;
+            } else {
+                now[i] = false;
+            }
+        }
+        self.update(|action| now[action as usize]);
+    }
+}
+
+/// Drops presses that come within an action's
+/// [`InputConfig::cooldowns`] of the last one let through, and the
+/// releases that end them.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    tick: u32,
+    last_press: [Option<u32>; Action::ALL.len()],
+    dropped: [bool; Action::ALL.len()],
+}
+
+impl Throttle {
+    /// Whether `event`, sent on the current tick, goes through.
+    pub fn allows(&mut self, event: ActionEvent, cooldown: u32) -> bool {
+        let i = event.action as usize;
+        if !event.pressed {
+            return !std::mem::take(&mut self.dropped[i]);
+        }
+        let cooling = self.last_press[i].is_some_and(|last| self.tick - last < cooldown);
+        if cooling {
+            self.dropped[i] = true;
+        } else {
+            self.last_press[i] = Some(self.tick);
+        }
+        !cooling
+    }
+
+    /// Moves on to the next tick.
+    pub fn advance(&mut self) {
+        self.tick += 1;
+    }
+}
+
+/// Samples the keyboard, and the [`TouchControls`] and [`Gestures`] if
+/// there are any, at the start of each tick, sending an [`ActionEvent`] for
+/// every action that started or stopped being held since the last sample
+/// and isn't held back by its cooldown.
+#[allow(clippy::too_many_arguments)]
+pub fn read_keyboard(
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<KeyBindings>,
+    config: Res<InputConfig>,
+    touch: Option<Res<TouchControls>>,
+    gestures: Option<Res<Gestures>>,
+    mut sampled: Local<[bool; Action::ALL.len()]>,
+    mut throttle: Local<Throttle>,
+    mut events: EventWriter<ActionEvent>,
+) {
+    throttle.advance();
+    for action in Action::ALL {
+        let held = keys.any_pressed(bindings.keys(action).iter().copied())
+            || touch.as_ref().is_some_and(|touch| touch.held(action))
+            || gestures
+                .as_ref()
+                .is_some_and(|gestures| gestures.held(action));
+        if std::mem::replace(&mut sampled[action as usize], held) != held {
+            let event = ActionEvent {
+                action,
+                pressed: held,
+            };
+            if throttle.allows(event, config.cooldown(action)) {
+                events.send(event);
+            }
+        }
+    }
+}
+
+/// Sends the events that take `actions` to holding exactly what `held`
+/// says, for inputs that know the whole state each tick rather than its
+/// changes.
+pub fn send_held(
+    actions: &ActionState,
+    held: impl Fn(Action) -> bool,
+    events: &mut EventWriter<ActionEvent>,
+) {
+    for action in Action::ALL {
+        let pressed = held(action);
+        if pressed != actions.held(action) {
+            events.send(ActionEvent { action, pressed });
+        }
+    }
+}
+
+/// Folds the tick's [`ActionEvent`]s into [`ActionState`], after every input
+/// has sent them.
+pub fn apply_action_events(mut events: EventReader<ActionEvent>, mut actions: ResMut<ActionState>) {
+    actions.apply(events.read().copied());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_a_press_released_within_the_tick_still_registers() {
+        let mut actions = ActionState::default();
+        actions.apply([
+            ActionEvent::press(Action::HardDrop),
+            ActionEvent::release(Action::HardDrop),
+        ]);
+        assert!(actions.just_pressed(Action::HardDrop));
+        assert!(actions.held(Action::HardDrop));
+
+        actions.apply([]);
+        assert!(!actions.held(Action::HardDrop));
+        assert!(!actions.just_pressed(Action::HardDrop));
+
+        // Tapping on consecutive ticks presses each time.
+        for _ in 0..2 {
+            actions.apply([
+                ActionEvent::press(Action::MoveLeft),
+                ActionEvent::release(Action::MoveLeft),
+            ]);
+            assert!(actions.just_pressed(Action::MoveLeft));
+        }
+    }
+
+    #[test]
+    fn test_binding_a_taken_key_is_refused_unless_swapped() {
+        let mut bindings = KeyBindings::default();
+        let defaults = bindings.clone();
+        assert_eq!(
+            bindings.bind(Action::Hold, KeyCode::Space),
+            Err(BindError::Taken(Action::HardDrop))
+        );
+        assert_eq!(bindings, defaults);
+
+        assert_eq!(bindings.bind(Action::Hold, KeyCode::A), Ok(()));
+        assert_eq!(
+            bindings.keys(Action::Hold),
+            [KeyCode::A, KeyCode::ShiftLeft]
+        );
+
+        bindings.swap(Action::Hold, KeyCode::Space);
+        assert_eq!(
+            bindings.keys(Action::Hold),
+            [KeyCode::Space, KeyCode::ShiftLeft]
+        );
+        assert_eq!(bindings.keys(Action::HardDrop), [KeyCode::A]);
+        assert_eq!(bindings.action_for(KeyCode::Space), Some(Action::Hold));
+    }
+}
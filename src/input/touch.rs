@@ -0,0 +1,332 @@
+//! On-screen touch buttons for playing without a keyboard.
+//!
+//! Each button stands for an [`Action`] and counts as held for as long as a
+//! finger rests on it, so holding a direction auto-shifts exactly as a held
+//! key does. The buttons sit in the space beside or below the board,
+//! whichever leaves them bigger, and are sized from the window.
+
+use bevy::input::touch::Touches;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use crate::game::AppState;
+use crate::input::keyboard::Action;
+use crate::rendering::shapes::BoardLayout;
+use crate::utils::config::Settings;
+
+/// The actions given a button, in on-screen order: the first half on the
+/// left, the rest on the right.
+pub const TOUCH_ACTIONS: [Action; 6] = [
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::SoftDrop,
+    Action::Hold,
+    Action::RotateCw,
+    Action::HardDrop,
+];
+
+/// Button side as a share of the window's shorter side, and the range it
+/// is kept within, in logical pixels.
+const BUTTON_SHARE: f32 = 0.14;
+const MIN_BUTTON: f32 = 40.0;
+const MAX_BUTTON: f32 = 120.0;
+
+/// Space between buttons and around them, as a share of the button side.
+const BUTTON_GAP: f32 = 0.2;
+
+/// When the buttons are shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TouchOverlay {
+    /// On phones and tablets, and anywhere else once the screen is touched.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl TouchOverlay {
+    pub const ALL: [TouchOverlay; 3] = [
+        TouchOverlay::Auto,
+        TouchOverlay::Always,
+        TouchOverlay::Never,
+    ];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            TouchOverlay::Auto => "touch.auto",
+            TouchOverlay::Always => "touch.always",
+            TouchOverlay::Never => "touch.never",
+        }
+    }
+}
+
+/// The touch buttons as of this frame.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct TouchControls {
+    /// Whether the buttons are shown and take touches.
+    pub shown: bool,
+    /// Whether the screen has been touched since the game started.
+    touched: bool,
+    /// Each button's place on screen, in logical pixels from the top left.
+    buttons: Vec<(Action, Rect)>,
+    held: [bool; Action::ALL.len()],
+}
+
+impl TouchControls {
+    /// Whether a finger is on the button for `action`.
+    pub fn held(&self, action: Action) -> bool {
+        self.held[action as usize]
+    }
+
+    pub fn buttons(&self) -> &[(Action, Rect)] {
+        &self.buttons
+    }
+}
+
+/// Where the board appears on screen when seen flat, in logical pixels
+/// from the top left.
+pub fn board_on_screen(layout: &BoardLayout) -> Rect {
+    let scale = layout.world_per_pixel();
+    let center = layout.content_rect().center();
+    let to_screen = |world: Vec2| {
+        let offset = (world - center) / scale;
+        layout.window / 2.0 + Vec2::new(offset.x, -offset.y)
+    };
+    let board = layout.board_rect();
+    Rect::from_corners(to_screen(board.min), to_screen(board.max))
+}
+
+/// Lays out a button for each of [`TOUCH_ACTIONS`] around `board`.
+///
+/// The buttons go in two columns at the bottom of the strips either side of
+/// the board, or else in a single row in the strip below it, whichever
+/// fits bigger buttons.
+pub fn button_layout(window: Vec2, board: Rect) -> Vec<(Action, Rect)> {
+    let wanted = (window.min_element() * BUTTON_SHARE).clamp(MIN_BUTTON, MAX_BUTTON);
+    let half = TOUCH_ACTIONS.len() / 2;
+    let spacing = 1.0 + BUTTON_GAP;
+    let beside_width = board.min.x.min(window.x - board.max.x);
+    let beside = wanted
+        .min((beside_width / spacing - BUTTON_GAP).max(0.0))
+        .min(window.y / (half as f32 * spacing + BUTTON_GAP));
+    let below_height = window.y - board.max.y;
+    let below = wanted
+        .min(window.x / (TOUCH_ACTIONS.len() as f32 * spacing + BUTTON_GAP))
+        .min((below_height / spacing - BUTTON_GAP).max(0.0));
+
+    let place = |corner: Vec2, size: f32| Rect::from_corners(corner, corner + size);
+    if beside >= below {
+        let gap = beside * BUTTON_GAP;
+        TOUCH_ACTIONS
+            .into_iter()
+            .enumerate()
+            .map(|(index, action)| {
+                let row = (index % half) as f32;
+                let x = if index < half {
+                    gap
+                } else {
+                    window.x - gap - beside
+                };
+                let y = window.y - (half as f32 - row) * (beside + gap);
+                (action, place(Vec2::new(x, y), beside))
+            })
+            .collect()
+    } else {
+        let gap = below * BUTTON_GAP;
+        let row = TOUCH_ACTIONS.len() as f32 * (below + gap) - gap;
+        let left = (window.x - row) / 2.0;
+        TOUCH_ACTIONS
+            .into_iter()
+            .enumerate()
+            .map(|(index, action)| {
+                let x = left + index as f32 * (below + gap);
+                (action, place(Vec2::new(x, window.y - gap - below), below))
+            })
+            .collect()
+    }
+}
+
+fn symbol(action: Action) -> &'static str {
+    match action {
+        Action::MoveLeft => "◀",
+        Action::MoveRight => "▶",
+        Action::SoftDrop => "▼",
+        Action::HardDrop => "⏬",
+        Action::RotateCw => "⟳",
+        Action::RotateCcw => "⟲",
+        Action::Hold => "⇄",
+        Action::Undo => "↶",
+        Action::FirmDrop => "⤓",
+        Action::SetCheckpoint => "⚑",
+        Action::ResetCheckpoint => "⤺",
+    }
+}
+
+pub struct TouchPlugin;
+
+impl Plugin for TouchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchControls>()
+            .add_systems(PreUpdate, read_touches.after(InputSystem))
+            .add_systems(
+                Update,
+                draw_buttons.run_if(in_state(AppState::Playing).and_then(buttons_shown)),
+            );
+    }
+}
+
+fn buttons_shown(controls: Res<TouchControls>) -> bool {
+    controls.shown
+}
+
+/// Lays out the buttons for this frame and works out which are held.
+pub fn read_touches(
+    touches: Res<Touches>,
+    settings: Res<Settings>,
+    layout: Res<BoardLayout>,
+    state: Res<State<AppState>>,
+    mut controls: ResMut<TouchControls>,
+) {
+    controls.touched |= touches.iter().next().is_some();
+    controls.shown = match settings.touch_controls {
+        TouchOverlay::Auto => {
+            cfg!(any(target_os = "android", target_os = "ios")) || controls.touched
+        }
+        TouchOverlay::Always => true,
+        TouchOverlay::Never => false,
+    };
+    let buttons = button_layout(layout.window, board_on_screen(&layout));
+
+    let mut held = [false; Action::ALL.len()];
+    if controls.shown && *state.get() == AppState::Playing {
+        for touch in touches.iter() {
+            let hit = buttons
+                .iter()
+                .find(|(_, rect)| rect.contains(touch.position()));
+            if let Some(&(action, _)) = hit {
+                held[action as usize] = true;
+            }
+        }
+    }
+    controls.buttons = buttons;
+    controls.held = held;
+}
+
+fn draw_buttons(mut contexts: EguiContexts, controls: Res<TouchControls>) {
+    let painter = contexts.ctx_mut().layer_painter(egui::LayerId::new(
+        egui::Order::Background,
+        egui::Id::new("touch_controls"),
+    ));
+    for &(action, rect) in controls.buttons() {
+        let area = egui::Rect::from_min_max(
+            egui::pos2(rect.min.x, rect.min.y),
+            egui::pos2(rect.max.x, rect.max.y),
+        );
+        let alpha = if controls.held(action) { 160 } else { 70 };
+        painter.rect_filled(
+            area,
+            rect.width() * 0.2,
+            egui::Color32::from_white_alpha(alpha),
+        );
+        painter.text(
+            area.center(),
+            egui::Align2::CENTER_CENTER,
+            symbol(action),
+            egui::FontId::proportional(rect.width() * 0.5),
+            egui::Color32::WHITE,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::input::touch::{touch_screen_input_system, TouchInput, TouchPhase};
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::components::board::Board;
+    use crate::game::{ActivePiece, GamePlugin};
+    use crate::utils::config::LayoutConfig;
+
+    #[test]
+    fn test_buttons_stay_off_the_board_and_scale_with_the_window() {
+        for window in [
+            Vec2::new(1280.0, 720.0),
+            Vec2::new(720.0, 1280.0),
+            Vec2::new(2560.0, 1440.0),
+        ] {
+            let layout = BoardLayout::fit(window, &Board::default(), &LayoutConfig::default());
+            let board = board_on_screen(&layout);
+            let buttons = button_layout(window, board);
+            assert_eq!(buttons.len(), TOUCH_ACTIONS.len());
+            for (action, rect) in &buttons {
+                assert!(
+                    rect.intersect(board).is_empty(),
+                    "{action:?} covers the board in a {window} window"
+                );
+                assert!(rect.min.cmpge(Vec2::ZERO).all() && rect.max.cmple(window).all());
+                assert!(rect.width() >= MIN_BUTTON.min(window.min_element() * BUTTON_SHARE));
+            }
+        }
+        let small = button_layout(
+            Vec2::new(1280.0, 720.0),
+            Rect::new(400.0, 0.0, 880.0, 720.0),
+        );
+        let large = button_layout(
+            Vec2::new(2560.0, 1440.0),
+            Rect::new(800.0, 0.0, 1760.0, 1440.0),
+        );
+        assert!(large[0].1.width() > small[0].1.width());
+    }
+
+    #[test]
+    fn test_touching_rotate_rotates_the_piece() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin))
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<Touches>()
+            .init_resource::<BoardLayout>()
+            .init_resource::<TouchControls>()
+            .add_event::<TouchInput>()
+            .add_systems(
+                PreUpdate,
+                (
+                    touch_screen_input_system.in_set(InputSystem),
+                    read_touches.after(InputSystem),
+                ),
+            )
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        app.world.resource_mut::<Settings>().touch_controls = TouchOverlay::Always;
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+        app.world.run_schedule(FixedUpdate);
+        let spawned = app.world.resource::<ActivePiece>().0.unwrap();
+
+        let rotate = app
+            .world
+            .resource::<TouchControls>()
+            .buttons()
+            .iter()
+            .find(|(action, _)| *action == Action::RotateCw)
+            .map(|&(_, rect)| rect.center())
+            .unwrap();
+        app.world.send_event(TouchInput {
+            phase: TouchPhase::Started,
+            position: rotate,
+            force: None,
+            id: 0,
+        });
+        app.update();
+        assert!(app.world.resource::<TouchControls>().held(Action::RotateCw));
+        app.world.run_schedule(FixedUpdate);
+        let turned = app.world.resource::<ActivePiece>().0.unwrap();
+        assert_eq!(turned.rotation, (spawned.rotation + 1) % 4);
+    }
+}
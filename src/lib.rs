@@ -0,0 +1,70 @@
+//! RustBlocks: a lightweight, good-looking Tetris built on Bevy.
+//!
+//! The crate is split the same way as the game: pure rules in
+//! [`components`], the Bevy game loop in [`game`], and presentation in
+//! [`rendering`], [`ui`] and [`audio`].
+
+pub mod ai;
+
+pub mod audio {
+    pub mod aeffects;
+    pub mod device;
+    pub mod music;
+}
+
+pub mod autosave;
+
+pub mod components {
+    pub mod bitboard;
+    pub mod board;
+    pub mod garbage;
+    pub mod scoring;
+    pub mod tetromino;
+}
+
+pub mod error;
+
+#[cfg(test)]
+mod fixtures;
+
+pub mod game;
+
+pub mod input {
+    pub mod gesture;
+    pub mod keyboard;
+    pub mod touch;
+}
+
+pub mod rendering {
+    pub mod effects;
+    pub mod layers;
+    pub mod shapes;
+}
+
+pub mod net;
+
+pub mod race;
+
+pub mod replay;
+
+pub mod ui {
+    pub mod attract;
+    pub mod editor;
+    pub mod hud;
+    pub mod menus;
+    pub mod review;
+    pub mod share;
+    pub mod transition;
+}
+
+pub mod utils {
+    pub mod checksum;
+    pub mod clipboard;
+    pub mod config;
+    pub mod debug;
+    pub mod i18n;
+    pub mod seed;
+    pub mod storage;
+}
+
+pub mod versus;
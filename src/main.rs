@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+use bevy::window::WindowResolution;
+use bevy_egui::EguiPlugin;
+
+use rustblocks::ai::AiPlugin;
+use rustblocks::audio::aeffects::SfxPlugin;
+use rustblocks::audio::device::AudioDevicePlugin;
+use rustblocks::audio::music::MusicPlugin;
+use rustblocks::autosave::AutosavePlugin;
+use rustblocks::error::ErrorPlugin;
+use rustblocks::game::GamePlugin;
+use rustblocks::input::gesture::GesturePlugin;
+use rustblocks::input::touch::TouchPlugin;
+use rustblocks::net::NetPlugin;
+use rustblocks::race::RacePlugin;
+use rustblocks::rendering::effects::EffectsPlugin;
+use rustblocks::rendering::shapes::ShapesPlugin;
+use rustblocks::replay::ReplayPlugin;
+use rustblocks::ui::attract::AttractPlugin;
+use rustblocks::ui::editor::BoardEditorPlugin;
+use rustblocks::ui::hud::HudPlugin;
+use rustblocks::ui::menus::MenuPlugin;
+use rustblocks::ui::review::ReviewPlugin;
+use rustblocks::ui::share::SharePlugin;
+use rustblocks::ui::transition::TransitionPlugin;
+#[cfg(feature = "frame_step")]
+use rustblocks::utils::debug::FrameStepPlugin;
+use rustblocks::utils::debug::{
+    log_plugin, trace_enabled, CoordinateOverlayPlugin, GameplayLogPlugin,
+};
+use rustblocks::versus::VersusPlugin;
+
+#[bevy_main]
+fn main() {
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    title: "RustBlocks".into(),
+                    resolution: WindowResolution::new(720.0, 720.0),
+                    resizable: true,
+                    ..default()
+                }),
+                ..default()
+            })
+            .set(log_plugin(trace_enabled())),
+    )
+    .add_plugins((ErrorPlugin, EguiPlugin, AudioDevicePlugin))
+    .add_plugins((
+        GamePlugin,
+        ShapesPlugin,
+        EffectsPlugin,
+        HudPlugin,
+        MenuPlugin,
+    ))
+    .add_plugins((
+        ReplayPlugin,
+        AttractPlugin,
+        AiPlugin,
+        SfxPlugin,
+        MusicPlugin,
+    ))
+    .add_plugins((
+        TransitionPlugin,
+        SharePlugin,
+        ReviewPlugin,
+        GameplayLogPlugin,
+        CoordinateOverlayPlugin,
+        AutosavePlugin,
+        TouchPlugin,
+        GesturePlugin,
+        BoardEditorPlugin,
+        VersusPlugin,
+        NetPlugin,
+        RacePlugin,
+    ));
+    #[cfg(feature = "frame_step")]
+    app.add_plugins(FrameStepPlugin);
+    app.run();
+}
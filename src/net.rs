@@ -0,0 +1,187 @@
+//! The seam online versus will plug into.
+//!
+//! A game with a [`NetLink`] tells its peer about every lock and clear, and
+//! the garbage each clear sends, as [`NetMessage`]s over a
+//! [`NetTransport`], and raises whatever garbage comes back the other way.
+//! How the messages cross is the transport's business: [`Loopback`] hands
+//! them straight to the other end in the same process, which is all local
+//! [versus](crate::versus) needs, and a future WebSocket or WebRTC
+//! transport only has to do the same over the wire.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+
+use crate::components::garbage::GarbageQueue;
+use crate::components::tetromino::Tetromino;
+use crate::game::{GameplayEvent, TickSet};
+use crate::versus::garbage_for;
+
+/// What one side of a match tells the other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetMessage {
+    /// A piece joined the sender's stack.
+    PieceLocked(Tetromino),
+    /// The sender cleared this many rows at once.
+    LinesCleared(u32),
+    /// This many garbage rows are on their way to the receiver.
+    Garbage(u32),
+}
+
+/// Carries [`NetMessage`]s to the peer and back.
+pub trait NetTransport: Send + Sync {
+    fn send(&mut self, message: NetMessage);
+
+    /// Every message the peer sent that hasn't been polled yet, oldest
+    /// first.
+    fn poll(&mut self) -> Vec<NetMessage>;
+}
+
+type Mailbox = Arc<Mutex<VecDeque<NetMessage>>>;
+
+/// One end of an in-process transport, for local play and tests.
+#[derive(Debug, Default)]
+pub struct Loopback {
+    outgoing: Mailbox,
+    incoming: Mailbox,
+}
+
+impl Loopback {
+    /// Two ends, each receiving what the other sends.
+    pub fn pair() -> (Self, Self) {
+        let one = Self::default();
+        let two = Self {
+            outgoing: one.incoming.clone(),
+            incoming: one.outgoing.clone(),
+        };
+        (one, two)
+    }
+}
+
+impl NetTransport for Loopback {
+    fn send(&mut self, message: NetMessage) {
+        self.outgoing.lock().unwrap().push_back(message);
+    }
+
+    fn poll(&mut self) -> Vec<NetMessage> {
+        self.incoming.lock().unwrap().drain(..).collect()
+    }
+}
+
+/// The game's connection to its peer, while it has one.
+#[derive(Resource)]
+pub struct NetLink(pub Box<dyn NetTransport>);
+
+pub struct NetPlugin;
+
+impl Plugin for NetPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            FixedUpdate,
+            (
+                receive_from_peer
+                    .after(TickSet::Input)
+                    .before(TickSet::Simulate),
+                send_to_peer.after(TickSet::Simulate),
+            )
+                .run_if(resource_exists::<NetLink>()),
+        );
+    }
+}
+
+/// Sends the tick's locks and clears, and the garbage the clears owe once
+/// the rows they cancelled from our own queue are taken off.
+pub fn send_to_peer(mut events: EventReader<GameplayEvent>, mut link: ResMut<NetLink>) {
+    for event in events.read() {
+        match *event {
+            GameplayEvent::Locked(piece) => link.0.send(NetMessage::PieceLocked(piece)),
+            GameplayEvent::LinesCleared {
+                count, cancelled, ..
+            } => {
+                link.0.send(NetMessage::LinesCleared(count));
+                let rows = garbage_for(count).saturating_sub(cancelled);
+                if rows > 0 {
+                    link.0.send(NetMessage::Garbage(rows));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Queues the garbage the peer sent to rise before the next spawn.
+fn receive_from_peer(mut link: ResMut<NetLink>, mut garbage: ResMut<GarbageQueue>) {
+    for message in link.0.poll() {
+        if let NetMessage::Garbage(rows) = message {
+            garbage.push(rows);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::components::scoring::Score;
+    use crate::fixtures::set_up_a_double;
+    use crate::game::{AppState, GamePlugin};
+    use crate::input::keyboard::{Action, ActionEvent};
+    use crate::utils::config::LINE_CLEAR_DELAY;
+
+    fn linked_app(transport: Loopback) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin, NetPlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+            .insert_resource(NetLink(Box::new(transport)));
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+        app
+    }
+
+    /// Sets up a double and clears it.
+    fn drop_a_double(app: &mut App) {
+        set_up_a_double(&mut app.world);
+        app.world.send_event(ActionEvent::press(Action::HardDrop));
+        app.world.send_event(ActionEvent::release(Action::HardDrop));
+    }
+
+    fn tick_both(one: &mut App, two: &mut App) {
+        for _ in 0..LINE_CLEAR_DELAY + 2 {
+            one.world.run_schedule(FixedUpdate);
+            two.world.run_schedule(FixedUpdate);
+        }
+    }
+
+    #[test]
+    fn test_garbage_round_trips_between_two_linked_boards() {
+        let (near, far) = Loopback::pair();
+        let mut one = linked_app(near);
+        let mut two = linked_app(far);
+        tick_both(&mut one, &mut two);
+
+        drop_a_double(&mut one);
+        tick_both(&mut one, &mut two);
+        assert_eq!(one.world.resource::<Score>().lines, 2);
+        assert_eq!(two.world.resource::<GarbageQueue>().pending(), 1);
+        assert_eq!(one.world.resource::<GarbageQueue>().pending(), 0);
+
+        // The first double back only cancels the row on its way up.
+        drop_a_double(&mut two);
+        tick_both(&mut one, &mut two);
+        assert_eq!(two.world.resource::<Score>().lines, 2);
+        assert_eq!(two.world.resource::<GarbageQueue>().pending(), 0);
+        assert_eq!(one.world.resource::<GarbageQueue>().pending(), 0);
+
+        drop_a_double(&mut two);
+        tick_both(&mut one, &mut two);
+        assert_eq!(two.world.resource::<Score>().lines, 4);
+        assert_eq!(one.world.resource::<GarbageQueue>().pending(), 1);
+    }
+}
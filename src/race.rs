@@ -0,0 +1,312 @@
+//! Racing a ghost of a best run.
+//!
+//! The recording of each mode's best game is kept, and racing it plays that
+//! recording back beside a new game, as a translucent board the player can
+//! keep pace with. The ghost is a whole second [`World`] running
+//! [`GamePlugin`] and [`ReplayPlugin`] under the recording's own rules,
+//! ticked once for every tick of the live game. The live game is played
+//! under those rules too, from the same seed, and the player's own come
+//! back once the race ends. Beyond that nothing the player does reaches the
+//! ghost, and nothing the ghost does reaches the player.
+
+use std::time::Duration;
+
+use bevy::ecs::event::ManualEventReader;
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use crate::components::board::Board;
+use crate::components::scoring::{Score, ScoringRules};
+use crate::game::{
+    start_new_game, ActivePiece, AppState, GameMode, GamePlugin, Modifiers, NewGame, TickSet,
+};
+use crate::rendering::shapes::Palette;
+use crate::replay::{Replay, ReplayPlayback, ReplayPlugin, ReplayRules};
+use crate::ui::menus::board_thumbnail;
+use crate::utils::i18n::Locale;
+use crate::utils::storage;
+
+/// Pixels a cell takes in the picture of the ghost's board.
+const GHOST_CELL: f32 = 10.0;
+
+/// How much of the ghost's board shows through.
+const GHOST_OPACITY: f32 = 0.45;
+
+/// The recording of the best game of each mode, set of modifiers and
+/// scoring table. Saved between runs.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BestRuns {
+    runs: Vec<Replay>,
+}
+
+impl BestRuns {
+    const FOLDER: &'static str = "replays";
+    const FILE: &'static str = "best_runs.ron";
+
+    /// The saved recordings, or none if none have been saved or they can't
+    /// be read, reporting to `app` recordings that don't parse.
+    pub fn load(app: &mut App) -> Self {
+        storage::read_ron(app, Self::FOLDER, Self::FILE).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        storage::write_ron(Self::FOLDER, Self::FILE, self)
+    }
+
+    /// Keeps `run` as the best of its mode, modifiers and scoring, in place
+    /// of the one it beat.
+    pub fn keep(&mut self, run: Replay) {
+        let key = (run.mode, run.modifiers, run.scoring);
+        self.runs
+            .retain(|best| (best.mode, best.modifiers, best.scoring) != key);
+        self.runs.push(run);
+    }
+
+    /// The recording of the best game played under `mode`, `modifiers` and
+    /// `scoring`.
+    pub fn best(
+        &self,
+        mode: GameMode,
+        modifiers: Modifiers,
+        scoring: ScoringRules,
+    ) -> Option<&Replay> {
+        let key = (mode, modifiers, scoring);
+        self.runs
+            .iter()
+            .find(|best| (best.mode, best.modifiers, best.scoring) == key)
+    }
+}
+
+/// Races the recording against the new game sent alongside it, which
+/// should be dealt from the recording's seed.
+#[derive(Event, Debug, Clone)]
+pub struct RaceGhost(pub Replay);
+
+/// A ghost being raced.
+#[derive(Resource, Debug)]
+pub struct GhostRace {
+    ghost: World,
+    /// The player's rules, put back once the race ends.
+    saved: Replay,
+}
+
+impl GhostRace {
+    /// A ghost playing `run` back from its first tick, raced by a player
+    /// whose own rules are `saved`.
+    pub fn new(run: Replay, saved: Replay) -> Self {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin, ReplayPlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        let rules = run.clone();
+        app.world
+            .run_system_once(move |mut replay_rules: ReplayRules| {
+                replay_rules.apply(&rules);
+            });
+        app.world.insert_resource(run.piece_queue());
+        app.world.insert_resource(ReplayPlayback::new(run));
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.finish();
+        app.cleanup();
+        app.update();
+        Self {
+            ghost: std::mem::take(&mut app.world),
+            saved,
+        }
+    }
+
+    /// The ghost's game, as [`Board`], [`Score`] and the rest.
+    pub fn ghost(&self) -> &World {
+        &self.ghost
+    }
+
+    /// Plays the ghost's next tick, if its recording has one left.
+    fn tick(&mut self) {
+        self.ghost.run_schedule(First);
+        self.ghost.run_schedule(FixedUpdate);
+        self.ghost.run_schedule(StateTransition);
+    }
+}
+
+pub struct RacePlugin;
+
+impl Plugin for RacePlugin {
+    fn build(&self, app: &mut App) {
+        let best_runs = BestRuns::load(app);
+        app.add_event::<RaceGhost>()
+            .insert_resource(best_runs)
+            .add_systems(OnEnter(AppState::MainMenu), end_race)
+            .add_systems(Update, start_race.before(start_new_game))
+            .add_systems(
+                FixedUpdate,
+                play_ghost
+                    .after(TickSet::Simulate)
+                    .run_if(in_state(AppState::Playing).and_then(resource_exists::<GhostRace>())),
+            )
+            .add_systems(
+                Update,
+                draw_ghost_board.run_if(
+                    resource_exists::<GhostRace>()
+                        .and_then(in_state(AppState::Playing).or_else(in_state(AppState::Paused))),
+                ),
+            );
+    }
+}
+
+/// Starts racing the ghost sent with a new game, under its rules, and stops
+/// racing when a game starts without one. Runs before the new game is
+/// dealt, so it deals under the rules raced. Exclusive, as the ghost's
+/// world is set up by running its schedules, which can't wait on the task
+/// pool from inside one of its tasks.
+fn start_race(
+    world: &mut World,
+    mut games: Local<ManualEventReader<NewGame>>,
+    mut races: Local<ManualEventReader<RaceGhost>>,
+) {
+    let started = games.read(world.resource::<Events<NewGame>>()).count() > 0;
+    let run = races
+        .read(world.resource::<Events<RaceGhost>>())
+        .last()
+        .map(|RaceGhost(run)| run.clone());
+    if !started {
+        return;
+    }
+    let Some(run) = run else {
+        end_race(world);
+        return;
+    };
+    let rules = run.clone();
+    let replaced =
+        world.run_system_once(move |mut replay_rules: ReplayRules| replay_rules.apply(&rules));
+    // Racing again keeps the rules the player had before the first race.
+    let saved = world
+        .remove_resource::<GhostRace>()
+        .map_or(replaced, |race| race.saved);
+    world.insert_resource(GhostRace::new(run, saved));
+}
+
+/// Leaves the race, if there is one, and puts back the player's rules.
+fn end_race(world: &mut World) {
+    if let Some(race) = world.remove_resource::<GhostRace>() {
+        world.run_system_once(move |mut replay_rules: ReplayRules| {
+            replay_rules.apply(&race.saved);
+        });
+    }
+}
+
+fn play_ghost(world: &mut World) {
+    world.resource_mut::<GhostRace>().tick();
+}
+
+/// The ghost's board, piece and score, faded, at the left of the window.
+fn draw_ghost_board(
+    mut contexts: EguiContexts,
+    locale: Res<Locale>,
+    palette: Res<Palette>,
+    race: Res<GhostRace>,
+) {
+    let ghost = race.ghost();
+    let mut board = ghost.resource::<Board>().clone();
+    if let Some(piece) = ghost.resource::<ActivePiece>().0 {
+        board.lock_piece(&piece);
+    }
+    let score = ghost.resource::<Score>();
+    egui::Window::new(locale.t("race.ghost"))
+        .anchor(egui::Align2::LEFT_TOP, [8.0, 8.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            board_thumbnail(ui, &board.to_ascii(), &palette, GHOST_CELL, GHOST_OPACITY);
+            ui.label(format!("{}: {}", locale.t("stats.score"), score.points));
+            ui.label(format!("{}: {}", locale.t("stats.lines"), score.lines));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::tetromino::PieceQueue;
+    use crate::replay::tests::playback_app;
+    use crate::replay::ReplayRecorder;
+    use crate::ui::attract::demo_replay;
+    use crate::utils::config::Settings;
+
+    #[test]
+    fn test_the_ghost_plays_its_recording_apart_from_the_live_board() {
+        let mut run = demo_replay();
+        run.spawn_shift_up = true;
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin))
+            .add_event::<RaceGhost>()
+            .add_systems(Update, start_race.before(start_new_game))
+            .add_systems(FixedUpdate, play_ghost.after(TickSet::Simulate))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        app.world.send_event(NewGame {
+            seed: Some(run.seed),
+        });
+        app.world.send_event(RaceGhost(run.clone()));
+        app.update();
+        app.update();
+        assert_eq!(app.world.resource::<PieceQueue>().seed(), run.seed);
+        // The live game plays by the recording's rules too.
+        assert!(app.world.resource::<Settings>().spawn_shift_up);
+        let recorder = app.world.resource::<ReplayRecorder>();
+        assert!(recorder.0.spawn_shift_up);
+
+        // A ghost ticks exactly as the recording plays back on its own,
+        // while the live game, left alone, locks nothing.
+        let mut reference = playback_app(run.clone(), AppState::Playing);
+        let ticks = run.length.min(400);
+        for _ in 0..ticks {
+            app.world.run_schedule(FixedUpdate);
+            reference.world.run_schedule(FixedUpdate);
+        }
+        let race = app.world.resource::<GhostRace>();
+        let ghost = race.ghost();
+        assert_eq!(
+            ghost.resource::<Board>(),
+            reference.world.resource::<Board>()
+        );
+        assert_eq!(
+            ghost.resource::<Score>(),
+            reference.world.resource::<Score>()
+        );
+        assert!(ghost.resource::<Score>().pieces > 0);
+        assert_eq!(app.world.resource::<Score>().pieces, 0);
+        assert!(app.world.resource::<Board>().is_empty());
+
+        // A game started without a ghost leaves the race behind, and the
+        // player's rules with it.
+        app.world.send_event(NewGame::default());
+        app.update();
+        assert!(!app.world.contains_resource::<GhostRace>());
+        assert!(!app.world.resource::<Settings>().spawn_shift_up);
+        assert!(!app.world.resource::<ReplayRecorder>().0.spawn_shift_up);
+    }
+
+    #[test]
+    fn test_best_runs_are_kept_apart_by_scoring() {
+        let mut guideline = demo_replay();
+        guideline.scoring = ScoringRules::Guideline;
+        let mut classic = guideline.clone();
+        classic.scoring = ScoringRules::Classic;
+        let mut runs = BestRuns::default();
+        runs.keep(guideline.clone());
+        runs.keep(classic.clone());
+        let (mode, modifiers) = (guideline.mode, guideline.modifiers);
+        assert_eq!(
+            runs.best(mode, modifiers, ScoringRules::Guideline),
+            Some(&guideline)
+        );
+        assert_eq!(
+            runs.best(mode, modifiers, ScoringRules::Classic),
+            Some(&classic)
+        );
+    }
+}
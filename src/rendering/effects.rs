@@ -0,0 +1,867 @@
+//! Transient effects drawn over the board: the flash on a freshly locked
+//! piece, the red flash of a refused hold, the glint along the edge a
+//! rotation kicked off, the animated line clear and the afterimage of the
+//! rows it took, the stack sliding up over rising garbage, the top-out
+//! dissolve and the fade of [invisible](crate::game::Modifiers::invisible)
+//! blocks.
+//!
+//! The effects tint the [`CellSprite`]s after [`draw_board`] has coloured
+//! them, so they always follow the board as it currently is, and raise
+//! [`CellsTinted`] so it colours them afresh for the next frame. Garbage
+//! rises into the board at once, as the simulation needs; only its sprites
+//! are drawn catching up.
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use rand::Rng;
+
+use crate::components::board::Board;
+use crate::game::{AppState, Dissolve, GameplayEvent, LineClear, Modifiers};
+use crate::rendering::layers;
+use crate::rendering::shapes::{
+    draw_board, draw_panels, BoardLayout, CellSprite, CellsTinted, Palette, Panel, PlayfieldEntity,
+};
+use crate::utils::config::{Settings, LINE_CLEAR_DELAY};
+
+const FLASH_COLOR: Color = Color::WHITE;
+const LOCK_FLASH_SECS: f32 = 0.15;
+/// How far a locked piece is tinted towards [`FLASH_COLOR`] at first.
+const LOCK_FLASH_STRENGTH: f32 = 0.6;
+
+const HOLD_REJECT_COLOR: Color = Color::rgb(0.9, 0.15, 0.15);
+const HOLD_REJECT_SECS: f32 = 0.2;
+/// How far the hold panel is tinted towards [`HOLD_REJECT_COLOR`] at
+/// first.
+const HOLD_REJECT_STRENGTH: f32 = 0.7;
+
+const KICK_FLASH_SECS: f32 = 0.15;
+/// Opacity of the kicked-off edge at first.
+const KICK_FLASH_ALPHA: f32 = 0.35;
+/// Thickness of the kicked-off edge, in cells.
+const KICK_FLASH_WIDTH: f32 = 0.15;
+
+/// Seconds the afterimage of cleared rows takes to fade.
+const AFTERIMAGE_SECS: f32 = 0.35;
+/// Opacity of the afterimage at first.
+const AFTERIMAGE_ALPHA: f32 = 0.4;
+
+/// Seconds the stack takes to slide up over freshly risen garbage.
+const GARBAGE_RISE_SECS: f32 = 0.2;
+/// How far risen garbage rows are tinted towards [`FLASH_COLOR`] at first.
+const GARBAGE_FLASH_STRENGTH: f32 = 0.5;
+
+/// Seconds an invisible block takes to fade once its delay is up.
+const INVISIBLE_FADE_SECS: f32 = 0.25;
+
+/// Downward acceleration of dissolving blocks, in cells per second squared.
+const DISSOLVE_GRAVITY: f32 = 60.0;
+/// Largest random sideways and upward kick, in cells per second.
+const DISSOLVE_JITTER: f32 = 4.0;
+/// Longest random wait before a block starts to fall, in seconds.
+const DISSOLVE_MAX_DELAY: f32 = 0.3;
+
+/// Cells of the most recently locked piece while its flash fades.
+#[derive(Resource, Debug, Default)]
+pub struct LockFlash {
+    cells: Vec<(i32, i32)>,
+    timer: Timer,
+}
+
+impl LockFlash {
+    fn strength(&self) -> f32 {
+        LOCK_FLASH_STRENGTH * self.timer.percent_left()
+    }
+
+    fn raise(&mut self, rows: u32) {
+        for (_, y) in &mut self.cells {
+            *y += rows as i32;
+        }
+    }
+
+    fn collapse(&mut self, rows: u64) {
+        self.cells = self
+            .cells
+            .iter()
+            .filter_map(|&(x, y)| collapsed_row(y, rows).map(|y| (x, y)))
+            .collect();
+    }
+}
+
+/// Fading tint of the hold panel after a refused hold.
+#[derive(Resource, Debug, Default)]
+pub struct HoldRejectFlash(Timer);
+
+/// The rows of garbage that last rose, while the stack is drawn sliding up
+/// to where the board already has it.
+#[derive(Resource, Debug, Default)]
+pub struct GarbageRise {
+    rows: u32,
+    timer: Timer,
+}
+
+impl GarbageRise {
+    /// Rows below its cell a block is drawn, easing out from the rows that
+    /// rose to none.
+    pub fn offset(&self) -> f32 {
+        let left = self.timer.percent_left();
+        self.rows as f32 * left * left
+    }
+
+    fn active(&self) -> bool {
+        self.rows > 0 && !self.timer.finished()
+    }
+}
+
+/// A bar lighting the board edge a rotation kicked off while it fades.
+#[derive(Component, Debug)]
+pub struct KickFlash(Timer);
+
+/// A faint copy of a block from a cleared row, left where the row was
+/// while it fades.
+#[derive(Component, Debug)]
+pub struct ClearAfterimage {
+    /// The board cell it was copied from, coloured through the current
+    /// [`Palette`].
+    cell: usize,
+    timer: Timer,
+}
+
+/// Seconds since each locked cell was filled, for fading out invisible
+/// blocks.
+#[derive(Resource, Debug, Default)]
+pub struct CellAges {
+    ages: HashMap<(i32, i32), f32>,
+}
+
+impl CellAges {
+    /// Cells locked before tracking began count as long settled.
+    fn age(&self, x: i32, y: i32) -> f32 {
+        self.ages.get(&(x, y)).copied().unwrap_or(f32::INFINITY)
+    }
+
+    fn raise(&mut self, rows: u32) {
+        self.ages = self
+            .ages
+            .drain()
+            .map(|((x, y), age)| ((x, y + rows as i32), age))
+            .collect();
+    }
+
+    fn collapse(&mut self, rows: u64) {
+        self.ages = self
+            .ages
+            .drain()
+            .filter_map(|((x, y), age)| collapsed_row(y, rows).map(|y| ((x, y), age)))
+            .collect();
+    }
+}
+
+/// Where row `y` ends up once the rows in the `rows` bitmask are cleared:
+/// gone if it was one of them, otherwise lower by the number cleared below
+/// it.
+fn collapsed_row(y: i32, rows: u64) -> Option<i32> {
+    (rows & 1 << y == 0).then(|| y - (rows & ((1 << y) - 1)).count_ones() as i32)
+}
+
+/// A copy of a locked block falling away after a top-out.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct DissolveBlock {
+    /// The board cell it was copied from, coloured through the current
+    /// [`Palette`] so a theme change reaches it too.
+    cell: usize,
+    /// Cells per second.
+    velocity: Vec2,
+    /// Seconds before the block starts to fall.
+    delay: f32,
+}
+
+pub struct EffectsPlugin;
+
+impl Plugin for EffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LockFlash>()
+            .init_resource::<CellAges>()
+            .init_resource::<HoldRejectFlash>()
+            .init_resource::<GarbageRise>()
+            .add_systems(OnEnter(AppState::Dissolving), spawn_dissolve_blocks)
+            .add_systems(OnExit(AppState::Dissolving), despawn_dissolve_blocks)
+            .add_systems(
+                Update,
+                (
+                    track_lock_flash,
+                    track_cell_ages,
+                    track_hold_rejects,
+                    track_garbage_rise,
+                    (spawn_kick_flashes, fade_kick_flashes).chain(),
+                    (spawn_afterimages, fade_afterimages).chain(),
+                    hide_invisible_cells,
+                    draw_flashes,
+                    draw_garbage_rise,
+                    draw_hold_reject,
+                    (hide_dissolved_cells, animate_dissolve).run_if(in_state(AppState::Dissolving)),
+                )
+                    .chain()
+                    .after(draw_board)
+                    .after(draw_panels),
+            );
+    }
+}
+
+/// Blends `from` towards `to` by `amount`, from 0.0 to 1.0.
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    let from = Vec4::from_array(from.as_rgba_f32());
+    let to = Vec4::from_array(to.as_rgba_f32());
+    Color::from(from.lerp(to, amount))
+}
+
+fn track_lock_flash(
+    time: Res<Time>,
+    mut events: EventReader<GameplayEvent>,
+    mut flash: ResMut<LockFlash>,
+) {
+    flash.timer.tick(time.delta());
+    for event in events.read() {
+        match *event {
+            GameplayEvent::Locked(piece) => {
+                flash.cells = piece.cells().to_vec();
+                flash.timer = Timer::from_seconds(LOCK_FLASH_SECS, TimerMode::Once);
+            }
+            GameplayEvent::LinesCleared { rows, .. } => flash.collapse(rows),
+            GameplayEvent::GarbageRose(rows) => flash.raise(rows),
+            GameplayEvent::Reset => flash.cells.clear(),
+            _ => {}
+        }
+    }
+    if flash.timer.finished() {
+        flash.cells.clear();
+    }
+}
+
+fn track_cell_ages(
+    time: Res<Time>,
+    mut events: EventReader<GameplayEvent>,
+    mut ages: ResMut<CellAges>,
+) {
+    for age in ages.ages.values_mut() {
+        *age += time.delta_seconds();
+    }
+    for event in events.read() {
+        match *event {
+            GameplayEvent::Locked(piece) => {
+                ages.ages.extend(piece.cells().map(|cell| (cell, 0.0)));
+            }
+            GameplayEvent::LinesCleared { rows, .. } => ages.collapse(rows),
+            GameplayEvent::GarbageRose(rows) => ages.raise(rows),
+            GameplayEvent::Reset => ages.ages.clear(),
+            _ => {}
+        }
+    }
+}
+
+/// Fades locked blocks into the empty board once they have been settled
+/// for [`Settings::invisible_delay`]. Only the sprites change; the board
+/// itself is untouched.
+fn hide_invisible_cells(
+    modifiers: Res<Modifiers>,
+    settings: Res<Settings>,
+    palette: Res<Palette>,
+    ages: Res<CellAges>,
+    mut tinted: ResMut<CellsTinted>,
+    mut cells: Query<(&CellSprite, &mut Sprite)>,
+) {
+    if !modifiers.invisible {
+        return;
+    }
+    for (cell, mut sprite) in &mut cells {
+        let fade = (ages.age(cell.x, cell.y) - settings.invisible_delay) / INVISIBLE_FADE_SECS;
+        if fade >= 1.0 {
+            sprite.color = palette.empty_cell;
+        } else if fade > 0.0 {
+            sprite.color = mix(sprite.color, palette.empty_cell, fade);
+        }
+        tinted.0 |= fade > 0.0;
+    }
+}
+
+fn draw_flashes(
+    settings: Res<Settings>,
+    flash: Res<LockFlash>,
+    clear: Res<LineClear>,
+    mut tinted: ResMut<CellsTinted>,
+    mut cells: Query<(&CellSprite, &mut Sprite)>,
+) {
+    let progress = 1.0 - clear.ticks_left as f32 / LINE_CLEAR_DELAY as f32;
+    let flashes = settings.effects().flashes;
+    for (cell, mut sprite) in &mut cells {
+        let mut amount: f32 = 0.0;
+        if flashes && flash.cells.contains(&(cell.x, cell.y)) {
+            amount = flash.strength();
+        }
+        if clear.rows & 1 << cell.y != 0 {
+            amount = amount.max(progress);
+        }
+        if amount > 0.0 {
+            sprite.color = mix(sprite.color, FLASH_COLOR, amount);
+            tinted.0 = true;
+        }
+    }
+}
+
+fn track_garbage_rise(
+    time: Res<Time>,
+    mut events: EventReader<GameplayEvent>,
+    mut rise: ResMut<GarbageRise>,
+) {
+    rise.timer.tick(time.delta());
+    for event in events.read() {
+        match *event {
+            GameplayEvent::GarbageRose(rows) => {
+                // Rows still sliding keep going from where they are drawn.
+                let sliding = if rise.active() { rise.offset() } else { 0.0 };
+                rise.rows = rows + sliding.ceil() as u32;
+                rise.timer = Timer::from_seconds(GARBAGE_RISE_SECS, TimerMode::Once);
+            }
+            GameplayEvent::Reset => *rise = GarbageRise::default(),
+            _ => {}
+        }
+    }
+}
+
+/// Draws the board's blocks [`GarbageRise::offset`] rows low, and the risen
+/// rows flashing, until the slide is done.
+fn draw_garbage_rise(
+    settings: Res<Settings>,
+    layout: Res<BoardLayout>,
+    rise: Res<GarbageRise>,
+    mut tinted: ResMut<CellsTinted>,
+    mut cells: Query<(&CellSprite, &mut Sprite, &mut Transform)>,
+) {
+    if !rise.active() {
+        return;
+    }
+    let effects = settings.effects();
+    let drop = if effects.motion {
+        rise.offset() * layout.block_size()
+    } else {
+        0.0
+    };
+    let flash = GARBAGE_FLASH_STRENGTH * rise.timer.percent_left();
+    for (cell, mut sprite, mut transform) in &mut cells {
+        transform.translation.y -= drop;
+        if effects.flashes && cell.y < rise.rows as i32 {
+            sprite.color = mix(sprite.color, FLASH_COLOR, flash);
+        }
+    }
+    tinted.0 = true;
+}
+
+fn track_hold_rejects(
+    time: Res<Time>,
+    mut events: EventReader<GameplayEvent>,
+    mut flash: ResMut<HoldRejectFlash>,
+) {
+    flash.0.tick(time.delta());
+    if events
+        .read()
+        .any(|event| *event == GameplayEvent::HoldRejected)
+    {
+        flash.0 = Timer::from_seconds(HOLD_REJECT_SECS, TimerMode::Once);
+    }
+}
+
+/// The edge a piece pushed away from by `kick` was kicked off: the wall
+/// it moved away from sideways, or else the floor. Returned as the strip of
+/// the board along that edge, in world coordinates.
+fn kicked_edge(layout: &BoardLayout, kick: (i32, i32)) -> Rect {
+    let board = layout.board_rect();
+    let width = KICK_FLASH_WIDTH * layout.cell_size;
+    match kick.0.signum() {
+        1 => Rect::new(board.min.x, board.min.y, board.min.x + width, board.max.y),
+        -1 => Rect::new(board.max.x - width, board.min.y, board.max.x, board.max.y),
+        _ => Rect::new(board.min.x, board.min.y, board.max.x, board.min.y + width),
+    }
+}
+
+fn spawn_kick_flashes(
+    mut commands: Commands,
+    mut events: EventReader<GameplayEvent>,
+    settings: Res<Settings>,
+    layout: Res<BoardLayout>,
+) {
+    for event in events.read() {
+        let GameplayEvent::Kicked(kick) = *event else {
+            continue;
+        };
+        if !settings.effects().kick_flash {
+            continue;
+        }
+        let edge = kicked_edge(&layout, kick);
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: FLASH_COLOR.with_a(KICK_FLASH_ALPHA),
+                    custom_size: Some(edge.size()),
+                    ..default()
+                },
+                transform: Transform::from_translation(edge.center().extend(layers::KICK_FLASH)),
+                ..default()
+            },
+            KickFlash(Timer::from_seconds(KICK_FLASH_SECS, TimerMode::Once)),
+            PlayfieldEntity,
+        ));
+    }
+}
+
+fn fade_kick_flashes(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut flashes: Query<(Entity, &mut KickFlash, &mut Sprite)>,
+) {
+    for (entity, mut flash, mut sprite) in &mut flashes {
+        if flash.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        } else {
+            sprite
+                .color
+                .set_a(KICK_FLASH_ALPHA * flash.0.percent_left());
+        }
+    }
+}
+
+/// Copies the blocks of each cleared row out of the board as it was
+/// before the clear. That board is the one last seen with this frame's
+/// locks and clears played onto it in order, so a piece that filled a row
+/// and was cleared within the same frame is still in its afterimage.
+fn spawn_afterimages(
+    mut commands: Commands,
+    mut events: EventReader<GameplayEvent>,
+    settings: Res<Settings>,
+    board: Res<Board>,
+    layout: Res<BoardLayout>,
+    palette: Res<Palette>,
+    mut seen: Local<Option<Board>>,
+) {
+    let mut before = seen.take().unwrap_or_else(|| board.clone());
+    for event in events.read() {
+        match *event {
+            GameplayEvent::Locked(piece) => before.lock_piece(&piece),
+            GameplayEvent::LinesCleared { rows, .. } => {
+                if settings.effects().afterimage {
+                    spawn_afterimage(&mut commands, &before, rows, &layout, &palette);
+                }
+                before.clear_lines();
+            }
+            GameplayEvent::GarbageRose(_) | GameplayEvent::Reset => before = board.clone(),
+            _ => {}
+        }
+    }
+    *seen = Some(if board.is_changed() {
+        board.clone()
+    } else {
+        before
+    });
+}
+
+fn spawn_afterimage(
+    commands: &mut Commands,
+    board: &Board,
+    rows: u64,
+    layout: &BoardLayout,
+    palette: &Palette,
+) {
+    let cleared = (0..board.height() as i32).filter(|&y| rows & 1 << y != 0);
+    for y in cleared.filter(|&y| layout.shows_row(y)) {
+        for x in 0..board.width() as i32 {
+            let Some(kind) = board.get(x, y) else {
+                continue;
+            };
+            let center = layout.cell_center(x, y);
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: palette.cell(kind).with_a(AFTERIMAGE_ALPHA),
+                        custom_size: Some(Vec2::splat(layout.block_size() - palette.block_gap)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(center.extend(layers::AFTERIMAGE)),
+                    ..default()
+                },
+                ClearAfterimage {
+                    cell: kind,
+                    timer: Timer::from_seconds(AFTERIMAGE_SECS, TimerMode::Once),
+                },
+                PlayfieldEntity,
+            ));
+        }
+    }
+}
+
+fn fade_afterimages(
+    mut commands: Commands,
+    time: Res<Time>,
+    palette: Res<Palette>,
+    mut afterimages: Query<(Entity, &mut ClearAfterimage, &mut Sprite)>,
+) {
+    for (entity, mut afterimage, mut sprite) in &mut afterimages {
+        if afterimage.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        } else {
+            let alpha = AFTERIMAGE_ALPHA * afterimage.timer.percent_left();
+            sprite.color = palette.cell(afterimage.cell).with_a(alpha);
+        }
+    }
+}
+
+fn draw_hold_reject(
+    settings: Res<Settings>,
+    flash: Res<HoldRejectFlash>,
+    mut panels: Query<(&Panel, &mut Sprite)>,
+) {
+    let amount = HOLD_REJECT_STRENGTH * flash.0.percent_left();
+    if amount <= 0.0 || !settings.effects().flashes {
+        return;
+    }
+    for (panel, mut sprite) in &mut panels {
+        if *panel == Panel::Hold {
+            sprite.color = mix(sprite.color, HOLD_REJECT_COLOR, amount);
+        }
+    }
+}
+
+fn spawn_dissolve_blocks(
+    mut commands: Commands,
+    board: Res<Board>,
+    layout: Res<BoardLayout>,
+    palette: Res<Palette>,
+) {
+    let mut rng = rand::thread_rng();
+    for y in 0..board.visible_height() as i32 {
+        for x in 0..board.width() as i32 {
+            let Some(kind) = board.get(x, y) else {
+                continue;
+            };
+            let center = layout.cell_center(x, y);
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: palette.cell(kind),
+                        custom_size: Some(Vec2::splat(layout.block_size() - palette.block_gap)),
+                        ..default()
+                    },
+                    transform: Transform::from_xyz(center.x, center.y, layers::PARTICLES),
+                    ..default()
+                },
+                DissolveBlock {
+                    cell: kind,
+                    velocity: Vec2::new(
+                        rng.gen_range(-DISSOLVE_JITTER..=DISSOLVE_JITTER),
+                        rng.gen_range(0.0..=DISSOLVE_JITTER),
+                    ),
+                    delay: rng.gen_range(0.0..=DISSOLVE_MAX_DELAY),
+                },
+                PlayfieldEntity,
+            ));
+        }
+    }
+}
+
+fn despawn_dissolve_blocks(mut commands: Commands, blocks: Query<Entity, With<DissolveBlock>>) {
+    for entity in &blocks {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// The locked blocks are shown by their dissolving copies instead.
+fn hide_dissolved_cells(
+    palette: Res<Palette>,
+    mut tinted: ResMut<CellsTinted>,
+    mut cells: Query<&mut Sprite, With<CellSprite>>,
+) {
+    for mut sprite in &mut cells {
+        sprite.color = palette.empty_cell;
+    }
+    tinted.0 = true;
+}
+
+fn animate_dissolve(
+    time: Res<Time>,
+    dissolve: Res<Dissolve>,
+    layout: Res<BoardLayout>,
+    palette: Res<Palette>,
+    mut blocks: Query<(&mut DissolveBlock, &mut Sprite, &mut Transform)>,
+) {
+    let dt = time.delta_seconds();
+    let alpha = dissolve.0.percent_left();
+    for (mut block, mut sprite, mut transform) in &mut blocks {
+        sprite.color = palette.cell(block.cell).with_a(alpha);
+        if block.delay > 0.0 {
+            block.delay -= dt;
+            continue;
+        }
+        block.velocity.y -= DISSOLVE_GRAVITY * dt;
+        transform.translation += (block.velocity * layout.block_size() * dt).extend(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::components::garbage::GarbageQueue;
+    use crate::components::tetromino::{PieceKind, PieceQueue, Tetromino};
+    use crate::game::{announce_board_changes, reset_game, BoardChanged, GameMode};
+    use crate::rendering::shapes::{apply_palette, COLORS, EMPTY_CELL_COLOR};
+
+    #[test]
+    fn test_invisible_blocks_leave_the_board_intact() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<GameplayEvent>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                250,
+            )))
+            .insert_resource(Modifiers {
+                invisible: true,
+                ..default()
+            })
+            .init_resource::<Settings>()
+            .init_resource::<Palette>()
+            .init_resource::<Board>()
+            .init_resource::<BoardLayout>()
+            .init_resource::<CellAges>()
+            .init_resource::<CellsTinted>()
+            .add_event::<BoardChanged>()
+            .add_systems(
+                Update,
+                (
+                    announce_board_changes,
+                    draw_board,
+                    track_cell_ages,
+                    hide_invisible_cells,
+                )
+                    .chain(),
+            );
+        let board = app.world.resource::<Board>();
+        let piece = board.landing_position(&Tetromino::spawn(PieceKind::O, board));
+        for (x, y) in piece.cells() {
+            app.world.spawn((
+                CellSprite { x, y },
+                Sprite::default(),
+                Transform::default(),
+                Visibility::default(),
+            ));
+        }
+        app.world.resource_mut::<Board>().lock_piece(&piece);
+        app.world.send_event(GameplayEvent::Locked(piece));
+        let board = app.world.resource::<Board>().clone();
+
+        app.update();
+        let mut sprites = app.world.query::<&Sprite>();
+        assert!(sprites
+            .iter(&app.world)
+            .all(|sprite| sprite.color == COLORS[PieceKind::O.index()]));
+
+        let delay = app.world.resource::<Settings>().invisible_delay;
+        let updates = ((delay + INVISIBLE_FADE_SECS) / 0.25).ceil() as usize;
+        for _ in 0..updates {
+            app.update();
+        }
+        assert!(sprites
+            .iter(&app.world)
+            .all(|sprite| sprite.color == EMPTY_CELL_COLOR));
+        assert_eq!(*app.world.resource::<Board>(), board);
+    }
+
+    #[test]
+    fn test_risen_garbage_is_on_the_board_at_once_and_slides_in_after() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<GameplayEvent>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                50,
+            )))
+            .init_resource::<Settings>()
+            .init_resource::<Palette>()
+            .init_resource::<Board>()
+            .init_resource::<BoardLayout>()
+            .init_resource::<GarbageRise>()
+            .init_resource::<CellsTinted>()
+            .add_event::<BoardChanged>()
+            .add_systems(
+                Update,
+                (
+                    announce_board_changes,
+                    draw_board,
+                    track_garbage_rise,
+                    draw_garbage_rise,
+                )
+                    .chain(),
+            );
+        let cell = app
+            .world
+            .spawn((
+                CellSprite { x: 0, y: 1 },
+                Sprite::default(),
+                Transform::default(),
+                Visibility::default(),
+            ))
+            .id();
+        app.update();
+
+        let mut garbage = GarbageQueue::with_seed(4);
+        garbage.push(2);
+        let mut board = app.world.resource::<Board>().clone();
+        assert_eq!(garbage.insert(&mut board), Ok(2));
+        app.world.insert_resource(board.clone());
+        app.world.send_event(GameplayEvent::GarbageRose(2));
+
+        let layout = *app.world.resource::<BoardLayout>();
+        let resting = layout.cell_center(0, 1).y;
+        let mut heights = Vec::new();
+        for _ in 0..6 {
+            app.update();
+            assert_eq!(*app.world.resource::<Board>(), board);
+            heights.push(app.world.get::<Transform>(cell).unwrap().translation.y);
+        }
+        assert!(heights[0] < resting);
+        assert!(heights.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(*heights.last().unwrap(), resting);
+        let color = app.world.get::<Sprite>(cell).unwrap().color;
+        let palette = app.world.resource::<Palette>();
+        assert_eq!(color, palette.cell(board.get(0, 1).unwrap()));
+    }
+
+    #[test]
+    fn test_cleared_rows_leave_an_afterimage_of_their_blocks_that_fades() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<GameplayEvent>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )))
+            .insert_resource(Settings {
+                clear_afterimage: true,
+                ..default()
+            })
+            .init_resource::<Palette>()
+            .init_resource::<Board>()
+            .init_resource::<BoardLayout>()
+            .add_systems(Update, (spawn_afterimages, fade_afterimages).chain());
+
+        // The bottom row full but for a gap an I fills, with a block above
+        // it that drops into its place.
+        let mut board = Board::default();
+        let piece = Tetromino::spawn(PieceKind::I, &board);
+        let piece = board.landing_position(&piece);
+        let gap = piece.cells().map(|(x, _)| x);
+        for x in 0..board.width() as i32 {
+            if !gap.contains(&x) {
+                board.set(x, 0, Some(PieceKind::T.index()));
+            }
+        }
+        board.set(0, 1, Some(PieceKind::S.index()));
+        app.world.insert_resource(board.clone());
+        app.update();
+
+        // The piece locks and the row clears within one frame.
+        board.lock_piece(&piece);
+        assert_eq!(board.clear_lines(), 1);
+        app.world.insert_resource(board);
+        app.world.send_event(GameplayEvent::Locked(piece));
+        app.world.send_event(GameplayEvent::LinesCleared {
+            count: 1,
+            rows: 1,
+            cancelled: 0,
+        });
+        app.update();
+
+        let layout = *app.world.resource::<BoardLayout>();
+        let mut afterimages = app
+            .world
+            .query::<(&ClearAfterimage, &Transform)>()
+            .iter(&app.world)
+            .map(|(afterimage, transform)| (afterimage.cell, transform.translation.truncate()))
+            .collect::<Vec<_>>();
+        afterimages.sort_by(|a, b| a.1.x.total_cmp(&b.1.x));
+        let expected = (0..layout.columns as i32)
+            .map(|x| {
+                let kind = if gap.contains(&x) {
+                    PieceKind::I
+                } else {
+                    PieceKind::T
+                };
+                (kind.index(), layout.cell_center(x, 0))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(afterimages, expected);
+
+        let updates = (AFTERIMAGE_SECS / 0.1).ceil() as usize;
+        for _ in 0..updates {
+            app.update();
+        }
+        let mut left = app.world.query::<&ClearAfterimage>();
+        assert_eq!(left.iter(&app.world).count(), 0);
+
+        // Turned off, or under reduced motion, clears leave nothing behind.
+        app.world.resource_mut::<Settings>().reduce_motion = true;
+        app.world.send_event(GameplayEvent::LinesCleared {
+            count: 1,
+            rows: 1,
+            cancelled: 0,
+        });
+        app.update();
+        assert_eq!(left.iter(&app.world).count(), 0);
+    }
+
+    #[test]
+    fn test_dissolving_blocks_follow_a_theme_change() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(ClearColor::default())
+            .insert_resource(Dissolve(Timer::from_seconds(1.0, TimerMode::Once)))
+            .init_resource::<Settings>()
+            .init_resource::<GameMode>()
+            .init_resource::<Palette>()
+            .init_resource::<Board>()
+            .init_resource::<BoardLayout>()
+            .add_systems(Update, (apply_palette, animate_dissolve).chain());
+        app.world
+            .resource_mut::<Board>()
+            .set(0, 0, Some(PieceKind::T.index()));
+        app.world.run_system_once(spawn_dissolve_blocks);
+        app.update();
+        let mut sprites = app.world.query_filtered::<&Sprite, With<DissolveBlock>>();
+        let color = sprites.single(&app.world).color;
+        assert_eq!(color, COLORS[PieceKind::T.index()]);
+
+        app.world.resource_mut::<Settings>().high_contrast = true;
+        app.update();
+        let color = sprites.single(&app.world).color;
+        assert_eq!(color, Palette::HIGH_CONTRAST.piece(PieceKind::T));
+    }
+
+    #[test]
+    fn test_reset_game_forgets_cell_ages() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<GameplayEvent>()
+            .init_resource::<Modifiers>()
+            .init_resource::<CellAges>()
+            .add_systems(Update, track_cell_ages);
+        let piece = Tetromino::spawn(PieceKind::O, &Board::default());
+        app.world.send_event(GameplayEvent::Locked(piece));
+        app.update();
+        let (x, y) = piece.cells()[0];
+        assert!(app.world.resource::<CellAges>().age(x, y).is_finite());
+
+        app.world.run_system_once(|mut commands: Commands| {
+            reset_game(&mut commands, PieceQueue::new());
+        });
+        app.update();
+        assert_eq!(app.world.resource::<CellAges>().age(x, y), f32::INFINITY);
+    }
+}
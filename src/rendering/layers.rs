@@ -0,0 +1,44 @@
+//! Draw order of everything on screen, back to front.
+//!
+//! Sprites sharing a `z` flicker against each other, so every rendering
+//! system takes its depth from here rather than picking its own. The list
+//! below is the order they stack in; [`ORDER`] holds the same list for the
+//! test that keeps it honest.
+
+/// The well and side panel backdrops.
+pub const BACKGROUND: f32 = 0.0;
+/// Board cells, locked blocks and the hold and next pieces.
+pub const CELLS: f32 = 1.0;
+/// The board editor's highlight on the cell under the mouse.
+pub const HOVER: f32 = 1.5;
+/// The fading afterimage of rows just cleared, over the cells that drop
+/// into their place.
+pub const AFTERIMAGE: f32 = 1.75;
+/// The ghost and the hold preview, over the cells they shade.
+pub const GHOST: f32 = 2.0;
+/// The drop shadow of the piece in play, over the ghost and under the
+/// piece casting it.
+pub const SHADOW: f32 = 2.5;
+/// The piece in play, over its own ghost.
+pub const ACTIVE: f32 = 3.0;
+/// The glint along an edge a rotation kicked off.
+pub const KICK_FLASH: f32 = 3.5;
+/// Blocks flying off in the top-out dissolve.
+pub const PARTICLES: f32 = 4.0;
+/// Panel titles, the score readout and the garbage meter.
+pub const HUD: f32 = 10.0;
+
+/// Every layer, back to front.
+pub const ORDER: [f32; 10] = [
+    BACKGROUND, CELLS, HOVER, AFTERIMAGE, GHOST, SHADOW, ACTIVE, KICK_FLASH, PARTICLES, HUD,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_are_strictly_ordered() {
+        assert!(ORDER.windows(2).all(|pair| pair[0] < pair[1]), "{ORDER:?}");
+    }
+}
@@ -0,0 +1,1979 @@
+//! Block rendering for the board, the active and ghost pieces, the hold
+//! swap preview and the hold and next panels.
+//!
+//! Every block is a pre-spawned sprite whose colour, size and position are
+//! written each frame from the game resources and the current
+//! [`BoardLayout`], so the view never drifts from the simulation.
+
+use bevy::core_pipeline::core_2d::Camera2d;
+use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
+use bevy::window::{PrimaryWindow, WindowResized};
+
+use crate::components::board::{Board, SharedField};
+use crate::components::tetromino::{PieceKind, PieceQueue, RotationSystem, Tetromino};
+use crate::game::{
+    announce_board_changes, ActivePiece, AppState, BoardChanged, GameMode, Hold, Modifiers,
+    PartnerPiece,
+};
+use crate::rendering::layers;
+use crate::utils::config::{
+    Framing, HudLayout, LayoutConfig, NextOrientation, Settings, BUFFER_PEEK_ROWS,
+};
+
+/// Block colours indexed by [`PieceKind::index`].
+pub const COLORS: [Color; 7] = [
+    Color::rgb(0.0, 0.85, 0.95),
+    Color::rgb(0.98, 0.85, 0.1),
+    Color::rgb(0.65, 0.25, 0.9),
+    Color::rgb(0.3, 0.85, 0.3),
+    Color::rgb(0.95, 0.25, 0.25),
+    Color::rgb(0.2, 0.4, 0.95),
+    Color::rgb(0.98, 0.55, 0.1),
+];
+
+/// Colour of the rows that rise in survival.
+pub const GARBAGE_COLOR: Color = Color::rgb(0.45, 0.45, 0.5);
+
+pub const BACKGROUND_COLOR: Color = Color::rgb(0.05, 0.05, 0.08);
+/// Backgrounds of the modes other than Marathon, each only a shade off
+/// [`BACKGROUND_COLOR`] so blocks read the same on all of them.
+const ZEN_BACKGROUND: Color = Color::rgb(0.04, 0.08, 0.07);
+const SURVIVAL_BACKGROUND: Color = Color::rgb(0.1, 0.06, 0.04);
+const SPRINT_BACKGROUND: Color = Color::rgb(0.03, 0.06, 0.12);
+const CHEESE_BACKGROUND: Color = Color::rgb(0.08, 0.07, 0.03);
+const VERSUS_BACKGROUND: Color = Color::rgb(0.09, 0.03, 0.06);
+const WELL_COLOR: Color = Color::rgb(0.02, 0.02, 0.04);
+pub const EMPTY_CELL_COLOR: Color = Color::rgb(0.08, 0.08, 0.12);
+const PANEL_COLOR: Color = Color::rgb(0.09, 0.09, 0.13);
+const HOLD_LOCKED_COLOR: Color = Color::rgb(0.35, 0.35, 0.4);
+const TEXT_COLOR: Color = Color::rgb(0.85, 0.85, 0.9);
+const GHOST_ALPHA: f32 = 0.25;
+/// Rows out from landing the ghost starts to show when it's only shown
+/// near landing, until changed.
+pub const GHOST_NEAR_ROWS: u32 = 4;
+/// Fainter than the ghost, so the two never read as the same thing.
+const HOLD_PREVIEW_ALPHA: f32 = 0.12;
+/// Where the piece's shadow falls, in cells from the piece, and how dark it
+/// is. Black rather than the piece colour, to stay apart from the ghost.
+const SHADOW_OFFSET: Vec2 = Vec2::new(0.15, -0.15);
+const SHADOW_ALPHA: f32 = 0.35;
+/// Opacity of blocks locked in the shown buffer rows.
+const BUFFER_ALPHA: f32 = 0.5;
+
+/// Width of a side panel, in cells.
+const PANEL_COLUMNS: f32 = 5.0;
+/// Space between the board and each side panel, in cells.
+const PANEL_GAP: f32 = 1.0;
+/// Rows taken by a panel's title.
+const PANEL_TITLE_ROWS: f32 = 1.0;
+/// Rows given to each piece in the hold and next panels.
+const SLOT_ROWS: f32 = 3.0;
+/// Rows of the compact strip's slots, and the rows kept under the board
+/// for the stats in the compact layout.
+const COMPACT_SLOT_ROWS: f32 = 2.0;
+const COMPACT_STATS_ROWS: f32 = 3.0;
+/// Preview cells a compact slot is wide, enough for an I with room
+/// either side.
+const COMPACT_SLOT_COLUMNS: f32 = 5.0;
+/// Pixels left between neighbouring blocks so the grid stays readable.
+pub const BLOCK_GAP: f32 = 1.0;
+pub const MIN_CELL_SIZE: f32 = 4.0;
+
+/// How far the camera sits from the playfield along its view direction.
+/// Only matters once tilted, where it keeps the far edge of the board
+/// inside the clip range.
+const CAMERA_DISTANCE: f32 = 500.0;
+/// Lean of the board away from the viewer when tilt is on, in degrees.
+const TILT_DEGREES: f32 = 25.0;
+/// Vertical field of view of the tilted camera, in radians.
+const TILT_FOV: f32 = std::f32::consts::FRAC_PI_4;
+
+/// Every colour the playfield and HUD are drawn in, following
+/// [`Settings::high_contrast`].
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct Palette {
+    /// Block colours indexed by [`PieceKind::index`].
+    pub pieces: [Color; 7],
+    pub garbage: Color,
+    pub background: Color,
+    /// Tint the background by [`GameMode`].
+    pub mode_tints: bool,
+    /// Behind the board's cells, so it shows between them as gridlines.
+    pub well: Color,
+    pub empty_cell: Color,
+    pub panel: Color,
+    /// The held piece while it can't be swapped.
+    pub hold_locked: Color,
+    pub text: Color,
+    /// Pixels left between neighbouring blocks.
+    pub block_gap: f32,
+}
+
+impl Palette {
+    pub const STANDARD: Palette = Palette {
+        pieces: COLORS,
+        garbage: GARBAGE_COLOR,
+        background: BACKGROUND_COLOR,
+        mode_tints: true,
+        well: WELL_COLOR,
+        empty_cell: EMPTY_CELL_COLOR,
+        panel: PANEL_COLOR,
+        hold_locked: HOLD_LOCKED_COLOR,
+        text: TEXT_COLOR,
+        block_gap: BLOCK_GAP,
+    };
+
+    /// Saturated pieces on black, split by thick white gridlines.
+    pub const HIGH_CONTRAST: Palette = Palette {
+        pieces: [
+            Color::rgb(0.0, 1.0, 1.0),
+            Color::rgb(1.0, 1.0, 0.0),
+            Color::rgb(1.0, 0.0, 1.0),
+            Color::rgb(0.0, 1.0, 0.0),
+            Color::rgb(1.0, 0.0, 0.0),
+            Color::rgb(0.3, 0.5, 1.0),
+            Color::rgb(1.0, 0.55, 0.0),
+        ],
+        garbage: Color::rgb(0.7, 0.7, 0.7),
+        background: Color::BLACK,
+        mode_tints: false,
+        well: Color::WHITE,
+        empty_cell: Color::BLACK,
+        panel: Color::rgb(0.12, 0.12, 0.12),
+        hold_locked: Color::rgb(0.45, 0.45, 0.45),
+        text: Color::WHITE,
+        block_gap: 3.0,
+    };
+
+    pub fn for_settings(settings: &Settings) -> &'static Palette {
+        if settings.high_contrast {
+            &Palette::HIGH_CONTRAST
+        } else {
+            &Palette::STANDARD
+        }
+    }
+
+    /// The colour of a locked [`Board`] cell: its piece's colour, or
+    /// [`Self::garbage`].
+    pub fn cell(&self, cell: usize) -> Color {
+        self.pieces.get(cell).copied().unwrap_or(self.garbage)
+    }
+
+    pub fn piece(&self, kind: PieceKind) -> Color {
+        self.pieces[kind.index()]
+    }
+
+    /// The background under `mode`, faintly tinted so the mode shows at a
+    /// glance unless the palette keeps to its plain background.
+    pub fn background_for(&self, mode: GameMode) -> Color {
+        if !self.mode_tints {
+            return self.background;
+        }
+        match mode {
+            GameMode::Marathon => self.background,
+            GameMode::Zen => ZEN_BACKGROUND,
+            GameMode::Survival => SURVIVAL_BACKGROUND,
+            GameMode::Sprint(_) => SPRINT_BACKGROUND,
+            GameMode::Cheese(_) => CHEESE_BACKGROUND,
+            GameMode::Versus => VERSUS_BACKGROUND,
+        }
+    }
+
+    /// The colour of the piece in play: its kind's colour lightened by
+    /// [`Settings::active_emphasis`].
+    pub fn active(&self, kind: PieceKind, emphasis: f32) -> Color {
+        let [r, g, b, a] = self.piece(kind).as_rgba_f32();
+        let lighten = |channel: f32| channel + (1.0 - channel) * emphasis.clamp(0.0, 1.0);
+        Color::rgba(lighten(r), lighten(g), lighten(b), a)
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::STANDARD
+    }
+}
+
+/// Screen placement of the board and its panels.
+///
+/// Recomputed whenever the window size or [`LayoutConfig`] changes, using
+/// the configured cell size or else the largest whole-pixel cell size that
+/// fits the board and both side panels inside the margin.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct BoardLayout {
+    /// The window size the layout was fitted to.
+    pub window: Vec2,
+    /// Side length of one cell in pixels.
+    pub cell_size: f32,
+    /// World position of the bottom-left corner of the visible board.
+    pub origin: Vec2,
+    /// Board columns and visible rows.
+    pub columns: usize,
+    pub rows: usize,
+    /// Buffer rows shown, faded, above the visible rows.
+    pub buffer_rows: usize,
+    /// Cells each board cell covers along each side, 2 under big mode.
+    pub block_scale: usize,
+    /// Space kept clear around the content, in cells.
+    pub margin: f32,
+    /// Lean of the view in radians, 0.0 when the board is seen flat.
+    pub tilt: f32,
+    /// Hold panel on the right and next queue on the left.
+    pub mirror: bool,
+    /// Hold and next share one strip above the board, whichever way
+    /// [`Self::mirror`] is set, and the stats go below it.
+    pub compact: bool,
+    /// Whether the next panel stacks its slots down or lays them along,
+    /// outside the compact layout.
+    pub next_orientation: NextOrientation,
+    /// Whether the flat view keeps cells square or fills the window.
+    pub framing: Framing,
+}
+
+impl BoardLayout {
+    pub fn fit(window: Vec2, board: &Board, config: &LayoutConfig) -> Self {
+        let columns = board.width();
+        let rows = board.visible_height();
+        let buffer_rows = if config.show_buffer {
+            BUFFER_PEEK_ROWS.min(board.height() - rows)
+        } else {
+            0
+        };
+        let block_scale = config.block_scale.max(1);
+        let extent = Vec2::new(columns as f32, (rows + buffer_rows) as f32) * block_scale as f32;
+        let panels = if config.hud.compact {
+            let rows = 2.0 * PANEL_GAP + PANEL_TITLE_ROWS + COMPACT_SLOT_ROWS + COMPACT_STATS_ROWS;
+            Vec2::new(0.0, rows)
+        } else if config.hud.next_orientation == NextOrientation::Horizontal {
+            let slots = PieceQueue::PREVIEW_LEN as f32;
+            Vec2::new((slots + 1.0) * PANEL_COLUMNS + 2.0 * PANEL_GAP, 0.0)
+        } else {
+            Vec2::new(2.0 * (PANEL_COLUMNS + PANEL_GAP), 0.0)
+        };
+        let content = extent + panels + 2.0 * config.margin;
+        let cell_size = config
+            .cell_size
+            .map_or_else(|| (window / content).min_element(), f32::round)
+            .floor()
+            .max(MIN_CELL_SIZE);
+        let origin = (-extent * cell_size / 2.0).floor();
+        Self {
+            window,
+            cell_size,
+            origin,
+            columns,
+            rows,
+            buffer_rows,
+            block_scale,
+            margin: config.margin,
+            tilt: if config.tilt {
+                TILT_DEGREES.to_radians()
+            } else {
+                0.0
+            },
+            mirror: config.hud.mirror,
+            compact: config.hud.compact,
+            next_orientation: config.hud.next_orientation,
+            framing: config.framing,
+        }
+    }
+
+    /// Side length of a board cell's block in pixels: the cell size, or a
+    /// multiple of it under big mode.
+    pub fn block_size(&self) -> f32 {
+        self.cell_size * self.block_scale as f32
+    }
+
+    /// World position of the centre of board cell `(x, y)`.
+    pub fn cell_center(&self, x: i32, y: i32) -> Vec2 {
+        self.origin + (Vec2::new(x as f32, y as f32) + 0.5) * self.block_size()
+    }
+
+    /// The board cell drawn at world position `world`, if any.
+    pub fn cell_at(&self, world: Vec2) -> Option<(i32, i32)> {
+        let cell = ((world - self.origin) / self.block_size()).floor();
+        let (x, y) = (cell.x as i32, cell.y as i32);
+        ((0..self.columns as i32).contains(&x) && self.shows_row(y)).then_some((x, y))
+    }
+
+    /// The world position shown at `screen`, in logical pixels from the top
+    /// left of the window, when seen flat.
+    pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+        let offset = screen - self.window / 2.0;
+        self.content_rect().center() + Vec2::new(offset.x, -offset.y) * self.world_per_pixel()
+    }
+
+    pub fn board_rect(&self) -> Rect {
+        let size = Vec2::new(self.columns as f32, self.rows as f32) * self.block_size();
+        Rect::from_corners(self.origin, self.origin + size)
+    }
+
+    /// The visible board and the buffer rows shown above it.
+    pub fn field_rect(&self) -> Rect {
+        let board = self.board_rect();
+        let buffer = Vec2::new(0.0, self.buffer_rows as f32 * self.block_size());
+        Rect::from_corners(board.min, board.max + buffer)
+    }
+
+    /// Whether board row `y` is drawn.
+    pub fn shows_row(&self, y: i32) -> bool {
+        (0..(self.rows + self.buffer_rows) as i32).contains(&y)
+    }
+
+    /// The field and both panels, and the stats when they're below the
+    /// board, with the margin around them.
+    pub fn content_rect(&self) -> Rect {
+        let mut content = self
+            .field_rect()
+            .union(self.hold_panel())
+            .union(self.next_panel());
+        if self.compact {
+            let board = self.board_rect();
+            let rows = PANEL_GAP + COMPACT_STATS_ROWS;
+            content = content.union_point(board.min - Vec2::new(0.0, rows * self.cell_size));
+        }
+        content.inset(self.margin * self.cell_size)
+    }
+
+    /// Orthographic scale that frames [`Self::content_rect`] in the window,
+    /// zooming out when a configured cell size leaves the content too big
+    /// and staying pixel-exact otherwise.
+    pub fn camera_scale(&self) -> f32 {
+        if self.window.min_element() <= 0.0 {
+            return 1.0;
+        }
+        (self.content_rect().size() / self.window)
+            .max_element()
+            .max(1.0)
+    }
+
+    /// World units each logical pixel of the window covers along each
+    /// axis in the flat view: [`Self::camera_scale`] on both when
+    /// letterboxed, and whatever fills the window with
+    /// [`Self::content_rect`] when stretched.
+    pub fn world_per_pixel(&self) -> Vec2 {
+        if self.stretched() {
+            self.content_rect().size() / self.window
+        } else {
+            Vec2::splat(self.camera_scale())
+        }
+    }
+
+    /// Width and height on screen of one board block, in logical pixels.
+    /// Square unless [stretched](Framing::Stretch).
+    pub fn block_on_screen(&self) -> Vec2 {
+        self.block_size() / self.world_per_pixel()
+    }
+
+    /// Whether the flat view is stretched to the window. The tilted view
+    /// always keeps its proportions.
+    fn stretched(&self) -> bool {
+        self.framing == Framing::Stretch && self.tilt == 0.0 && self.window.min_element() > 0.0
+    }
+
+    /// The camera's projection: orthographic at [`Self::camera_scale`], or
+    /// fixed to [`Self::content_rect`] when stretched, for the flat view,
+    /// and perspective when tilted so the far edge of the board recedes.
+    pub fn camera_projection(&self) -> Projection {
+        if self.stretched() {
+            let size = self.content_rect().size();
+            return Projection::Orthographic(OrthographicProjection {
+                scaling_mode: ScalingMode::Fixed {
+                    width: size.x,
+                    height: size.y,
+                },
+                ..Camera2dBundle::default().projection
+            });
+        }
+        if self.tilt == 0.0 {
+            return Projection::Orthographic(OrthographicProjection {
+                scale: self.camera_scale(),
+                ..Camera2dBundle::default().projection
+            });
+        }
+        Projection::Perspective(PerspectiveProjection {
+            fov: TILT_FOV,
+            far: 4.0 * self.perspective_distance(),
+            ..default()
+        })
+    }
+
+    /// Camera placement looking at the centre of [`Self::content_rect`],
+    /// leaned back by [`Self::tilt`]. The tilt lives entirely in the view,
+    /// so world and board coordinates are the same either way.
+    pub fn camera_transform(&self) -> Transform {
+        let rotation = Quat::from_rotation_x(self.tilt);
+        let center = self.content_rect().center().extend(0.0);
+        let distance = if self.tilt == 0.0 {
+            CAMERA_DISTANCE
+        } else {
+            self.perspective_distance()
+        };
+        Transform::from_translation(center + rotation * Vec3::Z * distance).with_rotation(rotation)
+    }
+
+    /// How far the tilted camera stands back to fit [`Self::content_rect`],
+    /// with room for the near edge, which the lean brings closer.
+    fn perspective_distance(&self) -> f32 {
+        let half = self.content_rect().size() / 2.0;
+        let tan = (TILT_FOV / 2.0).tan();
+        let aspect = if self.window.min_element() > 0.0 {
+            self.window.x / self.window.y
+        } else {
+            1.0
+        };
+        let fit = (half.y / tan).max(half.x / (tan * aspect));
+        fit + half.y * self.tilt.sin()
+    }
+
+    /// The panel holding the held piece, left of the board unless mirrored,
+    /// or the first slot of the compact strip.
+    pub fn hold_panel(&self) -> Rect {
+        if self.compact {
+            let strip = self.compact_strip();
+            return Rect::from_corners(
+                strip.min,
+                Vec2::new(strip.min.x + self.compact_slot_width(), strip.max.y),
+            );
+        }
+        self.side_panel(self.mirror, PANEL_COLUMNS, SLOT_ROWS)
+    }
+
+    /// The panel listing upcoming pieces, right of the board unless
+    /// mirrored and one slot wide or, laid horizontally, as wide as all of
+    /// them, or the rest of the compact strip after the hold slot.
+    pub fn next_panel(&self) -> Rect {
+        if self.compact {
+            let strip = self.compact_strip();
+            return Rect::from_corners(
+                Vec2::new(strip.min.x + self.compact_slot_width(), strip.min.y),
+                strip.max,
+            );
+        }
+        if self.horizontal_next() {
+            let slots = PieceQueue::PREVIEW_LEN as f32;
+            return self.side_panel(!self.mirror, slots * PANEL_COLUMNS, SLOT_ROWS);
+        }
+        self.side_panel(
+            !self.mirror,
+            PANEL_COLUMNS,
+            SLOT_ROWS * PieceQueue::PREVIEW_LEN as f32,
+        )
+    }
+
+    /// Whether the next panel lays its slots out side by side beside the
+    /// board, rather than in the compact strip or down a column.
+    fn horizontal_next(&self) -> bool {
+        !self.compact && self.next_orientation == NextOrientation::Horizontal
+    }
+
+    /// The compact layout's strip of the hold slot and then the next
+    /// slots, across the top of the field.
+    fn compact_strip(&self) -> Rect {
+        let field = self.field_rect();
+        let bottom = field.max.y + PANEL_GAP * self.cell_size;
+        let height = (PANEL_TITLE_ROWS + COMPACT_SLOT_ROWS) * self.cell_size;
+        Rect::new(field.min.x, bottom, field.max.x, bottom + height)
+    }
+
+    fn compact_slot_width(&self) -> f32 {
+        self.compact_strip().width() / (PieceQueue::PREVIEW_LEN + 1) as f32
+    }
+
+    /// Side length of a block of a piece shown in a hold or next slot: the
+    /// cell size, or less to fit the narrower slots of the compact strip.
+    pub fn preview_cell_size(&self) -> f32 {
+        if self.compact {
+            (self.compact_slot_width() / COMPACT_SLOT_COLUMNS)
+                .floor()
+                .min(self.cell_size)
+        } else {
+            self.cell_size
+        }
+    }
+
+    /// Top centre of the score readout: under the hold panel, or under the
+    /// board in the compact layout.
+    pub fn stats_anchor(&self) -> Vec2 {
+        if self.compact {
+            let board = self.board_rect();
+            return Vec2::new(board.center().x, board.min.y - PANEL_GAP * self.cell_size);
+        }
+        let panel = self.hold_panel();
+        Vec2::new(panel.center().x, panel.min.y - self.cell_size)
+    }
+
+    /// A panel level with the top of the board, beside it on the right or
+    /// left, `columns` wide with `rows` of slots under its title.
+    fn side_panel(&self, right: bool, columns: f32, rows: f32) -> Rect {
+        let board = self.board_rect();
+        let gap = PANEL_GAP * self.cell_size;
+        let size = Vec2::new(columns, PANEL_TITLE_ROWS + rows) * self.cell_size;
+        let left = if right {
+            board.max.x + gap
+        } else {
+            board.min.x - gap - size.x
+        };
+        Rect::from_corners(
+            Vec2::new(left, board.max.y - size.y),
+            Vec2::new(left + size.x, board.max.y),
+        )
+    }
+
+    /// Centre of a panel's title row.
+    pub fn panel_title(&self, panel: Rect) -> Vec2 {
+        Vec2::new(
+            panel.center().x,
+            panel.max.y - PANEL_TITLE_ROWS * self.cell_size / 2.0,
+        )
+    }
+
+    pub fn hold_slot_center(&self) -> Vec2 {
+        self.slot_center(self.hold_panel(), 0)
+    }
+
+    pub fn next_slot_center(&self, slot: usize) -> Vec2 {
+        if self.horizontal_next() {
+            // Each slot is as wide as the hold panel, one row of them.
+            let first = self.slot_center(self.next_panel(), 0);
+            let left = self.next_panel().min.x;
+            let x = left + (slot as f32 + 0.5) * PANEL_COLUMNS * self.cell_size;
+            return Vec2::new(x, first.y);
+        }
+        self.slot_center(self.next_panel(), slot)
+    }
+
+    fn slot_center(&self, panel: Rect, slot: usize) -> Vec2 {
+        let top = panel.max.y - PANEL_TITLE_ROWS * self.cell_size;
+        if self.compact {
+            return Vec2::new(
+                panel.min.x + (slot as f32 + 0.5) * self.compact_slot_width(),
+                top - COMPACT_SLOT_ROWS * self.cell_size / 2.0,
+            );
+        }
+        Vec2::new(
+            panel.center().x,
+            top - (slot as f32 + 0.5) * SLOT_ROWS * self.cell_size,
+        )
+    }
+}
+
+impl Default for BoardLayout {
+    fn default() -> Self {
+        Self::fit(
+            Vec2::new(1280.0, 720.0),
+            &Board::default(),
+            &LayoutConfig::default(),
+        )
+    }
+}
+
+/// Offsets of a piece's spawn-orientation cells under `system` from its
+/// visual centre, in cells, for drawing it inside a panel slot.
+pub fn preview_offsets(kind: PieceKind, system: RotationSystem) -> [Vec2; 4] {
+    let cells = kind.shape_in(system, 0);
+    let (min, max) = cells
+        .iter()
+        .fold((IVec2::MAX, IVec2::MIN), |(min, max), &(column, row)| {
+            let cell = IVec2::new(column, row);
+            (min.min(cell), max.max(cell))
+        });
+    let center = (min + max).as_vec2() / 2.0;
+    cells.map(|(column, row)| Vec2::new(column as f32 - center.x, center.y - row as f32))
+}
+
+/// Marks everything that belongs to an in-progress game's view.
+#[derive(Component)]
+pub struct PlayfieldEntity;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    Well,
+    Hold,
+    Next,
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct CellSprite {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Set by anything that tints [`CellSprite`]s after [`draw_board`], so the
+/// next frame paints them from the board again even if it hasn't changed.
+#[derive(Resource, Debug, Default)]
+pub struct CellsTinted(pub bool);
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ActiveBlock(pub usize);
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct GhostBlock(pub usize);
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ShadowBlock(pub usize);
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct HoldBlock(pub usize);
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct HoldPreviewBlock(pub usize);
+
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PreviewBlock {
+    pub slot: usize,
+    pub index: usize,
+}
+
+/// Seconds the next previews take to slide up a slot after the front piece
+/// leaves the queue.
+const PREVIEW_SLIDE_SECS: f32 = 0.12;
+
+/// The next previews sliding up into their new slots.
+#[derive(Resource, Debug, Default)]
+pub struct PreviewSlide {
+    /// The previewed pieces as last drawn.
+    upcoming: Vec<PieceKind>,
+    timer: Timer,
+}
+
+impl PreviewSlide {
+    /// How far below its slot a preview is drawn, easing out from a whole
+    /// slot to none.
+    fn offset(&self, layout: &BoardLayout, slot: usize) -> Vec2 {
+        let left = self.timer.percent_left();
+        let eased = left * left;
+        (layout.next_slot_center(slot + 1) - layout.next_slot_center(slot)) * eased
+    }
+}
+
+pub struct ShapesPlugin;
+
+impl Plugin for ShapesPlugin {
+    fn build(&self, app: &mut App) {
+        let hud = HudLayout::load(app);
+        app.insert_resource(ClearColor(BACKGROUND_COLOR))
+            .init_resource::<Palette>()
+            .insert_resource(LayoutConfig { hud, ..default() })
+            .init_resource::<BoardLayout>()
+            .init_resource::<CellsTinted>()
+            .init_resource::<PreviewSlide>()
+            .add_systems(Startup, (spawn_camera, fit_layout_to_window))
+            .add_systems(OnExit(AppState::MainMenu), spawn_playfield)
+            .add_systems(OnEnter(AppState::MainMenu), despawn_playfield)
+            .add_systems(
+                Update,
+                (
+                    apply_palette,
+                    apply_smoothing,
+                    save_hud_layout,
+                    sync_block_scale,
+                    update_layout,
+                    frame_camera,
+                    (
+                        draw_panels,
+                        draw_board.after(announce_board_changes),
+                        draw_ghost,
+                        draw_hold_preview,
+                        draw_piece_shadow,
+                        draw_active_piece,
+                        draw_hold,
+                        (track_preview_slide, draw_next_preview).chain(),
+                    ),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// A 2D camera whose projection can switch to perspective for the tilted
+/// view.
+fn spawn_camera(mut commands: Commands) {
+    let camera = Camera2dBundle::default();
+    let projection = Projection::Orthographic(camera.projection.clone());
+    commands
+        .spawn(camera)
+        .remove::<OrthographicProjection>()
+        .insert(projection);
+}
+
+fn fit_layout_to_window(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    board: Res<Board>,
+    config: Res<LayoutConfig>,
+    mut layout: ResMut<BoardLayout>,
+) {
+    if let Ok(window) = windows.get_single() {
+        let size = Vec2::new(window.width(), window.height());
+        *layout = BoardLayout::fit(size, &board, &config);
+    }
+}
+
+/// Saves [`HudLayout`] whenever the settings menu changes it.
+fn save_hud_layout(config: Res<LayoutConfig>, mut saved: Local<Option<HudLayout>>) {
+    if saved.is_some_and(|saved| saved == config.hud) {
+        return;
+    }
+    if saved.is_some() {
+        if let Err(error) = config.hud.save() {
+            warn!("could not save the HUD layout: {error}");
+        }
+    }
+    *saved = Some(config.hud);
+}
+
+/// Turns multisampling off under [`Settings::low_detail`] and back on
+/// without it.
+fn apply_smoothing(settings: Res<Settings>, msaa: Option<ResMut<Msaa>>) {
+    let Some(mut msaa) = msaa.filter(|_| settings.is_changed()) else {
+        return;
+    };
+    msaa.set_if_neq(if settings.effects().smoothing {
+        Msaa::default()
+    } else {
+        Msaa::Off
+    });
+}
+
+/// Switches the [`Palette`] when the theme setting changes, and the
+/// background when either it or the [`GameMode`] does.
+pub fn apply_palette(
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    mut palette: ResMut<Palette>,
+    mut clear_color: ResMut<ClearColor>,
+) {
+    if !settings.is_changed() && !mode.is_changed() {
+        return;
+    }
+    let wanted = Palette::for_settings(&settings);
+    if *palette != *wanted {
+        *palette = wanted.clone();
+    }
+    let background = palette.background_for(*mode);
+    if clear_color.0 != background {
+        clear_color.0 = background;
+    }
+}
+
+/// Follows the [`Modifiers`] of the game being set up, so big mode draws
+/// its doubled blocks.
+fn sync_block_scale(modifiers: Res<Modifiers>, mut config: ResMut<LayoutConfig>) {
+    let block_scale = modifiers.block_scale();
+    if config.block_scale != block_scale {
+        config.block_scale = block_scale;
+    }
+}
+
+/// Refits the layout to the latest window size, [`LayoutConfig`] or board
+/// size so the board, ghost, panels and HUD stay centred and whole when any
+/// of them changes.
+pub fn update_layout(
+    mut resized: EventReader<WindowResized>,
+    board: Res<Board>,
+    config: Res<LayoutConfig>,
+    mut layout: ResMut<BoardLayout>,
+) {
+    let window = resized
+        .read()
+        .last()
+        .map(|event| Vec2::new(event.width, event.height));
+    let resized_board = (board.width(), board.visible_height()) != (layout.columns, layout.rows);
+    if window.is_some() || config.is_changed() || resized_board {
+        *layout = BoardLayout::fit(window.unwrap_or(layout.window), &board, &config);
+    }
+}
+
+/// Centres the camera on the content, tilting it if configured and
+/// zooming out if the content would not fit.
+fn frame_camera(
+    layout: Res<BoardLayout>,
+    mut cameras: Query<(&mut Transform, &mut Projection), With<Camera2d>>,
+) {
+    if !layout.is_changed() {
+        return;
+    }
+    for (mut transform, mut projection) in &mut cameras {
+        *transform = layout.camera_transform();
+        *projection = layout.camera_projection();
+    }
+}
+
+fn block(color: Color, z: f32) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite { color, ..default() },
+        transform: Transform::from_xyz(0.0, 0.0, z),
+        ..default()
+    }
+}
+
+fn spawn_playfield(mut commands: Commands, board: Res<Board>) {
+    for panel in [Panel::Well, Panel::Hold, Panel::Next] {
+        commands.spawn((
+            block(Color::NONE, layers::BACKGROUND),
+            panel,
+            PlayfieldEntity,
+        ));
+    }
+    let rows = (board.visible_height() + BUFFER_PEEK_ROWS).min(board.height());
+    for y in 0..rows as i32 {
+        for x in 0..board.width() as i32 {
+            commands.spawn((
+                block(EMPTY_CELL_COLOR, layers::CELLS),
+                CellSprite { x, y },
+                PlayfieldEntity,
+            ));
+        }
+    }
+    for index in 0..4 {
+        commands.spawn((
+            block(Color::NONE, layers::ACTIVE),
+            ActiveBlock(index),
+            PlayfieldEntity,
+        ));
+        commands.spawn((
+            block(Color::NONE, layers::GHOST),
+            GhostBlock(index),
+            PlayfieldEntity,
+        ));
+        commands.spawn((
+            block(Color::NONE, layers::SHADOW),
+            ShadowBlock(index),
+            PlayfieldEntity,
+        ));
+        commands.spawn((
+            block(Color::NONE, layers::CELLS),
+            HoldBlock(index),
+            PlayfieldEntity,
+        ));
+        commands.spawn((
+            block(Color::NONE, layers::GHOST),
+            HoldPreviewBlock(index),
+            PlayfieldEntity,
+        ));
+        for slot in 0..PieceQueue::PREVIEW_LEN {
+            commands.spawn((
+                block(Color::NONE, layers::CELLS),
+                PreviewBlock { slot, index },
+                PlayfieldEntity,
+            ));
+        }
+    }
+}
+
+fn despawn_playfield(mut commands: Commands, entities: Query<Entity, With<PlayfieldEntity>>) {
+    for entity in &entities {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Places a block sprite of side `size` centred on `center`, leaving `gap`
+/// pixels to its neighbours.
+fn place(sprite: &mut Sprite, transform: &mut Transform, center: Vec2, size: f32, gap: f32) {
+    sprite.custom_size = Some(Vec2::splat((size - gap).max(1.0)));
+    transform.translation.x = center.x;
+    transform.translation.y = center.y;
+}
+
+pub fn draw_panels(
+    layout: Res<BoardLayout>,
+    palette: Res<Palette>,
+    mut panels: Query<(&Panel, &mut Sprite, &mut Transform)>,
+) {
+    for (panel, mut sprite, mut transform) in &mut panels {
+        let (rect, color) = match panel {
+            Panel::Well => (layout.board_rect(), palette.well),
+            Panel::Hold => (layout.hold_panel(), palette.panel),
+            Panel::Next => (layout.next_panel(), palette.panel),
+        };
+        sprite.color = color;
+        sprite.custom_size = Some(rect.size());
+        transform.translation.x = rect.center().x;
+        transform.translation.y = rect.center().y;
+    }
+}
+
+/// Colours each shown cell sprite from the locked board contents. Buffer
+/// rows are faded, with their empty cells left clear so the field keeps its
+/// top edge.
+///
+/// Only runs on a [`BoardChanged`], a new layout or palette, fresh sprites
+/// or the end of a tint, so a settled board costs nothing per frame.
+pub fn draw_board(
+    mut changes: EventReader<BoardChanged>,
+    layout: Res<BoardLayout>,
+    palette: Res<Palette>,
+    board: Res<Board>,
+    mut tinted: ResMut<CellsTinted>,
+    added: Query<(), Added<CellSprite>>,
+    mut cells: Query<(&CellSprite, &mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    let changed = changes.read().count() > 0;
+    if !(changed || layout.is_changed() || palette.is_changed() || tinted.0 || !added.is_empty()) {
+        return;
+    }
+    tinted.0 = false;
+    for (cell, mut sprite, mut transform, mut visibility) in &mut cells {
+        *visibility = if layout.shows_row(cell.y) {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        let locked = board.get(cell.x, cell.y);
+        sprite.color = if cell.y < layout.rows as i32 {
+            locked.map_or(palette.empty_cell, |cell| palette.cell(cell))
+        } else {
+            locked.map_or(Color::NONE, |cell| palette.cell(cell).with_a(BUFFER_ALPHA))
+        };
+        let center = layout.cell_center(cell.x, cell.y);
+        let size = layout.block_size();
+        place(&mut sprite, &mut transform, center, size, palette.block_gap);
+    }
+}
+
+/// Draws up to four blocks at board coordinates, hiding any that fall
+/// outside the visible field.
+fn draw_piece_blocks<'a>(
+    layout: &BoardLayout,
+    gap: f32,
+    cells: Option<([(i32, i32); 4], Color)>,
+    blocks: impl Iterator<
+        Item = (
+            usize,
+            Mut<'a, Sprite>,
+            Mut<'a, Transform>,
+            Mut<'a, Visibility>,
+        ),
+    >,
+) {
+    for (index, mut sprite, mut transform, mut visibility) in blocks {
+        let Some(((x, y), color)) = cells.map(|(cells, color)| (cells[index], color)) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        *visibility = if layout.shows_row(y) {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+        sprite.color = color;
+        place(
+            &mut sprite,
+            &mut transform,
+            layout.cell_center(x, y),
+            layout.block_size(),
+            gap,
+        );
+    }
+}
+
+fn draw_active_piece(
+    layout: Res<BoardLayout>,
+    palette: Res<Palette>,
+    settings: Res<Settings>,
+    active: Res<ActivePiece>,
+    mut blocks: Query<(&ActiveBlock, &mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    let cells = active.0.map(|piece| {
+        let color = palette.active(piece.kind, settings.active_emphasis);
+        (piece.cells(), color)
+    });
+    draw_piece_blocks(
+        &layout,
+        palette.block_gap,
+        cells,
+        blocks.iter_mut().map(|(block, s, t, v)| (block.0, s, t, v)),
+    );
+}
+
+/// The active piece's shadow, [`SHADOW_OFFSET`] away from it, while
+/// [`Settings::piece_shadow`] is on.
+fn draw_piece_shadow(
+    layout: Res<BoardLayout>,
+    palette: Res<Palette>,
+    settings: Res<Settings>,
+    active: Res<ActivePiece>,
+    mut blocks: Query<(&ShadowBlock, &mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    let cells = active
+        .0
+        .filter(|_| settings.effects().shadow)
+        .map(|piece| (piece.cells(), Color::BLACK.with_a(SHADOW_ALPHA)));
+    draw_piece_blocks(
+        &layout,
+        palette.block_gap,
+        cells,
+        blocks.iter_mut().map(|(block, s, t, v)| (block.0, s, t, v)),
+    );
+    let offset = (SHADOW_OFFSET * layout.block_size()).extend(0.0);
+    for (_, _, mut transform, _) in &mut blocks {
+        transform.translation += offset;
+    }
+}
+
+/// How much of the ghost shows with the piece `distance` rows above its
+/// landing: all of it under `near` of `None`, and otherwise none from
+/// `near` rows out, fading in to all of it on landing.
+pub fn ghost_visibility(distance: i32, near: Option<u32>) -> f32 {
+    match near {
+        None => 1.0,
+        Some(rows) => (1.0 - distance as f32 / rows.max(1) as f32).clamp(0.0, 1.0),
+    }
+}
+
+fn draw_ghost(
+    layout: Res<BoardLayout>,
+    palette: Res<Palette>,
+    settings: Res<Settings>,
+    board: Res<Board>,
+    active: Res<ActivePiece>,
+    partner: Res<PartnerPiece>,
+    mut blocks: Query<(&GhostBlock, &mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    let field = SharedField::new(&*board, partner.0.as_ref());
+    let cells = active.0.map(|piece| {
+        let distance = field.drop_distance(&piece);
+        let alpha = GHOST_ALPHA * ghost_visibility(distance, settings.ghost_near);
+        let ghost = piece.moved(0, -distance);
+        (ghost.cells(), palette.piece(piece.kind).with_a(alpha))
+    });
+    draw_piece_blocks(
+        &layout,
+        palette.block_gap,
+        cells,
+        blocks.iter_mut().map(|(block, s, t, v)| (block.0, s, t, v)),
+    );
+}
+
+/// Where the held piece would enter if swapped in now, or `None` when there
+/// is nothing the active piece could be swapped for.
+pub fn hold_preview(
+    hold: &Hold,
+    active: &ActivePiece,
+    board: &Board,
+    system: RotationSystem,
+) -> Option<Tetromino> {
+    let kind = hold.piece.filter(|_| hold.can_hold && active.0.is_some())?;
+    Some(Tetromino::spawn_in(kind, system, board))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_hold_preview(
+    layout: Res<BoardLayout>,
+    palette: Res<Palette>,
+    settings: Res<Settings>,
+    modifiers: Res<Modifiers>,
+    board: Res<Board>,
+    hold: Res<Hold>,
+    active: Res<ActivePiece>,
+    mut blocks: Query<(
+        &HoldPreviewBlock,
+        &mut Sprite,
+        &mut Transform,
+        &mut Visibility,
+    )>,
+) {
+    let cells = hold_preview(&hold, &active, &board, settings.rotation_system)
+        .filter(|_| settings.hold_preview && !modifiers.no_hold)
+        .map(|piece| {
+            let color = palette.piece(piece.kind).with_a(HOLD_PREVIEW_ALPHA);
+            (piece.cells(), color)
+        });
+    draw_piece_blocks(
+        &layout,
+        palette.block_gap,
+        cells,
+        blocks.iter_mut().map(|(block, s, t, v)| (block.0, s, t, v)),
+    );
+}
+
+/// Draws a piece with the given [`preview_offsets`] centred on `center`, or
+/// hides the blocks when there is no piece to show.
+#[allow(clippy::too_many_arguments)]
+fn draw_panel_piece(
+    sprite: &mut Sprite,
+    transform: &mut Transform,
+    visibility: &mut Visibility,
+    piece: Option<([Vec2; 4], Color)>,
+    index: usize,
+    center: Vec2,
+    cell_size: f32,
+    gap: f32,
+) {
+    let Some((offsets, color)) = piece else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Visible;
+    sprite.color = color;
+    let offset = offsets[index] * cell_size;
+    place(sprite, transform, center + offset, cell_size, gap);
+}
+
+fn draw_hold(
+    layout: Res<BoardLayout>,
+    palette: Res<Palette>,
+    settings: Res<Settings>,
+    hold: Res<Hold>,
+    mut blocks: Query<(&HoldBlock, &mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    let piece = hold.piece.map(|kind| {
+        let color = if hold.can_hold {
+            palette.piece(kind)
+        } else {
+            palette.hold_locked
+        };
+        (preview_offsets(kind, settings.rotation_system), color)
+    });
+    for (block, mut sprite, mut transform, mut visibility) in &mut blocks {
+        draw_panel_piece(
+            &mut sprite,
+            &mut transform,
+            &mut visibility,
+            piece,
+            block.0,
+            layout.hold_slot_center(),
+            layout.preview_cell_size(),
+            palette.block_gap,
+        );
+    }
+}
+
+/// Starts the slide when the queue has moved up by one, leaving it alone
+/// for anything else such as a fresh game's queue.
+fn track_preview_slide(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    queue: Res<PieceQueue>,
+    mut slide: ResMut<PreviewSlide>,
+) {
+    slide.timer.tick(time.delta());
+    if !queue.is_changed() {
+        return;
+    }
+    let upcoming: Vec<PieceKind> = queue.peek(PieceQueue::PREVIEW_LEN).collect();
+    let moved_up = slide.upcoming.len() > 1
+        && upcoming.starts_with(&slide.upcoming[1..])
+        && upcoming != slide.upcoming;
+    if moved_up && settings.effects().motion {
+        slide.timer = Timer::from_seconds(PREVIEW_SLIDE_SECS, TimerMode::Once);
+    }
+    slide.upcoming = upcoming;
+}
+
+fn draw_next_preview(
+    layout: Res<BoardLayout>,
+    palette: Res<Palette>,
+    settings: Res<Settings>,
+    queue: Res<PieceQueue>,
+    slide: Res<PreviewSlide>,
+    mut blocks: Query<(&PreviewBlock, &mut Sprite, &mut Transform, &mut Visibility)>,
+) {
+    let upcoming: Vec<PieceKind> = queue.peek(PieceQueue::PREVIEW_LEN).collect();
+    for (block, mut sprite, mut transform, mut visibility) in &mut blocks {
+        let piece = upcoming.get(block.slot).map(|&kind| {
+            (
+                preview_offsets(kind, settings.rotation_system),
+                palette.piece(kind),
+            )
+        });
+        draw_panel_piece(
+            &mut sprite,
+            &mut transform,
+            &mut visibility,
+            piece,
+            block.index,
+            layout.next_slot_center(block.slot) + slide.offset(&layout, block.slot),
+            layout.preview_cell_size(),
+            palette.block_gap,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::render::camera::CameraProjection;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::game::GamePlugin;
+    use crate::input::keyboard::{Action, ActionEvent};
+    use crate::utils::config::MAX_GRAVITY;
+
+    fn resize(app: &mut App, width: f32, height: f32) {
+        app.world.send_event(WindowResized {
+            window: Entity::PLACEHOLDER,
+            width,
+            height,
+        });
+        app.update();
+    }
+
+    #[test]
+    fn test_resize_recenters_board() {
+        let mut app = App::new();
+        app.add_event::<WindowResized>()
+            .init_resource::<Board>()
+            .init_resource::<LayoutConfig>()
+            .init_resource::<BoardLayout>()
+            .add_systems(Update, update_layout);
+
+        resize(&mut app, 720.0, 660.0);
+        let small = *app.world.resource::<BoardLayout>();
+        assert_eq!(small.cell_size, 30.0);
+        assert_eq!(small.origin, Vec2::new(-150.0, -300.0));
+
+        resize(&mut app, 1440.0, 1000.0);
+        let large = *app.world.resource::<BoardLayout>();
+        assert_eq!(large.cell_size, 45.0);
+        assert_eq!(large.origin, Vec2::new(-225.0, -450.0));
+        assert_eq!(large.board_rect().center(), Vec2::ZERO);
+    }
+
+    #[test]
+    fn test_layout_keeps_panels_inside_window() {
+        let window = Vec2::new(500.0, 900.0);
+        let layout = BoardLayout::fit(window, &Board::default(), &LayoutConfig::default());
+        let half = window / 2.0;
+        for rect in [
+            layout.board_rect(),
+            layout.hold_panel(),
+            layout.next_panel(),
+        ] {
+            assert!(rect.min.cmpge(-half).all() && rect.max.cmple(half).all());
+        }
+        assert_eq!(layout.cell_size, layout.cell_size.floor());
+    }
+
+    #[test]
+    fn test_doubling_cell_size_doubles_board_extent() {
+        let window = Vec2::new(720.0, 720.0);
+        let board = Board::default();
+        let fit = |cell_size| {
+            let config = LayoutConfig {
+                cell_size: Some(cell_size),
+                ..default()
+            };
+            BoardLayout::fit(window, &board, &config)
+        };
+        let small = fit(16.0);
+        let large = fit(32.0);
+
+        assert_eq!(large.board_rect().size(), small.board_rect().size() * 2.0);
+        assert_eq!(large.board_rect().center(), small.board_rect().center());
+        for (x, y) in [(0, 0), (3, 7), (9, 19)] {
+            let offset = |layout: &BoardLayout| layout.cell_center(x, y) - layout.origin;
+            assert_eq!(offset(&large), offset(&small) * 2.0);
+        }
+        for layout in [small, large] {
+            assert_eq!(layout.origin, layout.origin.floor());
+        }
+    }
+
+    #[test]
+    fn test_letterboxed_cells_stay_square_and_stretched_ones_fill_the_window() {
+        let fit = |window, framing| {
+            let config = LayoutConfig {
+                framing,
+                ..default()
+            };
+            BoardLayout::fit(window, &Board::default(), &config)
+        };
+        for window in [
+            Vec2::new(1920.0, 600.0),
+            Vec2::new(500.0, 900.0),
+            Vec2::new(700.0, 700.0),
+        ] {
+            let letterboxed = fit(window, Framing::Letterbox);
+            let block = letterboxed.block_on_screen();
+            assert_eq!(block.x, block.y, "{window}");
+
+            let stretched = fit(window, Framing::Stretch);
+            let filled = stretched.content_rect().size() / stretched.world_per_pixel();
+            assert!((filled - window).abs().max_element() < 1e-3, "{window}");
+            let corner = stretched.screen_to_world(Vec2::ZERO);
+            let expected = Vec2::new(
+                stretched.content_rect().min.x,
+                stretched.content_rect().max.y,
+            );
+            assert!((corner - expected).abs().max_element() < 1e-3, "{window}");
+        }
+        let wide = fit(Vec2::new(1920.0, 600.0), Framing::Stretch).block_on_screen();
+        assert!(wide.x > wide.y);
+        let tall = fit(Vec2::new(500.0, 900.0), Framing::Stretch).block_on_screen();
+        assert!(tall.x < tall.y);
+    }
+
+    #[test]
+    fn test_camera_scale_keeps_board_in_small_window() {
+        let window = Vec2::new(320.0, 240.0);
+        let config = LayoutConfig {
+            cell_size: Some(32.0),
+            ..default()
+        };
+        let layout = BoardLayout::fit(window, &Board::default(), &config);
+        let scale = layout.camera_scale();
+        assert!(scale > 1.0);
+
+        let center = layout.content_rect().center();
+        let view = Rect::from_center_size(center, window * scale);
+        for rect in [
+            layout.board_rect(),
+            layout.hold_panel(),
+            layout.next_panel(),
+        ] {
+            assert!(view.contains(rect.min) && view.contains(rect.max));
+        }
+
+        let fitted = BoardLayout::fit(window, &Board::default(), &LayoutConfig::default());
+        assert_eq!(fitted.camera_scale(), 1.0);
+    }
+
+    #[test]
+    fn test_mirrored_hud_puts_hold_panel_right_of_board() {
+        let window = Vec2::new(1280.0, 720.0);
+        let config = LayoutConfig {
+            hud: HudLayout {
+                mirror: true,
+                ..default()
+            },
+            ..default()
+        };
+        let layout = BoardLayout::fit(window, &Board::default(), &config);
+        let board = layout.board_rect();
+        assert!(layout.hold_panel().min.x > board.max.x);
+        assert!(layout.hold_slot_center().x > board.max.x);
+        assert!(layout.next_panel().max.x < board.min.x);
+
+        let standard = BoardLayout::fit(window, &Board::default(), &LayoutConfig::default());
+        assert_eq!(layout.content_rect(), standard.content_rect());
+    }
+
+    #[test]
+    fn test_compact_hud_lines_hold_and_next_up_in_one_strip_above_the_board() {
+        let window = Vec2::new(400.0, 800.0);
+        let config = LayoutConfig {
+            hud: HudLayout {
+                compact: true,
+                ..default()
+            },
+            ..default()
+        };
+        let layout = BoardLayout::fit(window, &Board::default(), &config);
+        let board = layout.board_rect();
+        let (hold, next) = (layout.hold_panel(), layout.next_panel());
+        assert!(hold.min.y > board.max.y);
+        assert_eq!((hold.min.y, hold.max.y), (next.min.y, next.max.y));
+        assert_eq!(hold.min.x, board.min.x);
+        assert_eq!(hold.max.x, next.min.x);
+        assert_eq!(next.max.x, board.max.x);
+
+        // The held piece comes first, then the queue in order, left to
+        // right inside the strip.
+        let mut slots = vec![layout.hold_slot_center()];
+        slots.extend((0..PieceQueue::PREVIEW_LEN).map(|slot| layout.next_slot_center(slot)));
+        assert!(slots.windows(2).all(|pair| pair[0].x < pair[1].x));
+        assert!(hold.contains(slots[0]));
+        assert!(slots[1..].iter().all(|&slot| next.contains(slot)));
+        let reach = 2.0 * layout.preview_cell_size();
+        assert!(slots[1..].iter().all(|slot| slot.x + reach <= next.max.x));
+
+        assert!(layout.stats_anchor().y < board.min.y);
+        let content = layout.content_rect();
+        assert!(content.contains(layout.stats_anchor()));
+        let standard = BoardLayout::fit(window, &Board::default(), &LayoutConfig::default());
+        assert!(layout.cell_size > standard.cell_size);
+    }
+
+    #[test]
+    fn test_horizontal_next_queue_lays_its_slots_along_the_x_axis() {
+        let window = Vec2::new(1920.0, 600.0);
+        let config = LayoutConfig {
+            hud: HudLayout {
+                next_orientation: NextOrientation::Horizontal,
+                ..default()
+            },
+            ..default()
+        };
+        let layout = BoardLayout::fit(window, &Board::default(), &config);
+        let board = layout.board_rect();
+        let next = layout.next_panel();
+        assert!(next.min.x > board.max.x);
+        assert_eq!(next.max.y, board.max.y);
+
+        // Slots share a row under the title, a panel's width apart, each
+        // with room for an I either side of its centre.
+        let slots: Vec<Vec2> = (0..PieceQueue::PREVIEW_LEN)
+            .map(|slot| layout.next_slot_center(slot))
+            .collect();
+        assert!(slots.iter().all(|slot| slot.y == slots[0].y));
+        let step = PANEL_COLUMNS * layout.cell_size;
+        assert!(slots.windows(2).all(|pair| pair[1].x - pair[0].x == step));
+        let reach = 2.0 * layout.preview_cell_size();
+        assert!(slots[0].x - reach >= next.min.x);
+        assert!(slots[slots.len() - 1].x + reach <= next.max.x);
+        assert!(slots.iter().all(|&slot| next.contains(slot)));
+        assert!(layout.content_rect().contains(next.max));
+
+        assert!(layout.hold_panel().max.x < board.min.x);
+    }
+
+    #[test]
+    fn test_big_mode_line_clear_empties_its_doubled_rows() {
+        let modifiers = Modifiers {
+            big: true,
+            ..default()
+        };
+        let mut app = App::new();
+        app.add_event::<WindowResized>()
+            .add_event::<BoardChanged>()
+            .insert_resource(modifiers)
+            .insert_resource(modifiers.board())
+            .init_resource::<Palette>()
+            .init_resource::<LayoutConfig>()
+            .init_resource::<BoardLayout>()
+            .init_resource::<CellsTinted>()
+            .add_systems(
+                Update,
+                (
+                    sync_block_scale,
+                    update_layout,
+                    announce_board_changes,
+                    draw_board,
+                )
+                    .chain(),
+            );
+        app.world.run_system_once(spawn_playfield);
+        let board = app.world.resource::<Board>().clone();
+        for x in 0..board.width() as i32 {
+            app.world.resource_mut::<Board>().set(x, 0, Some(0));
+        }
+        app.update();
+
+        // The sprite covering each on-screen cell of the bottom two rows.
+        let layout = *app.world.resource::<BoardLayout>();
+        assert_eq!(layout.block_scale, 2);
+        let well = layout.board_rect();
+        let covered = |app: &mut App| -> Vec<Color> {
+            let mut sprites = app.world.query::<(&Sprite, &Transform)>();
+            let mut colors = Vec::new();
+            for row in 0..2 {
+                for column in 0..(well.width() / layout.cell_size) as i32 {
+                    let point =
+                        well.min + (Vec2::new(column as f32, row as f32) + 0.5) * layout.cell_size;
+                    let (sprite, _) = sprites
+                        .iter(&app.world)
+                        .find(|(sprite, transform)| {
+                            sprite.custom_size.is_some_and(|size| {
+                                Rect::from_center_size(transform.translation.truncate(), size)
+                                    .contains(point)
+                            }) && transform.translation.z == layers::CELLS
+                        })
+                        .expect("every on-screen cell is covered by a block");
+                    colors.push(sprite.color);
+                }
+            }
+            colors
+        };
+        let filled = covered(&mut app);
+        assert_eq!(filled.len(), 20);
+        assert!(filled.iter().all(|&color| color == COLORS[0]));
+
+        assert_eq!(app.world.resource_mut::<Board>().clear_lines(), 1);
+        app.update();
+        assert!(covered(&mut app)
+            .iter()
+            .all(|&color| color == EMPTY_CELL_COLOR));
+    }
+
+    #[test]
+    fn test_high_contrast_switches_the_palette() {
+        let mut app = App::new();
+        app.insert_resource(ClearColor(BACKGROUND_COLOR))
+            .init_resource::<Settings>()
+            .init_resource::<GameMode>()
+            .init_resource::<Palette>()
+            .init_resource::<Board>()
+            .init_resource::<BoardLayout>()
+            .init_resource::<CellsTinted>()
+            .add_event::<BoardChanged>()
+            .add_systems(Update, (apply_palette, draw_board).chain());
+        app.world
+            .resource_mut::<Board>()
+            .set(0, 0, Some(PieceKind::T.index()));
+        let filled = app
+            .world
+            .spawn((
+                CellSprite { x: 0, y: 0 },
+                Sprite::default(),
+                Transform::default(),
+                Visibility::default(),
+            ))
+            .id();
+        let empty = app
+            .world
+            .spawn((
+                CellSprite { x: 1, y: 0 },
+                Sprite::default(),
+                Transform::default(),
+                Visibility::default(),
+            ))
+            .id();
+        app.update();
+        assert_eq!(app.world.resource::<ClearColor>().0, BACKGROUND_COLOR);
+        assert_eq!(
+            app.world.get::<Sprite>(filled).unwrap().color,
+            COLORS[PieceKind::T.index()]
+        );
+
+        app.world.resource_mut::<Settings>().high_contrast = true;
+        app.update();
+        let high_contrast = Palette::HIGH_CONTRAST;
+        assert_eq!(*app.world.resource::<Palette>(), high_contrast);
+        assert_eq!(app.world.resource::<ClearColor>().0, Color::BLACK);
+        assert_eq!(
+            app.world.get::<Sprite>(filled).unwrap().color,
+            high_contrast.piece(PieceKind::T)
+        );
+        assert_eq!(
+            app.world.get::<Sprite>(empty).unwrap().color,
+            high_contrast.empty_cell
+        );
+        assert_ne!(
+            high_contrast.piece(PieceKind::T),
+            COLORS[PieceKind::T.index()]
+        );
+        let size = app.world.get::<Sprite>(empty).unwrap().custom_size.unwrap();
+        let layout = app.world.resource::<BoardLayout>();
+        assert_eq!(
+            size,
+            Vec2::splat(layout.block_size() - high_contrast.block_gap)
+        );
+    }
+
+    #[test]
+    fn test_switching_modes_tints_the_background() {
+        let mut app = App::new();
+        app.insert_resource(ClearColor(BACKGROUND_COLOR))
+            .init_resource::<Settings>()
+            .init_resource::<GameMode>()
+            .init_resource::<Palette>()
+            .add_systems(Update, apply_palette);
+        let background = |app: &mut App, mode| {
+            app.insert_resource(mode);
+            app.update();
+            app.world.resource::<ClearColor>().0
+        };
+        let marathon = background(&mut app, GameMode::Marathon);
+        assert_eq!(marathon, BACKGROUND_COLOR);
+        let tints: Vec<_> = GameMode::ALL
+            .into_iter()
+            .map(|mode| background(&mut app, mode))
+            .collect();
+        for (i, tint) in tints.iter().enumerate() {
+            assert!(tints[..i].iter().all(|other| other != tint));
+            // Still dark enough to keep the blocks readable.
+            assert!(tint.r() + tint.g() + tint.b() < 0.3);
+        }
+
+        app.world.resource_mut::<Settings>().high_contrast = true;
+        for mode in GameMode::ALL {
+            assert_eq!(background(&mut app, mode), Color::BLACK);
+        }
+    }
+
+    #[test]
+    fn test_previews_slide_up_to_the_new_queue_order() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                50,
+            )))
+            .insert_resource(PieceQueue::with_seed(42, default()))
+            .init_resource::<Settings>()
+            .init_resource::<Palette>()
+            .init_resource::<BoardLayout>()
+            .init_resource::<PreviewSlide>()
+            .add_systems(Update, (track_preview_slide, draw_next_preview).chain());
+        let blocks: Vec<Entity> = (0..PieceQueue::PREVIEW_LEN)
+            .map(|slot| {
+                app.world
+                    .spawn((
+                        PreviewBlock { slot, index: 0 },
+                        Sprite::default(),
+                        Transform::default(),
+                        Visibility::default(),
+                    ))
+                    .id()
+            })
+            .collect();
+        app.update();
+        app.world.resource_mut::<PieceQueue>().next_piece();
+        app.update();
+
+        let queue = app.world.resource::<PieceQueue>();
+        let palette = app.world.resource::<Palette>();
+        let layout = *app.world.resource::<BoardLayout>();
+        let upcoming: Vec<PieceKind> = queue.peek(PieceQueue::PREVIEW_LEN).collect();
+        let targets: Vec<Vec2> = upcoming
+            .iter()
+            .enumerate()
+            .map(|(slot, &kind)| {
+                let offset = preview_offsets(kind, RotationSystem::Srs)[0] * layout.cell_size;
+                layout.next_slot_center(slot) + offset
+            })
+            .collect();
+        for (slot, &block) in blocks.iter().enumerate() {
+            let sprite = app.world.get::<Sprite>(block).unwrap();
+            assert_eq!(sprite.color, palette.piece(upcoming[slot]));
+            let y = app.world.get::<Transform>(block).unwrap().translation.y;
+            assert!(y < targets[slot].y, "slot {slot} starts below its target");
+        }
+
+        for _ in 0..4 {
+            app.update();
+        }
+        for (slot, &block) in blocks.iter().enumerate() {
+            let translation = app.world.get::<Transform>(block).unwrap().translation;
+            assert_eq!(translation.truncate(), targets[slot]);
+        }
+    }
+
+    #[test]
+    fn test_tilt_leaves_board_coordinates_unchanged() {
+        let mut app = App::new();
+        app.add_event::<WindowResized>()
+            .init_resource::<Board>()
+            .init_resource::<LayoutConfig>()
+            .init_resource::<BoardLayout>()
+            .add_systems(Update, update_layout);
+        let mut board = Board::default();
+        board.set(4, 0, Some(2));
+        app.world.insert_resource(board.clone());
+        let flat = *app.world.resource::<BoardLayout>();
+
+        app.world.resource_mut::<LayoutConfig>().tilt = true;
+        app.update();
+        let tilted = *app.world.resource::<BoardLayout>();
+        assert!(tilted.tilt > 0.0);
+        assert_eq!(*app.world.resource::<Board>(), board);
+        for y in 0..board.visible_height() as i32 {
+            for x in 0..board.width() as i32 {
+                assert_eq!(tilted.cell_center(x, y), flat.cell_center(x, y));
+            }
+        }
+
+        let camera = tilted.camera_transform();
+        let center = tilted.content_rect().center().extend(0.0);
+        let distance = camera.translation.distance(center);
+        let target = camera.translation + camera.forward() * distance;
+        assert!(target.distance(center) < 1e-3);
+        assert_ne!(camera.rotation, flat.camera_transform().rotation);
+    }
+
+    #[test]
+    fn test_tilt_narrows_the_far_edge_of_the_board() {
+        let window = Vec2::new(1280.0, 720.0);
+        let config = LayoutConfig {
+            tilt: true,
+            ..default()
+        };
+        let layout = BoardLayout::fit(window, &Board::default(), &config);
+        let Projection::Perspective(mut projection) = layout.camera_projection() else {
+            panic!("the tilted view should use a perspective projection");
+        };
+        projection.update(window.x, window.y);
+        let view = projection.get_projection_matrix()
+            * layout.camera_transform().compute_matrix().inverse();
+        let ndc = |corner: Vec2| view.project_point3(corner.extend(0.0));
+
+        let board = layout.board_rect();
+        let bottom =
+            ndc(Vec2::new(board.max.x, board.min.y)).x - ndc(Vec2::new(board.min.x, board.min.y)).x;
+        let top =
+            ndc(Vec2::new(board.max.x, board.max.y)).x - ndc(Vec2::new(board.min.x, board.max.y)).x;
+        assert!(top < bottom * 0.95);
+
+        let content = layout.content_rect();
+        for corner in [
+            content.min,
+            content.max,
+            Vec2::new(content.min.x, content.max.y),
+            Vec2::new(content.max.x, content.min.y),
+        ] {
+            let point = ndc(corner);
+            assert!(
+                point.x.abs() <= 1.0 && point.y.abs() <= 1.0,
+                "{corner} at {point}"
+            );
+        }
+
+        let flat = BoardLayout::fit(window, &Board::default(), &LayoutConfig::default());
+        assert!(matches!(
+            flat.camera_projection(),
+            Projection::Orthographic(_)
+        ));
+    }
+
+    #[test]
+    fn test_hold_preview_shows_the_held_kind_at_spawn() {
+        let board = Board::default();
+        let active = ActivePiece(Some(Tetromino::spawn(PieceKind::T, &board).moved(0, -6)));
+        let mut hold = Hold {
+            piece: Some(PieceKind::L),
+            can_hold: true,
+        };
+        for system in RotationSystem::ALL {
+            let preview = hold_preview(&hold, &active, &board, system).unwrap();
+            assert_eq!(Some(preview.kind), hold.piece);
+            assert_eq!(preview, Tetromino::spawn_in(PieceKind::L, system, &board));
+        }
+        hold.can_hold = false;
+        assert!(hold_preview(&hold, &active, &board, default()).is_none());
+        let empty = Hold::default();
+        assert!(hold_preview(&empty, &active, &board, default()).is_none());
+    }
+
+    #[test]
+    fn test_shown_buffer_rows_are_drawn_and_framed() {
+        let window = Vec2::new(720.0, 720.0);
+        let board = Board::default();
+        let top = board.visible_height() as i32;
+        let config = LayoutConfig {
+            show_buffer: true,
+            ..default()
+        };
+        let shown = BoardLayout::fit(window, &board, &config);
+        let hidden = BoardLayout::fit(window, &board, &LayoutConfig::default());
+        for y in [top, top + 1] {
+            assert!(shown.shows_row(y) && !hidden.shows_row(y));
+            let center = shown.cell_center(0, y);
+            assert!(shown.field_rect().contains(center));
+            assert!(shown.content_rect().contains(center));
+            assert!(!hidden.field_rect().contains(hidden.cell_center(0, y)));
+        }
+        assert!(!shown.shows_row(top + 2));
+
+        let mut app = App::new();
+        app.insert_resource(board)
+            .insert_resource(shown)
+            .init_resource::<Palette>()
+            .init_resource::<CellsTinted>()
+            .add_event::<BoardChanged>();
+        app.world.run_system_once(spawn_playfield);
+        app.world.run_system_once(draw_board);
+        let mut cells = app.world.query::<(&CellSprite, &Visibility)>();
+        let visible_rows: Vec<i32> = cells
+            .iter(&app.world)
+            .filter(|(_, visibility)| **visibility != Visibility::Hidden)
+            .map(|(cell, _)| cell.y)
+            .collect();
+        assert!(visible_rows.contains(&top) && visible_rows.contains(&(top + 1)));
+
+        app.insert_resource(hidden);
+        app.world.run_system_once(draw_board);
+        assert!(cells
+            .iter(&app.world)
+            .all(|(cell, visibility)| (*visibility == Visibility::Hidden) == (cell.y >= top)));
+    }
+
+    #[test]
+    fn test_active_piece_is_lighter_than_its_locked_kind() {
+        let mut board = Board::default();
+        let piece = board.landing_position(&Tetromino::spawn(PieceKind::T, &board));
+        board.set(0, 0, Some(PieceKind::T.index()));
+        let mut app = App::new();
+        app.insert_resource(board)
+            .insert_resource(ActivePiece(Some(piece)))
+            .init_resource::<BoardLayout>()
+            .init_resource::<Palette>()
+            .init_resource::<Settings>()
+            .init_resource::<CellsTinted>()
+            .add_event::<BoardChanged>();
+        app.world.run_system_once(spawn_playfield);
+        let colors = |app: &mut App| {
+            app.world.run_system_once(draw_board);
+            app.world.run_system_once(draw_active_piece);
+            let locked = app
+                .world
+                .query::<(&CellSprite, &Sprite)>()
+                .iter(&app.world)
+                .find(|(cell, _)| (cell.x, cell.y) == (0, 0))
+                .map(|(_, sprite)| sprite.color)
+                .unwrap();
+            let active = app
+                .world
+                .query::<(&ActiveBlock, &Sprite)>()
+                .iter(&app.world)
+                .next()
+                .map(|(_, sprite)| sprite.color)
+                .unwrap();
+            (locked, active)
+        };
+        let brightness = |color: Color| color.r() + color.g() + color.b();
+
+        let (locked, active) = colors(&mut app);
+        assert_ne!(active, locked);
+        assert!(brightness(active) > brightness(locked));
+
+        app.world.resource_mut::<Settings>().active_emphasis = 0.0;
+        let (locked, active) = colors(&mut app);
+        assert_eq!(active, locked);
+    }
+
+    #[test]
+    fn test_ghost_shows_only_near_landing_when_configured() {
+        let board = Board::default();
+        let spawned = Tetromino::spawn(PieceKind::T, &board);
+        let mut app = App::new();
+        app.insert_resource(board.clone())
+            .insert_resource(ActivePiece(Some(spawned)))
+            .init_resource::<PartnerPiece>()
+            .init_resource::<BoardLayout>()
+            .init_resource::<Palette>()
+            .insert_resource(Settings {
+                ghost_near: Some(GHOST_NEAR_ROWS),
+                ..Settings::default()
+            });
+        app.world.run_system_once(spawn_playfield);
+        let ghost_alpha = |app: &mut App| {
+            app.world.run_system_once(draw_ghost);
+            let alphas: Vec<f32> = app
+                .world
+                .query::<(&GhostBlock, &Sprite)>()
+                .iter(&app.world)
+                .map(|(_, sprite)| sprite.color.a())
+                .collect();
+            assert!(alphas.windows(2).all(|pair| pair[0] == pair[1]));
+            alphas[0]
+        };
+
+        assert!(board.drop_distance(&spawned) > GHOST_NEAR_ROWS as i32);
+        assert_eq!(ghost_alpha(&mut app), 0.0);
+        let landing = board.landing_position(&spawned);
+        app.insert_resource(ActivePiece(Some(landing.moved(0, 2))));
+        assert_eq!(ghost_alpha(&mut app), GHOST_ALPHA / 2.0);
+        app.insert_resource(ActivePiece(Some(landing)));
+        assert_eq!(ghost_alpha(&mut app), GHOST_ALPHA);
+
+        app.world.resource_mut::<Settings>().ghost_near = None;
+        app.insert_resource(ActivePiece(Some(spawned)));
+        assert_eq!(ghost_alpha(&mut app), GHOST_ALPHA);
+    }
+
+    #[test]
+    fn test_shadow_follows_the_piece_at_its_offset() {
+        let board = Board::default();
+        let piece = Tetromino::spawn(PieceKind::S, &board).moved(0, -5);
+        let mut app = App::new();
+        app.insert_resource(board)
+            .insert_resource(ActivePiece(Some(piece)))
+            .init_resource::<BoardLayout>()
+            .init_resource::<Palette>()
+            .insert_resource(Settings {
+                piece_shadow: true,
+                ..Settings::default()
+            });
+        app.world.run_system_once(spawn_playfield);
+        let shadow = |app: &mut App| -> Vec<(Vec3, Visibility)> {
+            app.world.run_system_once(draw_piece_shadow);
+            let mut blocks: Vec<_> = app
+                .world
+                .query::<(&ShadowBlock, &Transform, &Visibility)>()
+                .iter(&app.world)
+                .map(|(block, transform, visibility)| (block.0, transform.translation, *visibility))
+                .collect();
+            blocks.sort_by_key(|&(index, _, _)| index);
+            blocks
+                .into_iter()
+                .map(|(_, at, visibility)| (at, visibility))
+                .collect()
+        };
+        let expected = |app: &App, piece: Tetromino| -> Vec<(Vec3, Visibility)> {
+            let layout = app.world.resource::<BoardLayout>();
+            piece
+                .cells()
+                .iter()
+                .map(|&(x, y)| {
+                    let at = layout.cell_center(x, y) + SHADOW_OFFSET * layout.block_size();
+                    (at.extend(layers::SHADOW), Visibility::Visible)
+                })
+                .collect()
+        };
+
+        assert_eq!(shadow(&mut app), expected(&app, piece));
+        let turned = piece.moved(2, -1).rotated(true);
+        app.world.resource_mut::<ActivePiece>().0 = Some(turned);
+        assert_eq!(shadow(&mut app), expected(&app, turned));
+
+        app.world.resource_mut::<Settings>().piece_shadow = false;
+        assert!(shadow(&mut app)
+            .iter()
+            .all(|&(_, visibility)| visibility == Visibility::Hidden));
+    }
+
+    #[test]
+    fn test_at_20g_a_piece_is_first_drawn_where_it_lands() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+            .init_resource::<BoardLayout>()
+            .init_resource::<Palette>();
+        app.world.resource_mut::<Settings>().custom_gravity = Some(MAX_GRAVITY);
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+        app.world.run_system_once(spawn_playfield);
+
+        let drawn = |app: &mut App| -> (Vec<Vec2>, Vec<Vec2>) {
+            app.world.run_system_once(draw_active_piece);
+            app.world.run_system_once(draw_ghost);
+            let mut active: Vec<_> = app
+                .world
+                .query::<(&ActiveBlock, &Transform)>()
+                .iter(&app.world)
+                .map(|(block, transform)| (block.0, transform.translation.truncate()))
+                .collect();
+            let mut ghost: Vec<_> = app
+                .world
+                .query::<(&GhostBlock, &Transform)>()
+                .iter(&app.world)
+                .map(|(block, transform)| (block.0, transform.translation.truncate()))
+                .collect();
+            active.sort_by_key(|&(index, _)| index);
+            ghost.sort_by_key(|&(index, _)| index);
+            let positions = |blocks: Vec<(usize, Vec2)>| blocks.into_iter().map(|(_, at)| at);
+            (positions(active).collect(), positions(ghost).collect())
+        };
+
+        for _ in 0..3 {
+            // The first tick the piece is in play is the first frame it's
+            // drawn in, and it's already on the floor of the stack.
+            while app.world.resource::<ActivePiece>().0.is_none() {
+                app.world.run_schedule(FixedUpdate);
+            }
+            let piece = app.world.resource::<ActivePiece>().0.unwrap();
+            let landing = app.world.resource::<Board>().landing_position(&piece);
+            assert_eq!(piece, landing);
+            let layout = *app.world.resource::<BoardLayout>();
+            let expected: Vec<Vec2> = landing
+                .cells()
+                .iter()
+                .map(|&(x, y)| layout.cell_center(x, y))
+                .collect();
+            let (active, ghost) = drawn(&mut app);
+            assert_eq!(active, expected);
+            assert_eq!(ghost, expected);
+
+            app.world.send_event(ActionEvent::press(Action::HardDrop));
+            app.world.send_event(ActionEvent::release(Action::HardDrop));
+            app.world.run_schedule(FixedUpdate);
+        }
+    }
+}
@@ -0,0 +1,400 @@
+//! Recording and playback of games.
+//!
+//! The simulation is deterministic, so a game is fully described by its
+//! seed, mode, modifiers, gravity, top-out, queue, rotation and timing rules
+//! plus the actions held on each tick. A [`Replay`] stores only those, and playback
+//! feeds the held actions back into the normal gameplay systems in place
+//! of the keyboard.
+//!
+//! Playback keeps a [`TickState`] snapshot every [`SNAPSHOT_INTERVAL`]
+//! placements, so [`seek`] can go back by restoring the nearest one and
+//! replaying the inputs from there.
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::scoring::ScoringRules;
+use crate::components::tetromino::{PieceQueue, QueueRules, RotationSystem};
+use crate::game::{AppState, GameMode, GameplayEvent, Modifiers, TickSet, TickState};
+use crate::input::keyboard::{apply_action_events, send_held, ActionEvent, ActionState};
+use crate::utils::config::{InputConfig, LockReset, Settings, TopOutRule};
+
+/// A recorded game.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub mode: GameMode,
+    #[serde(default)]
+    pub modifiers: Modifiers,
+    pub input: InputConfig,
+    pub instant_line_clear: bool,
+    /// [`Settings::custom_gravity`] during the game.
+    #[serde(default)]
+    pub custom_gravity: Option<f32>,
+    /// [`Settings::top_out`] during the game.
+    #[serde(default)]
+    pub top_out: TopOutRule,
+    /// [`Settings::spawn_shift_up`] during the game.
+    #[serde(default)]
+    pub spawn_shift_up: bool,
+    /// [`Settings::lock_reset`] during the game.
+    #[serde(default)]
+    pub lock_reset: LockReset,
+    /// [`Settings::scoring`] during the game.
+    #[serde(default)]
+    pub scoring: ScoringRules,
+    /// [`Settings::soft_drop_cap`] during the game. Recordings from before
+    /// the cap existed played without one.
+    #[serde(default)]
+    pub soft_drop_cap: Option<u32>,
+    /// [`Settings::freeplay`] during the game.
+    #[serde(default)]
+    pub freeplay: bool,
+    /// [`Settings::hard_drop_grace`] during the game.
+    #[serde(default)]
+    pub hard_drop_grace: bool,
+    /// [`Settings::queue`] during the game.
+    #[serde(default)]
+    pub queue: QueueRules,
+    /// [`Settings::rotation_system`] during the game.
+    #[serde(default)]
+    pub rotation_system: RotationSystem,
+    /// Ticks recorded.
+    pub length: u32,
+    /// `(tick, held)` for every tick on which the held actions changed, in
+    /// the bit layout of [`ActionState::held_bits`].
+    pub inputs: Vec<(u32, u16)>,
+}
+
+impl Replay {
+    /// An empty recording of a game played under the given rules.
+    pub fn new(
+        seed: u64,
+        mode: GameMode,
+        modifiers: Modifiers,
+        input: &InputConfig,
+        settings: &Settings,
+    ) -> Self {
+        Self {
+            seed,
+            mode,
+            modifiers,
+            input: input.clone(),
+            instant_line_clear: settings.instant_line_clear,
+            custom_gravity: settings.custom_gravity,
+            top_out: settings.top_out,
+            spawn_shift_up: settings.spawn_shift_up,
+            lock_reset: settings.lock_reset,
+            scoring: settings.scoring,
+            soft_drop_cap: settings.soft_drop_cap,
+            freeplay: settings.freeplay,
+            hard_drop_grace: settings.hard_drop_grace,
+            queue: settings.queue,
+            rotation_system: settings.rotation_system,
+            length: 0,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Appends one tick on which `held` was held.
+    pub fn record(&mut self, held: u16) {
+        if self.inputs.last().map_or(0, |&(_, last)| last) != held {
+            self.inputs.push((self.length, held));
+        }
+        self.length += 1;
+    }
+
+    /// The actions held on `tick`.
+    pub fn held_at(&self, tick: u32) -> u16 {
+        let changes = self.inputs.partition_point(|&(at, _)| at <= tick);
+        changes
+            .checked_sub(1)
+            .map_or(0, |index| self.inputs[index].1)
+    }
+
+    /// Applies the recorded mode, modifiers, gravity, top-out, lock reset,
+    /// scoring, soft-drop cap, freeplay, hard-drop grace, queue, rotation and timing rules,
+    /// returning the ones they replaced as an empty replay that puts them
+    /// back.
+    pub fn apply_rules(
+        &self,
+        mode: &mut GameMode,
+        modifiers: &mut Modifiers,
+        input: &mut InputConfig,
+        settings: &mut Settings,
+    ) -> Replay {
+        let previous = Replay::new(self.seed, *mode, *modifiers, input, settings);
+        *mode = self.mode;
+        *modifiers = self.modifiers;
+        *input = self.input.clone();
+        settings.instant_line_clear = self.instant_line_clear;
+        settings.custom_gravity = self.custom_gravity;
+        settings.top_out = self.top_out;
+        settings.spawn_shift_up = self.spawn_shift_up;
+        settings.lock_reset = self.lock_reset;
+        settings.scoring = self.scoring;
+        settings.soft_drop_cap = self.soft_drop_cap;
+        settings.freeplay = self.freeplay;
+        settings.hard_drop_grace = self.hard_drop_grace;
+        settings.queue = self.queue;
+        settings.rotation_system = self.rotation_system;
+        previous
+    }
+
+    /// The piece queue the game dealt from.
+    pub fn piece_queue(&self) -> PieceQueue {
+        PieceQueue::with_seed(self.seed, self.queue)
+    }
+
+    pub fn to_ron(&self) -> String {
+        ron::to_string(self).expect("replays always serialize")
+    }
+
+    pub fn from_ron(source: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(source)
+    }
+}
+
+/// The replay of the game being played, started afresh with each game.
+#[derive(Resource, Debug, Clone)]
+pub struct ReplayRecorder(pub Replay);
+
+/// Placements between the snapshots taken during playback.
+pub const SNAPSHOT_INTERVAL: u32 = 10;
+
+/// The game state at the start of a tick during playback.
+#[derive(Debug, Clone)]
+struct Snapshot {
+    tick: u32,
+    placements: u32,
+    state: TickState,
+}
+
+/// A replay being played back.
+#[derive(Resource, Debug, Clone)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    /// The next tick to play.
+    pub tick: u32,
+    /// Pieces locked so far.
+    pub placements: u32,
+    /// Holds the game still; set once the replay runs out.
+    pub paused: bool,
+    /// Playback rate, 1.0 being real time.
+    pub speed: f32,
+    snapshots: Vec<Snapshot>,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            tick: 0,
+            placements: 0,
+            paused: false,
+            speed: 1.0,
+            snapshots: Vec::new(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tick >= self.replay.length
+    }
+}
+
+/// The resources a replay's rules overwrite.
+#[derive(SystemParam)]
+pub struct ReplayRules<'w> {
+    mode: ResMut<'w, GameMode>,
+    modifiers: ResMut<'w, Modifiers>,
+    input: ResMut<'w, InputConfig>,
+    settings: ResMut<'w, Settings>,
+}
+
+impl ReplayRules<'_> {
+    /// Applies `replay`'s rules, returning the replaced ones as
+    /// [`Replay::apply_rules`] does.
+    pub fn apply(&mut self, replay: &Replay) -> Replay {
+        replay.apply_rules(
+            &mut self.mode,
+            &mut self.modifiers,
+            &mut self.input,
+            &mut self.settings,
+        )
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+}
+
+fn playback_running(playback: Option<Res<ReplayPlayback>>) -> bool {
+    playback.is_none_or(|playback| !playback.paused)
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            FixedUpdate,
+            (TickSet::Input, TickSet::Simulate).run_if(playback_running),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                record_input.after(apply_action_events).run_if(
+                    in_state(AppState::Playing).and_then(resource_exists::<ReplayRecorder>()),
+                ),
+                play_input
+                    .before(apply_action_events)
+                    .run_if(resource_exists::<ReplayPlayback>()),
+            )
+                .in_set(TickSet::Input),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                take_snapshots.before(TickSet::Input),
+                count_placements.after(TickSet::Simulate),
+            )
+                .run_if(resource_exists::<ReplayPlayback>()),
+        );
+    }
+}
+
+fn record_input(actions: Res<ActionState>, mut recorder: ResMut<ReplayRecorder>) {
+    recorder.0.record(actions.held_bits());
+}
+
+/// Snapshots the state every [`SNAPSHOT_INTERVAL`] placements, and before
+/// the first tick.
+fn take_snapshots(world: &mut World) {
+    let playback = world.resource::<ReplayPlayback>();
+    let due = playback.snapshots.last().is_none_or(|last| {
+        playback.placements > last.placements
+            && playback.placements.is_multiple_of(SNAPSHOT_INTERVAL)
+    });
+    if due {
+        let snapshot = Snapshot {
+            tick: playback.tick,
+            placements: playback.placements,
+            state: TickState::capture(world),
+        };
+        world
+            .resource_mut::<ReplayPlayback>()
+            .snapshots
+            .push(snapshot);
+    }
+}
+
+/// Counts the pieces locked this tick, pausing once the replay runs out.
+fn count_placements(mut events: EventReader<GameplayEvent>, mut playback: ResMut<ReplayPlayback>) {
+    let locked = events
+        .read()
+        .filter(|event| matches!(event, GameplayEvent::Locked(_)))
+        .count();
+    playback.placements += locked as u32;
+    if playback.is_finished() {
+        playback.paused = true;
+    }
+}
+
+/// Moves playback to just after the `placement`th piece has locked, or to
+/// the end if the game never gets that far, leaving it paused.
+///
+/// Going back restores the nearest earlier snapshot; either way the ticks
+/// in between are replayed through the normal gameplay schedule. Their
+/// events are dropped for a single [`GameplayEvent::Reset`], so effects
+/// rebuild from the board as it now is, with every block settled, rather
+/// than from cells that may since have moved.
+pub fn seek(world: &mut World, placement: u32) {
+    let mut playback = world.resource_mut::<ReplayPlayback>();
+    if placement < playback.placements {
+        let snapshot = playback
+            .snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.placements <= placement)
+            .cloned()
+            .expect("playback snapshots its first tick");
+        playback.tick = snapshot.tick;
+        playback.placements = snapshot.placements;
+        snapshot.state.restore(world);
+    }
+    world.resource_mut::<ReplayPlayback>().paused = false;
+    loop {
+        let playback = world.resource::<ReplayPlayback>();
+        if playback.placements >= placement || playback.paused {
+            break;
+        }
+        world.run_schedule(FixedUpdate);
+    }
+    world.resource_mut::<ReplayPlayback>().paused = true;
+    // The skipped ticks shouldn't set off a burst of sounds and flashes.
+    let mut events = world.resource_mut::<Events<GameplayEvent>>();
+    events.clear();
+    events.send(GameplayEvent::Reset);
+}
+
+/// Stands in for [`read_keyboard`](crate::input::keyboard::read_keyboard)
+/// while a replay plays.
+fn play_input(
+    mut playback: ResMut<ReplayPlayback>,
+    actions: Res<ActionState>,
+    mut events: EventWriter<ActionEvent>,
+) {
+    let held = playback.replay.held_at(playback.tick);
+    send_held(
+        &actions,
+        |action| held & 1 << action as u16 != 0,
+        &mut events,
+    );
+    playback.tick += 1;
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::time::Duration;
+
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::game::GamePlugin;
+    use crate::input::keyboard::Action;
+
+    /// A headless game playing `replay` back under its own rules in
+    /// `state`, with time advanced only by running [`FixedUpdate`].
+    pub(crate) fn playback_app(replay: Replay, state: AppState) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin, ReplayPlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        let rules = replay.clone();
+        app.world
+            .run_system_once(move |mut replay_rules: ReplayRules| {
+                replay_rules.apply(&rules);
+            });
+        app.world.insert_resource(replay.piece_queue());
+        app.world.insert_resource(ReplayPlayback::new(replay));
+        app.world.resource_mut::<NextState<AppState>>().set(state);
+        app.update();
+        app
+    }
+
+    #[test]
+    fn test_replay_only_stores_input_changes() {
+        let mut replay = Replay::new(3, GameMode::Marathon, default(), &default(), &default());
+        let hard_drop = 1 << Action::HardDrop as u16;
+        for held in [0, hard_drop, hard_drop, 0, 0, hard_drop] {
+            replay.record(held);
+        }
+        assert_eq!(replay.length, 6);
+        assert_eq!(replay.inputs, vec![(1, hard_drop), (3, 0), (5, hard_drop)]);
+        let held: Vec<u16> = (0..6).map(|tick| replay.held_at(tick)).collect();
+        assert_eq!(held, vec![0, hard_drop, hard_drop, 0, 0, hard_drop]);
+        assert_eq!(Replay::from_ron(&replay.to_ron()).unwrap(), replay);
+    }
+}
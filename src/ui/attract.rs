@@ -0,0 +1,177 @@
+//! Attract mode: after the main menu sits idle, a demo game plays over the
+//! playfield until any input returns to the menu. The [`AiPlayer`] plays it
+//! live on a fresh seed, or with [`Settings::ai_demo`] off the bundled demo
+//! replay plays instead.
+//!
+//! [`Settings::ai_demo`]: crate::utils::config::Settings::ai_demo
+
+use bevy::ecs::system::SystemParam;
+use bevy::input::touch::Touches;
+use bevy::prelude::*;
+use bevy::window::CursorMoved;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::ai::AiPlayer;
+use crate::components::tetromino::PieceQueue;
+use crate::game::{reset_game, AppState, GameMode, GameplayEvent};
+use crate::replay::{Replay, ReplayPlayback, ReplayRules};
+use crate::ui::menus::{SeedMenu, SettingsMenu};
+use crate::utils::i18n::Locale;
+
+/// Seconds the main menu must sit untouched before the demo starts.
+const ATTRACT_IDLE_SECS: f32 = 20.0;
+
+const DEMO_REPLAY: &str = include_str!("../../assets/replays/demo.ron");
+
+/// The bundled attract-mode replay.
+pub fn demo_replay() -> Replay {
+    Replay::from_ron(DEMO_REPLAY).expect("bundled demo replay is valid RON")
+}
+
+#[derive(Resource, Debug)]
+pub struct Attract {
+    idle: Timer,
+    /// The player's rules, put back once the demo ends.
+    saved: Option<Replay>,
+}
+
+impl Default for Attract {
+    fn default() -> Self {
+        Self {
+            idle: Timer::from_seconds(ATTRACT_IDLE_SECS, TimerMode::Once),
+            saved: None,
+        }
+    }
+}
+
+/// Every source of player input that counts as activity.
+#[derive(SystemParam)]
+struct Activity<'w, 's> {
+    keys: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+    touches: Res<'w, Touches>,
+    cursor: EventReader<'w, 's, CursorMoved>,
+}
+
+impl Activity<'_, '_> {
+    fn any(&mut self) -> bool {
+        let moved = self.cursor.read().count() > 0;
+        moved
+            || self.keys.get_just_pressed().next().is_some()
+            || self.mouse.get_just_pressed().next().is_some()
+            || self.touches.any_just_pressed()
+    }
+}
+
+pub struct AttractPlugin;
+
+impl Plugin for AttractPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Attract>()
+            .add_systems(OnEnter(AppState::MainMenu), reset_idle)
+            .add_systems(OnExit(AppState::Demo), end_demo)
+            .add_systems(
+                Update,
+                (
+                    start_demo_when_idle.run_if(in_state(AppState::MainMenu)),
+                    (stop_demo, demo_banner).run_if(in_state(AppState::Demo)),
+                ),
+            );
+    }
+}
+
+fn reset_idle(mut attract: ResMut<Attract>) {
+    attract.idle.reset();
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_demo_when_idle(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut activity: Activity,
+    settings_menu: Res<SettingsMenu>,
+    seed_menu: Res<SeedMenu>,
+    mut attract: ResMut<Attract>,
+    mut rules: ReplayRules,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if activity.any() || settings_menu.open || seed_menu.open {
+        attract.idle.reset();
+        return;
+    }
+    if !attract.idle.tick(time.delta()).finished() {
+        return;
+    }
+    if rules.settings().ai_demo {
+        // A Marathon game under the default rules, whatever the player's.
+        let game = Replay::new(
+            rand::random(),
+            GameMode::Marathon,
+            default(),
+            &default(),
+            &default(),
+        );
+        attract.saved = Some(rules.apply(&game));
+        reset_game(&mut commands, game.piece_queue());
+        commands.insert_resource(AiPlayer::default());
+    } else {
+        let replay = demo_replay();
+        attract.saved = Some(rules.apply(&replay));
+        reset_game(&mut commands, replay.piece_queue());
+        commands.insert_resource(ReplayPlayback::new(replay));
+    }
+    next_state.set(AppState::Demo);
+}
+
+fn stop_demo(
+    mut activity: Activity,
+    playback: Option<Res<ReplayPlayback>>,
+    mut events: EventReader<GameplayEvent>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let topped_out = events.read().any(|event| *event == GameplayEvent::GameOver);
+    let finished = playback.is_some_and(|playback| playback.is_finished());
+    if activity.any() || topped_out || finished {
+        next_state.set(AppState::MainMenu);
+    }
+}
+
+/// Puts back the player's rules and leaves a clean game behind.
+fn end_demo(mut commands: Commands, mut attract: ResMut<Attract>, mut rules: ReplayRules) {
+    if let Some(saved) = attract.saved.take() {
+        rules.apply(&saved);
+    }
+    commands.remove_resource::<ReplayPlayback>();
+    commands.remove_resource::<AiPlayer>();
+    reset_game(&mut commands, PieceQueue::new());
+}
+
+fn demo_banner(mut contexts: EguiContexts, locale: Res<Locale>) {
+    egui::Area::new("demo_banner")
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 16.0])
+        .interactable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.heading(locale.t("demo.banner"));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::scoring::Score;
+    use crate::game::Phase;
+    use crate::replay::tests::playback_app;
+
+    #[test]
+    fn test_demo_replay_plays_through_without_topping_out() {
+        let replay = demo_replay();
+        let mut app = playback_app(replay.clone(), AppState::Demo);
+
+        for _ in 0..replay.length {
+            app.world.run_schedule(FixedUpdate);
+            assert_ne!(*app.world.resource::<Phase>(), Phase::ToppedOut);
+        }
+        assert!(app.world.resource::<ReplayPlayback>().is_finished());
+        assert!(app.world.resource::<Score>().lines > 0);
+    }
+}
@@ -0,0 +1,222 @@
+//! Setting up a board by hand in Zen, the practice mode.
+//!
+//! With [`Settings::board_editor`] on, the cell under the mouse is
+//! highlighted, a left click fills it in the colour picked from the paint
+//! window and a right click empties it. The mouse is mapped back through
+//! [`BoardLayout::screen_to_world`], so the editor only works in the flat
+//! view. A board changed by hand no longer follows from the seed and the
+//! inputs, so editing drops the game's recording.
+
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::components::board::Board;
+use crate::components::garbage::GARBAGE_CELL;
+use crate::components::tetromino::PieceKind;
+use crate::game::{announce_board_changes, ActivePiece, AppState, GameMode};
+use crate::rendering::layers;
+use crate::rendering::shapes::{BoardLayout, Palette};
+use crate::replay::ReplayRecorder;
+use crate::ui::menus::egui_color;
+use crate::utils::config::Settings;
+use crate::utils::i18n::Locale;
+
+/// Opacity of the highlight over the hovered cell.
+const HOVER_ALPHA: f32 = 0.3;
+
+/// Side of a colour swatch in the paint window, in logical pixels.
+const SWATCH_SIZE: f32 = 20.0;
+
+/// The cell under the mouse and the colour clicks paint with.
+#[derive(Resource, Debug, Default)]
+pub struct BoardEditor {
+    pub hovered: Option<(i32, i32)>,
+    /// A [`Board`] cell value: a [`PieceKind::index`] or [`GARBAGE_CELL`].
+    pub brush: usize,
+}
+
+#[derive(Component)]
+struct HoverHighlight;
+
+pub struct BoardEditorPlugin;
+
+impl Plugin for BoardEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BoardEditor>()
+            .add_systems(Startup, spawn_highlight)
+            .add_systems(
+                Update,
+                (
+                    track_hover,
+                    (paint_window, paint_cells).run_if(editing),
+                    draw_highlight,
+                )
+                    .chain()
+                    .before(announce_board_changes),
+            );
+    }
+}
+
+/// Whether the board can be edited: in a Zen game, playing or paused,
+/// seen flat, with the editor turned on.
+fn editable(settings: &Settings, mode: GameMode, layout: &BoardLayout, state: AppState) -> bool {
+    settings.board_editor
+        && mode == GameMode::Zen
+        && layout.tilt == 0.0
+        && matches!(state, AppState::Playing | AppState::Paused)
+}
+
+fn editing(
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    layout: Res<BoardLayout>,
+    state: Res<State<AppState>>,
+) -> bool {
+    editable(&settings, *mode, &layout, *state.get())
+}
+
+fn spawn_highlight(mut commands: Commands) {
+    commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_xyz(0.0, 0.0, layers::HOVER),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        HoverHighlight,
+    ));
+}
+
+/// Finds the cell under the mouse, leaving none while the mouse is over
+/// an egui window or the board can't be edited.
+fn track_hover(
+    settings: Res<Settings>,
+    mode: Res<GameMode>,
+    layout: Res<BoardLayout>,
+    state: Res<State<AppState>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut contexts: EguiContexts,
+    mut editor: ResMut<BoardEditor>,
+) {
+    let hovered = windows
+        .get_single()
+        .ok()
+        .and_then(Window::cursor_position)
+        .filter(|_| editable(&settings, *mode, &layout, *state.get()))
+        .filter(|_| !contexts.ctx_mut().is_pointer_over_area())
+        .and_then(|cursor| layout.cell_at(layout.screen_to_world(cursor)));
+    if editor.hovered != hovered {
+        editor.hovered = hovered;
+    }
+}
+
+fn paint_window(
+    mut contexts: EguiContexts,
+    locale: Res<Locale>,
+    palette: Res<Palette>,
+    mut editor: ResMut<BoardEditor>,
+) {
+    egui::Window::new(locale.t("editor.title"))
+        .anchor(egui::Align2::LEFT_BOTTOM, [16.0, -16.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.horizontal(|ui| {
+                let cells = PieceKind::ALL.map(PieceKind::index);
+                for cell in cells.into_iter().chain([GARBAGE_CELL]) {
+                    let stroke = if editor.brush == cell {
+                        egui::Stroke::new(2.0, egui::Color32::WHITE)
+                    } else {
+                        egui::Stroke::NONE
+                    };
+                    let swatch = egui::Button::new("")
+                        .fill(egui_color(palette.cell(cell)))
+                        .stroke(stroke)
+                        .min_size(egui::vec2(SWATCH_SIZE, SWATCH_SIZE));
+                    if ui.add(swatch).clicked() {
+                        editor.brush = cell;
+                    }
+                }
+            });
+            ui.label(locale.t("editor.hint"));
+        });
+}
+
+/// Fills the hovered cell on a left click and empties it on a right click,
+/// leaving the cells of the piece in play alone.
+fn paint_cells(
+    mut commands: Commands,
+    mouse: Option<Res<Input<MouseButton>>>,
+    editor: Res<BoardEditor>,
+    active: Res<ActivePiece>,
+    mut board: ResMut<Board>,
+) {
+    let (Some(mouse), Some((x, y))) = (mouse, editor.hovered) else {
+        return;
+    };
+    let cell = if mouse.pressed(MouseButton::Left) {
+        Some(editor.brush)
+    } else if mouse.pressed(MouseButton::Right) {
+        None
+    } else {
+        return;
+    };
+    let under_piece = active
+        .0
+        .is_some_and(|piece| piece.cells().contains(&(x, y)));
+    if under_piece || board.get(x, y) == cell {
+        return;
+    }
+    board.set(x, y, cell);
+    commands.remove_resource::<ReplayRecorder>();
+}
+
+fn draw_highlight(
+    editor: Res<BoardEditor>,
+    layout: Res<BoardLayout>,
+    palette: Res<Palette>,
+    mut highlight: Query<(&mut Sprite, &mut Transform, &mut Visibility), With<HoverHighlight>>,
+) {
+    let Ok((mut sprite, mut transform, mut visibility)) = highlight.get_single_mut() else {
+        return;
+    };
+    let Some((x, y)) = editor.hovered else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+    *visibility = Visibility::Visible;
+    sprite.color = palette.text.with_a(HOVER_ALPHA);
+    sprite.custom_size = Some(Vec2::splat(layout.block_size()));
+    let center = layout.cell_center(x, y);
+    transform.translation = center.extend(layers::HOVER);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::config::LayoutConfig;
+
+    #[test]
+    fn test_screen_positions_map_to_the_cells_drawn_there() {
+        let window = Vec2::new(720.0, 720.0);
+        let layout = BoardLayout::fit(window, &Board::default(), &LayoutConfig::default());
+        let board = crate::input::touch::board_on_screen(&layout);
+        let block = board.width() / layout.columns as f32;
+
+        let bottom_left = Vec2::new(board.min.x, board.max.y) + Vec2::new(0.5, -0.5) * block;
+        assert_eq!(
+            layout.cell_at(layout.screen_to_world(bottom_left)),
+            Some((0, 0))
+        );
+        let inside = Vec2::new(board.min.x, board.max.y) + Vec2::new(3.9, -7.1) * block;
+        assert_eq!(layout.cell_at(layout.screen_to_world(inside)), Some((3, 7)));
+        let top_right = Vec2::new(board.max.x - 1.0, board.min.y + 1.0);
+        assert_eq!(
+            layout.cell_at(layout.screen_to_world(top_right)),
+            Some((layout.columns as i32 - 1, layout.rows as i32 - 1))
+        );
+        assert_eq!(layout.cell_at(layout.screen_to_world(Vec2::ZERO)), None);
+        let left_of_board = Vec2::new(board.min.x - 1.0, board.center().y);
+        assert_eq!(layout.cell_at(layout.screen_to_world(left_of_board)), None);
+    }
+}
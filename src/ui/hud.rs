@@ -0,0 +1,350 @@
+//! In-game heads-up display: panel titles, the score readout, whether hold
+//! is spent and the garbage meter, and the key copying the seed.
+
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+
+use crate::components::garbage::GarbageQueue;
+use crate::components::scoring::Score;
+use crate::components::tetromino::PieceQueue;
+use crate::error::init_locale;
+use crate::game::{AppState, GameMode, Hold, Sprint};
+use crate::rendering::layers;
+use crate::rendering::shapes::{BoardLayout, Palette, PlayfieldEntity};
+use crate::ui::share::format_split;
+use crate::utils::clipboard::Clipboard;
+use crate::utils::config::LayoutConfig;
+use crate::utils::i18n::Locale;
+
+/// Copies the game's seed to the clipboard.
+pub const COPY_SEED_KEY: KeyCode = KeyCode::F4;
+
+/// Font size relative to the cell size, so text scales with the board.
+const FONT_SCALE: f32 = 0.6;
+
+/// Width of the garbage meter, in cells.
+const GARBAGE_METER_WIDTH: f32 = 0.35;
+const GARBAGE_METER_COLOR: Color = Color::rgb(0.9, 0.25, 0.2);
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HudText {
+    HoldTitle,
+    NextTitle,
+    Stats,
+    /// Under the hold panel while hold is spent until the next lock.
+    HoldStatus,
+}
+
+/// The bar up the left edge of the board, a row tall for every garbage row
+/// waiting to rise.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct GarbageMeter;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        init_locale(app);
+        app.init_resource::<Clipboard>()
+            .add_systems(OnExit(AppState::MainMenu), spawn_hud)
+            .add_systems(
+                Update,
+                (
+                    update_hud,
+                    update_garbage_meter,
+                    copy_seed.run_if(not(in_state(AppState::MainMenu))),
+                ),
+            );
+    }
+}
+
+fn spawn_hud(mut commands: Commands) {
+    for (hud, anchor) in [
+        (HudText::HoldTitle, Anchor::Center),
+        (HudText::NextTitle, Anchor::Center),
+        (HudText::Stats, Anchor::TopCenter),
+        (HudText::HoldStatus, Anchor::Center),
+    ] {
+        commands.spawn((
+            Text2dBundle {
+                text: Text::from_section("", TextStyle::default()),
+                text_anchor: anchor,
+                transform: Transform::from_xyz(0.0, 0.0, layers::HUD),
+                ..default()
+            },
+            hud,
+            PlayfieldEntity,
+        ));
+    }
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: GARBAGE_METER_COLOR,
+                ..default()
+            },
+            transform: Transform::from_xyz(0.0, 0.0, layers::HUD),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        GarbageMeter,
+        PlayfieldEntity,
+    ));
+}
+
+/// Where the garbage meter stands with `pending` rows waiting, rising from
+/// the floor and capped at the height of the visible field, or `None` when
+/// nothing is waiting.
+pub fn garbage_meter(layout: &BoardLayout, pending: u32) -> Option<Rect> {
+    if pending == 0 {
+        return None;
+    }
+    let board = layout.board_rect();
+    let rows = pending.min(layout.rows as u32) as f32;
+    let width = GARBAGE_METER_WIDTH * layout.cell_size;
+    Some(Rect::new(
+        board.min.x - width,
+        board.min.y,
+        board.min.x,
+        board.min.y + rows * layout.block_size(),
+    ))
+}
+
+fn update_garbage_meter(
+    layout: Res<BoardLayout>,
+    garbage: Res<GarbageQueue>,
+    mut meters: Query<(&mut Sprite, &mut Transform, &mut Visibility), With<GarbageMeter>>,
+) {
+    let Ok((mut sprite, mut transform, mut visibility)) = meters.get_single_mut() else {
+        return;
+    };
+    let Some(rect) = garbage_meter(&layout, garbage.pending()) else {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    };
+    visibility.set_if_neq(Visibility::Inherited);
+    sprite.custom_size = Some(rect.size());
+    transform.translation.x = rect.center().x;
+    transform.translation.y = rect.center().y;
+}
+
+/// The score readout from its `(label, value)` lines and the seed's, if
+/// shown: a column of each label over its value, or in the compact layout
+/// one row of them all with the seed, the longest, under it.
+fn stats_text(
+    stats: &[(String, String)],
+    seed: Option<&(String, String)>,
+    compact: bool,
+) -> String {
+    if !compact {
+        let lines: Vec<_> = stats
+            .iter()
+            .chain(seed)
+            .map(|(label, value)| format!("{label}\n{value}"))
+            .collect();
+        return lines.join("\n\n");
+    }
+    let row: Vec<_> = stats
+        .iter()
+        .map(|(label, value)| format!("{label} {value}"))
+        .collect();
+    let row = row.join("   ");
+    match seed {
+        Some((label, value)) => format!("{row}\n{label} {value}"),
+        None => row,
+    }
+}
+
+/// Copies the seed the game is dealt from, to share the board.
+fn copy_seed(keys: Res<Input<KeyCode>>, queue: Res<PieceQueue>, mut clipboard: ResMut<Clipboard>) {
+    if keys.just_pressed(COPY_SEED_KEY) {
+        clipboard.copy(&queue.seed().to_string());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_hud(
+    layout: Res<BoardLayout>,
+    config: Res<LayoutConfig>,
+    palette: Res<Palette>,
+    score: Res<Score>,
+    queue: Res<PieceQueue>,
+    mode: Res<GameMode>,
+    sprint: Res<Sprint>,
+    hold: Res<Hold>,
+    locale: Res<Locale>,
+    mut texts: Query<(&HudText, &mut Text, &mut Transform)>,
+) {
+    for (hud, mut text, mut transform) in &mut texts {
+        let stale = locale.is_changed() || text.sections[0].value.is_empty();
+        let position = match hud {
+            HudText::HoldTitle => {
+                if stale {
+                    text.sections[0].value = locale.t("hud.hold").to_owned();
+                }
+                layout.panel_title(layout.hold_panel())
+            }
+            HudText::NextTitle => {
+                if stale {
+                    text.sections[0].value = locale.t("hud.next").to_owned();
+                }
+                layout.panel_title(layout.next_panel())
+            }
+            HudText::Stats => {
+                if stale
+                    || score.is_changed()
+                    || sprint.is_changed()
+                    || mode.is_changed()
+                    || config.is_changed()
+                {
+                    // A sprint counts its lines towards the goal, against
+                    // the clock.
+                    let (lines, timed) = match *mode {
+                        GameMode::Sprint(goal) => {
+                            (format!("{}/{}", score.lines, goal.lines()), true)
+                        }
+                        GameMode::Cheese(_) => (score.lines.to_string(), true),
+                        _ => (score.lines.to_string(), false),
+                    };
+                    let mut stats = vec![
+                        ("stats.score", score.points.to_string()),
+                        ("stats.level", score.level.to_string()),
+                        ("stats.lines", lines),
+                    ];
+                    if timed {
+                        stats.push(("stats.time", format_split(sprint.ticks)));
+                    }
+                    let label =
+                        |(key, value): (&str, String)| (locale.t(key).to_uppercase(), value);
+                    let stats: Vec<_> = stats.into_iter().map(label).collect();
+                    let seed = config
+                        .hud
+                        .show_seed
+                        .then(|| label(("stats.seed", queue.seed().to_string())));
+                    text.sections[0].value = stats_text(&stats, seed.as_ref(), layout.compact);
+                }
+                layout.stats_anchor()
+            }
+            HudText::HoldStatus => {
+                if stale || hold.is_changed() {
+                    // Hold comes back on the next lock, not after a time,
+                    // so this says that it's spent rather than counting.
+                    text.sections[0].value = if hold.can_hold {
+                        String::new()
+                    } else {
+                        locale.t("hud.hold_locked").to_owned()
+                    };
+                }
+                let panel = layout.hold_panel();
+                Vec2::new(panel.center().x, panel.min.y - layout.cell_size / 2.0)
+            }
+        };
+        let font_size = layout.cell_size * FONT_SCALE;
+        let color = match hud {
+            HudText::HoldStatus => palette.hold_locked,
+            _ => palette.text,
+        };
+        let style = &text.sections[0].style;
+        if style.font_size != font_size || style.color != color {
+            let style = &mut text.sections[0].style;
+            style.font_size = font_size;
+            style.color = color;
+        }
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::game::{GamePlugin, NewGame};
+    use crate::input::keyboard::{Action, ActionEvent};
+
+    #[test]
+    fn test_hold_status_shows_while_hold_is_spent() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin, HudPlugin))
+            .init_resource::<BoardLayout>()
+            .init_resource::<LayoutConfig>()
+            .init_resource::<Palette>()
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+        let status = |app: &mut App| {
+            app.update();
+            let mut texts = app.world.query::<(&HudText, &Text)>();
+            let (_, text) = texts
+                .iter(&app.world)
+                .find(|(hud, _)| **hud == HudText::HoldStatus)
+                .unwrap();
+            text.sections[0].value.clone()
+        };
+        let tap = |app: &mut App, action| {
+            app.world.send_event(ActionEvent::press(action));
+            app.world.send_event(ActionEvent::release(action));
+            app.world.run_schedule(FixedUpdate);
+        };
+        let locked = app
+            .world
+            .resource::<Locale>()
+            .t("hud.hold_locked")
+            .to_owned();
+
+        app.world.run_schedule(FixedUpdate);
+        assert_eq!(status(&mut app), "");
+        tap(&mut app, Action::Hold);
+        assert!(!app.world.resource::<Hold>().can_hold);
+        assert_eq!(status(&mut app), locked);
+        tap(&mut app, Action::HardDrop);
+        assert!(app.world.resource::<Hold>().can_hold);
+        assert_eq!(status(&mut app), "");
+    }
+
+    #[test]
+    fn test_the_seed_shown_is_the_one_the_queue_deals_from() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin, HudPlugin))
+            .init_resource::<BoardLayout>()
+            .init_resource::<LayoutConfig>()
+            .init_resource::<Palette>()
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        app.world.send_event(NewGame {
+            seed: Some(9_876_543),
+        });
+        app.update();
+        app.update();
+        let stats = |app: &mut App| {
+            app.update();
+            let mut texts = app.world.query::<(&HudText, &Text)>();
+            let (_, text) = texts
+                .iter(&app.world)
+                .find(|(hud, _)| **hud == HudText::Stats)
+                .unwrap();
+            text.sections[0].value.clone()
+        };
+        let seed = app.world.resource::<PieceQueue>().seed();
+        assert_eq!(seed, 9_876_543);
+        let label = app
+            .world
+            .resource::<Locale>()
+            .t("stats.seed")
+            .to_uppercase();
+        assert!(stats(&mut app).ends_with(&format!("{label}\n{seed}")));
+
+        app.world.resource_mut::<LayoutConfig>().hud.compact = true;
+        app.world.resource_mut::<BoardLayout>().compact = true;
+        assert!(stats(&mut app).ends_with(&format!("\n{label} {seed}")));
+
+        app.world.resource_mut::<LayoutConfig>().hud.show_seed = false;
+        let hidden = stats(&mut app);
+        assert!(!hidden.contains(&label) && !hidden.contains(&seed.to_string()));
+    }
+}
@@ -0,0 +1,1169 @@
+//! Game menus: start, pause, settings and game over.
+
+use std::time::Duration;
+
+use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts, EguiInput, EguiSet};
+use bevy_kira_audio::prelude::*;
+
+use crate::audio::aeffects::{PreviewChannel, SoundEffect, SoundEffects};
+use crate::audio::music::{MusicTrack, MusicTracks};
+use crate::autosave::{Autosave, AutosavePreview, ContinueGame};
+use crate::components::scoring::{
+    timestamp, CheeseRecord, HighScoreEntry, HighScores, Score, ScoringRules, SprintRecord,
+    SOFT_DROP_CAP,
+};
+use crate::components::tetromino::{
+    PieceKind, PieceQueue, QueueRules, RandomizerKind, RotationSystem,
+};
+use crate::error::init_locale;
+use crate::game::{
+    repeat_due, AppState, CheeseRows, ConfirmExit, ExitPrompt, GameMode, Modifiers, NewGame, Phase,
+    Sprint, SprintGoal,
+};
+use crate::input::keyboard::{Action, BindError, KeyBindings};
+use crate::input::touch::TouchOverlay;
+use crate::race::{BestRuns, RaceGhost};
+use crate::rendering::shapes::{BoardLayout, Palette, GHOST_NEAR_ROWS, MIN_CELL_SIZE};
+use crate::replay::ReplayRecorder;
+use crate::ui::review::WatchReplay;
+use crate::ui::share::{format_clock, format_split, ShareCard, ShareStatus};
+use crate::utils::config::{
+    Framing, HandlingProfile, InputConfig, LayoutConfig, LockReset, MenuRepeat, NextOrientation,
+    OpposingDirections, Settings, TopOutRule, MAX_GRAVITY, TICKS_PER_SECOND,
+};
+use crate::utils::i18n::{Language, Locale};
+use crate::utils::seed::{daily_seed, parse_seed, Date};
+use crate::versus::VersusMatch;
+
+/// Largest cell size offered by the settings slider, in pixels.
+const MAX_CELL_SIZE: f32 = 96.0;
+
+/// Slowest custom gravity offered, in rows per tick.
+const MIN_GRAVITY: f32 = 0.01;
+
+/// Longest fade-out delay offered for invisible blocks, in seconds.
+const MAX_INVISIBLE_DELAY: f32 = 5.0;
+
+/// Strongest lightening offered for the piece in play.
+const MAX_ACTIVE_EMPHASIS: f32 = 0.6;
+
+/// Pixels a cell takes in the Continue option's picture of the saved board.
+const THUMBNAIL_CELL: f32 = 4.0;
+
+/// Most rows from landing the ghost can be set to appear at.
+const MAX_GHOST_NEAR_ROWS: u32 = 10;
+
+/// Highest soft-drop cap offered, in points a piece.
+const MAX_SOFT_DROP_CAP: u32 = 40;
+
+/// Range and starting point of the idle time before an auto-pause, in
+/// seconds.
+const MIN_AUTO_PAUSE: f32 = 10.0;
+const MAX_AUTO_PAUSE: f32 = 300.0;
+const DEFAULT_AUTO_PAUSE: f32 = 60.0;
+
+/// Whether the settings window is replacing the current menu.
+#[derive(Resource, Debug, Default)]
+pub struct SettingsMenu {
+    pub open: bool,
+    /// The handling profile text being exported or pasted for import.
+    profile: String,
+    /// Why the last import failed.
+    profile_error: Option<String>,
+}
+
+fn settings_open(menu: Res<SettingsMenu>) -> bool {
+    menu.open
+}
+
+fn settings_closed(menu: Res<SettingsMenu>) -> bool {
+    !menu.open
+}
+
+/// Progress of rebinding a key from the settings menu.
+#[derive(Resource, Debug, Default)]
+pub struct Rebinding {
+    /// The action waiting for its new key.
+    action: Option<Action>,
+    /// A pressed key that another action already uses, waiting for the
+    /// player to swap or cancel.
+    conflict: Option<(KeyCode, Action)>,
+}
+
+/// The seed entry window shown over the main menu.
+#[derive(Resource, Debug, Default)]
+pub struct SeedMenu {
+    pub open: bool,
+    /// What has been typed so far.
+    pub text: String,
+}
+
+fn seed_open(menu: Res<SeedMenu>) -> bool {
+    menu.open
+}
+
+fn seed_closed(menu: Res<SeedMenu>) -> bool {
+    !menu.open
+}
+
+fn exit_prompt_open(prompt: Res<ExitPrompt>) -> bool {
+    prompt.open
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        init_locale(app);
+        let scores = HighScores::load(app);
+        app.init_resource::<SettingsMenu>()
+            .init_resource::<SeedMenu>()
+            .init_resource::<Rebinding>()
+            .init_resource::<MenuHold>()
+            .init_resource::<ShareCard>()
+            .insert_resource(scores)
+            .add_systems(OnEnter(AppState::GameOver), record_high_score)
+            .add_systems(
+                Update,
+                (
+                    main_menu.run_if(in_state(AppState::MainMenu).and_then(seed_closed)),
+                    seed_menu.run_if(in_state(AppState::MainMenu).and_then(seed_open)),
+                    pause_menu.run_if(in_state(AppState::Paused).and_then(not(exit_prompt_open))),
+                    exit_prompt.run_if(exit_prompt_open),
+                    game_over_menu.run_if(in_state(AppState::GameOver)),
+                )
+                    .run_if(settings_closed),
+            )
+            .add_systems(Update, settings_menu.run_if(settings_open))
+            .add_systems(Update, apply_ui_theme)
+            .add_systems(
+                PreUpdate,
+                repeat_menu_keys
+                    .after(EguiSet::ProcessInput)
+                    .before(EguiSet::BeginFrame)
+                    .run_if(not(in_state(AppState::Playing))),
+            );
+    }
+}
+
+/// The arrow key held in a menu, and for how long.
+#[derive(Resource, Debug, Default)]
+struct MenuHold {
+    key: Option<KeyCode>,
+    ticks: u32,
+    /// Time held beyond the last whole tick.
+    partial: Duration,
+}
+
+impl MenuHold {
+    /// Advances by `elapsed` with `key` held, returning how many times it
+    /// repeats meanwhile. The press itself isn't counted: egui takes the
+    /// first step through the menu on its own.
+    fn update(&mut self, key: Option<KeyCode>, elapsed: Duration, repeat: MenuRepeat) -> u32 {
+        if key != self.key {
+            *self = Self { key, ..default() };
+            return 0;
+        }
+        if key.is_none() {
+            return 0;
+        }
+        let tick = Duration::from_secs_f64(1.0 / TICKS_PER_SECOND);
+        self.partial += elapsed;
+        let mut repeats = 0;
+        while self.partial >= tick {
+            self.partial -= tick;
+            self.ticks += 1;
+            if repeat_due(self.ticks, repeat.delay, repeat.interval.max(1)) {
+                repeats += 1;
+            }
+        }
+        repeats
+    }
+}
+
+/// Repeats a held up or down arrow through the open menu at
+/// [`Settings::menu_repeat`], as more presses for egui to move focus by.
+fn repeat_menu_keys(
+    time: Res<Time<Real>>,
+    keys: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    mut hold: ResMut<MenuHold>,
+    mut inputs: Query<&mut EguiInput>,
+) {
+    let key = match (keys.pressed(KeyCode::Up), keys.pressed(KeyCode::Down)) {
+        (true, false) => Some(KeyCode::Up),
+        (false, true) => Some(KeyCode::Down),
+        _ => None,
+    };
+    let repeats = hold.update(key, time.delta(), settings.menu_repeat);
+    let key = match key {
+        Some(KeyCode::Up) => egui::Key::ArrowUp,
+        _ => egui::Key::ArrowDown,
+    };
+    for mut input in &mut inputs {
+        for _ in 0..repeats {
+            input.0.events.push(egui::Event::Key {
+                key,
+                pressed: true,
+                repeat: true,
+                modifiers: default(),
+            });
+        }
+    }
+}
+
+/// egui's dark theme, or with [`Settings::high_contrast`] white text and
+/// thick white borders on black.
+pub fn ui_visuals(high_contrast: bool) -> egui::Visuals {
+    let mut visuals = egui::Visuals::dark();
+    if high_contrast {
+        let white = egui::Color32::WHITE;
+        let stroke = egui::Stroke::new(2.0, white);
+        visuals.override_text_color = Some(white);
+        visuals.window_fill = egui::Color32::BLACK;
+        visuals.panel_fill = egui::Color32::BLACK;
+        visuals.extreme_bg_color = egui::Color32::BLACK;
+        visuals.window_stroke = stroke;
+        let widgets = &mut visuals.widgets;
+        for widget in [
+            &mut widgets.noninteractive,
+            &mut widgets.inactive,
+            &mut widgets.hovered,
+            &mut widgets.active,
+            &mut widgets.open,
+        ] {
+            widget.bg_stroke = stroke;
+            widget.fg_stroke.color = white;
+        }
+    }
+    visuals
+}
+
+fn apply_ui_theme(
+    mut contexts: EguiContexts,
+    settings: Res<Settings>,
+    mut applied: Local<Option<bool>>,
+) {
+    if *applied != Some(settings.high_contrast) {
+        *applied = Some(settings.high_contrast);
+        contexts
+            .ctx_mut()
+            .set_visuals(ui_visuals(settings.high_contrast));
+    }
+}
+
+/// Checkboxes for the challenge [`Modifiers`] of the next game.
+fn modifier_toggles(ui: &mut egui::Ui, locale: &Locale, modifiers: &mut Modifiers) {
+    ui.collapsing(locale.t("modifier.title"), |ui| {
+        ui.checkbox(&mut modifiers.no_hold, locale.t("modifier.no_hold"));
+        ui.checkbox(
+            &mut modifiers.no_hard_drop,
+            locale.t("modifier.no_hard_drop"),
+        );
+        ui.checkbox(&mut modifiers.invisible, locale.t("modifier.invisible"));
+        ui.checkbox(&mut modifiers.big, locale.t("modifier.big"));
+    });
+}
+
+/// A fixed, centred menu window.
+pub(crate) fn menu_window(title: &str) -> egui::Window<'_> {
+    egui::Window::new(title)
+        .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+        .collapsible(false)
+        .resizable(false)
+}
+
+pub(crate) fn egui_color(color: Color) -> egui::Color32 {
+    let [r, g, b, a] = color.as_rgba_u8();
+    egui::Color32::from_rgba_unmultiplied(r, g, b, a)
+}
+
+/// A small picture of the board in `rows`, laid out as by
+/// [`Board::to_ascii`](crate::components::board::Board::to_ascii).
+/// Each cell takes `cell` pixels, drawn at `opacity`.
+pub(crate) fn board_thumbnail(
+    ui: &mut egui::Ui,
+    rows: &str,
+    palette: &Palette,
+    cell: f32,
+    opacity: f32,
+) {
+    let fill = |color: Color| egui_color(color.with_a(color.a() * opacity));
+    let width = rows
+        .lines()
+        .map(|row| row.chars().count())
+        .max()
+        .unwrap_or(0);
+    let height = rows.lines().count();
+    if width == 0 {
+        return;
+    }
+    let size = egui::vec2(width as f32, height as f32) * cell;
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let painter = ui.painter();
+    painter.rect_filled(rect, 0.0, fill(palette.well));
+    for (y, row) in rows.lines().enumerate() {
+        for (x, letter) in row.chars().enumerate().filter(|&(_, letter)| letter != '.') {
+            let color = PieceKind::ALL
+                .into_iter()
+                .find(|kind| kind.letter() == letter)
+                .map_or(palette.garbage, |kind| palette.piece(kind));
+            let min = rect.min + egui::vec2(x as f32, y as f32) * cell;
+            let block = egui::Rect::from_min_size(min, egui::Vec2::splat(cell));
+            painter.rect_filled(block, 0.0, fill(color));
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn main_menu(
+    mut contexts: EguiContexts,
+    locale: Res<Locale>,
+    mut settings_menu: ResMut<SettingsMenu>,
+    mut seed_menu: ResMut<SeedMenu>,
+    mut mode: ResMut<GameMode>,
+    mut modifiers: ResMut<Modifiers>,
+    mut goal: Local<SprintGoal>,
+    mut cheese: Local<CheeseRows>,
+    mut new_game: EventWriter<NewGame>,
+    autosave: Res<Autosave>,
+    preview: Res<AutosavePreview>,
+    palette: Res<Palette>,
+    mut continue_game: EventWriter<ContinueGame>,
+    mut exit: EventWriter<AppExit>,
+) {
+    menu_window(locale.t("title")).show(contexts.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            if let Some(rows) = autosave.continue_preview(&preview) {
+                board_thumbnail(ui, rows, &palette, THUMBNAIL_CELL, 1.0);
+                if ui.button(locale.t("menu.continue")).clicked() {
+                    continue_game.send(ContinueGame);
+                }
+            }
+            for choice in GameMode::ALL {
+                let choice = with_goal(choice, *goal, *cheese);
+                if ui.button(locale.t(choice.label_key())).clicked() {
+                    *mode = choice;
+                    new_game.send(NewGame::default());
+                }
+            }
+            sprint_goal_choice(ui, &locale, &mut goal);
+            cheese_rows_choice(ui, &locale, &mut cheese);
+            modifier_toggles(ui, &locale, &mut modifiers);
+            if ui.button(locale.t("menu.enter_seed")).clicked() {
+                seed_menu.open = true;
+            }
+            if ui.button(locale.t("menu.settings")).clicked() {
+                settings_menu.open = true;
+            }
+            if ui.button(locale.t("menu.quit")).clicked() {
+                exit.send(AppExit);
+            }
+        });
+    });
+}
+
+fn seed_menu(
+    mut contexts: EguiContexts,
+    locale: Res<Locale>,
+    mut menu: ResMut<SeedMenu>,
+    mut mode: ResMut<GameMode>,
+    mut modifiers: ResMut<Modifiers>,
+    mut new_game: EventWriter<NewGame>,
+) {
+    let menu = &mut *menu;
+    let mut start = None;
+    menu_window(locale.t("seed.title")).show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.label(locale.t("seed.mode"));
+            let (goal, cheese) = match *mode {
+                GameMode::Sprint(goal) => (goal, default()),
+                GameMode::Cheese(cheese) => (default(), cheese),
+                _ => default(),
+            };
+            for choice in GameMode::ALL {
+                let choice = with_goal(choice, goal, cheese);
+                ui.radio_value(&mut *mode, choice, locale.t(choice.label_key()));
+            }
+        });
+        match &mut *mode {
+            GameMode::Sprint(goal) => sprint_goal_choice(ui, &locale, goal),
+            GameMode::Cheese(cheese) => cheese_rows_choice(ui, &locale, cheese),
+            _ => {}
+        }
+        modifier_toggles(ui, &locale, &mut modifiers);
+        if ui.text_edit_singleline(&mut menu.text).changed() {
+            menu.text.retain(|c| c.is_ascii_digit());
+        }
+        let seed = parse_seed(&menu.text);
+        if seed.is_none() && !menu.text.is_empty() {
+            ui.colored_label(egui::Color32::LIGHT_RED, locale.t("seed.invalid"));
+        }
+        ui.vertical_centered(|ui| {
+            if ui
+                .add_enabled(seed.is_some(), egui::Button::new(locale.t("seed.start")))
+                .clicked()
+            {
+                start = seed;
+            }
+            if ui.button(locale.t("seed.daily")).clicked() {
+                start = Some(daily_seed(Date::today()));
+            }
+            if ui.button(locale.t("settings.back")).clicked() {
+                menu.open = false;
+            }
+        });
+    });
+    if start.is_some() {
+        menu.open = false;
+        new_game.send(NewGame { seed: start });
+    }
+}
+
+/// `mode`, racing to `goal` if it is a sprint and digging out `cheese` if
+/// it is a cheese race.
+fn with_goal(mode: GameMode, goal: SprintGoal, cheese: CheeseRows) -> GameMode {
+    match mode {
+        GameMode::Sprint(_) => GameMode::Sprint(goal),
+        GameMode::Cheese(_) => GameMode::Cheese(cheese),
+        other => other,
+    }
+}
+
+fn cheese_rows_choice(ui: &mut egui::Ui, locale: &Locale, rows: &mut CheeseRows) {
+    egui::ComboBox::from_label(locale.t("cheese.rows"))
+        .selected_text(locale.t(rows.label_key()))
+        .show_ui(ui, |ui| {
+            for choice in CheeseRows::ALL {
+                ui.selectable_value(rows, choice, locale.t(choice.label_key()));
+            }
+        });
+}
+
+fn sprint_goal_choice(ui: &mut egui::Ui, locale: &Locale, goal: &mut SprintGoal) {
+    egui::ComboBox::from_label(locale.t("sprint.goal"))
+        .selected_text(locale.t(goal.label_key()))
+        .show_ui(ui, |ui| {
+            for choice in SprintGoal::ALL {
+                ui.selectable_value(goal, choice, locale.t(choice.label_key()));
+            }
+        });
+}
+
+/// Asks whether to quit the game in progress.
+fn exit_prompt(
+    mut contexts: EguiContexts,
+    locale: Res<Locale>,
+    mut prompt: ResMut<ExitPrompt>,
+    mut confirm: EventWriter<ConfirmExit>,
+) {
+    menu_window(locale.t("exit.title")).show(contexts.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            ui.label(locale.t("exit.body"));
+            if ui.button(locale.t("exit.confirm")).clicked() {
+                confirm.send(ConfirmExit);
+            }
+            if ui.button(locale.t("exit.cancel")).clicked() {
+                prompt.open = false;
+            }
+        });
+    });
+}
+
+fn pause_menu(
+    mut contexts: EguiContexts,
+    locale: Res<Locale>,
+    mut settings_menu: ResMut<SettingsMenu>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    menu_window(locale.t("menu.paused")).show(contexts.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            if ui.button(locale.t("menu.resume")).clicked() {
+                next_state.set(AppState::Playing);
+            }
+            if ui.button(locale.t("menu.settings")).clicked() {
+                settings_menu.open = true;
+            }
+            if ui.button(locale.t("menu.main_menu")).clicked() {
+                next_state.set(AppState::MainMenu);
+            }
+        });
+    });
+}
+
+/// Where the game-over menu can go next.
+#[derive(SystemParam)]
+struct NextGame<'w> {
+    watch: EventWriter<'w, WatchReplay>,
+    new_game: EventWriter<'w, NewGame>,
+    race: EventWriter<'w, RaceGhost>,
+    state: ResMut<'w, NextState<AppState>>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn game_over_menu(
+    mut contexts: EguiContexts,
+    locale: Res<Locale>,
+    score: Res<Score>,
+    queue: Res<PieceQueue>,
+    mode: Res<GameMode>,
+    modifiers: Res<Modifiers>,
+    settings: Res<Settings>,
+    scores: Res<HighScores>,
+    phase: Res<Phase>,
+    sprint: Res<Sprint>,
+    recorder: Option<Res<ReplayRecorder>>,
+    versus: Option<Res<VersusMatch>>,
+    best_runs: Res<BestRuns>,
+    mut card: ResMut<ShareCard>,
+    mut next: NextGame,
+) {
+    menu_window(locale.t("menu.game_over")).show(contexts.ctx_mut(), |ui| {
+        ui.vertical_centered(|ui| {
+            ui.label(locale.t(mode.label_key()));
+            if let Some(winner) = versus.and_then(|versus| versus.winner) {
+                ui.heading(locale.t(winner.wins_key()));
+            }
+            if let GameMode::Sprint(goal) = *mode {
+                ui.label(locale.t(goal.label_key()));
+                if *phase == Phase::Finished {
+                    let time = format_split(sprint.ticks);
+                    ui.label(format!("{}: {time}", locale.t("sprint.finished")));
+                }
+                if let Some(best) = scores.best_sprint(goal, *modifiers) {
+                    let time = format_split(best.ticks);
+                    ui.label(format!("{}: {time}", locale.t("stats.best")));
+                }
+            }
+            if let GameMode::Cheese(rows) = *mode {
+                ui.label(locale.t(rows.label_key()));
+                if *phase == Phase::Finished {
+                    let time = format_split(sprint.ticks);
+                    ui.label(format!("{}: {time}", locale.t("sprint.finished")));
+                }
+                if let Some(best) = scores.best_cheese(rows, *modifiers) {
+                    let time = format_split(best.ticks);
+                    ui.label(format!("{}: {time}", locale.t("stats.best")));
+                }
+            }
+            ui.label(format!("{}: {}", locale.t("stats.score"), score.points));
+            ui.label(format!("{}: {}", locale.t("stats.lines"), score.lines));
+            ui.label(format!("{}: {}", locale.t("stats.level"), score.level));
+            ui.label(format!("{}: {}", locale.t("stats.seed"), queue.seed()));
+            let best = scores.best(*mode, *modifiers, settings.scoring);
+            let race = matches!(*mode, GameMode::Sprint(_) | GameMode::Cheese(_));
+            if let (Some(best), false) = (best, race) {
+                ui.label(format!("{}: {}", locale.t("stats.best"), best.points));
+            }
+            let active: Vec<_> = modifiers.label_keys().map(|key| locale.t(key)).collect();
+            if !active.is_empty() {
+                ui.label(format!(
+                    "{}: {}",
+                    locale.t("modifier.title"),
+                    active.join(", ")
+                ));
+            }
+            if let Some(recorder) = &recorder {
+                let time = format_clock(recorder.0.length);
+                ui.label(format!("{}: {}", locale.t("stats.time"), time));
+            }
+            ui.separator();
+            if ui.button(locale.t("menu.save_card")).clicked() {
+                card.request();
+            }
+            match &card.status {
+                ShareStatus::Idle | ShareStatus::Requested => {}
+                ShareStatus::Saving => {
+                    ui.label(locale.t("share.saving"));
+                }
+                ShareStatus::Saved(path) => {
+                    ui.label(format!("{} {}", locale.t("share.saved"), path.display()));
+                }
+                ShareStatus::Failed(error) => {
+                    let message = format!("{}: {error}", locale.t("share.failed"));
+                    ui.colored_label(egui::Color32::LIGHT_RED, message);
+                }
+            }
+            if recorder.is_some() && ui.button(locale.t("menu.watch_replay")).clicked() {
+                next.watch.send(WatchReplay);
+            }
+            if ui.button(locale.t("menu.play_again")).clicked() {
+                next.new_game.send(NewGame::default());
+            }
+            if ui.button(locale.t("menu.retry_seed")).clicked() {
+                next.new_game.send(NewGame::retry(&queue));
+            }
+            if let Some(best) = best_runs.best(*mode, *modifiers, settings.scoring) {
+                if ui.button(locale.t("menu.race_best")).clicked() {
+                    next.new_game.send(NewGame {
+                        seed: Some(best.seed),
+                    });
+                    next.race.send(RaceGhost(best.clone()));
+                }
+            }
+            if ui.button(locale.t("menu.main_menu")).clicked() {
+                next.state.set(AppState::MainMenu);
+            }
+        });
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn settings_menu(
+    mut contexts: EguiContexts,
+    mut locale: ResMut<Locale>,
+    mut menu: ResMut<SettingsMenu>,
+    state: Res<State<AppState>>,
+    mut settings: ResMut<Settings>,
+    mut input: ResMut<InputConfig>,
+    mut layout_config: ResMut<LayoutConfig>,
+    layout: Res<BoardLayout>,
+    effects: Option<Res<SoundEffects>>,
+    tracks: Option<Res<MusicTracks>>,
+    preview: Option<Res<AudioChannel<PreviewChannel>>>,
+    keys: Res<Input<KeyCode>>,
+    mut bindings: ResMut<KeyBindings>,
+    mut rebinding: ResMut<Rebinding>,
+) {
+    // Edit a copy so `Settings` only reports a change when a value moves.
+    let mut edited = settings.clone();
+    let mut edited_layout = layout_config.clone();
+    let mut language = locale.language;
+    // A game's replay records its rules when it starts, so they can only
+    // change between games.
+    let rules_locked = *state.get() != AppState::MainMenu;
+    let t = |key| locale.t(key);
+    menu_window(t("settings.title")).show(contexts.ctx_mut(), |ui| {
+        egui::ComboBox::from_label(t("settings.language"))
+            .selected_text(language.name())
+            .show_ui(ui, |ui| {
+                for choice in Language::ALL {
+                    ui.selectable_value(&mut language, choice, choice.name());
+                }
+            });
+        // Without an audio device the sound plugins are left out.
+        let audio = effects.zip(tracks).zip(preview.as_ref());
+        ui.add_enabled_ui(audio.is_some(), |ui| {
+            ui.add(
+                egui::Slider::new(&mut edited.music_volume, 0.0..=1.0).text(t("settings.music")),
+            );
+            ui.add(
+                egui::Slider::new(&mut edited.sfx_volume, 0.0..=1.0).text(t("settings.effects")),
+            );
+        });
+        if audio.is_none() {
+            ui.label(t("settings.no_audio"));
+        }
+        ui.checkbox(
+            &mut edited.game_over_dissolve,
+            t("settings.game_over_dissolve"),
+        );
+        ui.checkbox(&mut edited.reduce_motion, t("settings.reduce_motion"));
+        ui.checkbox(&mut edited.kick_flash, t("settings.kick_flash"));
+        ui.add_enabled(
+            !edited.reduce_motion,
+            egui::Checkbox::new(&mut edited.clear_afterimage, t("settings.clear_afterimage")),
+        );
+        ui.checkbox(&mut edited.hold_preview, t("settings.hold_preview"));
+        let mut ghost_near = edited.ghost_near.is_some();
+        ui.checkbox(&mut ghost_near, t("settings.ghost_near"));
+        edited.ghost_near = ghost_near.then(|| {
+            let mut rows = edited.ghost_near.unwrap_or(GHOST_NEAR_ROWS);
+            ui.add(
+                egui::Slider::new(&mut rows, 1..=MAX_GHOST_NEAR_ROWS)
+                    .text(t("settings.ghost_near_rows")),
+            );
+            rows
+        });
+        ui.checkbox(&mut edited.piece_shadow, t("settings.piece_shadow"));
+        ui.checkbox(&mut edited.board_editor, t("settings.board_editor"));
+        ui.checkbox(&mut edited.audio_cues, t("settings.audio_cues"));
+        ui.checkbox(&mut edited.confirm_exit, t("settings.confirm_exit"));
+        ui.checkbox(&mut edited.ai_demo, t("settings.ai_demo"));
+        ui.checkbox(&mut edited.high_contrast, t("settings.high_contrast"));
+        ui.checkbox(&mut edited.low_detail, t("settings.low_detail"));
+        egui::ComboBox::from_label(t("settings.touch_controls"))
+            .selected_text(t(edited.touch_controls.label_key()))
+            .show_ui(ui, |ui| {
+                for choice in TouchOverlay::ALL {
+                    ui.selectable_value(&mut edited.touch_controls, choice, t(choice.label_key()));
+                }
+            });
+
+        ui.separator();
+        if rules_locked {
+            ui.label(t("settings.rules_locked"));
+        }
+        ui.add_enabled_ui(!rules_locked, |ui| {
+            ui.checkbox(
+                &mut edited.instant_line_clear,
+                t("settings.instant_line_clear"),
+            );
+            ui.checkbox(
+                &mut edited.queue.safe_first_piece,
+                t("settings.safe_first_piece"),
+            );
+            egui::ComboBox::from_label(t("settings.randomizer"))
+                .selected_text(t(edited.queue.randomizer.label_key()))
+                .show_ui(ui, |ui| {
+                    for choice in RandomizerKind::ALL {
+                        ui.selectable_value(
+                            &mut edited.queue.randomizer,
+                            choice,
+                            t(choice.label_key()),
+                        );
+                    }
+                });
+            if edited.queue.randomizer == RandomizerKind::SevenBag {
+                ui.add(
+                    egui::Slider::new(
+                        &mut edited.queue.bag_multiplier,
+                        1..=QueueRules::MAX_BAG_MULTIPLIER,
+                    )
+                    .text(t("settings.bag_multiplier")),
+                );
+            }
+            egui::ComboBox::from_label(t("settings.rotation_system"))
+                .selected_text(t(edited.rotation_system.label_key()))
+                .show_ui(ui, |ui| {
+                    for choice in RotationSystem::ALL {
+                        ui.selectable_value(
+                            &mut edited.rotation_system,
+                            choice,
+                            t(choice.label_key()),
+                        );
+                    }
+                });
+            egui::ComboBox::from_label(t("settings.top_out"))
+                .selected_text(t(edited.top_out.label_key()))
+                .show_ui(ui, |ui| {
+                    for choice in TopOutRule::ALL {
+                        ui.selectable_value(&mut edited.top_out, choice, t(choice.label_key()));
+                    }
+                });
+            ui.add_enabled(
+                edited.top_out.block_out(),
+                egui::Checkbox::new(&mut edited.spawn_shift_up, t("settings.spawn_shift_up")),
+            );
+            egui::ComboBox::from_label(t("settings.scoring"))
+                .selected_text(t(edited.scoring.label_key()))
+                .show_ui(ui, |ui| {
+                    for choice in ScoringRules::ALL {
+                        ui.selectable_value(&mut edited.scoring, choice, t(choice.label_key()));
+                    }
+                });
+            let mut soft_drop_cap = edited.soft_drop_cap.is_some();
+            ui.checkbox(&mut soft_drop_cap, t("settings.soft_drop_cap"));
+            edited.soft_drop_cap = soft_drop_cap.then(|| {
+                let mut cap = edited.soft_drop_cap.unwrap_or(SOFT_DROP_CAP);
+                ui.add(
+                    egui::Slider::new(&mut cap, 1..=MAX_SOFT_DROP_CAP)
+                        .text(t("settings.soft_drop_points")),
+                );
+                cap
+            });
+            ui.checkbox(&mut edited.freeplay, t("settings.freeplay"));
+            ui.checkbox(&mut edited.hard_drop_grace, t("settings.hard_drop_grace"));
+            egui::ComboBox::from_label(t("settings.lock_reset"))
+                .selected_text(t(edited.lock_reset.label_key()))
+                .show_ui(ui, |ui| {
+                    for choice in LockReset::ALL {
+                        ui.selectable_value(&mut edited.lock_reset, choice, t(choice.label_key()));
+                    }
+                });
+            let mut custom_gravity = edited.custom_gravity.is_some();
+            ui.checkbox(&mut custom_gravity, t("settings.custom_gravity"));
+            edited.custom_gravity = custom_gravity.then(|| {
+                let mut g = edited.custom_gravity.unwrap_or(1.0);
+                ui.add(
+                    egui::Slider::new(&mut g, MIN_GRAVITY..=MAX_GRAVITY)
+                        .logarithmic(true)
+                        .text(t("settings.gravity")),
+                );
+                g
+            });
+        });
+
+        ui.add(
+            egui::Slider::new(&mut edited.invisible_delay, 0.0..=MAX_INVISIBLE_DELAY)
+                .text(t("settings.invisible_delay")),
+        );
+        ui.add(
+            egui::Slider::new(&mut edited.active_emphasis, 0.0..=MAX_ACTIVE_EMPHASIS)
+                .text(t("settings.active_emphasis")),
+        );
+        ui.add(
+            egui::Slider::new(&mut edited.menu_repeat.delay, 1..=MenuRepeat::MAX_DELAY)
+                .text(t("settings.menu_repeat_delay")),
+        );
+        ui.add(
+            egui::Slider::new(
+                &mut edited.menu_repeat.interval,
+                1..=MenuRepeat::MAX_INTERVAL,
+            )
+            .text(t("settings.menu_repeat_interval")),
+        );
+        let mut auto_pause = edited.auto_pause.is_some();
+        ui.checkbox(&mut auto_pause, t("settings.auto_pause"));
+        edited.auto_pause = auto_pause.then(|| {
+            let mut seconds = edited.auto_pause.unwrap_or(DEFAULT_AUTO_PAUSE);
+            ui.add(
+                egui::Slider::new(&mut seconds, MIN_AUTO_PAUSE..=MAX_AUTO_PAUSE)
+                    .suffix(" s")
+                    .text(t("settings.auto_pause_after")),
+            );
+            seconds
+        });
+
+        ui.separator();
+        ui.checkbox(&mut edited_layout.tilt, t("settings.tilt"));
+        ui.checkbox(&mut edited_layout.show_buffer, t("settings.show_buffer"));
+        ui.checkbox(&mut edited_layout.hud.mirror, t("settings.mirror_hud"));
+        ui.checkbox(&mut edited_layout.hud.compact, t("settings.compact_hud"));
+        ui.add_enabled_ui(!edited_layout.hud.compact, |ui| {
+            let orientation = &mut edited_layout.hud.next_orientation;
+            egui::ComboBox::from_label(t("settings.next_orientation"))
+                .selected_text(t(orientation.label_key()))
+                .show_ui(ui, |ui| {
+                    for choice in NextOrientation::ALL {
+                        ui.selectable_value(orientation, choice, t(choice.label_key()));
+                    }
+                });
+        });
+        ui.checkbox(&mut edited_layout.hud.show_seed, t("settings.show_seed"));
+        egui::ComboBox::from_label(t("settings.framing"))
+            .selected_text(t(edited_layout.framing.label_key()))
+            .show_ui(ui, |ui| {
+                for choice in Framing::ALL {
+                    ui.selectable_value(&mut edited_layout.framing, choice, t(choice.label_key()));
+                }
+            });
+        let mut fit_window = edited_layout.cell_size.is_none();
+        ui.checkbox(&mut fit_window, t("settings.fit_board"));
+        edited_layout.cell_size = if fit_window {
+            None
+        } else {
+            let mut cell_size = edited_layout.cell_size.unwrap_or(layout.cell_size);
+            ui.add(
+                egui::Slider::new(&mut cell_size, MIN_CELL_SIZE..=MAX_CELL_SIZE)
+                    .step_by(1.0)
+                    .text(t("settings.cell_size")),
+            );
+            Some(cell_size)
+        };
+        ui.add(
+            egui::Slider::new(&mut edited_layout.margin, 0.0..=4.0)
+                .step_by(0.5)
+                .text(t("settings.margin")),
+        );
+
+        controls_section(ui, &locale, &keys, &mut bindings, &mut rebinding);
+        profile_section(
+            ui,
+            &locale,
+            &mut menu,
+            &mut edited,
+            &mut input,
+            rules_locked,
+        );
+
+        if let Some(((effects, tracks), preview)) = &audio {
+            egui::CollapsingHeader::new(t("settings.sound_test"))
+                .id_source("sound_test")
+                .show(ui, |ui| {
+                    egui::Grid::new("sound_test").show(ui, |ui| {
+                        for effect in SoundEffect::ALL {
+                            ui.label(t(effect.label_key()));
+                            if ui.button(t("settings.play")).clicked() {
+                                preview
+                                    .play(effects.get(effect))
+                                    .with_volume(f64::from(edited.sfx_volume));
+                            }
+                            ui.end_row();
+                        }
+                        for track in MusicTrack::ALL {
+                            ui.label(format!("{}: {}", t("settings.music"), t(track.label_key())));
+                            if ui.button(t("settings.play")).clicked() {
+                                preview.stop();
+                                preview
+                                    .play(tracks.get(track))
+                                    .with_volume(f64::from(edited.music_volume));
+                            }
+                            ui.end_row();
+                        }
+                    });
+                    if ui.button(t("settings.stop_all")).clicked() {
+                        preview.stop();
+                    }
+                });
+        }
+
+        ui.separator();
+        ui.vertical_centered(|ui| {
+            if ui.button(t("settings.back")).clicked() {
+                if let Some(preview) = &preview {
+                    preview.stop();
+                }
+                *rebinding = Rebinding::default();
+                menu.open = false;
+            }
+        });
+    });
+    settings.set_if_neq(edited);
+    layout_config.set_if_neq(edited_layout);
+    if locale.language != language {
+        locale.language = language;
+    }
+}
+
+/// The key bound to each action, with a button to rebind it. A key that is
+/// already taken is refused with a warning, offering to swap the two.
+/// Exporting the handling profile, and importing one between games.
+fn profile_section(
+    ui: &mut egui::Ui,
+    locale: &Locale,
+    menu: &mut SettingsMenu,
+    settings: &mut Settings,
+    input: &mut ResMut<InputConfig>,
+    rules_locked: bool,
+) {
+    let t = |key| locale.t(key);
+    egui::CollapsingHeader::new(t("settings.profile"))
+        .id_source("profile")
+        .show(ui, |ui| {
+            ui.text_edit_singleline(&mut menu.profile);
+            ui.horizontal(|ui| {
+                if ui.button(t("profile.export")).clicked() {
+                    menu.profile = HandlingProfile::current(input, settings).encode();
+                    menu.profile_error = None;
+                    ui.output_mut(|output| output.copied_text = menu.profile.clone());
+                }
+                let import = ui.add_enabled(!rules_locked, egui::Button::new(t("profile.import")));
+                if import.clicked() {
+                    match HandlingProfile::decode(&menu.profile) {
+                        Ok(profile) => {
+                            let mut imported = input.clone();
+                            profile.apply(&mut imported, settings);
+                            input.set_if_neq(imported);
+                            menu.profile_error = None;
+                        }
+                        Err(error) => menu.profile_error = Some(error.to_string()),
+                    }
+                }
+            });
+            ui.add_enabled_ui(!rules_locked, |ui| {
+                let mut opposing = input.opposing;
+                egui::ComboBox::from_label(t("settings.opposing"))
+                    .selected_text(t(opposing.label_key()))
+                    .show_ui(ui, |ui| {
+                        for choice in OpposingDirections::ALL {
+                            ui.selectable_value(&mut opposing, choice, t(choice.label_key()));
+                        }
+                    });
+                if opposing != input.opposing {
+                    input.opposing = opposing;
+                }
+            });
+            if let Some(error) = &menu.profile_error {
+                ui.colored_label(
+                    egui::Color32::LIGHT_RED,
+                    format!("{} {error}", t("profile.invalid")),
+                );
+            }
+        });
+}
+
+fn controls_section(
+    ui: &mut egui::Ui,
+    locale: &Locale,
+    keys: &Input<KeyCode>,
+    bindings: &mut KeyBindings,
+    rebinding: &mut Rebinding,
+) {
+    let t = |key| locale.t(key);
+    if let (Some(action), None) = (rebinding.action, rebinding.conflict) {
+        if let Some(&key) = keys.get_just_pressed().next() {
+            match bindings.bind(action, key) {
+                Ok(()) => rebinding.action = None,
+                Err(BindError::Taken(other)) => rebinding.conflict = Some((key, other)),
+            }
+        }
+    }
+    egui::CollapsingHeader::new(t("settings.controls"))
+        .id_source("controls")
+        .show(ui, |ui| {
+            egui::Grid::new("controls").show(ui, |ui| {
+                for action in Action::ALL {
+                    ui.label(t(action.label_key()));
+                    let bound: Vec<_> = bindings
+                        .keys(action)
+                        .iter()
+                        .map(|key| format!("{key:?}"))
+                        .collect();
+                    let text = if rebinding.action == Some(action) {
+                        t("controls.press_key").to_owned()
+                    } else {
+                        bound.join(", ")
+                    };
+                    if ui.button(text).clicked() {
+                        *rebinding = Rebinding {
+                            action: Some(action),
+                            conflict: None,
+                        };
+                    }
+                    ui.end_row();
+                }
+            });
+            if let (Some(action), Some((key, other))) = (rebinding.action, rebinding.conflict) {
+                ui.colored_label(
+                    egui::Color32::LIGHT_RED,
+                    format!("{key:?} {} {}", t("controls.taken"), t(other.label_key())),
+                );
+                ui.horizontal(|ui| {
+                    if ui.button(t("controls.swap")).clicked() {
+                        bindings.swap(action, key);
+                        *rebinding = Rebinding::default();
+                    }
+                    if ui.button(t("controls.cancel")).clicked() {
+                        *rebinding = Rebinding::default();
+                    }
+                });
+            }
+            if ui.button(t("controls.reset")).clicked() {
+                *bindings = KeyBindings::default();
+                *rebinding = Rebinding::default();
+            }
+        });
+}
+
+/// Puts the finished game on the high-score table, and a completed race's
+/// time on its table, and saves them if either ranks. A game that beats the
+/// mode's best has its recording kept to race.
+#[allow(clippy::too_many_arguments)]
+fn record_high_score(
+    recorder: Option<Res<ReplayRecorder>>,
+    mut best_runs: ResMut<BestRuns>,
+    score: Res<Score>,
+    queue: Res<PieceQueue>,
+    mode: Res<GameMode>,
+    modifiers: Res<Modifiers>,
+    settings: Res<Settings>,
+    phase: Res<Phase>,
+    sprint: Res<Sprint>,
+    mut scores: ResMut<HighScores>,
+) {
+    // Player two's garbage makes a versus score no measure of anything.
+    if *mode == GameMode::Versus {
+        return;
+    }
+    let fastest = match *mode {
+        GameMode::Sprint(goal) if *phase == Phase::Finished => scores.record_sprint(SprintRecord {
+            goal,
+            modifiers: *modifiers,
+            ticks: sprint.ticks,
+            seed: queue.seed(),
+        }),
+        GameMode::Cheese(rows) if *phase == Phase::Finished => scores.record_cheese(CheeseRecord {
+            rows,
+            modifiers: *modifiers,
+            ticks: sprint.ticks,
+            seed: queue.seed(),
+        }),
+        _ => false,
+    };
+    let entry = HighScoreEntry {
+        points: score.points,
+        lines: score.lines,
+        level: score.level,
+        seed: queue.seed(),
+        mode: *mode,
+        modifiers: *modifiers,
+        randomizer: settings.queue.randomizer,
+        scoring: settings.scoring,
+        recorded_at: timestamp(),
+        pieces: score.pieces,
+    };
+    let ranked = scores.insert(entry.clone());
+    if ranked || fastest {
+        if let Err(error) = scores.save() {
+            warn!("could not save the high scores: {error}");
+        }
+    }
+    // Races are won on time, and every other mode on points.
+    let personal_best = match *mode {
+        GameMode::Sprint(_) | GameMode::Cheese(_) => fastest,
+        _ => scores.best(*mode, *modifiers, settings.scoring) == Some(&entry),
+    };
+    if let Some(recorder) = recorder.filter(|_| personal_best) {
+        best_runs.keep(recorder.0.clone());
+        if let Err(error) = best_runs.save() {
+            warn!("could not save the best run: {error}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    #[test]
+    fn test_holding_down_repeats_through_a_menu_at_the_set_rate() {
+        let tick = Duration::from_secs_f64(1.0 / TICKS_PER_SECOND);
+        let repeat = MenuRepeat {
+            delay: 6,
+            interval: 3,
+        };
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<MenuHold>()
+            .insert_resource(Settings {
+                menu_repeat: repeat,
+                ..default()
+            })
+            .insert_resource(TimeUpdateStrategy::ManualDuration(tick))
+            .add_systems(Update, repeat_menu_keys);
+        let window = app.world.spawn(EguiInput::default()).id();
+        app.update();
+
+        app.world
+            .resource_mut::<Input<KeyCode>>()
+            .press(KeyCode::Down);
+        let mut repeated_on = Vec::new();
+        for frame in 0..=repeat.delay + 4 * repeat.interval {
+            app.update();
+            let mut input = app.world.get_mut::<EguiInput>(window).unwrap();
+            for event in input.0.events.drain(..) {
+                assert!(matches!(
+                    event,
+                    egui::Event::Key {
+                        key: egui::Key::ArrowDown,
+                        pressed: true,
+                        ..
+                    }
+                ));
+                repeated_on.push(frame);
+            }
+        }
+        assert_eq!(repeated_on, [6, 9, 12, 15, 18]);
+
+        app.world
+            .resource_mut::<Input<KeyCode>>()
+            .release(KeyCode::Down);
+        for _ in 0..repeat.delay * 2 {
+            app.update();
+        }
+        assert!(app
+            .world
+            .get::<EguiInput>(window)
+            .unwrap()
+            .0
+            .events
+            .is_empty());
+    }
+}
@@ -0,0 +1,322 @@
+//! Reviewing the game just played: the recording plays back under
+//! VCR-style controls for pausing, stepping a placement at a time and
+//! changing the speed.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::components::tetromino::PieceQueue;
+use crate::game::{reset_game, AppState};
+use crate::replay::{seek, Replay, ReplayPlayback, ReplayRecorder, ReplayRules};
+use crate::utils::i18n::Locale;
+
+/// Slowest and fastest playback speeds offered.
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 4.0;
+
+/// Starts reviewing the last recorded game.
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct WatchReplay;
+
+/// The player's rules, put back once the review ends.
+#[derive(Resource, Debug)]
+struct SavedRules(Replay);
+
+/// A placement to seek to, set by the controls and carried out once the
+/// frame's egui pass is done.
+#[derive(Resource, Debug, Default)]
+struct SeekRequest(Option<u32>);
+
+pub struct ReviewPlugin;
+
+impl Plugin for ReviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<WatchReplay>()
+            .init_resource::<SeekRequest>()
+            .add_systems(OnExit(AppState::Review), end_review)
+            .add_systems(
+                Update,
+                (
+                    start_review.run_if(on_event::<WatchReplay>()),
+                    (review_controls, apply_seek, stay_in_review, sync_speed)
+                        .chain()
+                        .run_if(in_state(AppState::Review)),
+                ),
+            );
+    }
+}
+
+fn start_review(
+    mut commands: Commands,
+    mut events: EventReader<WatchReplay>,
+    recorder: Option<Res<ReplayRecorder>>,
+    mut rules: ReplayRules,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    events.clear();
+    let Some(recorder) = recorder else {
+        return;
+    };
+    let replay = recorder.0.clone();
+    commands.insert_resource(SavedRules(rules.apply(&replay)));
+    reset_game(&mut commands, replay.piece_queue());
+    commands.insert_resource(ReplayPlayback::new(replay));
+    next_state.set(AppState::Review);
+}
+
+fn review_controls(
+    mut contexts: EguiContexts,
+    locale: Res<Locale>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut request: ResMut<SeekRequest>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    egui::Window::new(locale.t("review.title"))
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -16.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            ui.label(format!(
+                "{}: {}",
+                locale.t("review.placement"),
+                playback.placements
+            ));
+            ui.horizontal(|ui| {
+                let back = ui.add_enabled(playback.placements > 0, egui::Button::new("⏮"));
+                if back.on_hover_text(locale.t("review.step_back")).clicked() {
+                    request.0 = Some(playback.placements - 1);
+                }
+                let toggle = if playback.paused {
+                    locale.t("review.play")
+                } else {
+                    locale.t("review.pause")
+                };
+                let finished = playback.is_finished();
+                if ui
+                    .add_enabled(!finished, egui::Button::new(toggle))
+                    .clicked()
+                {
+                    playback.paused = !playback.paused;
+                }
+                let forward = ui.add_enabled(!finished, egui::Button::new("⏭"));
+                if forward
+                    .on_hover_text(locale.t("review.step_forward"))
+                    .clicked()
+                {
+                    request.0 = Some(playback.placements + 1);
+                }
+            });
+            ui.add(
+                egui::Slider::new(&mut playback.speed, MIN_SPEED..=MAX_SPEED)
+                    .logarithmic(true)
+                    .suffix("×")
+                    .text(locale.t("review.speed")),
+            );
+            if ui.button(locale.t("menu.main_menu")).clicked() {
+                next_state.set(AppState::MainMenu);
+            }
+        });
+}
+
+fn apply_seek(world: &mut World) {
+    if let Some(placement) = world.resource_mut::<SeekRequest>().0.take() {
+        seek(world, placement);
+    }
+}
+
+/// Keeps the replay's own top-out from ending the review.
+fn stay_in_review(mut next_state: ResMut<NextState<AppState>>) {
+    if matches!(
+        next_state.0,
+        Some(AppState::Dissolving | AppState::GameOver)
+    ) {
+        next_state.0 = None;
+    }
+}
+
+fn sync_speed(playback: Res<ReplayPlayback>, mut time: ResMut<Time<Virtual>>) {
+    if playback.is_changed() && time.relative_speed() != playback.speed {
+        time.set_relative_speed(playback.speed);
+    }
+}
+
+/// Puts back the player's rules and leaves a clean game behind.
+fn end_review(
+    mut commands: Commands,
+    saved: Option<Res<SavedRules>>,
+    mut rules: ReplayRules,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    if let Some(saved) = saved {
+        rules.apply(&saved.0);
+        commands.remove_resource::<SavedRules>();
+    }
+    time.set_relative_speed(1.0);
+    commands.remove_resource::<ReplayPlayback>();
+    reset_game(&mut commands, PieceQueue::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::components::board::Board;
+    use crate::components::scoring::Score;
+    use crate::game::{ActivePiece, GameMode, GameplayEvent};
+    use crate::input::keyboard::Action;
+    use crate::replay::tests::playback_app;
+    use crate::replay::SNAPSHOT_INTERVAL;
+    use crate::ui::attract::demo_replay;
+    use crate::utils::checksum::state_hash;
+
+    /// Plays on until the replay runs out, returning the board and score it
+    /// ends on.
+    fn play_out(app: &mut App) -> (Board, Score) {
+        while !app.world.resource::<ReplayPlayback>().paused {
+            app.world.run_schedule(FixedUpdate);
+        }
+        (
+            app.world.resource::<Board>().clone(),
+            app.world.resource::<Score>().clone(),
+        )
+    }
+
+    #[test]
+    fn test_pausing_mid_replay_ends_on_the_same_board() {
+        let expected = play_out(&mut playback_app(demo_replay(), AppState::Review));
+
+        let mut app = playback_app(demo_replay(), AppState::Review);
+        for _ in 0..500 {
+            app.world.run_schedule(FixedUpdate);
+        }
+        assert!(!app.world.resource::<ReplayPlayback>().is_finished());
+        app.world.resource_mut::<ReplayPlayback>().paused = true;
+        let tick = app.world.resource::<ReplayPlayback>().tick;
+        let board = app.world.resource::<Board>().clone();
+
+        // Two seconds of frames go by, fixed ticks and all, while paused.
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+            1.0 / 60.0,
+        )));
+        for _ in 0..120 {
+            app.update();
+        }
+        assert_eq!(app.world.resource::<ReplayPlayback>().tick, tick);
+        assert_eq!(*app.world.resource::<Board>(), board);
+
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO));
+        app.update();
+        app.world.resource_mut::<ReplayPlayback>().paused = false;
+        assert_eq!(play_out(&mut app), expected);
+    }
+
+    /// A Zen replay on seed 5 tapping each of `taps` in turn, held for one
+    /// tick and let go for three.
+    pub(crate) fn zen_replay(taps: impl IntoIterator<Item = Action>) -> Replay {
+        let mut replay = Replay::new(5, GameMode::Zen, default(), &default(), &default());
+        replay.record(0);
+        for action in taps {
+            replay.record(1 << action as u16);
+            for _ in 0..3 {
+                replay.record(0);
+            }
+        }
+        replay
+    }
+
+    /// Taps that drop `count` pieces, each pushed out to the left or right
+    /// wall in turn so the stack stays low.
+    pub(crate) fn drops(count: usize) -> Vec<Action> {
+        (0..count)
+            .flat_map(|piece| {
+                let side = if piece % 2 == 0 {
+                    Action::MoveLeft
+                } else {
+                    Action::MoveRight
+                };
+                [side, side, side, side, Action::HardDrop]
+            })
+            .collect()
+    }
+
+    /// The checksum of the board, score, queue and piece in play.
+    pub(crate) fn checksum(app: &App) -> u64 {
+        state_hash(
+            app.world.resource::<Board>(),
+            app.world.resource::<Score>(),
+            app.world.resource::<PieceQueue>(),
+            app.world.resource::<ActivePiece>().0.as_ref(),
+        )
+    }
+
+    #[test]
+    fn test_seeking_back_past_undos_ends_where_straight_playback_does() {
+        // Undo back past the first snapshot, then play on.
+        let mut taps = drops(SNAPSHOT_INTERVAL as usize + 4);
+        taps.extend([Action::Undo; 8]);
+        taps.extend(drops(4));
+        let replay = zen_replay(taps);
+
+        let mut straight = playback_app(replay.clone(), AppState::Review);
+        play_out(&mut straight);
+        let expected = checksum(&straight);
+        assert!(straight.world.resource::<ReplayPlayback>().placements > SNAPSHOT_INTERVAL);
+
+        let mut app = playback_app(replay, AppState::Review);
+        seek(&mut app.world, u32::MAX);
+        assert_eq!(checksum(&app), expected);
+        seek(&mut app.world, SNAPSHOT_INTERVAL + 2);
+        seek(&mut app.world, u32::MAX);
+        assert_eq!(checksum(&app), expected);
+    }
+
+    #[test]
+    fn test_seeking_back_past_a_checkpoint_resets_to_the_one_then_set() {
+        // The reset after the first snapshot goes back to the first
+        // checkpoint, though the second is the one set at the end.
+        let mut taps = vec![Action::SetCheckpoint];
+        taps.extend(drops(SNAPSHOT_INTERVAL as usize + 2));
+        taps.push(Action::ResetCheckpoint);
+        taps.extend(drops(1));
+        taps.push(Action::SetCheckpoint);
+        taps.extend(drops(2));
+        taps.push(Action::ResetCheckpoint);
+        taps.extend(drops(2));
+        let replay = zen_replay(taps);
+
+        let mut straight = playback_app(replay.clone(), AppState::Review);
+        play_out(&mut straight);
+        let expected = checksum(&straight);
+
+        let mut app = playback_app(replay, AppState::Review);
+        seek(&mut app.world, u32::MAX);
+        assert_eq!(checksum(&app), expected);
+        seek(&mut app.world, SNAPSHOT_INTERVAL + 1);
+        seek(&mut app.world, u32::MAX);
+        assert_eq!(checksum(&app), expected);
+    }
+
+    #[test]
+    fn test_seeking_back_rebuilds_the_board() {
+        let mut app = playback_app(demo_replay(), AppState::Review);
+
+        // A placement between snapshots, so seeking has to replay inputs.
+        let target = 13;
+        seek(&mut app.world, target);
+        let expected = app.world.resource::<Board>().clone();
+        seek(&mut app.world, u32::MAX);
+        let playback = app.world.resource::<ReplayPlayback>();
+        assert!(playback.is_finished());
+        assert!(playback.placements > target);
+
+        seek(&mut app.world, target);
+        assert_eq!(app.world.resource::<ReplayPlayback>().placements, target);
+        assert_eq!(*app.world.resource::<Board>(), expected);
+        let events = app.world.resource::<Events<GameplayEvent>>();
+        let pending: Vec<_> = events.get_reader().read(events).copied().collect();
+        assert_eq!(pending, [GameplayEvent::Reset]);
+    }
+}
@@ -0,0 +1,160 @@
+//! Saving the game-over recap as an image to share.
+//!
+//! The recap is an egui window, so the card is a screenshot of the window.
+//! It is requested in `PostUpdate`, after the menus have laid out the recap
+//! for the frame being captured.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use bevy::prelude::*;
+use bevy::render::view::screenshot::ScreenshotManager;
+use bevy::window::PrimaryWindow;
+
+use crate::components::scoring::Score;
+use crate::components::tetromino::PieceQueue;
+use crate::game::AppState;
+use crate::utils::config::TICKS_PER_SECOND;
+use crate::utils::storage;
+
+/// Subdirectory of the data directory the cards are saved in.
+const CARD_FOLDER: &str = "cards";
+
+/// Progress of the share card for the current game-over screen.
+#[derive(Resource, Debug, Default)]
+pub struct ShareCard {
+    pub status: ShareStatus,
+    /// Filled in from the screenshot thread once the file is written.
+    result: Arc<Mutex<Option<Result<PathBuf, String>>>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ShareStatus {
+    #[default]
+    Idle,
+    /// Capture at the end of this frame.
+    Requested,
+    Saving,
+    Saved(PathBuf),
+    Failed(String),
+}
+
+impl ShareCard {
+    pub fn request(&mut self) {
+        if !matches!(self.status, ShareStatus::Requested | ShareStatus::Saving) {
+            self.status = ShareStatus::Requested;
+        }
+    }
+}
+
+/// A game length in ticks as `minutes:seconds`.
+pub fn format_clock(ticks: u32) -> String {
+    let seconds = (f64::from(ticks) / TICKS_PER_SECOND) as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// A game length in ticks as `minutes:seconds.hundredths`, for races.
+pub fn format_split(ticks: u32) -> String {
+    let hundredths = (f64::from(ticks) * 100.0 / TICKS_PER_SECOND) as u32;
+    format!(
+        "{}:{:02}.{:02}",
+        hundredths / 6000,
+        hundredths / 100 % 60,
+        hundredths % 100
+    )
+}
+
+pub struct SharePlugin;
+
+impl Plugin for SharePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ShareCard>()
+            .add_systems(OnEnter(AppState::GameOver), reset_card)
+            .add_systems(
+                PostUpdate,
+                (capture_card, collect_result).run_if(in_state(AppState::GameOver)),
+            );
+    }
+}
+
+fn reset_card(mut card: ResMut<ShareCard>) {
+    *card = ShareCard::default();
+}
+
+fn capture_card(
+    mut card: ResMut<ShareCard>,
+    score: Res<Score>,
+    queue: Res<PieceQueue>,
+    windows: Query<Entity, With<PrimaryWindow>>,
+    mut screenshots: ResMut<ScreenshotManager>,
+) {
+    if card.status != ShareStatus::Requested {
+        return;
+    }
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    let name = format!("rustblocks-{}-{}.png", queue.seed(), score.points);
+    let path = match storage::file_path(CARD_FOLDER, &name) {
+        Ok(path) => path,
+        Err(error) => {
+            card.status = ShareStatus::Failed(error.to_string());
+            return;
+        }
+    };
+    let result = card.result.clone();
+    let requested = screenshots.take_screenshot(window, move |image| {
+        let saved = image
+            .try_into_dynamic()
+            .map_err(|error| error.to_string())
+            .and_then(|image| {
+                image
+                    .to_rgb8()
+                    .save(&path)
+                    .map_err(|error| error.to_string())
+            })
+            .map(|()| path);
+        *result
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(saved);
+    });
+    card.status = match requested {
+        Ok(()) => ShareStatus::Saving,
+        Err(_) => ShareStatus::Failed("a screenshot is already being taken".into()),
+    };
+}
+
+fn collect_result(mut card: ResMut<ShareCard>) {
+    let finished = card
+        .result
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .take();
+    match finished {
+        Some(Ok(path)) => {
+            info!("Saved result image to {}", path.display());
+            card.status = ShareStatus::Saved(path);
+        }
+        Some(Err(error)) => {
+            warn!("Could not save result image: {error}");
+            card.status = ShareStatus::Failed(error);
+        }
+        None => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_shows_minutes_and_seconds() {
+        assert_eq!(format_clock(0), "0:00");
+        assert_eq!(format_clock(59), "0:00");
+        assert_eq!(format_clock(60 * 75), "1:15");
+        assert_eq!(format_clock(60 * 3600), "60:00");
+        assert_eq!(format_split(0), "0:00.00");
+        assert_eq!(format_split(30), "0:00.50");
+        assert_eq!(format_split(60 * 75 + 6), "1:15.10");
+    }
+}
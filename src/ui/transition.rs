@@ -0,0 +1,206 @@
+//! Fades through black between screens.
+//!
+//! State changes are requested through `NextState<AppState>` as usual.
+//! Requests that enter or leave a menu screen are caught before Bevy applies
+//! them and replayed at the darkest point of a [`Transition`], so every
+//! caller gets the fade without knowing about it. Pausing and the top-out
+//! dissolve still switch at once.
+
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::game::{AppState, TickSet};
+use crate::utils::config::Settings;
+
+/// Length of a whole fade out and back in, in seconds.
+const TRANSITION_SECS: f32 = 0.3;
+
+/// A fade in progress towards `target`.
+#[derive(Component, Debug)]
+pub struct Transition {
+    pub target: AppState,
+    timer: Timer,
+    /// Whether `target` has been entered, at the midpoint.
+    swapped: bool,
+}
+
+impl Transition {
+    pub fn new(target: AppState) -> Self {
+        Self {
+            target,
+            timer: Timer::from_seconds(TRANSITION_SECS, TimerMode::Once),
+            swapped: false,
+        }
+    }
+
+    /// Opacity of the black overlay, peaking at the midpoint.
+    fn opacity(&self) -> f32 {
+        1.0 - (2.0 * self.timer.percent() - 1.0).abs()
+    }
+}
+
+/// Whether moving from `from` to `to` fades rather than cuts.
+fn is_animated(from: AppState, to: AppState) -> bool {
+    let menus = [AppState::MainMenu, AppState::GameOver];
+    from != to && (menus.contains(&from) || menus.contains(&to))
+}
+
+fn no_transition(transitions: Query<(), With<Transition>>) -> bool {
+    transitions.is_empty()
+}
+
+pub struct TransitionPlugin;
+
+impl Plugin for TransitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            FixedUpdate,
+            (TickSet::Input, TickSet::Simulate).run_if(no_transition),
+        )
+        .add_systems(
+            PreUpdate,
+            ignore_input
+                .after(bevy::input::InputSystem)
+                .run_if(not(no_transition)),
+        )
+        .add_systems(Update, (advance_transition, draw_transition).chain())
+        .add_systems(PostUpdate, begin_transition);
+    }
+}
+
+/// Holds back a requested state change that should fade, starting a
+/// [`Transition`] to it instead.
+fn begin_transition(
+    mut commands: Commands,
+    settings: Res<Settings>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut transitions: Query<&mut Transition>,
+) {
+    let Some(target) = next_state.0 else {
+        return;
+    };
+    match transitions.get_single_mut() {
+        // Past the midpoint the screen is clearing, so let changes through.
+        Ok(transition) if transition.swapped => {}
+        Ok(mut transition) => {
+            transition.target = target;
+            next_state.0 = None;
+        }
+        Err(_) if !settings.effects().motion || !is_animated(*state.get(), target) => {}
+        Err(_) => {
+            commands.spawn(Transition::new(target));
+            next_state.0 = None;
+        }
+    }
+}
+
+fn advance_transition(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut transitions: Query<(Entity, &mut Transition)>,
+) {
+    for (entity, mut transition) in &mut transitions {
+        transition.timer.tick(time.delta());
+        if !transition.swapped && transition.timer.percent() >= 0.5 {
+            transition.swapped = true;
+            next_state.set(transition.target);
+        }
+        if transition.timer.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Drops every key and button press while a fade is running, so menus
+/// and gameplay can't be triggered twice mid-transition.
+fn ignore_input(mut keys: ResMut<Input<KeyCode>>, mut mouse: ResMut<Input<MouseButton>>) {
+    keys.reset_all();
+    mouse.reset_all();
+}
+
+/// Covers the whole window, egui menus included, and swallows clicks.
+fn draw_transition(mut contexts: EguiContexts, transitions: Query<&Transition>) {
+    let Ok(transition) = transitions.get_single() else {
+        return;
+    };
+    let ctx = contexts.ctx_mut();
+    let screen = ctx.screen_rect();
+    let alpha = (transition.opacity() * 255.0).round() as u8;
+    egui::Area::new("transition")
+        .order(egui::Order::Foreground)
+        .fixed_pos(screen.min)
+        .show(ctx, |ui| {
+            ui.allocate_rect(screen, egui::Sense::click_and_drag());
+            ui.painter()
+                .rect_filled(screen, 0.0, egui::Color32::from_black_alpha(alpha));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    const STEP_SECS: f32 = 0.05;
+
+    fn transition_app(reduce_motion: bool) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_state::<AppState>()
+            .insert_resource(Settings {
+                reduce_motion,
+                ..default()
+            })
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+                STEP_SECS,
+            )))
+            .add_systems(Update, advance_transition)
+            .add_systems(PostUpdate, begin_transition);
+        app.update();
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        // Finish the frame as if a menu had made the request during Update.
+        app.world.run_schedule(PostUpdate);
+        app
+    }
+
+    fn state(app: &App) -> AppState {
+        *app.world.resource::<State<AppState>>().get()
+    }
+
+    #[test]
+    fn test_state_change_waits_for_the_fade_midpoint() {
+        let mut app = transition_app(false);
+        let mut updates = 0;
+        while state(&app) == AppState::MainMenu {
+            app.update();
+            updates += 1;
+            assert!(updates < 100, "never entered the target state");
+        }
+        let elapsed = updates as f32 * STEP_SECS;
+        assert!(
+            elapsed >= TRANSITION_SECS / 2.0,
+            "switched after {elapsed}s"
+        );
+
+        for _ in 0..(TRANSITION_SECS / STEP_SECS) as usize {
+            app.update();
+        }
+        let mut transitions = app.world.query::<&Transition>();
+        assert_eq!(transitions.iter(&app.world).count(), 0);
+        assert_eq!(state(&app), AppState::Playing);
+    }
+
+    #[test]
+    fn test_reduce_motion_switches_at_once() {
+        let mut app = transition_app(true);
+        app.update();
+        assert_eq!(state(&app), AppState::Playing);
+    }
+}
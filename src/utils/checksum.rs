@@ -0,0 +1,146 @@
+//! Checksums of the deterministic game state, for catching desyncs.
+//!
+//! Two runs given the same seed, rules and inputs must reach the same
+//! state on every tick, and [`state_hash`] boils that state down to a
+//! number the two can compare: a replay against the recording, or one side
+//! of a rollback match against the other. It hashes with 64-bit FNV-1a over
+//! integers written in a fixed byte order, never floats or anything laid
+//! out by the standard library's hasher, so the value is the same on every
+//! platform and Rust release.
+
+use bevy::prelude::*;
+
+use crate::components::board::Board;
+use crate::components::scoring::Score;
+use crate::components::tetromino::{PieceQueue, Tetromino};
+use crate::game::ActivePiece;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// 64-bit FNV-1a.
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_i32(&mut self, value: i32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+}
+
+/// A stable hash of the board's cells, the score, how far into the queue
+/// play has got and the piece in play.
+pub fn state_hash(
+    board: &Board,
+    score: &Score,
+    queue: &PieceQueue,
+    active: Option<&Tetromino>,
+) -> u64 {
+    let mut hash = Fnv(FNV_OFFSET);
+    hash.write_u32(board.width() as u32);
+    hash.write_u32(board.height() as u32);
+    for y in 0..board.height() as i32 {
+        for x in 0..board.width() as i32 {
+            // Empty is 0, so a block of kind 0 still differs from no block.
+            let cell = board.get(x, y).map_or(0, |cell| cell as u32 + 1);
+            hash.write_u32(cell);
+        }
+    }
+    for value in [score.points, score.lines, score.level, score.pieces] {
+        hash.write_u32(value);
+    }
+    hash.write_u64(queue.seed());
+    hash.write_u64(queue.dealt());
+    for kind in queue.peek(PieceQueue::PREVIEW_LEN) {
+        hash.write_u32(kind.index() as u32);
+    }
+    match active {
+        Some(piece) => {
+            hash.write(&[
+                1,
+                piece.kind.index() as u8,
+                piece.rotation,
+                piece.system as u8,
+            ]);
+            hash.write_i32(piece.x);
+            hash.write_i32(piece.y);
+        }
+        None => hash.write(&[0]),
+    }
+    hash.0
+}
+
+/// The [`state_hash`] at the end of the last tick.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateHash(pub u64);
+
+/// Updates [`StateHash`] once the tick's simulation is done.
+pub fn hash_state(
+    board: Res<Board>,
+    score: Res<Score>,
+    queue: Res<PieceQueue>,
+    active: Res<ActivePiece>,
+    mut hash: ResMut<StateHash>,
+) {
+    hash.0 = state_hash(&board, &score, &queue, active.0.as_ref());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use crate::game::{AppState, GamePlugin};
+    use crate::input::keyboard::{Action, ActionEvent};
+
+    /// The hash after each of `ticks` ticks of a game on seed 3, with
+    /// `action` tapped on the ticks `taps` picks.
+    fn hashes(ticks: u32, action: Action, taps: impl Fn(u32) -> bool) -> Vec<u64> {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+            .insert_resource(PieceQueue::with_seed(3, default()));
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+        (0..ticks)
+            .map(|tick| {
+                if taps(tick) {
+                    app.world.send_event(ActionEvent::press(action));
+                    app.world.send_event(ActionEvent::release(action));
+                }
+                app.world.run_schedule(FixedUpdate);
+                app.world.resource::<StateHash>().0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_seed_and_inputs_hash_the_same_every_tick() {
+        let drops = |tick| tick % 7 == 3;
+        let first = hashes(200, Action::HardDrop, drops);
+        assert_eq!(first, hashes(200, Action::HardDrop, drops));
+        assert!(first.windows(2).any(|pair| pair[0] != pair[1]));
+
+        let diverged = hashes(200, Action::HardDrop, |tick| drops(tick) || tick == 50);
+        assert_eq!(first[..50], diverged[..50]);
+        assert_ne!(first[50], diverged[50]);
+    }
+}
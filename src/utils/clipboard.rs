@@ -0,0 +1,41 @@
+//! The system clipboard, where the `clipboard` feature brings one in.
+//!
+//! Builds without the feature, and sessions with no clipboard to reach,
+//! log why nothing was copied and carry on.
+
+use bevy::prelude::*;
+
+/// The connection to the system clipboard, opened on the first copy.
+///
+/// It's kept open afterwards because on X11 what was copied only lasts as
+/// long as the connection that copied it.
+#[derive(Resource, Default)]
+pub struct Clipboard {
+    #[cfg(feature = "clipboard")]
+    system: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    /// Puts `text` on the clipboard, returning whether it got there.
+    #[cfg(feature = "clipboard")]
+    pub fn copy(&mut self, text: &str) -> bool {
+        let copied = match &mut self.system {
+            Some(system) => system.set_text(text),
+            None => arboard::Clipboard::new().and_then(|mut system| {
+                system.set_text(text)?;
+                self.system = Some(system);
+                Ok(())
+            }),
+        };
+        copied
+            .map_err(|error| warn!("couldn't copy to the clipboard: {error}"))
+            .is_ok()
+    }
+
+    /// Logs that there's no clipboard in this build, and copies nothing.
+    #[cfg(not(feature = "clipboard"))]
+    pub fn copy(&mut self, text: &str) -> bool {
+        info!("built without the clipboard feature, so {text:?} wasn't copied");
+        false
+    }
+}
@@ -0,0 +1,742 @@
+//! Game configuration.
+//!
+//! Gameplay runs on a fixed tick, so every duration here is measured in
+//! ticks rather than seconds to keep the simulation deterministic.
+
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::scoring::{ScoringRules, SOFT_DROP_CAP};
+use crate::components::tetromino::{QueueRules, RotationSystem};
+use crate::input::keyboard::Action;
+use crate::input::touch::TouchOverlay;
+use crate::utils::storage;
+
+/// Rate of the fixed gameplay tick.
+pub const TICKS_PER_SECOND: f64 = 60.0;
+
+/// Ticks a grounded piece waits before it locks, in modes that don't set
+/// their own in [`GameMode::lock_delay`].
+///
+/// [`GameMode::lock_delay`]: crate::game::GameMode::lock_delay
+pub const LOCK_DELAY: u32 = 30;
+/// Zen's lock delay, twice the usual to suit its unhurried play.
+pub const ZEN_LOCK_DELAY: u32 = 60;
+
+/// Ticks a hard-dropped piece rests before it locks when
+/// [`Settings::hard_drop_grace`] is on, a moment to slide it away.
+pub const HARD_DROP_GRACE: u32 = 8;
+
+/// Moves or rotations that may reset the lock delay before the piece
+/// locks regardless.
+pub const MAX_LOCK_RESETS: u32 = 15;
+
+/// Ticks completed rows flash before they collapse.
+pub const LINE_CLEAR_DELAY: u32 = 20;
+
+/// Locked pieces that can be undone in practice modes.
+pub const UNDO_HISTORY_LEN: usize = 32;
+
+/// Seconds the board takes to dissolve after a top-out. Purely cosmetic,
+/// so unlike the gameplay timings it runs on frame time.
+pub const DISSOLVE_SECS: f32 = 1.0;
+
+/// Screen cells a block covers along each side under the big modifier.
+pub const BIG_BLOCK_SCALE: usize = 2;
+
+/// Buffer rows shown above the visible field when
+/// [`LayoutConfig::show_buffer`] is on, enough to see pieces enter.
+pub const BUFFER_PEEK_ROWS: usize = 2;
+
+/// Fastest custom gravity, in rows per tick. At 20G a piece crosses the
+/// whole visible board in a single tick.
+pub const MAX_GRAVITY: f32 = 20.0;
+
+/// Keyboard handling timings.
+#[derive(Resource, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputConfig {
+    /// Delayed auto shift: ticks a direction is held before it repeats.
+    pub das: u32,
+    /// Auto repeat rate: ticks between repeated moves, 0 moving straight to
+    /// the wall.
+    pub arr: u32,
+    /// How many times faster than gravity a piece falls while soft drop is
+    /// held.
+    pub soft_drop_factor: u32,
+    /// Soft drop moves the piece straight to the floor, without locking it.
+    pub soft_drop_infinite: bool,
+    /// What holding left and right together does.
+    #[serde(default)]
+    pub opposing: OpposingDirections,
+    /// Fewest ticks between two presses of each action, in the order of
+    /// [`Action::ALL`]. A press that comes sooner is dropped, against key
+    /// chatter and turbo buttons; 0 lets every press through.
+    #[serde(default, deserialize_with = "padded_cooldowns")]
+    pub cooldowns: [u32; Action::ALL.len()],
+}
+
+/// Reads [`InputConfig::cooldowns`] saved before the last actions were
+/// added, leaving the cooldowns of the actions missing from it at 0.
+fn padded_cooldowns<'de, D>(deserializer: D) -> Result<[u32; Action::ALL.len()], D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct Cooldowns;
+
+    impl<'de> serde::de::Visitor<'de> for Cooldowns {
+        type Value = [u32; Action::ALL.len()];
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "at most {} cooldowns", Action::ALL.len())
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            let mut cooldowns = [0; Action::ALL.len()];
+            for cooldown in &mut cooldowns {
+                match seq.next_element()? {
+                    Some(value) => *cooldown = value,
+                    None => return Ok(cooldowns),
+                }
+            }
+            match seq.next_element::<u32>()? {
+                Some(_) => Err(serde::de::Error::invalid_length(
+                    Action::ALL.len() + 1,
+                    &self,
+                )),
+                None => Ok(cooldowns),
+            }
+        }
+    }
+
+    deserializer.deserialize_tuple(Action::ALL.len(), Cooldowns)
+}
+
+impl InputConfig {
+    pub fn cooldown(&self, action: Action) -> u32 {
+        self.cooldowns[action as usize]
+    }
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            das: 10,
+            arr: 2,
+            soft_drop_factor: 20,
+            soft_drop_infinite: false,
+            opposing: OpposingDirections::default(),
+            cooldowns: [0; Action::ALL.len()],
+        }
+    }
+}
+
+/// How left and right held at once move the piece.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OpposingDirections {
+    /// The direction pressed last wins, and the other takes over with a
+    /// fresh delayed auto shift once it's released. Pressed on the same
+    /// tick, neither moves the piece until one is let go.
+    #[default]
+    LastPressed,
+    /// The two cancel out and the piece stays put while both are held.
+    Cancel,
+}
+
+impl OpposingDirections {
+    pub const ALL: [OpposingDirections; 2] =
+        [OpposingDirections::LastPressed, OpposingDirections::Cancel];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            OpposingDirections::LastPressed => "opposing.last_pressed",
+            OpposingDirections::Cancel => "opposing.cancel",
+        }
+    }
+}
+
+/// Swipe and tap thresholds for gesture input, in logical pixels and
+/// seconds.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GestureConfig {
+    pub enabled: bool,
+    /// Sideways travel that moves the piece one column.
+    pub column_distance: f32,
+    /// Downward travel that starts a soft drop, or a hard drop if fast
+    /// enough.
+    pub swipe_distance: f32,
+    /// Average downward speed, in pixels per second, that makes a swipe a
+    /// hard drop.
+    pub hard_drop_speed: f32,
+    /// Furthest a finger may wander and still tap.
+    pub tap_distance: f32,
+    /// Longest a tap may last.
+    pub tap_secs: f32,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            column_distance: 40.0,
+            swipe_distance: 60.0,
+            hard_drop_speed: 1500.0,
+            tap_distance: 12.0,
+            tap_secs: 0.25,
+        }
+    }
+}
+
+/// Board sizing and view, adjustable from the settings menu.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct LayoutConfig {
+    /// Side length of a cell in pixels, or `None` to fit the window.
+    pub cell_size: Option<f32>,
+    /// Space kept clear around the board and panels, in cells.
+    pub margin: f32,
+    /// Lean the board back for a touch of depth. Off by default, as the
+    /// flat view is easier to read at speed.
+    pub tilt: bool,
+    /// Show the bottom [`BUFFER_PEEK_ROWS`] of the hidden buffer, faded,
+    /// instead of cutting the field off at its top edge.
+    pub show_buffer: bool,
+    pub hud: HudLayout,
+    /// Screen cells each board cell covers along each side, following
+    /// [`Modifiers::block_scale`](crate::game::Modifiers::block_scale).
+    pub block_scale: usize,
+    pub framing: Framing,
+}
+
+/// How the flat view fills a window of a different shape from the board
+/// and panels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Framing {
+    /// Keep cells square and leave bars of background either side.
+    #[default]
+    Letterbox,
+    /// Scale each axis on its own to fill the window, cells and all.
+    Stretch,
+}
+
+impl Framing {
+    pub const ALL: [Framing; 2] = [Framing::Letterbox, Framing::Stretch];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            Framing::Letterbox => "framing.letterbox",
+            Framing::Stretch => "framing.stretch",
+        }
+    }
+}
+
+/// Which way the next queue's slots follow one another.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NextOrientation {
+    /// One under another, in a column beside the board.
+    #[default]
+    Vertical,
+    /// One after another, in a row beside the top of the board, for wide
+    /// windows with room to spare either side.
+    Horizontal,
+}
+
+impl NextOrientation {
+    pub const ALL: [NextOrientation; 2] = [NextOrientation::Vertical, NextOrientation::Horizontal];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            NextOrientation::Vertical => "next_orientation.vertical",
+            NextOrientation::Horizontal => "next_orientation.horizontal",
+        }
+    }
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        Self {
+            cell_size: None,
+            margin: 1.0,
+            tilt: false,
+            show_buffer: false,
+            hud: HudLayout::default(),
+            block_scale: 1,
+            framing: Framing::default(),
+        }
+    }
+}
+
+/// Which side of the board each HUD panel sits on, and what the stats
+/// show. Saved between runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HudLayout {
+    /// Put the hold panel and stats right of the board and the next queue
+    /// left of it.
+    pub mirror: bool,
+    /// Merge the hold and next panels into one strip above the board, held
+    /// piece first, with the stats below the board, for narrow screens.
+    #[serde(default)]
+    pub compact: bool,
+    /// List the game's seed with the stats, to share the board it deals.
+    #[serde(default = "shown")]
+    pub show_seed: bool,
+    /// Whether the next queue stacks down beside the board or runs along
+    /// it. The compact strip always runs along.
+    #[serde(default)]
+    pub next_orientation: NextOrientation,
+}
+
+impl Default for HudLayout {
+    fn default() -> Self {
+        Self {
+            mirror: false,
+            compact: false,
+            show_seed: shown(),
+            next_orientation: NextOrientation::default(),
+        }
+    }
+}
+
+fn shown() -> bool {
+    true
+}
+
+impl HudLayout {
+    const FOLDER: &'static str = "settings";
+    const FILE: &'static str = "hud.ron";
+
+    /// The saved layout, or the default if none has been saved or it can't
+    /// be read, reporting to `app` a layout that doesn't parse.
+    pub fn load(app: &mut App) -> Self {
+        storage::read_ron(app, Self::FOLDER, Self::FILE).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        storage::write_ron(Self::FOLDER, Self::FILE, self)
+    }
+}
+
+/// Which conditions end the game.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TopOutRule {
+    /// A new piece overlaps the stack as it spawns.
+    #[default]
+    BlockOut,
+    /// A piece locks entirely above the visible field. Spawns that overlap
+    /// the stack are pushed up into the buffer instead, as under
+    /// [`Settings::spawn_shift_up`].
+    LockOut,
+    /// Either of the above.
+    Both,
+}
+
+impl TopOutRule {
+    pub const ALL: [TopOutRule; 3] = [TopOutRule::BlockOut, TopOutRule::LockOut, TopOutRule::Both];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            TopOutRule::BlockOut => "top_out.block_out",
+            TopOutRule::LockOut => "top_out.lock_out",
+            TopOutRule::Both => "top_out.both",
+        }
+    }
+
+    pub fn block_out(self) -> bool {
+        matches!(self, TopOutRule::BlockOut | TopOutRule::Both)
+    }
+
+    pub fn lock_out(self) -> bool {
+        matches!(self, TopOutRule::LockOut | TopOutRule::Both)
+    }
+}
+
+/// When a piece resting on the stack gets its lock delay back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LockReset {
+    /// Every move or rotation restarts the delay, up to
+    /// [`MAX_LOCK_RESETS`] times per piece.
+    #[default]
+    Move,
+    /// Only landing lower than the piece has been before restarts it.
+    Step,
+    /// The delay runs from the piece's first landing and never restarts.
+    Never,
+}
+
+impl LockReset {
+    pub const ALL: [LockReset; 3] = [LockReset::Move, LockReset::Step, LockReset::Never];
+
+    /// Translation key of the display name.
+    pub fn label_key(self) -> &'static str {
+        match self {
+            LockReset::Move => "lock_reset.move",
+            LockReset::Step => "lock_reset.step",
+            LockReset::Never => "lock_reset.never",
+        }
+    }
+}
+
+/// The handling a player takes from one setup to the next: the keyboard
+/// timings, lock reset policy and rotation system. Shared as the compact
+/// string [`HandlingProfile::encode`] writes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandlingProfile {
+    pub input: InputConfig,
+    pub lock_reset: LockReset,
+    pub rotation_system: RotationSystem,
+}
+
+/// Why a handling profile couldn't be imported.
+#[derive(Debug)]
+pub enum ProfileError {
+    Parse(ron::error::SpannedError),
+    /// A value is out of range.
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProfileError::Parse(error) => write!(f, "{error}"),
+            ProfileError::Invalid(errors) => write!(f, "{}", errors.join("; ")),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+impl HandlingProfile {
+    /// Longest delayed auto shift accepted, in ticks.
+    pub const MAX_DAS: u32 = 60;
+    /// Longest auto repeat rate accepted, in ticks.
+    pub const MAX_ARR: u32 = 30;
+    /// Largest soft drop factor accepted.
+    pub const MAX_SOFT_DROP_FACTOR: u32 = 40;
+    /// Longest action cooldown accepted, in ticks.
+    pub const MAX_COOLDOWN: u32 = 60;
+
+    /// The profile currently in effect.
+    pub fn current(input: &InputConfig, settings: &Settings) -> Self {
+        Self {
+            input: input.clone(),
+            lock_reset: settings.lock_reset,
+            rotation_system: settings.rotation_system,
+        }
+    }
+
+    /// Puts the profile into effect.
+    pub fn apply(&self, input: &mut InputConfig, settings: &mut Settings) {
+        *input = self.input.clone();
+        settings.lock_reset = self.lock_reset;
+        settings.rotation_system = self.rotation_system;
+    }
+
+    /// The profile as a single line of RON, to paste wherever.
+    pub fn encode(&self) -> String {
+        ron::to_string(self).expect("handling profiles always serialize")
+    }
+
+    /// Parses and checks a profile from [`HandlingProfile::encode`],
+    /// ignoring surrounding whitespace.
+    pub fn decode(source: &str) -> Result<Self, ProfileError> {
+        let profile: Self = ron::from_str(source.trim()).map_err(ProfileError::Parse)?;
+        let mut errors = Vec::new();
+        let input = &profile.input;
+        if input.das > Self::MAX_DAS {
+            errors.push(format!("DAS {} is over {}", input.das, Self::MAX_DAS));
+        }
+        if input.arr > Self::MAX_ARR {
+            errors.push(format!("ARR {} is over {}", input.arr, Self::MAX_ARR));
+        }
+        if !(1..=Self::MAX_SOFT_DROP_FACTOR).contains(&input.soft_drop_factor) {
+            errors.push(format!(
+                "soft drop factor {} is outside 1 to {}",
+                input.soft_drop_factor,
+                Self::MAX_SOFT_DROP_FACTOR
+            ));
+        }
+        for action in Action::ALL {
+            let cooldown = input.cooldown(action);
+            if cooldown > Self::MAX_COOLDOWN {
+                errors.push(format!(
+                    "{action:?} cooldown {cooldown} is over {}",
+                    Self::MAX_COOLDOWN
+                ));
+            }
+        }
+        if errors.is_empty() {
+            Ok(profile)
+        } else {
+            Err(ProfileError::Invalid(errors))
+        }
+    }
+}
+
+/// Auto repeat for a held arrow key in menus: the piece's auto shift, on
+/// quicker timings of its own so long lists go by fast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MenuRepeat {
+    /// Ticks an arrow key is held before the selection starts repeating.
+    pub delay: u32,
+    /// Ticks between repeats.
+    pub interval: u32,
+}
+
+impl MenuRepeat {
+    pub const MAX_DELAY: u32 = 30;
+    pub const MAX_INTERVAL: u32 = 10;
+}
+
+impl Default for MenuRepeat {
+    fn default() -> Self {
+        Self {
+            delay: 8,
+            interval: 2,
+        }
+    }
+}
+
+/// Player preferences adjustable from the settings menu.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Background music volume, from 0.0 to 1.0.
+    pub music_volume: f32,
+    /// Sound effect volume, from 0.0 to 1.0.
+    pub sfx_volume: f32,
+    /// Collapse completed rows immediately instead of animating the clear.
+    pub instant_line_clear: bool,
+    /// Dissolve the board before showing the game-over screen.
+    pub game_over_dissolve: bool,
+    /// Fixed gravity in G (rows per tick) replacing the level curve, or
+    /// `None` to follow it.
+    pub custom_gravity: Option<f32>,
+    /// Seconds locked blocks stay visible under the invisible modifier.
+    pub invisible_delay: f32,
+    /// How far the piece in play is lightened towards white to stand out
+    /// from the stack, from 0.0 for not at all to 1.0 for white.
+    pub active_emphasis: f32,
+    pub top_out: TopOutRule,
+    /// A piece that overlaps the stack as it spawns rises into the buffer
+    /// to the first place it fits, even under block-out, and the game ends
+    /// only once the buffer is full too.
+    pub spawn_shift_up: bool,
+    pub lock_reset: LockReset,
+    pub scoring: ScoringRules,
+    /// Most points one piece can earn by soft-dropping, or `None` for no
+    /// cap.
+    pub soft_drop_cap: Option<u32>,
+    /// In Zen, pieces never lock on their own and stay in play until hard
+    /// dropped.
+    pub freeplay: bool,
+    /// Hard drop rests the piece for [`HARD_DROP_GRACE`] ticks before
+    /// locking it instead of locking it at once. A second hard drop locks
+    /// it straight away.
+    pub hard_drop_grace: bool,
+    pub queue: QueueRules,
+    pub rotation_system: RotationSystem,
+    /// Seconds without input after which the game pauses itself and drops
+    /// to a low frame rate to save battery, or `None` to never.
+    pub auto_pause: Option<f32>,
+    /// Cut straight between screens instead of fading.
+    pub reduce_motion: bool,
+    /// Briefly light the edge of the board a rotation kicked off.
+    pub kick_flash: bool,
+    /// Leave a fading afterimage of cleared rows where they were, to see
+    /// what went at speed.
+    pub clear_afterimage: bool,
+    /// Faintly show where the held piece would enter if swapped in.
+    pub hold_preview: bool,
+    /// Show the ghost only once the piece is within this many rows of
+    /// landing, fading in as it gets closer, or always with `None`.
+    pub ghost_near: Option<u32>,
+    /// Cast a soft shadow down and right of the piece in play, for depth.
+    pub piece_shadow: bool,
+    /// In Zen, highlight the board cell under the mouse and let clicks
+    /// fill and empty cells.
+    pub board_editor: bool,
+    /// Tick when a piece spawns and when hold is ready again, to follow
+    /// the game by ear.
+    pub audio_cues: bool,
+    /// Ask before quitting from a game in progress.
+    pub confirm_exit: bool,
+    /// How a held arrow key repeats through a menu.
+    pub menu_repeat: MenuRepeat,
+    /// Have the computer play the attract demo live instead of showing the
+    /// bundled replay.
+    pub ai_demo: bool,
+    /// Pure colours on black with white gridlines and text, for low
+    /// vision.
+    pub high_contrast: bool,
+    /// Turn off every purely visual effect at once, for weak hardware,
+    /// whatever the individual switches say. See [`Settings::effects`].
+    pub low_detail: bool,
+    /// When to show the on-screen touch buttons.
+    pub touch_controls: TouchOverlay,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            music_volume: 0.5,
+            sfx_volume: 0.8,
+            instant_line_clear: false,
+            game_over_dissolve: true,
+            custom_gravity: None,
+            invisible_delay: 1.0,
+            active_emphasis: 0.2,
+            top_out: TopOutRule::default(),
+            spawn_shift_up: false,
+            lock_reset: LockReset::default(),
+            scoring: ScoringRules::default(),
+            soft_drop_cap: Some(SOFT_DROP_CAP),
+            freeplay: false,
+            hard_drop_grace: false,
+            queue: QueueRules::default(),
+            rotation_system: RotationSystem::default(),
+            auto_pause: None,
+            reduce_motion: false,
+            kick_flash: true,
+            clear_afterimage: false,
+            hold_preview: false,
+            ghost_near: None,
+            piece_shadow: false,
+            board_editor: false,
+            audio_cues: false,
+            confirm_exit: true,
+            menu_repeat: MenuRepeat::default(),
+            ai_demo: true,
+            high_contrast: false,
+            low_detail: false,
+            touch_controls: TouchOverlay::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// The effects to draw, each on when its own switch is and
+    /// [`Self::low_detail`] is off.
+    pub fn effects(&self) -> VisualEffects {
+        let full = !self.low_detail;
+        VisualEffects {
+            flashes: full,
+            kick_flash: full && self.kick_flash,
+            afterimage: full && self.clear_afterimage && !self.reduce_motion,
+            dissolve: full && self.game_over_dissolve,
+            shadow: full && self.piece_shadow,
+            motion: full && !self.reduce_motion,
+            smoothing: full,
+        }
+    }
+}
+
+/// Which purely visual effects are drawn, as [`Settings::effects`] works
+/// them out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VisualEffects {
+    /// The flash on a freshly locked piece and on a refused hold.
+    pub flashes: bool,
+    /// The glint along the edge a rotation kicked off.
+    pub kick_flash: bool,
+    /// The fading copy of rows just cleared.
+    pub afterimage: bool,
+    /// Blocks falling away before the game-over screen.
+    pub dissolve: bool,
+    /// The drop shadow under the piece in play.
+    pub shadow: bool,
+    /// Fades between screens and the sliding next queue.
+    pub motion: bool,
+    /// Multisampled, antialiased edges.
+    pub smoothing: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handling_profile_round_trips_and_rejects_bad_values() {
+        let profile = HandlingProfile {
+            input: InputConfig {
+                das: 7,
+                arr: 0,
+                soft_drop_factor: 40,
+                soft_drop_infinite: true,
+                opposing: OpposingDirections::Cancel,
+                cooldowns: [0, 0, 0, 4, 2, 2, 0, 0, 0, 0, 0],
+            },
+            lock_reset: LockReset::Step,
+            rotation_system: RotationSystem::Ars,
+        };
+        let code = profile.encode();
+        assert!(!code.contains('\n'));
+        assert_eq!(
+            HandlingProfile::decode(&format!(" {code}\n")).unwrap(),
+            profile
+        );
+
+        let mut settings = Settings::default();
+        let mut input = InputConfig::default();
+        profile.apply(&mut input, &mut settings);
+        assert_eq!(HandlingProfile::current(&input, &settings), profile);
+
+        // A profile from before the checkpoint actions keeps its cooldowns.
+        let short = code.replace("0,0,0,4,2,2,0,0,0,0,0", "0,0,0,4,2,2,0,0,0");
+        assert_ne!(short, code);
+        assert_eq!(HandlingProfile::decode(&short).unwrap(), profile);
+
+        assert!(matches!(
+            HandlingProfile::decode("(input: (das: 7"),
+            Err(ProfileError::Parse(_))
+        ));
+        let mut bad = profile.clone();
+        bad.input.das = 99;
+        bad.input.soft_drop_factor = 0;
+        bad.input.cooldowns[Action::Hold as usize] = HandlingProfile::MAX_COOLDOWN + 1;
+        match HandlingProfile::decode(&bad.encode()) {
+            Err(ProfileError::Invalid(errors)) => assert_eq!(errors.len(), 3),
+            other => panic!("expected a range error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_low_detail_turns_every_effect_off() {
+        let mut settings = Settings {
+            reduce_motion: false,
+            kick_flash: true,
+            clear_afterimage: true,
+            game_over_dissolve: true,
+            piece_shadow: true,
+            ..Settings::default()
+        };
+        let effects = settings.effects();
+        assert!(
+            effects.flashes
+                && effects.kick_flash
+                && effects.afterimage
+                && effects.dissolve
+                && effects.shadow
+                && effects.motion
+                && effects.smoothing
+        );
+        settings.low_detail = true;
+        assert_eq!(
+            settings.effects(),
+            VisualEffects {
+                flashes: false,
+                kick_flash: false,
+                afterimage: false,
+                dissolve: false,
+                shadow: false,
+                motion: false,
+                smoothing: false,
+            }
+        );
+    }
+}
@@ -0,0 +1,584 @@
+//! Diagnostic logging of gameplay.
+//!
+//! Spawns, locks, line clears, level ups and top-outs are logged at debug
+//! level under [`GAMEPLAY_TARGET`], which Bevy's default filter hides. Set
+//! [`TRACE_ENV`] to show them without a rebuild, or name the target in
+//! `RUST_LOG` as with any other.
+//!
+//! [`self_check`] validates the hand-entered piece, kick and colour tables
+//! at startup, [`audit_tick_order`] checks every frame of a debug build
+//! that the tick still runs in its documented order, and [`OVERLAY_KEY`]
+//! labels board cells with their coordinates for checking collision and
+//! rotation bugs by eye. While the AI plays the demo the same key outlines
+//! where it means to put the piece and what the heuristic made of the
+//! result, for tuning [`Weights`]. Turn it on from the main menu, since
+//! any key press ends the demo.
+//!
+//! Built with the `frame_step` feature, `FRAME_STEP_KEY` stops the
+//! gameplay clock and shows the coordinates, and `STEP_KEY` then plays
+//! one tick per press, for stepping through gravity, lock and clear bugs.
+//!
+//! [`Weights`]: crate::ai::Weights
+
+use std::collections::HashSet;
+
+use bevy::ecs::schedule::{NodeId, ScheduleGraph};
+#[cfg(feature = "frame_step")]
+use bevy::input::InputSystem;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::ai::{column_heights, count_holes, AiDecision, AiPlayer};
+use crate::components::board::Board;
+use crate::components::scoring::Score;
+use crate::components::tetromino::{kick_offsets, PieceKind, RotationSystem, Tetromino};
+use crate::game::{ActivePiece, GameplayEvent, TickSet, TickStage};
+use crate::rendering::shapes::{BoardLayout, COLORS};
+
+/// Log target of every gameplay trace.
+pub const GAMEPLAY_TARGET: &str = "rustblocks::gameplay";
+
+/// Environment variable that turns on gameplay tracing when set to
+/// anything but `0`.
+pub const TRACE_ENV: &str = "RUSTBLOCKS_TRACE";
+
+/// Whether [`TRACE_ENV`] asks for gameplay tracing.
+pub fn trace_enabled() -> bool {
+    std::env::var_os(TRACE_ENV).is_some_and(|value| value != "0")
+}
+
+/// Bevy's default logging, with gameplay traces shown when `trace` is set.
+pub fn log_plugin(trace: bool) -> LogPlugin {
+    let mut plugin = LogPlugin::default();
+    if trace {
+        plugin.filter.push_str(&format!(",{GAMEPLAY_TARGET}=debug"));
+    }
+    plugin
+}
+
+/// Toggles the [`CoordinateOverlay`].
+pub const OVERLAY_KEY: KeyCode = KeyCode::F3;
+
+/// Turns [`FrameStep`] on and off.
+#[cfg(feature = "frame_step")]
+pub const FRAME_STEP_KEY: KeyCode = KeyCode::F7;
+
+/// Plays one gameplay tick while [`FrameStep`] is on.
+#[cfg(feature = "frame_step")]
+pub const STEP_KEY: KeyCode = KeyCode::F8;
+
+/// Empty cells within this many cells of the active piece are labelled
+/// along with the filled ones.
+const OVERLAY_REACH: i32 = 2;
+
+/// Whether board cells are labelled with their `(x, y)` coordinates.
+#[derive(Resource, Debug, Default)]
+pub struct CoordinateOverlay(pub bool);
+
+/// Whether the gameplay clock is stopped, leaving [`STEP_KEY`] to play
+/// each tick.
+#[cfg(feature = "frame_step")]
+#[derive(Resource, Debug, Default)]
+pub struct FrameStep(pub bool);
+
+pub struct GameplayLogPlugin;
+
+impl Plugin for GameplayLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, log_gameplay);
+    }
+}
+
+pub struct CoordinateOverlayPlugin;
+
+impl Plugin for CoordinateOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CoordinateOverlay>().add_systems(
+            Update,
+            (
+                toggle_overlay,
+                draw_coordinates.run_if(|overlay: Res<CoordinateOverlay>| overlay.0),
+                draw_ai_plan.run_if(
+                    (|overlay: Res<CoordinateOverlay>| overlay.0)
+                        .and_then(resource_exists::<AiPlayer>())
+                        .and_then(resource_exists::<AiDecision>()),
+                ),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Frame stepping, only built with the `frame_step` feature.
+#[cfg(feature = "frame_step")]
+pub struct FrameStepPlugin;
+
+#[cfg(feature = "frame_step")]
+impl Plugin for FrameStepPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FrameStep>().add_systems(
+            PreUpdate,
+            (toggle_frame_step, step_frame.run_if(stepping))
+                .chain()
+                .after(InputSystem),
+        );
+    }
+}
+
+#[cfg(feature = "frame_step")]
+fn stepping(step: Res<FrameStep>) -> bool {
+    step.0
+}
+
+/// Stops or restarts the virtual clock [`FixedUpdate`] keeps time by, and
+/// shows the coordinates on stopping it.
+#[cfg(feature = "frame_step")]
+fn toggle_frame_step(
+    keys: Res<Input<KeyCode>>,
+    mut step: ResMut<FrameStep>,
+    mut time: ResMut<Time<Virtual>>,
+    overlay: Option<ResMut<CoordinateOverlay>>,
+) {
+    if !keys.just_pressed(FRAME_STEP_KEY) {
+        return;
+    }
+    step.0 = !step.0;
+    if step.0 {
+        time.pause();
+        if let Some(mut overlay) = overlay {
+            overlay.0 = true;
+        }
+    } else {
+        time.unpause();
+    }
+}
+
+/// Plays one tick of [`FixedUpdate`] on each press of [`STEP_KEY`], with
+/// the fixed clock moved on by a timestep as Bevy's own loop would, ahead
+/// of [`Update`] drawing what it did. Exclusive, to run the schedule.
+#[cfg(feature = "frame_step")]
+fn step_frame(world: &mut World) {
+    if !world.resource::<Input<KeyCode>>().just_pressed(STEP_KEY) {
+        return;
+    }
+    let mut fixed = world.resource_mut::<Time<Fixed>>();
+    let timestep = fixed.timestep();
+    fixed.advance_by(timestep);
+    *world.resource_mut::<Time>() = world.resource::<Time<Fixed>>().as_generic();
+    world.run_schedule(FixedUpdate);
+    *world.resource_mut::<Time>() = world.resource::<Time<Virtual>>().as_generic();
+}
+
+fn toggle_overlay(keys: Res<Input<KeyCode>>, mut overlay: ResMut<CoordinateOverlay>) {
+    if keys.just_pressed(OVERLAY_KEY) {
+        overlay.0 = !overlay.0;
+    }
+}
+
+/// The visible cells worth labelling: every filled one, and the empty ones
+/// around the active piece where collisions and kicks happen. Labelling
+/// the whole board would bury the interesting cells and cost a text shape
+/// per cell every frame.
+pub fn overlay_cells(board: &Board, active: Option<&Tetromino>) -> Vec<(i32, i32)> {
+    let piece_cells = active.map(Tetromino::cells).unwrap_or_default();
+    let near_piece = |x: i32, y: i32| {
+        active.is_some()
+            && piece_cells
+                .iter()
+                .any(|&(px, py)| (px - x).abs().max((py - y).abs()) <= OVERLAY_REACH)
+    };
+    (0..board.visible_height() as i32)
+        .flat_map(|y| (0..board.width() as i32).map(move |x| (x, y)))
+        .filter(|&(x, y)| board.get(x, y).is_some() || near_piece(x, y))
+        .collect()
+}
+
+fn draw_coordinates(
+    mut contexts: EguiContexts,
+    board: Res<Board>,
+    active: Res<ActivePiece>,
+    layout: Res<BoardLayout>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+) {
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let painter = contexts.ctx_mut().layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("coordinate_overlay"),
+    ));
+    let font = egui::FontId::monospace((layout.block_size() * 0.25).max(6.0));
+    for (x, y) in overlay_cells(&board, active.0.as_ref()) {
+        let center = layout.cell_center(x, y).extend(0.0);
+        let Some(position) = camera.world_to_viewport(camera_transform, center) else {
+            continue;
+        };
+        painter.text(
+            egui::pos2(position.x, position.y),
+            egui::Align2::CENTER_CENTER,
+            format!("{x},{y}"),
+            font.clone(),
+            egui::Color32::WHITE,
+        );
+    }
+}
+
+/// Outlines the cells of the [`AiPlayer`]'s target and lists what each
+/// feature of the board it leads to scored.
+fn draw_ai_plan(
+    mut contexts: EguiContexts,
+    decision: Res<AiDecision>,
+    layout: Res<BoardLayout>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+) {
+    let (Some(plan), Ok((camera, camera_transform))) = (decision.plan, cameras.get_single()) else {
+        return;
+    };
+    let ctx = contexts.ctx_mut();
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("ai_plan_overlay"),
+    ));
+    let block = layout.block_on_screen();
+    let stroke = egui::Stroke::new(2.0, egui::Color32::YELLOW);
+    for (x, y) in plan.target.cells() {
+        let center = layout.cell_center(x, y).extend(0.0);
+        let Some(position) = camera.world_to_viewport(camera_transform, center) else {
+            continue;
+        };
+        let rect = egui::Rect::from_center_size(
+            egui::pos2(position.x, position.y),
+            egui::vec2(block.x, block.y) * 0.9,
+        );
+        painter.rect_stroke(rect, 0.0, stroke);
+    }
+    let breakdown = decision.breakdown;
+    let rows = [
+        ("height", breakdown.height),
+        ("holes", breakdown.holes),
+        ("bumpiness", breakdown.bumpiness),
+        ("lines", breakdown.lines),
+        ("total", breakdown.total()),
+        ("lookahead", plan.score),
+    ];
+    egui::Area::new("ai_plan_breakdown")
+        .anchor(egui::Align2::LEFT_BOTTOM, [8.0, -8.0])
+        .show(ctx, |ui| {
+            let held = if plan.hold { " (hold)" } else { "" };
+            ui.monospace(format!("plan {}{held}", decision.count));
+            for (feature, score) in rows {
+                ui.monospace(format!("{feature:<10}{score:>8.2}"));
+            }
+        });
+}
+
+fn log_gameplay(mut events: EventReader<GameplayEvent>, score: Res<Score>, board: Res<Board>) {
+    for event in events.read() {
+        match *event {
+            GameplayEvent::Spawned(piece) => {
+                debug!(target: GAMEPLAY_TARGET, kind = ?piece.kind, x = piece.x, y = piece.y, "spawn");
+            }
+            GameplayEvent::Locked(piece) => {
+                debug!(
+                    target: GAMEPLAY_TARGET,
+                    kind = ?piece.kind,
+                    x = piece.x,
+                    y = piece.y,
+                    rotation = piece.rotation,
+                    stack = column_heights(&board).into_iter().max().unwrap_or(0),
+                    holes = count_holes(&board),
+                    "lock"
+                );
+            }
+            GameplayEvent::LinesCleared {
+                count,
+                rows,
+                cancelled,
+            } => {
+                debug!(
+                    target: GAMEPLAY_TARGET,
+                    count,
+                    rows = format_args!("{rows:#b}"),
+                    cancelled,
+                    points = score.points,
+                    lines = score.lines,
+                    "clear"
+                );
+            }
+            GameplayEvent::LevelUp(level) => {
+                debug!(target: GAMEPLAY_TARGET, level, points = score.points, "level up");
+            }
+            GameplayEvent::GameOver => {
+                debug!(
+                    target: GAMEPLAY_TARGET,
+                    points = score.points,
+                    lines = score.lines,
+                    level = score.level,
+                    "game over"
+                );
+            }
+            GameplayEvent::Finished => {
+                debug!(
+                    target: GAMEPLAY_TARGET,
+                    points = score.points,
+                    lines = score.lines,
+                    "finished"
+                );
+            }
+            GameplayEvent::GarbageRose(rows) => {
+                debug!(target: GAMEPLAY_TARGET, rows, "garbage");
+            }
+            GameplayEvent::Reset => {
+                debug!(target: GAMEPLAY_TARGET, "reset");
+            }
+            GameplayEvent::Kicked((dx, dy)) => {
+                debug!(target: GAMEPLAY_TARGET, dx, dy, "kick");
+            }
+            GameplayEvent::Moved
+            | GameplayEvent::Rotated
+            | GameplayEvent::Held
+            | GameplayEvent::HoldRejected
+            | GameplayEvent::HoldReady => {}
+        }
+    }
+}
+
+/// Everything wrong with the piece shapes, kick tables and piece colours.
+pub fn table_errors() -> Vec<String> {
+    let mut errors = Vec::new();
+    if COLORS.len() != PieceKind::ALL.len() {
+        errors.push(format!(
+            "COLORS has {} entries for {} piece kinds",
+            COLORS.len(),
+            PieceKind::ALL.len()
+        ));
+    }
+    for (index, kind) in PieceKind::ALL.into_iter().enumerate() {
+        if kind.index() != index || PieceKind::from_index(index) != Some(kind) {
+            errors.push(format!("{kind:?} is out of place in PieceKind::ALL"));
+        }
+        let size = kind.box_size();
+        for rotation in 0..4 {
+            for system in RotationSystem::ALL {
+                let cells: HashSet<_> = kind.shape_in(system, rotation).into_iter().collect();
+                if cells.len() != 4 {
+                    errors.push(format!(
+                        "{kind:?} {system:?} rotation {rotation} has {} distinct cells",
+                        cells.len()
+                    ));
+                }
+                if cells
+                    .iter()
+                    .any(|&(x, y)| !(0..size).contains(&x) || !(0..size).contains(&y))
+                {
+                    errors.push(format!(
+                        "{kind:?} {system:?} rotation {rotation} leaves its {size}x{size} box"
+                    ));
+                }
+            }
+            let expected = if kind == PieceKind::O { 1 } else { 5 };
+            for to in [(rotation + 1) % 4, (rotation + 3) % 4] {
+                let kicks = kick_offsets(kind, rotation, to);
+                if kicks.len() != expected || kicks.first() != Some(&(0, 0)) {
+                    errors.push(format!(
+                        "{kind:?} kicks {rotation}->{to} should be {expected} offsets starting at (0, 0), found {kicks:?}"
+                    ));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Startup check of [`table_errors`], panicking in debug builds so a
+/// data-entry mistake is caught on the first run, and logging in release.
+pub fn self_check() {
+    let errors = table_errors();
+    if errors.is_empty() {
+        return;
+    }
+    let report = errors.join("\n  ");
+    if cfg!(debug_assertions) {
+        panic!("piece table self-check failed:\n  {report}");
+    }
+    error!("piece table self-check failed:\n  {report}");
+}
+
+/// The node of `set` in `graph`, if it has been configured.
+fn set_node(graph: &ScheduleGraph, set: &dyn SystemSet) -> Option<NodeId> {
+    graph
+        .system_sets()
+        .find(|&(_, candidate, _)| candidate == set)
+        .map(|(node, _, _)| node)
+}
+
+/// Every way the [`FixedUpdate`] `schedule` fails to run
+/// [`TickSet::Input`] before [`TickSet::Simulate`] and the
+/// [`TickStage`]s inside it one after another, as documented on
+/// [`TickStage`].
+pub fn tick_order_errors(schedule: &Schedule) -> Vec<String> {
+    let graph = schedule.graph();
+    let mut errors = Vec::new();
+    let mut node = |set: &dyn SystemSet| {
+        let node = set_node(graph, set);
+        if node.is_none() {
+            errors.push(format!("{set:?} is not configured"));
+        }
+        node
+    };
+    let input = node(&TickSet::Input);
+    let simulate = node(&TickSet::Simulate);
+    let stages: Vec<_> = TickStage::ORDER.iter().map(|stage| node(stage)).collect();
+
+    let before = |a: Option<NodeId>, b: Option<NodeId>| {
+        a.zip(b)
+            .is_none_or(|(a, b)| graph.dependency().graph().contains_edge(a, b))
+    };
+    if !before(input, simulate) {
+        errors.push("Input does not run before Simulate".to_owned());
+    }
+    for (pair, nodes) in TickStage::ORDER.windows(2).zip(stages.windows(2)) {
+        if !before(nodes[0], nodes[1]) {
+            errors.push(format!("{:?} does not run before {:?}", pair[0], pair[1]));
+        }
+    }
+    for (stage, &node) in TickStage::ORDER.iter().zip(&stages) {
+        let nested = simulate
+            .zip(node)
+            .is_none_or(|(simulate, node)| graph.hierarchy().graph().contains_edge(simulate, node));
+        if !nested {
+            errors.push(format!("{stage:?} is not part of Simulate"));
+        }
+    }
+    errors
+}
+
+/// Per-frame check of [`tick_order_errors`] in debug builds, panicking so
+/// a system added in the wrong place is caught on the first run.
+pub fn audit_tick_order(schedules: Res<Schedules>) {
+    let Some(schedule) = schedules.get(FixedUpdate) else {
+        return;
+    };
+    let errors = tick_order_errors(schedule);
+    if !errors.is_empty() {
+        panic!("tick order audit failed:\n  {}", errors.join("\n  "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "frame_step")]
+    use std::time::Duration;
+
+    #[cfg(feature = "frame_step")]
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    #[cfg(feature = "frame_step")]
+    use crate::game::{AppState, GamePlugin};
+    #[cfg(feature = "frame_step")]
+    use crate::utils::config::Settings;
+
+    /// Presses and lets go of `key` over one update.
+    #[cfg(feature = "frame_step")]
+    fn tap(app: &mut App, key: KeyCode) {
+        app.world.resource_mut::<Input<KeyCode>>().press(key);
+        app.update();
+        let mut keys = app.world.resource_mut::<Input<KeyCode>>();
+        keys.release(key);
+        keys.clear();
+    }
+
+    #[test]
+    #[cfg(feature = "frame_step")]
+    fn test_frame_step_advances_the_board_only_on_the_step_key() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin, FrameStepPlugin))
+            .init_resource::<Input<KeyCode>>()
+            .init_resource::<CoordinateOverlay>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                50,
+            )));
+        app.world.resource_mut::<Settings>().custom_gravity = Some(1.0);
+        app.world
+            .resource_mut::<NextState<AppState>>()
+            .set(AppState::Playing);
+        app.update();
+        let row = |app: &App| app.world.resource::<ActivePiece>().0.map(|piece| piece.y);
+        app.update();
+        app.update();
+        assert!(row(&app).is_some());
+
+        tap(&mut app, FRAME_STEP_KEY);
+        assert!(app.world.resource::<CoordinateOverlay>().0);
+        let stopped = row(&app);
+        for _ in 0..5 {
+            app.update();
+        }
+        assert_eq!(row(&app), stopped);
+
+        // One row per press at 1G, and none while the key stays down.
+        tap(&mut app, STEP_KEY);
+        assert_eq!(row(&app), stopped.map(|y| y - 1));
+        app.world.resource_mut::<Input<KeyCode>>().press(STEP_KEY);
+        app.update();
+        app.world.resource_mut::<Input<KeyCode>>().clear();
+        app.update();
+        app.update();
+        assert_eq!(row(&app), stopped.map(|y| y - 2));
+        app.world.resource_mut::<Input<KeyCode>>().release(STEP_KEY);
+
+        tap(&mut app, FRAME_STEP_KEY);
+        app.update();
+        assert!(row(&app) < stopped.map(|y| y - 2));
+    }
+
+    #[test]
+    fn test_piece_tables_pass_self_check() {
+        assert_eq!(table_errors(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_overlay_labels_filled_cells_and_the_active_surroundings() {
+        let mut board = Board::default();
+        board.set(9, 0, Some(0));
+        assert_eq!(overlay_cells(&board, None), [(9, 0)]);
+
+        let piece = board.landing_position(&Tetromino::spawn(PieceKind::O, &board));
+        let cells = overlay_cells(&board, Some(&piece));
+        assert!(cells.contains(&(9, 0)));
+        assert!(piece.cells().iter().all(|cell| cells.contains(cell)));
+        // An O on the floor at columns 4 and 5 reaches two columns either
+        // side and two rows up.
+        assert_eq!(cells.len(), 1 + 6 * 4);
+        assert!(!cells.contains(&(1, 0)));
+    }
+
+    #[test]
+    fn test_tick_order_audit_catches_a_stage_out_of_place() {
+        let mut schedule = Schedule::new(FixedUpdate);
+        schedule
+            .configure_sets((TickSet::Input, TickSet::Simulate).chain())
+            .configure_sets(
+                (
+                    TickStage::Spawn,
+                    TickStage::Fall,
+                    TickStage::Move,
+                    TickStage::Lock,
+                    TickStage::Clear,
+                    TickStage::Clock,
+                )
+                    .chain()
+                    .in_set(TickSet::Simulate),
+            );
+        assert_eq!(
+            tick_order_errors(&schedule),
+            [
+                "Spawn does not run before Move",
+                "Move does not run before Fall",
+                "Fall does not run before Lock",
+            ]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! Translated UI strings.
+//!
+//! Each [`Language`] has a RON table in `assets/i18n` mapping keys such as
+//! `"menu.quit"` to text. The tables are bundled into the binary so they
+//! load the same way on every platform, and any key missing from a
+//! translation falls back to English.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Language {
+    #[default]
+    English,
+    Spanish,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Spanish];
+
+    /// The language's name in itself, for the language selector.
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::Spanish => "Español",
+        }
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Language::English => include_str!("../../assets/i18n/en.ron"),
+            Language::Spanish => include_str!("../../assets/i18n/es.ron"),
+        }
+    }
+}
+
+type Table = HashMap<String, String>;
+
+/// The selected [`Language`] and every string table.
+#[derive(Resource, Debug)]
+pub struct Locale {
+    pub language: Language,
+    tables: HashMap<Language, Table>,
+}
+
+impl Locale {
+    /// Parses the bundled tables, warning about keys a translation lacks.
+    pub fn load(language: Language) -> Result<Self, ron::error::SpannedError> {
+        let tables = Language::ALL
+            .iter()
+            .map(|&language| Ok((language, ron::from_str::<Table>(language.source())?)))
+            .collect::<Result<HashMap<_, _>, ron::error::SpannedError>>()?;
+        let locale = Self { language, tables };
+        for language in Language::ALL {
+            for key in locale.missing_keys(language) {
+                warn!(
+                    "{} translation has no \"{key}\", using English",
+                    language.name()
+                );
+            }
+        }
+        Ok(locale)
+    }
+
+    /// A locale with no tables, showing every key as itself, for when the
+    /// bundled ones don't load.
+    pub fn untranslated() -> Self {
+        Self {
+            language: Language::default(),
+            tables: HashMap::new(),
+        }
+    }
+
+    /// The text for `key` in the current language, else in English, else
+    /// the key itself.
+    pub fn t<'a>(&'a self, key: &'a str) -> &'a str {
+        [self.language, Language::English]
+            .iter()
+            .find_map(|language| self.tables.get(language)?.get(key))
+            .map_or(key, String::as_str)
+    }
+
+    /// Keys present in English but not in `language`.
+    pub fn missing_keys(&self, language: Language) -> Vec<&str> {
+        let table = &self.tables[&language];
+        let mut missing: Vec<&str> = self.tables[&Language::English]
+            .keys()
+            .filter(|key| !table.contains_key(*key))
+            .map(String::as_str)
+            .collect();
+        missing.sort_unstable();
+        missing
+    }
+}
+
+/// The default language's translations, or none if they don't load. An
+/// app set up through [`init_locale`](crate::error::init_locale) reports
+/// why; this only logs it.
+impl Default for Locale {
+    fn default() -> Self {
+        Self::load(Language::default()).unwrap_or_else(|error| {
+            error!("couldn't load the translations: {error}");
+            Self::untranslated()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_locale_has_every_english_key() {
+        let locale = Locale::load(Language::English).unwrap();
+        for language in Language::ALL {
+            assert_eq!(locale.missing_keys(language), Vec::<&str>::new());
+        }
+    }
+
+    #[test]
+    fn test_lookup_falls_back_to_english_then_key() {
+        let mut locale = Locale::load(Language::Spanish).unwrap();
+        assert_eq!(locale.t("menu.quit"), "Salir");
+        locale
+            .tables
+            .get_mut(&Language::Spanish)
+            .unwrap()
+            .remove("menu.quit");
+        assert_eq!(locale.t("menu.quit"), "Quit");
+        assert_eq!(locale.t("no.such.key"), "no.such.key");
+    }
+}
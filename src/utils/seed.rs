@@ -0,0 +1,105 @@
+//! Piece sequence seeds for shared and daily challenges.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A calendar date in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn today() -> Self {
+        let days = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs() / 86_400);
+        Self::from_days(days as i64)
+    }
+
+    /// The date `days` after 1970-01-01, in the proleptic Gregorian
+    /// calendar.
+    pub fn from_days(days: i64) -> Self {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        // Months counted from March so the leap day ends the year.
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Self {
+            year: year as i32,
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+}
+
+/// The seed every player gets on `date`.
+pub fn daily_seed(date: Date) -> u64 {
+    let key = date.year as u64 * 10_000 + u64::from(date.month) * 100 + u64::from(date.day);
+    splitmix64(key)
+}
+
+/// Parses a typed seed, ignoring surrounding whitespace.
+pub fn parse_seed(text: &str) -> Option<u64> {
+    let text = text.trim();
+    if text.is_empty() || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+/// Spreads nearby inputs, like consecutive dates, across the whole range.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daily_seed_is_stable_for_a_date() {
+        let date = Date::from_days(19_796);
+        assert_eq!(
+            date,
+            Date {
+                year: 2024,
+                month: 3,
+                day: 14
+            }
+        );
+        assert_eq!(daily_seed(date), 14_643_664_791_795_935_190);
+        assert_ne!(daily_seed(Date::from_days(19_797)), daily_seed(date));
+        assert_eq!(
+            Date::from_days(11_016),
+            Date {
+                year: 2000,
+                month: 2,
+                day: 29
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_seed_accepts_only_u64() {
+        assert_eq!(parse_seed(" 42 "), Some(42));
+        assert_eq!(parse_seed("18446744073709551615"), Some(u64::MAX));
+        for invalid in ["", "-1", "+1", "4.2", "abc", "18446744073709551616"] {
+            assert_eq!(parse_seed(invalid), None, "{invalid:?}");
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Where the game keeps files on this device.
+
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use bevy::app::App;
+use bevy::log::warn;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::{report, GameError};
+
+/// Directory name under the platform's data directory.
+const APP_DIR: &str = "rustblocks";
+
+/// The game's data directory, which may not exist yet: the platform's
+/// per-user data directory when one can be found, otherwise the working
+/// directory.
+pub fn data_dir() -> PathBuf {
+    let base = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")));
+    base.map_or_else(|| PathBuf::from("."), |base| base.join(APP_DIR))
+}
+
+/// The path of `name` inside the `folder` subdirectory of [`data_dir`],
+/// creating the folder if needed.
+pub fn file_path(folder: &str, name: &str) -> std::io::Result<PathBuf> {
+    let dir = data_dir().join(folder);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(name))
+}
+
+/// Makes sure `dir` exists and takes new files, so saving there will work.
+pub fn check_writable(dir: &Path) -> Result<(), GameError> {
+    let probe = dir.join(".write-check");
+    std::fs::create_dir_all(dir)
+        .and_then(|()| std::fs::write(&probe, ""))
+        .and_then(|()| std::fs::remove_file(&probe))
+        .map_err(|error| GameError::Storage {
+            path: dir.to_owned(),
+            reason: error.to_string(),
+        })
+}
+
+/// Reads `name` from `folder` as RON, or `None` if it hasn't been saved.
+pub fn try_read_ron<T: DeserializeOwned>(folder: &str, name: &str) -> Result<Option<T>, GameError> {
+    let storage_error = |path: PathBuf, error: std::io::Error| GameError::Storage {
+        path,
+        reason: error.to_string(),
+    };
+    let path =
+        file_path(folder, name).map_err(|error| storage_error(data_dir().join(folder), error))?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(storage_error(path, error)),
+    };
+    ron::from_str(&text)
+        .map(Some)
+        .map_err(|error| GameError::Config {
+            path,
+            reason: error.to_string(),
+        })
+}
+
+/// Reads `name` from `folder` as RON as [`try_read_ron`] does, or `None`
+/// if it is missing or unreadable. A file that doesn't parse is
+/// [`report`]ed, to be listed among the app's start-up errors, and any
+/// other failure logged.
+pub fn read_ron<T: DeserializeOwned>(app: &mut App, folder: &str, name: &str) -> Option<T> {
+    match try_read_ron(folder, name) {
+        Ok(value) => value,
+        Err(error @ GameError::Config { .. }) => {
+            report(app, error);
+            None
+        }
+        Err(error) => {
+            warn!("{error}");
+            None
+        }
+    }
+}
+
+/// Writes `value` to `name` in `folder` as RON.
+pub fn write_ron<T: Serialize>(folder: &str, name: &str, value: &T) -> std::io::Result<()> {
+    let text = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(std::io::Error::other)?;
+    std::fs::write(file_path(folder, name)?, text)
+}
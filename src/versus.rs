@@ -0,0 +1,266 @@
+//! Local two-player versus on split boards.
+//!
+//! Player one plays the game as usual. Player two's game is a whole second
+//! [`World`] running [`GamePlugin`] on its own, dealt from the same seed
+//! and ticked once for every tick of player one's, with the keyboard
+//! mirrored into it for [`KeyBindings::player_two`] to read. The two trade
+//! [`garbage_for`] their line clears over a [`Loopback`] [`NetLink`], as an
+//! online match would over the wire, and the first to top out loses.
+//!
+//! Player two's game isn't recorded: garbage from across the table can't be
+//! replayed from one player's inputs, so versus games leave no replay and
+//! no autosave.
+
+use std::time::Duration;
+
+use bevy::ecs::event::ManualEventReader;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::components::board::Board;
+use crate::components::garbage::GarbageQueue;
+use crate::components::scoring::Score;
+use crate::components::tetromino::PieceQueue;
+use crate::game::{
+    ActivePiece, AppState, GameMode, GamePlugin, GameplayEvent, Modifiers, NewGame, Phase,
+};
+use crate::input::keyboard::KeyBindings;
+use crate::net::{send_to_peer, Loopback, NetLink, NetPlugin};
+use crate::rendering::shapes::Palette;
+use crate::replay::ReplayRecorder;
+use crate::ui::menus::board_thumbnail;
+use crate::utils::config::{InputConfig, Settings};
+use crate::utils::i18n::Locale;
+
+/// Pixels a cell takes in the picture of player two's board.
+const OPPONENT_CELL: f32 = 10.0;
+
+/// Garbage rows a clear of `lines` sends across: one for a double, two for
+/// a triple and four for a tetris.
+pub fn garbage_for(lines: u32) -> u32 {
+    match lines {
+        0 | 1 => 0,
+        2 => 1,
+        3 => 2,
+        lines => lines,
+    }
+}
+
+/// One side of a [`VersusMatch`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Player {
+    One,
+    Two,
+}
+
+impl Player {
+    /// Translation key of the line announcing this player won.
+    pub fn wins_key(self) -> &'static str {
+        match self {
+            Player::One => "versus.one_wins",
+            Player::Two => "versus.two_wins",
+        }
+    }
+}
+
+/// A versus game in progress against player two's game.
+#[derive(Resource, Debug)]
+pub struct VersusMatch {
+    opponent: World,
+    /// Whoever is still standing once the other tops out.
+    pub winner: Option<Player>,
+}
+
+impl VersusMatch {
+    /// A match against a fresh game for player two, dealt from `seed` under
+    /// the rules player one is playing by in `world`, which gets its end of
+    /// the link between them.
+    pub fn new(world: &mut World, seed: u64) -> Self {
+        let (one, two) = Loopback::pair();
+        world.insert_resource(NetLink(Box::new(one)));
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin, NetPlugin))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+            .insert_resource(GameMode::Versus)
+            .insert_resource(*world.resource::<Modifiers>())
+            .insert_resource(world.resource::<InputConfig>().clone())
+            .insert_resource(world.resource::<Settings>().clone())
+            .insert_resource(KeyBindings::player_two())
+            .insert_resource(NetLink(Box::new(two)));
+        app.world.send_event(NewGame { seed: Some(seed) });
+        app.finish();
+        app.cleanup();
+        // One update to deal the game, and one to start playing it.
+        app.update();
+        app.update();
+        Self {
+            opponent: std::mem::take(&mut app.world),
+            winner: None,
+        }
+    }
+
+    /// Player two's game, as [`Board`], [`Score`] and the rest.
+    pub fn opponent(&self) -> &World {
+        &self.opponent
+    }
+
+    /// Plays player two's side of the tick player one's `world` just
+    /// played, and settles the match if either side topped out.
+    fn tick(&mut self, world: &mut World) {
+        if self.winner.is_some() {
+            return;
+        }
+        if *world.resource::<Phase>() == Phase::ToppedOut {
+            self.winner = Some(Player::Two);
+            return;
+        }
+        self.opponent
+            .insert_resource(world.resource::<Input<KeyCode>>().clone());
+        self.opponent.run_schedule(First);
+        self.opponent.run_schedule(FixedUpdate);
+        self.opponent.run_schedule(StateTransition);
+
+        if *self.opponent.resource::<Phase>() == Phase::ToppedOut {
+            self.winner = Some(Player::One);
+            *world.resource_mut::<Phase>() = Phase::Finished;
+            world
+                .resource_mut::<NextState<AppState>>()
+                .set(AppState::GameOver);
+            world.send_event(GameplayEvent::Finished);
+        }
+    }
+}
+
+pub struct VersusPlugin;
+
+impl Plugin for VersusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostUpdate, start_match)
+            .add_systems(
+                FixedUpdate,
+                play_opponent
+                    .after(send_to_peer)
+                    .run_if(in_state(AppState::Playing).and_then(resource_exists::<VersusMatch>())),
+            )
+            .add_systems(
+                Update,
+                draw_opponent.run_if(
+                    resource_exists::<VersusMatch>()
+                        .and_then(in_state(AppState::Playing).or_else(in_state(AppState::Paused))),
+                ),
+            );
+    }
+}
+
+/// Sets up player two alongside every new versus game, and drops the
+/// match when a game of any other mode starts. Runs in [`PostUpdate`], once
+/// the new game's queue and recorder are in place.
+fn start_match(world: &mut World, mut games: Local<ManualEventReader<NewGame>>) {
+    if games
+        .read(world.resource::<Events<NewGame>>())
+        .last()
+        .is_none()
+    {
+        return;
+    }
+    if *world.resource::<GameMode>() != GameMode::Versus {
+        world.remove_resource::<VersusMatch>();
+        world.remove_resource::<NetLink>();
+        return;
+    }
+    let seed = world.resource::<PieceQueue>().seed();
+    let versus = VersusMatch::new(world, seed);
+    world.insert_resource(versus);
+    world.remove_resource::<ReplayRecorder>();
+}
+
+fn play_opponent(world: &mut World) {
+    let Some(mut versus) = world.remove_resource::<VersusMatch>() else {
+        return;
+    };
+    versus.tick(world);
+    world.insert_resource(versus);
+}
+
+/// Player two's board, piece, score and incoming garbage, at the right of
+/// the window.
+fn draw_opponent(
+    mut contexts: EguiContexts,
+    locale: Res<Locale>,
+    palette: Res<Palette>,
+    versus: Res<VersusMatch>,
+) {
+    let opponent = versus.opponent();
+    let mut board = opponent.resource::<Board>().clone();
+    if let Some(piece) = opponent.resource::<ActivePiece>().0 {
+        board.lock_piece(&piece);
+    }
+    let score = opponent.resource::<Score>();
+    let incoming = opponent.resource::<GarbageQueue>().pending();
+    egui::Window::new(locale.t("versus.player_two"))
+        .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+        .collapsible(false)
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            board_thumbnail(ui, &board.to_ascii(), &palette, OPPONENT_CELL, 1.0);
+            ui.label(format!("{}: {}", locale.t("stats.score"), score.points));
+            ui.label(format!("{}: {}", locale.t("stats.lines"), score.lines));
+            ui.label(format!("{}: {incoming}", locale.t("versus.incoming")));
+            ui.small(locale.t("versus.controls"));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::set_up_a_double;
+    use crate::input::keyboard::{Action, ActionEvent};
+    use crate::utils::config::LINE_CLEAR_DELAY;
+
+    /// Player one's side of a versus game on `seed`, with player two's set
+    /// up beside it. Player two's board isn't drawn, as there is no egui.
+    fn versus_app(seed: u64) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin, NetPlugin))
+            .add_systems(PostUpdate, start_match)
+            .add_systems(FixedUpdate, play_opponent.after(send_to_peer))
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::ZERO))
+            .insert_resource(GameMode::Versus);
+        app.world.send_event(NewGame { seed: Some(seed) });
+        app.update();
+        app.update();
+        app
+    }
+
+    #[test]
+    fn test_a_double_on_one_board_queues_a_row_on_the_other() {
+        let mut app = versus_app(11);
+        assert!(!app.world.contains_resource::<ReplayRecorder>());
+        let opponent_queue = |app: &App| {
+            let versus = app.world.resource::<VersusMatch>();
+            versus.opponent().resource::<PieceQueue>().seed()
+        };
+        assert_eq!(
+            opponent_queue(&app),
+            app.world.resource::<PieceQueue>().seed()
+        );
+        app.world.run_schedule(FixedUpdate);
+
+        set_up_a_double(&mut app.world);
+        app.world.send_event(ActionEvent::press(Action::HardDrop));
+        app.world.send_event(ActionEvent::release(Action::HardDrop));
+        for _ in 0..LINE_CLEAR_DELAY + 2 {
+            app.world.run_schedule(FixedUpdate);
+        }
+        assert_eq!(app.world.resource::<Score>().lines, 2);
+        let versus = app.world.resource::<VersusMatch>();
+        let garbage = versus.opponent().resource::<GarbageQueue>();
+        assert_eq!(garbage.pending(), garbage_for(2));
+        assert_eq!(garbage_for(2), 1);
+        assert_eq!(app.world.resource::<GarbageQueue>().pending(), 0);
+        assert_eq!(versus.winner, None);
+    }
+}